@@ -0,0 +1,237 @@
+//! Authenticating every call with a token, for a cluster behind an ingress that checks one.
+//!
+//! The token is either given as is, or read from a file that something else keeps up to date, as
+//! Kubernetes does with projected service account tokens: the file is checked at most once a second, off
+//! the calls, and read again whenever it changed. Calls go on with the token last read in the meantime.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use snafu::ResultExt;
+use tonic::body::Body;
+use tonic::codegen::http::{self, header::AUTHORIZATION, HeaderName, HeaderValue};
+
+use crate::config::{ConfigError, InvalidTokenSnafu, IoSnafu};
+use crate::utils::FileStamp;
+
+/// Where the token sent with every call comes from
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TokenSource {
+    /// The token itself
+    Token(String),
+    /// A file holding the token, read again whenever it changes
+    File(PathBuf),
+}
+
+impl std::fmt::Debug for TokenSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token(_) => f.debug_tuple("Token").field(&"<redacted>").finish(),
+            Self::File(path) => f.debug_tuple("File").field(path).finish(),
+        }
+    }
+}
+
+/// The header every call is authenticated with, and the token it carries
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Credentials {
+    /// Header the token is sent in: in `Authorization`, it is sent as `Bearer <token>`; in any other
+    /// header, as an API key, it is sent as is
+    pub header: HeaderName,
+    /// Where the token comes from
+    pub token: TokenSource,
+}
+
+impl Credentials {
+    /// Send the token of `token` in `header`
+    pub fn new(header: HeaderName, token: TokenSource) -> Self {
+        Self { header, token }
+    }
+
+    /// Send the token of `token` in `Authorization`, as `Bearer <token>`
+    pub fn bearer(token: TokenSource) -> Self {
+        Self::new(AUTHORIZATION, token)
+    }
+
+    /// Read the token, as the header value it is sent as.
+    ///
+    /// The surrounding whitespace of a token file is dropped: one written by hand usually ends with a
+    /// newline.
+    pub(crate) fn load(&self) -> Result<Loaded, ConfigError> {
+        let (token, stamp) = match &self.token {
+            TokenSource::Token(token) => (token.clone(), None),
+            TokenSource::File(path) => {
+                // Stamped before reading: a change while reading is seen by the next call.
+                let stamp = FileStamp::of(path);
+                let token = std::fs::read_to_string(path).context(IoSnafu {
+                    path: path.display().to_string(),
+                })?;
+                (token.trim().to_owned(), stamp)
+            }
+        };
+
+        let mut value = if self.header == AUTHORIZATION {
+            HeaderValue::try_from(format!("Bearer {token}"))
+        } else {
+            HeaderValue::try_from(token)
+        }
+        // The token is left out of the error: it is still a secret, even a mistyped one.
+        .context(InvalidTokenSnafu {})?;
+        // Kept out of the HTTP/2 header compression tables, and out of `Debug`.
+        value.set_sensitive(true);
+
+        Ok(Loaded { value, stamp })
+    }
+}
+
+/// A token, with what its file looked like when it was read
+#[derive(Debug)]
+pub(crate) struct Loaded {
+    value: HeaderValue,
+    stamp: Option<FileStamp>,
+}
+
+/// How long a token file goes unchecked after it was last checked
+const CHECK_EVERY: Duration = Duration::from_secs(1);
+
+/// How long a token file that cannot be read goes without being warned about again
+const WARN_EVERY: Duration = Duration::from_secs(60);
+
+/// Some [`Credentials`], and their token as last read
+#[derive(Debug)]
+pub(crate) struct Token {
+    credentials: Credentials,
+    state: Mutex<State>,
+}
+
+/// A token as last read, and when its file was checked
+#[derive(Debug)]
+struct State {
+    loaded: Loaded,
+    checked_at: Instant,
+    /// Whether the file is being checked
+    checking: bool,
+    /// When the file last could not be read and that was warned about, until it can be read again
+    warned_at: Option<Instant>,
+}
+
+impl Token {
+    /// Read the token of `credentials`.
+    pub(crate) fn new(credentials: Credentials) -> Result<Self, ConfigError> {
+        Ok(Self {
+            state: Mutex::new(State {
+                loaded: credentials.load()?,
+                checked_at: Instant::now(),
+                checking: false,
+                warned_at: None,
+            }),
+            credentials,
+        })
+    }
+
+    /// The header value to send, as last read.
+    ///
+    /// If its file was not checked for long, it is checked in the background, where the disk can be
+    /// waited on: the calls made meanwhile go on with the token last read.
+    fn value(self: &Arc<Self>) -> HeaderValue {
+        let mut state = self.state.lock().unwrap_or_else(|p| p.into_inner());
+
+        if matches!(self.credentials.token, TokenSource::File(_))
+            && !state.checking
+            && state.checked_at.elapsed() >= CHECK_EVERY
+        {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                state.checking = true;
+                let token = Arc::clone(self);
+                runtime.spawn_blocking(move || token.check());
+            }
+        }
+
+        state.loaded.value.clone()
+    }
+
+    /// Read the token file again if it changed.
+    ///
+    /// A file that cannot be read, as when it is being replaced, leaves the previous token in use: the
+    /// server is the one to tell whether it still is valid.
+    fn check(&self) {
+        let TokenSource::File(path) = &self.credentials.token else {
+            return;
+        };
+        let stamp = FileStamp::of(path);
+        let changed = stamp.is_none() || {
+            let state = self.state.lock().unwrap_or_else(|p| p.into_inner());
+            stamp != state.loaded.stamp
+        };
+        let reloaded = changed.then(|| self.credentials.load());
+
+        let mut state = self.state.lock().unwrap_or_else(|p| p.into_inner());
+        state.checking = false;
+        state.checked_at = Instant::now();
+        match reloaded {
+            None => {}
+            Some(Ok(reloaded)) => {
+                tracing::debug!("Token file `{}` changed, read it again", path.display());
+                state.loaded = reloaded;
+                state.warned_at = None;
+            }
+            Some(Err(err)) => {
+                if state
+                    .warned_at
+                    .is_none_or(|warned_at| warned_at.elapsed() >= WARN_EVERY)
+                {
+                    tracing::warn!(
+                        "Could not read the token file again, keeping the previous token: {err}"
+                    );
+                    state.warned_at = Some(Instant::now());
+                } else {
+                    tracing::debug!(
+                        "Could not read the token file again, keeping the previous token: {err}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Sets the header of some [`Credentials`] on every request.
+///
+/// Without credentials, requests are passed on as they are.
+#[derive(Debug, Clone)]
+pub(crate) struct Authorize<S> {
+    inner: S,
+    token: Option<Arc<Token>>,
+}
+
+impl<S> Authorize<S> {
+    /// Authenticate the calls of `inner` with `token`, if any.
+    pub(crate) fn new(inner: S, token: Option<Arc<Token>>) -> Self {
+        Self { inner, token }
+    }
+}
+
+impl<S> tower_service::Service<http::Request<Body>> for Authorize<S>
+where
+    S: tower_service::Service<http::Request<Body>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        if let Some(token) = &self.token {
+            request
+                .headers_mut()
+                .insert(token.credentials.header.clone(), token.value());
+        }
+        self.inner.call(request)
+    }
+}
@@ -0,0 +1,497 @@
+//! Spreading calls across several endpoints of the control plane, or across the addresses one name
+//! resolves to.
+//!
+//! Each endpoint gets a channel of its own, and calls go to them in turn. An endpoint whose connection
+//! fails, or that fails its health check, is left out for a while, then tried again.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::Uri;
+use hyper_util::client::legacy::connect::dns::Name;
+use hyper_util::client::legacy::connect::HttpConnector;
+use snafu::ResultExt;
+use tokio::time::Instant;
+use tonic::body::Body;
+use tonic::codegen::http;
+
+use crate::auth::Token;
+use crate::config::IncompatibleOptionsSnafu;
+use crate::connect::{transport_endpoint, Tls, UnresolvedSnafu};
+use crate::proxy::ProxyConnector;
+use crate::{Channel, ClientConfig, ConfigSnafu, ConnectionError, RetryPolicy, TransportSnafu};
+
+/// Time an endpoint that failed is left out for, when `eviction_backoff` is not set.
+const DEFAULT_EVICTION_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Bound on how many times the eviction backoff doubles for an endpoint failing again and again.
+const MAX_EVICTION_DOUBLINGS: u32 = 4;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// How an endpoint is checked to be healthy, when calls are spread across several of them.
+///
+/// Called with a channel to that endpoint alone, every `health_check_interval`: an endpoint is left out
+/// when its check resolves to `false`, or does not resolve within the interval.
+#[derive(Clone)]
+pub struct HealthCheck(Arc<dyn Fn(Channel) -> BoxFuture<bool> + Send + Sync>);
+
+impl HealthCheck {
+    /// Check endpoints with `check`.
+    pub fn new<F, Fut>(check: F) -> Self
+    where
+        F: Fn(Channel) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        Self(Arc::new(move |channel| Box::pin(check(channel))))
+    }
+}
+
+impl std::fmt::Debug for HealthCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HealthCheck")
+    }
+}
+
+/// Calls spread across the endpoints of a [`ClientConfig`].
+pub(crate) struct Balance {
+    shared: Arc<Shared>,
+    /// The endpoint picked by `poll_ready`, with the clone of its channel that was made ready.
+    ready: Option<(Arc<Member>, tonic::transport::Channel)>,
+}
+
+impl Clone for Balance {
+    fn clone(&self) -> Self {
+        // A clone picks its own endpoint: the readiness of this one's is not its to use.
+        Self {
+            shared: self.shared.clone(),
+            ready: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Balance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let members = self.shared.members();
+        f.debug_struct("Balance")
+            .field(
+                "endpoints",
+                &members
+                    .iter()
+                    .map(|member| member.to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// What the clones of a [`Balance`] share, and what its background tasks work on while it lives.
+struct Shared {
+    config: ClientConfig,
+    tls: Tls,
+    token: Option<Arc<Token>>,
+    members: Mutex<Vec<Arc<Member>>>,
+    next: AtomicUsize,
+}
+
+/// One endpoint calls are spread across.
+struct Member {
+    /// The endpoint as configured.
+    endpoint: Uri,
+    /// The address its host resolved to, when resolved periodically; resolved on each connection
+    /// otherwise.
+    address: Option<SocketAddr>,
+    channel: tonic::transport::Channel,
+    eviction: Mutex<Eviction>,
+}
+
+#[derive(Debug, Default)]
+struct Eviction {
+    /// Until when the endpoint is left out, if it is.
+    until: Option<Instant>,
+    /// How many times in a row it failed.
+    failures: u32,
+}
+
+impl std::fmt::Display for Member {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.address {
+            Some(address) => write!(f, "{} ({address})", self.endpoint),
+            None => write!(f, "{}", self.endpoint),
+        }
+    }
+}
+
+impl Member {
+    fn new(shared: &Shared, endpoint: Uri, address: Option<SocketAddr>) -> Self {
+        Self {
+            channel: transport_endpoint(&shared.config, endpoint.clone())
+                .connect_with_connector_lazy(shared.connector(address)),
+            endpoint,
+            address,
+            eviction: Default::default(),
+        }
+    }
+
+    /// The same endpoint, over `channel`, already connected.
+    fn connected(&self, channel: tonic::transport::Channel) -> Self {
+        Self {
+            endpoint: self.endpoint.clone(),
+            address: self.address,
+            channel,
+            eviction: Default::default(),
+        }
+    }
+
+    /// When it is left out until, if it is at `now`.
+    fn evicted_until(&self, now: Instant) -> Option<Instant> {
+        let eviction = self.eviction.lock().unwrap_or_else(|err| err.into_inner());
+        eviction.until.filter(|until| *until > now)
+    }
+
+    /// Leave it out, for longer the more times in a row it failed.
+    fn evict(&self, backoff: Duration, reason: &dyn std::fmt::Display) {
+        let now = Instant::now();
+        let mut eviction = self.eviction.lock().unwrap_or_else(|err| err.into_inner());
+        if eviction.until.is_some_and(|until| until > now) {
+            // The calls that were in flight when it went down fail as well: the same outage.
+            return;
+        }
+        let backoff = backoff * 2u32.pow(eviction.failures.min(MAX_EVICTION_DOUBLINGS));
+        eviction.failures = eviction.failures.saturating_add(1);
+        eviction.until = Some(now + backoff);
+        tracing::warn!(
+            "Leaving the endpoint {self} out for {}: {reason}",
+            humantime::format_duration(backoff)
+        );
+    }
+
+    /// Count it as working again.
+    fn restore(&self) {
+        let mut eviction = self.eviction.lock().unwrap_or_else(|err| err.into_inner());
+        if eviction.failures > 0 {
+            tracing::info!("The endpoint {self} is back");
+        }
+        *eviction = Eviction::default();
+    }
+}
+
+impl Shared {
+    fn members(&self) -> Vec<Arc<Member>> {
+        self.members
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    fn backoff(&self) -> Duration {
+        self.config
+            .eviction_backoff
+            .unwrap_or(DEFAULT_EVICTION_BACKOFF)
+    }
+
+    fn connector(
+        &self,
+        address: Option<SocketAddr>,
+    ) -> hyper_rustls::HttpsConnector<ProxyConnector<HttpConnector<Resolver>>> {
+        self.tls.wrap(
+            &self.config,
+            HttpConnector::new_with_resolver(Resolver { address }),
+        )
+    }
+
+    /// The next endpoint in turn that is not left out, or the one left out the earliest when they all are.
+    fn pick(&self) -> Arc<Member> {
+        let members = self.members.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..members.len())
+            .map(|i| &members[start.wrapping_add(i) % members.len()])
+            .find(|member| member.evicted_until(now).is_none())
+            .or_else(|| {
+                members
+                    .iter()
+                    .min_by_key(|member| member.evicted_until(now))
+            })
+            .expect("a balanced channel has at least one endpoint")
+            .clone()
+    }
+
+    /// Put `member` in place of `replaced`, if it still is there.
+    fn replace(&self, replaced: &Arc<Member>, member: Member) {
+        let mut members = self.members.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(slot) = members.iter_mut().find(|slot| Arc::ptr_eq(slot, replaced)) {
+            *slot = Arc::new(member);
+        }
+    }
+
+    /// Replace the endpoints with those of `resolved`, keeping the channel and the failures of the ones
+    /// already there.
+    fn update(&self, resolved: Vec<(Uri, Option<SocketAddr>)>) {
+        let mut members = self.members.lock().unwrap_or_else(|err| err.into_inner());
+        let updated = resolved
+            .into_iter()
+            .map(|(endpoint, address)| {
+                members
+                    .iter()
+                    .find(|member| member.endpoint == endpoint && member.address == address)
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        tracing::info!(
+                            "Spreading calls to {endpoint}{} as well",
+                            address.map(|a| format!(" ({a})")).unwrap_or_default()
+                        );
+                        Arc::new(Member::new(self, endpoint, address))
+                    })
+            })
+            .collect();
+        *members = updated;
+    }
+}
+
+impl tower_service::Service<http::Request<Body>> for Balance {
+    type Response = http::Response<Body>;
+    type Error = tonic::transport::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let (member, channel) = self.ready.get_or_insert_with(|| {
+            let member = self.shared.pick();
+            let channel = member.channel.clone();
+            (member, channel)
+        });
+        match channel.poll_ready(cx) {
+            Poll::Ready(Err(err)) => {
+                member.evict(self.shared.backoff(), &err);
+                self.ready = None;
+                Poll::Ready(Err(err))
+            }
+            poll => poll,
+        }
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let (member, mut channel) = self
+            .ready
+            .take()
+            .expect("`poll_ready` is called before `call`");
+        let backoff = self.shared.backoff();
+        let response = channel.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            // Only what kept the call from reaching the server fails here: statuses it answers with
+            // are responses.
+            match &response {
+                Ok(_) => member.restore(),
+                Err(err) => member.evict(backoff, err),
+            }
+            response
+        })
+    }
+}
+
+/// Resolves the host of an endpoint, or stands for the address it was already resolved to.
+///
+/// The port is the endpoint's: the connector sets it on the addresses.
+#[derive(Debug, Clone, Copy)]
+struct Resolver {
+    address: Option<SocketAddr>,
+}
+
+impl tower_service::Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = std::io::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let address = self.address;
+        Box::pin(async move {
+            match address {
+                Some(address) => Ok(vec![address].into_iter()),
+                None => Ok(tokio::net::lookup_host((name.as_str(), 0))
+                    .await?
+                    .collect::<Vec<_>>()
+                    .into_iter()),
+            }
+        })
+    }
+}
+
+/// The endpoints of `config`, each address of their hosts an endpoint of its own when they are resolved
+/// periodically.
+async fn resolve(config: &ClientConfig) -> Vec<(Uri, Option<SocketAddr>)> {
+    let endpoints =
+        std::iter::once(config.endpoint.clone()).chain(config.endpoints.iter().cloned());
+    if config.resolve_interval.is_none() {
+        return endpoints.map(|endpoint| (endpoint, None)).collect();
+    }
+
+    let mut resolved = Vec::new();
+    for endpoint in endpoints {
+        let Some(host) = endpoint.host() else {
+            continue;
+        };
+        // An IPv6 address is written between brackets in a URI, and resolves to itself without them.
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        match tokio::net::lookup_host((host, 0)).await {
+            Ok(addresses) => {
+                let mut addresses = addresses.collect::<Vec<_>>();
+                addresses.sort();
+                addresses.dedup();
+                resolved.extend(
+                    addresses
+                        .into_iter()
+                        .map(|address| (endpoint.clone(), Some(address))),
+                );
+            }
+            Err(err) => tracing::warn!("Could not resolve the endpoint {endpoint}: {err}"),
+        }
+    }
+    resolved
+}
+
+/// Spread calls across the endpoints of `config`, connected to before this returns if `eager`.
+///
+/// With `eager`, an endpoint that cannot be connected to starts out left out; it is an error only if
+/// none can be.
+pub(crate) async fn connect(
+    config: ClientConfig,
+    token: Option<Arc<Token>>,
+    eager: bool,
+) -> Result<Balance, ConnectionError> {
+    if config.health_check_interval.is_some() && config.health_check.is_none() {
+        return IncompatibleOptionsSnafu {
+            msg: String::from(
+                "`GrpcClient__HealthCheckInterval` is set, but there is no health check to run",
+            ),
+        }
+        .fail()
+        .context(ConfigSnafu {});
+    }
+
+    let resolved = resolve(&config).await;
+    if resolved.is_empty() {
+        return UnresolvedSnafu {
+            endpoint: config.endpoint,
+        }
+        .fail();
+    }
+
+    let shared = Arc::new(Shared {
+        tls: Tls::new(&config)?,
+        config,
+        token,
+        members: Default::default(),
+        next: Default::default(),
+    });
+    shared.update(resolved);
+
+    if eager {
+        let mut connecting = tokio::task::JoinSet::new();
+        for member in shared.members() {
+            let endpoint = transport_endpoint(&shared.config, member.endpoint.clone());
+            let connector = shared.connector(member.address);
+            connecting.spawn(async move {
+                let connected = endpoint.connect_with_connector(connector).await;
+                (member, connected)
+            });
+        }
+        let mut first_error = None;
+        let mut connected = false;
+        while let Some(joined) = connecting.join_next().await {
+            let (member, result) = joined.expect("connecting does not panic");
+            match result {
+                // Kept for the calls, rather than dialled again on the first of them.
+                Ok(channel) => {
+                    connected = true;
+                    shared.replace(&member, member.connected(channel));
+                }
+                Err(err) => {
+                    member.evict(shared.backoff(), &err);
+                    first_error.get_or_insert((member.endpoint.clone(), err));
+                }
+            }
+        }
+        if let (false, Some((endpoint, err))) = (connected, first_error) {
+            return Err(err).context(TransportSnafu { endpoint });
+        }
+    }
+
+    if let Some(interval) = shared.config.resolve_interval {
+        tokio::spawn(resolve_periodically(Arc::downgrade(&shared), interval));
+    }
+    if let (Some(interval), Some(check)) = (
+        shared.config.health_check_interval,
+        shared.config.health_check.clone(),
+    ) {
+        tokio::spawn(check_periodically(Arc::downgrade(&shared), interval, check));
+    }
+
+    Ok(Balance {
+        shared,
+        ready: None,
+    })
+}
+
+/// Resolve the endpoints again every `interval`, for as long as the channel is in use.
+async fn resolve_periodically(shared: Weak<Shared>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        let resolved = resolve(&shared.config).await;
+        if resolved.is_empty() {
+            // Better the addresses known to have worked than no endpoint at all.
+            tracing::warn!("No endpoint resolved to any address, keeping the previous ones");
+            continue;
+        }
+        shared.update(resolved);
+    }
+}
+
+/// Check each endpoint that is not left out every `interval`, for as long as the channel is in use.
+async fn check_periodically(shared: Weak<Shared>, interval: Duration, check: HealthCheck) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        let now = Instant::now();
+        let mut checks = tokio::task::JoinSet::new();
+        for member in shared.members() {
+            if member.evicted_until(now).is_some() {
+                continue;
+            }
+            let channel = Channel::new(
+                member.channel.clone().into(),
+                RetryPolicy::default(),
+                shared.token.clone(),
+            );
+            let healthy = tokio::time::timeout(interval, (check.0)(channel));
+            checks.spawn(async move { (member, healthy.await) });
+        }
+        let backoff = shared.backoff();
+        // Not held across the checks, so that dropping the channel ends this task.
+        drop(shared);
+        while let Some(joined) = checks.join_next().await {
+            let Ok((member, healthy)) = joined else {
+                continue;
+            };
+            match healthy {
+                Ok(true) => {}
+                Ok(false) => member.evict(backoff, &"its health check failed"),
+                Err(_) => member.evict(backoff, &"its health check timed out"),
+            }
+        }
+    }
+}
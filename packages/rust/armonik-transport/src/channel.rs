@@ -0,0 +1,94 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tonic::body::Body;
+use tonic::codegen::http;
+
+use crate::auth::{Authorize, Token};
+use crate::balance::Balance;
+use crate::retry::Retry;
+use crate::RetryPolicy;
+
+/// A channel to the control plane, as [`connect`](crate::connect) builds it: the `tonic` channel, with
+/// what the [`ClientConfig`](crate::ClientConfig) asks for on top of the connection.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    inner: Retry<Authorize<Transport>>,
+}
+
+impl Channel {
+    pub(crate) fn new(channel: Transport, retry: RetryPolicy, token: Option<Arc<Token>>) -> Self {
+        Self {
+            // Authenticated below the retries, so that each attempt sends the token as it then is.
+            inner: Retry::new(Authorize::new(channel, token), retry),
+        }
+    }
+
+    /// Get the policy calls are retried according to
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        self.inner.policy()
+    }
+}
+
+impl From<tonic::transport::Channel> for Channel {
+    /// Use a channel built some other way, as is: calls are neither retried nor authenticated.
+    fn from(channel: tonic::transport::Channel) -> Self {
+        Self::new(channel.into(), RetryPolicy::default(), None)
+    }
+}
+
+impl tower_service::Service<http::Request<Body>> for Channel {
+    type Response = http::Response<Body>;
+    type Error = tonic::transport::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        self.inner.call(request)
+    }
+}
+
+/// What calls go through once retried and authenticated: one connection, or calls spread across several
+/// endpoints.
+#[derive(Debug, Clone)]
+pub(crate) enum Transport {
+    Single(tonic::transport::Channel),
+    Balanced(Balance),
+}
+
+impl From<tonic::transport::Channel> for Transport {
+    fn from(channel: tonic::transport::Channel) -> Self {
+        Self::Single(channel)
+    }
+}
+
+impl From<Balance> for Transport {
+    fn from(balance: Balance) -> Self {
+        Self::Balanced(balance)
+    }
+}
+
+impl tower_service::Service<http::Request<Body>> for Transport {
+    type Response = http::Response<Body>;
+    type Error = tonic::transport::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            Self::Single(channel) => channel.poll_ready(cx),
+            Self::Balanced(balance) => balance.poll_ready(cx),
+        }
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        match self {
+            Self::Single(channel) => Box::pin(channel.call(request)),
+            Self::Balanced(balance) => balance.call(request),
+        }
+    }
+}
@@ -1,66 +1,169 @@
-//! Turning a [`ClientConfig`] into a connected `tonic` channel.
+//! Turning a [`ClientConfig`] into a connected channel.
 //!
-//! TLS, mTLS, and every timeout, keepalive and identity setting come together here.
+//! TLS, mTLS, tokens, and every timeout, keepalive and identity setting come together here.
 
 use std::sync::Arc;
 
 use hyper::Uri;
-use hyper_rustls::{ConfigBuilderExt, FixedServerNameResolver, HttpsConnector};
+use hyper_rustls::{FixedServerNameResolver, HttpsConnector};
 use hyper_util::client::legacy::connect::HttpConnector;
+use rustls::client::danger::ServerCertVerifier;
 use rustls::pki_types::{IpAddr, ServerName};
 use snafu::{ResultExt, Snafu};
 
+use crate::auth::Token;
 use crate::config::{ConfigError, IncompatibleOptionsSnafu};
-use crate::ClientConfig;
+use crate::proxy::{Proxies, ProxyConnector};
+use crate::reload::{ReloadingIdentity, ReloadingVerifier};
+use crate::trust::PinnedVerifier;
+use crate::{Channel, ClientConfig};
 
 /// Connect to the endpoint described by `config`, eagerly: this resolves once the connection is
 /// established, not lazily on the first request.
-pub async fn connect(config: ClientConfig) -> Result<tonic::transport::Channel, ConnectionError> {
+pub async fn connect(config: ClientConfig) -> Result<Channel, ConnectionError> {
     let endpoint = config.endpoint.clone();
-    let override_target = config.override_target.clone();
-    let http2_keep_alive_interval = config.http2_keep_alive_interval;
-    let http2_keep_alive_timeout = config.http2_keep_alive_timeout;
-    let http2_keep_alive_while_idle = config.http2_keep_alive_while_idle;
-    let http2_max_header_list_size = config.http2_max_header_list_size;
-    let user_agent = config.user_agent.clone();
-    let timeout = config.timeout;
-    let rate_limit = config.rate_limit;
+    let transport_endpoint = transport_endpoint(&config, config.endpoint.clone());
+    let retry = config.retry.clone();
+    let token = token(&config)?;
+
+    if let Some(path) = config.unix_socket {
+        // Checked beforehand so that a missing socket names its path, rather than surfacing as a
+        // transport error that only says the connection was refused.
+        #[cfg(unix)]
+        return Ok(Channel::new(
+            transport_endpoint
+                .connect_with_connector(unix_connector(path).await?)
+                .await
+                .context(TransportSnafu { endpoint })?
+                .into(),
+            retry,
+            token,
+        ));
+        #[cfg(not(unix))]
+        return UnsupportedUnixSocketSnafu { path }.fail();
+    }
+
+    if config.is_balanced() {
+        let balance = crate::balance::connect(config, token.clone(), true).await?;
+        return Ok(Channel::new(balance.into(), retry, token));
+    }
+
+    let https = https_connector(config).await?;
+
+    // Build the actual channel from the configuration
+    let channel = transport_endpoint
+        .connect_with_connector(https)
+        .await
+        .context(TransportSnafu { endpoint })?;
+    Ok(Channel::new(channel.into(), retry, token))
+}
+
+/// Build a channel to the endpoint described by `config`, lazily: nothing is dialled until the first
+/// request, so this succeeds whether or not the server is up yet.
+///
+/// Meant for a peer known to start alongside the caller, like the agent of a worker: an eager
+/// [`connect`] would race it.
+pub async fn connect_lazy(config: ClientConfig) -> Result<Channel, ConnectionError> {
+    let transport_endpoint = transport_endpoint(&config, config.endpoint.clone());
+    let retry = config.retry.clone();
+    let token = token(&config)?;
+
+    if let Some(path) = config.unix_socket {
+        #[cfg(unix)]
+        return Ok(Channel::new(
+            transport_endpoint
+                .connect_with_connector_lazy(crate::unix::UnixConnector::new(path))
+                .into(),
+            retry,
+            token,
+        ));
+        #[cfg(not(unix))]
+        return UnsupportedUnixSocketSnafu { path }.fail();
+    }
+
+    if config.is_balanced() {
+        let balance = crate::balance::connect(config, token.clone(), false).await?;
+        return Ok(Channel::new(balance.into(), retry, token));
+    }
 
     let https = https_connector(config).await?;
+    Ok(Channel::new(
+        transport_endpoint.connect_with_connector_lazy(https).into(),
+        retry,
+        token,
+    ))
+}
 
-    let mut transport_endpoint = tonic::transport::Endpoint::from(endpoint.clone());
-    if let Some(target) = override_target {
+/// The token of `config`, read before anything is dialled: a token file that went missing since the
+/// configuration was read is reported as such, rather than as every call being refused.
+fn token(config: &ClientConfig) -> Result<Option<Arc<Token>>, ConnectionError> {
+    config
+        .credentials
+        .clone()
+        .map(|credentials| Token::new(credentials).map(Arc::new))
+        .transpose()
+        .context(ConfigSnafu {})
+}
+
+/// The `tonic` endpoint for `endpoint`, with every timeout, limit and HTTP/2 setting of `config`
+/// applied: what is common to every way of connecting.
+pub(crate) fn transport_endpoint(
+    config: &ClientConfig,
+    endpoint: Uri,
+) -> tonic::transport::Endpoint {
+    let mut transport_endpoint = tonic::transport::Endpoint::from(endpoint);
+    if let Some(target) = config.override_target.clone() {
         transport_endpoint = transport_endpoint.origin(target);
     }
 
-    if let Some(timeout) = timeout {
+    if let Some(timeout) = config.timeout {
         transport_endpoint = transport_endpoint.timeout(timeout);
     }
-    if let Some((limit, duration)) = rate_limit {
+    if let Some((limit, duration)) = config.rate_limit {
         transport_endpoint = transport_endpoint.rate_limit(limit, duration);
     }
 
-    if let Some(interval) = http2_keep_alive_interval {
+    if let Some(interval) = config.http2_keep_alive_interval {
         transport_endpoint = transport_endpoint.http2_keep_alive_interval(interval);
     }
-    if let Some(timeout) = http2_keep_alive_timeout {
+    if let Some(timeout) = config.http2_keep_alive_timeout {
         transport_endpoint = transport_endpoint.keep_alive_timeout(timeout);
     }
-    transport_endpoint = transport_endpoint.keep_alive_while_idle(http2_keep_alive_while_idle);
-    if let Some(max) = http2_max_header_list_size {
+    transport_endpoint =
+        transport_endpoint.keep_alive_while_idle(config.http2_keep_alive_while_idle);
+    if let Some(max) = config.http2_max_header_list_size {
         transport_endpoint = transport_endpoint.http2_max_header_list_size(max);
     }
-    if let Some(ua) = user_agent {
+    if let Some(ua) = config.user_agent.clone() {
         transport_endpoint = transport_endpoint
             .user_agent(ua)
             .expect("HeaderValue is already validated, conversion is infallible");
     }
+    if let Some(timeout) = config
+        .connect_timeout
+        .filter(|_| config.unix_socket.is_some())
+    {
+        // The TCP connector applies its own; nothing else bounds dialling a socket.
+        transport_endpoint = transport_endpoint.connect_timeout(timeout);
+    }
 
-    // Build the actual channel from the configuration
     transport_endpoint
-        .connect_with_connector(https)
+}
+
+/// The connector for the Unix socket at `path`, once it is known to be there.
+#[cfg(unix)]
+async fn unix_connector(
+    path: std::path::PathBuf,
+) -> Result<crate::unix::UnixConnector, ConnectionError> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let metadata = tokio::fs::metadata(&path)
         .await
-        .context(TransportSnafu { endpoint })
+        .context(UnixSocketSnafu { path: path.clone() })?;
+    if !metadata.file_type().is_socket() {
+        return NotASocketSnafu { path }.fail();
+    }
+    Ok(crate::unix::UnixConnector::new(path))
 }
 
 /// Build the connector stack, TCP then TLS or mTLS, that [`connect`] wraps in a channel.
@@ -72,66 +175,139 @@ pub async fn connect(config: ClientConfig) -> Result<tonic::transport::Channel,
 #[doc(hidden)]
 pub async fn https_connector(
     config: ClientConfig,
-) -> Result<HttpsConnector<HttpConnector>, ConnectionError> {
-    let endpoint = config.endpoint;
-
-    // Get the default crypto provider or fallback to the ring crypto provider
-    let crypto_provider = rustls::crypto::CryptoProvider::get_default()
-        .cloned()
-        .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider()));
-
-    // Configure TLS with sane protocol defaults
-    let tls_config = rustls::ClientConfig::builder_with_provider(crypto_provider)
-        .with_safe_default_protocol_versions()
-        .with_context(|_| TlsSnafu {
-            endpoint: endpoint.clone(),
-        })?;
-
-    // Configure the server verification
-    let tls_config = if config.allow_unsafe_connection {
-        // Do not verify the server
-        tls_config
-            .dangerous()
-            .with_custom_certificate_verifier(Arc::new(crate::utils::InsecureCertVerifier))
-    } else if let Some(cacert) = config.cacert {
-        // Verify that the server certificate is signed with a specific CA cert
-        let mut root_cert_store = rustls::RootCertStore::empty();
-        root_cert_store.add(cacert).with_context(|_| TlsSnafu {
-            endpoint: endpoint.clone(),
-        })?;
-        tls_config.with_root_certificates(root_cert_store)
-    } else {
-        // Verify the server certificate using the system CAs
-        tls_config
-            .with_native_roots()
-            .with_context(|_| IoSnafu {})?
-    };
+) -> Result<HttpsConnector<ProxyConnector<HttpConnector>>, ConnectionError> {
+    Ok(Tls::new(&config)?.wrap(&config, HttpConnector::new()))
+}
+
+/// The TLS side of the connections to the endpoints of a [`ClientConfig`], set up once for all of them.
+#[derive(Clone)]
+pub(crate) struct Tls {
+    pub(crate) config: rustls::ClientConfig,
+    pub(crate) server_name: Option<ServerName<'static>>,
+    /// What `config` verifies the server with, kept for a diagnosis to wrap
+    pub(crate) verifier: Arc<dyn ServerCertVerifier>,
+    proxies: Arc<Proxies>,
+}
+
+impl Tls {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self, ConnectionError> {
+        let endpoint = &config.endpoint;
+        let reload = config.reload.clone().unwrap_or_default();
 
-    // Configure client identity for mTLS
-    let tls_config = if let Some((cert, key)) = config.identity {
-        // Use the the specified client certificate and key for the client authentication
-        tls_config
-            .with_client_auth_cert(vec![cert], key)
+        // Get the default crypto provider or fallback to the ring crypto provider
+        let crypto_provider = rustls::crypto::CryptoProvider::get_default()
+            .cloned()
+            .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider()));
+
+        // Configure TLS with sane protocol defaults
+        let tls_config = rustls::ClientConfig::builder_with_provider(crypto_provider.clone())
+            .with_safe_default_protocol_versions()
             .with_context(|_| TlsSnafu {
                 endpoint: endpoint.clone(),
-            })?
-    } else {
-        // No mTLS
-        tls_config.with_no_client_auth()
-    };
+            })?;
+
+        // Configure the server verification
+        let verifier: Arc<dyn ServerCertVerifier> = if config.allow_unsafe_connection {
+            // Do not verify the server
+            Arc::new(crate::utils::InsecureCertVerifier)
+        } else if let Some(path) = reload.ca_cert {
+            // Verify against CA certs read again once their file changed
+            Arc::new(
+                ReloadingVerifier::new(path, config.trust_native_roots, crypto_provider.clone())
+                    .context(ConfigSnafu {})?,
+            )
+        } else if !config.cacert.is_empty() {
+            // Verify that the server certificate is signed with one of specific CA certs, or of the
+            // system CAs as well if asked to
+            crate::trust::verifier(
+                config.cacert.clone(),
+                config.trust_native_roots,
+                crypto_provider.clone(),
+            )
+            .context(ConfigSnafu {})?
+        } else {
+            // Verify the server certificate using the system CAs
+            crate::trust::verifier(Vec::new(), true, crypto_provider.clone())
+                .context(ConfigSnafu {})?
+        };
+        // On top of which, require a pinned public key if any
+        let verifier = if config.pinned_spki.is_empty() {
+            verifier
+        } else {
+            Arc::new(PinnedVerifier::new(
+                verifier,
+                config.pinned_spki.clone(),
+                crypto_provider.clone(),
+            ))
+        };
+        let tls_config = tls_config
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone());
+
+        // Configure client identity for mTLS
+        let tls_config = if let Some((cert, key)) = reload.identity {
+            // Use the client certificate and key read again once their files changed
+            let identity =
+                ReloadingIdentity::new(cert, key, crypto_provider).context(ConfigSnafu {})?;
+            tls_config.with_client_cert_resolver(Arc::new(identity))
+        } else if let Some((chain, key)) = &config.identity {
+            // Use the the specified client certificate chain and key for the client authentication
+            tls_config
+                .with_client_auth_cert(chain.clone(), key.clone_key())
+                .with_context(|_| TlsSnafu {
+                    endpoint: endpoint.clone(),
+                })?
+        } else {
+            // No mTLS
+            tls_config.with_no_client_auth()
+        };
+
+        let server_name = config
+            .override_target
+            .as_ref()
+            .map(override_server_name)
+            .transpose()?;
 
-    // Configure the connector to use http or https depending on the URI scheme
-    let mut https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_tls_config(tls_config)
-        .https_or_http();
+        let proxies = Proxies::new(config);
 
-    if let Some(hostname) = &config.override_target {
-        let server_name = override_server_name(hostname)?;
-        https = https.with_server_name_resolver(FixedServerNameResolver::new(server_name));
-    };
+        Ok(Self {
+            config: tls_config,
+            server_name,
+            verifier,
+            proxies: Arc::new(proxies),
+        })
+    }
 
-    let mut http = HttpConnector::new();
-    http.enforce_http(false); // required for hyper-rustls to switch schemes
+    /// Wrap `http` in TLS, with the TCP settings of `config`, through the proxy that applies if any.
+    pub(crate) fn wrap<R>(
+        &self,
+        config: &ClientConfig,
+        mut http: HttpConnector<R>,
+    ) -> HttpsConnector<ProxyConnector<HttpConnector<R>>> {
+        // Configure the connector to use http or https depending on the URI scheme
+        let mut https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(self.config.clone())
+            .https_or_http();
+
+        if let Some(server_name) = &self.server_name {
+            https =
+                https.with_server_name_resolver(FixedServerNameResolver::new(server_name.clone()));
+        };
+
+        http.enforce_http(false); // required for hyper-rustls to switch schemes
+        configure_tcp(config, &mut http);
+        // The proxies are dialled by their own names, with the same TCP settings
+        let mut to_proxy = HttpConnector::new();
+        configure_tcp(config, &mut to_proxy);
+        let proxied =
+            ProxyConnector::new(http, to_proxy, self.proxies.clone(), config.connect_timeout);
+
+        https.enable_http1().enable_http2().wrap_connector(proxied)
+    }
+}
+
+/// Apply the TCP settings of `config` to `http`.
+fn configure_tcp<R>(config: &ClientConfig, http: &mut HttpConnector<R>) {
     http.set_nodelay(!config.tcp_nagle_algorithm);
     http.set_keepalive(config.tcp_keepalive);
     http.set_keepalive_interval(config.tcp_keepalive_interval);
@@ -139,8 +315,6 @@ pub async fn https_connector(
     if let Some(timeout) = config.connect_timeout {
         http.set_connect_timeout(Some(timeout));
     }
-
-    Ok(https.enable_http1().enable_http2().wrap_connector(http))
 }
 
 /// The name the server certificate is verified against, from the host of an override target.
@@ -207,6 +381,41 @@ pub enum ConnectionError {
         #[snafu(implicit)]
         location: snafu::Location,
     },
+    #[snafu(display("Could not reach the Unix socket `{}` [{location}]", path.display()))]
+    #[non_exhaustive]
+    UnixSocket {
+        path: std::path::PathBuf,
+        #[snafu(source(from(std::io::Error, Box::new)))]
+        source: Box<std::io::Error>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("`{}` exists but is not a Unix socket [{location}]", path.display()))]
+    #[non_exhaustive]
+    NotASocket {
+        path: std::path::PathBuf,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display(
+        "Unix sockets are not supported on this platform: `{}` [{location}]",
+        path.display()
+    ))]
+    #[non_exhaustive]
+    UnsupportedUnixSocket {
+        path: std::path::PathBuf,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display(
+        "No address could be resolved for {endpoint}, nor the other endpoints [{location}]"
+    ))]
+    #[non_exhaustive]
+    Unresolved {
+        endpoint: Uri,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
     #[snafu(display("Could not read system cert store [{location}]"))]
     #[non_exhaustive]
     Io {
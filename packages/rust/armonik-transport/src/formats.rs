@@ -0,0 +1,160 @@
+//! Reading configuration files, in JSON, TOML or YAML, into one tree of values.
+//!
+//! JSON goes through `serde_json`, always there. TOML takes the `toml` feature and YAML the `yaml` one,
+//! each reading its format whole through the crate of the same purpose.
+
+use std::path::Path;
+
+use serde_json::Value;
+use snafu::ResultExt;
+
+use crate::config::{ConfigError, InvalidFileSnafu, IoSnafu};
+
+/// The tree of values in the file at `path`, in the format its extension names.
+pub(crate) fn read(path: &Path) -> Result<Value, ConfigError> {
+    let display = path.display().to_string();
+    let text = std::fs::read_to_string(path).context(IoSnafu {
+        path: display.clone(),
+    })?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let parsed = match extension.as_str() {
+        "json" => serde_json::from_str(&text).map_err(|err| err.to_string()),
+        "toml" => toml(&text),
+        "yaml" | "yml" => yaml(&text),
+        _ => Err(String::from(
+            "only `.json`, `.toml`, `.yaml` and `.yml` files are read",
+        )),
+    };
+    parsed.map_err(|msg| InvalidFileSnafu { path: display, msg }.build())
+}
+
+/// The tree of values of the TOML document `text`.
+#[cfg(feature = "toml")]
+fn toml(text: &str) -> Result<Value, String> {
+    ::toml::from_str(text).map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "toml"))]
+fn toml(_text: &str) -> Result<Value, String> {
+    Err(String::from(
+        "reading TOML takes the `toml` feature of `armonik-transport`",
+    ))
+}
+
+/// The tree of values of the YAML document `text`, with its `<<` merge keys applied.
+#[cfg(feature = "yaml")]
+fn yaml(text: &str) -> Result<Value, String> {
+    let mut value =
+        serde_norway::from_str::<serde_norway::Value>(text).map_err(|err| err.to_string())?;
+    value.apply_merge().map_err(|err| err.to_string())?;
+    serde_norway::from_value(value).map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "yaml"))]
+fn yaml(_text: &str) -> Result<Value, String> {
+    Err(String::from(
+        "reading YAML takes the `yaml` feature of `armonik-transport`",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_is_read_whole() {
+        let value = super::toml(
+            r#"
+            current_profile = "dev" # the one used when none is named
+
+            [profiles.dev]
+            Endpoint = "http://localhost:5001"
+            AllowUnsafeConnection = true
+            TcpKeepaliveRetries = 3
+            Endpoints = [
+                "http://cp-1:5001",
+                'http://cp-2:5001',
+            ]
+            Proxy = { Uri = "http://proxy:3128" }
+
+            [profiles."prod cluster".GrpcClient]
+            endpoint = "https://armonik.example.com#not-a-comment"
+            "#,
+        )
+        .expect("valid TOML");
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "current_profile": "dev",
+                "profiles": {
+                    "dev": {
+                        "Endpoint": "http://localhost:5001",
+                        "AllowUnsafeConnection": true,
+                        "TcpKeepaliveRetries": 3,
+                        "Endpoints": ["http://cp-1:5001", "http://cp-2:5001"],
+                        "Proxy": { "Uri": "http://proxy:3128" },
+                    },
+                    "prod cluster": {
+                        "GrpcClient": { "endpoint": "https://armonik.example.com#not-a-comment" },
+                    },
+                },
+            })
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn invalid_toml_names_the_line() {
+        let error = super::toml("a = 1\na = 2").expect_err("a key set twice");
+        assert!(error.contains("line 2"), "{error}");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_is_read_whole() {
+        let value = super::yaml(
+            "---\n\
+             defaults: &defaults\n\
+             \x20 AllowUnsafeConnection: true\n\
+             GrpcClient:\n\
+             \x20 <<: *defaults\n\
+             \x20 Endpoint: http://localhost:5001 # a comment\n\
+             \x20 Endpoints:\n\
+             \x20 - http://cp-1:5001\n\
+             \x20 - 'http://cp-2:5001'\n\
+             \x20 RetryableCodes: [Unavailable, \"Aborted\"]\n\
+             \x20 CaCert: |\n\
+             \x20   /etc/ssl/ca.pem\n\
+             Logging: {LogLevel: Debug}\n",
+        )
+        .expect("valid YAML");
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "defaults": { "AllowUnsafeConnection": true },
+                "GrpcClient": {
+                    "AllowUnsafeConnection": true,
+                    "Endpoint": "http://localhost:5001",
+                    "Endpoints": ["http://cp-1:5001", "http://cp-2:5001"],
+                    "RetryableCodes": ["Unavailable", "Aborted"],
+                    "CaCert": "/etc/ssl/ca.pem\n",
+                },
+                "Logging": { "LogLevel": "Debug" },
+            })
+        );
+    }
+
+    #[cfg(not(all(feature = "toml", feature = "yaml")))]
+    #[test]
+    fn formats_left_out_of_the_build_name_their_feature() {
+        #[cfg(not(feature = "toml"))]
+        assert!(super::toml("").unwrap_err().contains("`toml` feature"));
+        #[cfg(not(feature = "yaml"))]
+        assert!(super::yaml("").unwrap_err().contains("`yaml` feature"));
+    }
+}
@@ -4,17 +4,44 @@
 //! Depending on this alone leaves protobuf codegen, and the `protoc` a build script would need, out of
 //! the build.
 
+mod auth;
+mod balance;
+mod channel;
 mod config;
 mod connect;
+mod diagnose;
+mod formats;
+mod loader;
+mod proxy;
+mod reload;
+mod retry;
+mod server;
+mod trust;
+#[cfg(unix)]
+mod unix;
 mod utils;
 
-pub use config::{ClientConfig, ClientConfigArgs, ConfigError};
-pub use connect::{connect, https_connector, ConnectionError};
+pub use auth::{Credentials, TokenSource};
+pub use balance::HealthCheck;
+pub use channel::Channel;
+pub use config::{CertificateFiles, ClientConfig, ClientConfigArgs, ConfigError};
+pub use connect::{connect, connect_lazy, https_connector, ConnectionError};
+pub use diagnose::{
+    diagnose, CertificateSummary, Outcome, Report, ServerVersions, Step, StepReport, TlsReport,
+};
+pub use loader::{ConfigLoader, LoadedArgs, Origin};
+pub use proxy::{Proxy, ProxyConnector};
 // Snafu's context selectors, so a caller in another crate can build the error with the location
 // captured at its own call site. Hidden: this is how the error is built, not API to design against.
 #[doc(hidden)]
-pub use connect::{ConfigSnafu, IoSnafu, TlsSnafu, TransportSnafu};
+pub use connect::{ConfigSnafu, IoSnafu, TlsSnafu, TransportSnafu, UnresolvedSnafu};
+pub use retry::{ClientStreaming, Retry, RetryPolicy};
+pub use server::{PeerCertificate, ServerConfig, ServerConfigArgs};
 pub use utils::ReadEnvError;
+// The readers behind `ClientConfigArgs::from_env`, so that settings read elsewhere (the worker's
+// `ComputePlane__*`) accept the same spellings and report the same errors. Hidden for the same reason.
+#[doc(hidden)]
+pub use utils::{read_env, read_env_bool};
 
 /// Re-exports of this crate's own dependencies, at the versions it was built with.
 ///
@@ -27,5 +54,6 @@ pub mod reexports {
     pub use rustls;
     #[cfg(feature = "serde")]
     pub use serde;
+    pub use tokio_rustls;
     pub use tonic;
 }
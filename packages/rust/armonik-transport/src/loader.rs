@@ -0,0 +1,379 @@
+//! Reading [`ClientConfigArgs`] from several places at once, each overriding the ones before it: the
+//! defaults, a profile, configuration files, the environment, then values set by the caller.
+//!
+//! The settings go by their names in the C# SDK, so that an `appsettings.json` is read as it is, and
+//! where each value was found is kept for the errors to point at.
+
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+use snafu::ResultExt;
+
+use crate::config::{
+    Blamed, ConfigError, EnvSnafu, Field, InvalidBooleanSnafu, InvalidFileSnafu, InvalidSnafu,
+    UnknownProfileSnafu, UnknownSettingSnafu, SETTINGS,
+};
+use crate::utils::NotUnicodeSnafu;
+use crate::{ClientConfig, ClientConfigArgs};
+
+/// The section the settings are in, as in `appsettings.json`.
+const SECTION: &str = "GrpcClient";
+
+/// The key of a profiles file naming the profile to use when none is asked for.
+const CURRENT_PROFILE: &str = "current_profile";
+
+/// Where the value of a setting was found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Origin {
+    /// Not set anywhere
+    #[default]
+    Default,
+    /// A profile of the profiles file
+    Profile { path: PathBuf, name: String },
+    /// A configuration file
+    File(PathBuf),
+    /// An environment variable, by its name
+    Env(String),
+    /// Set by the caller
+    Override,
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => f.write_str("the defaults"),
+            Self::Profile { path, name } => {
+                write!(f, "profile `{name}` of `{}`", path.display())
+            }
+            Self::File(path) => write!(f, "`{}`", path.display()),
+            Self::Env(name) => write!(f, "environment variable `{name}`"),
+            Self::Override => f.write_str("an override"),
+        }
+    }
+}
+
+/// Reads [`ClientConfigArgs`] from layers of sources.
+///
+/// From lowest to highest precedence: the defaults, a profile of the profiles file, each of the files
+/// in the order they were added, the `GrpcClient__*` environment variables, then the values set with
+/// [`set`](Self::set). A file may hold the settings in a `"GrpcClient"` section, as `appsettings.json`
+/// does, or at its top level; setting names are matched ignoring case and underscores, so `Endpoint`,
+/// `endpoint` and `cert_pem` all name a setting.
+///
+/// The profiles file defaults to `$XDG_CONFIG_HOME/armonik/config.toml`, or
+/// `~/.config/armonik/config.toml`. Each profile is a table under `profiles`, and the one used unless
+/// another is asked for is named by `current_profile`:
+///
+/// ```toml
+/// current_profile = "dev"
+///
+/// [profiles.dev]
+/// Endpoint = "http://localhost:5001"
+/// ```
+///
+/// Reading it takes the `toml` feature: without it, the default profiles file is not looked for.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    profiles_file: Option<PathBuf>,
+    profile: Option<String>,
+    files: Vec<PathBuf>,
+    skip_env: bool,
+    overrides: Vec<(String, String)>,
+}
+
+impl ConfigLoader {
+    /// A loader reading the profiles file and the environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the profiles from `path` rather than from the default location.
+    pub fn profiles_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.profiles_file = Some(path.into());
+        self
+    }
+
+    /// Use the profile `name` rather than the current one: it has to exist.
+    pub fn profile(mut self, name: impl Into<String>) -> Self {
+        self.profile = Some(name.into());
+        self
+    }
+
+    /// Read settings from the JSON, TOML or YAML file at `path`, over those of the files added before.
+    ///
+    /// TOML takes the `toml` feature, and YAML the `yaml` one.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Leave the environment out.
+    pub fn without_env(mut self) -> Self {
+        self.skip_env = true;
+        self
+    }
+
+    /// Set the setting `name` to `value`, over every other source.
+    pub fn set(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.overrides.push((name.into(), value.into()));
+        self
+    }
+
+    /// Read every source.
+    pub fn load(&self) -> Result<LoadedArgs, ConfigError> {
+        let mut loaded = LoadedArgs::default();
+
+        if let Some((path, name, settings)) = self.read_profile()? {
+            loaded.apply_tree(settings, &Origin::Profile { path, name })?;
+        }
+
+        for path in &self.files {
+            let tree = crate::formats::read(path)?;
+            loaded.apply_tree(tree, &Origin::File(path.clone()))?;
+        }
+
+        if !self.skip_env {
+            for (name, field) in SETTINGS {
+                let variable = format!("{SECTION}__{name}");
+                let value = match std::env::var(&variable) {
+                    Ok(value) => value,
+                    Err(std::env::VarError::NotPresent) => continue,
+                    Err(std::env::VarError::NotUnicode(value)) => {
+                        return Err(NotUnicodeSnafu {
+                            name: variable,
+                            value,
+                        }
+                        .build())
+                        .context(EnvSnafu {});
+                    }
+                };
+                loaded.apply(name, *field, value, Origin::Env(variable))?;
+            }
+        }
+
+        for (name, value) in &self.overrides {
+            let (name, field) =
+                setting(name).ok_or_else(|| UnknownSettingSnafu { name: name.clone() }.build())?;
+            loaded.apply(name, field, value.clone(), Origin::Override)?;
+        }
+
+        Ok(loaded)
+    }
+
+    /// The path, name and settings of the profile to use, if any.
+    fn read_profile(&self) -> Result<Option<(PathBuf, String, Value)>, ConfigError> {
+        let Some(path) = self.profiles_file.clone().or_else(default_profiles_file) else {
+            return Ok(None);
+        };
+        // Without a profile asked for, not having any is no error; nor is a build that cannot read the
+        // default profiles file, which is TOML.
+        if self.profile.is_none()
+            && (!path.exists() || (self.profiles_file.is_none() && !cfg!(feature = "toml")))
+        {
+            return Ok(None);
+        }
+
+        let mut tree = crate::formats::read(&path)?;
+        let name = match &self.profile {
+            Some(name) => name.clone(),
+            None => match tree.get(CURRENT_PROFILE) {
+                Some(Value::String(name)) => name.clone(),
+                None => return Ok(None),
+                Some(_) => {
+                    return InvalidFileSnafu {
+                        path: path.display().to_string(),
+                        msg: format!("`{CURRENT_PROFILE}` is not the name of a profile"),
+                    }
+                    .fail()
+                }
+            },
+        };
+        let settings = tree
+            .get_mut("profiles")
+            .and_then(|profiles| profiles.get_mut(&name))
+            .map(Value::take)
+            .ok_or_else(|| {
+                UnknownProfileSnafu {
+                    name: name.clone(),
+                    path: path.display().to_string(),
+                }
+                .build()
+            })?;
+        Ok(Some((path, name, settings)))
+    }
+}
+
+/// `$XDG_CONFIG_HOME/armonik/config.toml`, or `~/.config/armonik/config.toml`.
+fn default_profiles_file() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|config| !config.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| Path::new(&home).join(".config"))
+        })?;
+    Some(config.join("armonik").join("config.toml"))
+}
+
+/// The setting `name` stands for, ignoring case and underscores.
+fn setting(name: &str) -> Option<(&'static str, Field)> {
+    let name = name.replace('_', "");
+    SETTINGS
+        .iter()
+        .find(|(setting, _)| setting.eq_ignore_ascii_case(&name))
+        .copied()
+}
+
+/// The string a setting is read from, for a value of a file.
+fn text(value: Value) -> Option<String> {
+    match value {
+        Value::Null | Value::Object(_) => None,
+        Value::String(value) => Some(value),
+        Value::Array(items) => Some(
+            items
+                .into_iter()
+                .filter_map(text)
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        value => Some(value.to_string()),
+    }
+}
+
+/// [`ClientConfigArgs`] read from layers of sources, with where each setting was found.
+#[derive(Debug, Clone)]
+pub struct LoadedArgs {
+    args: ClientConfigArgs,
+    /// Indexed as [`SETTINGS`].
+    origins: Vec<Origin>,
+}
+
+impl Default for LoadedArgs {
+    fn default() -> Self {
+        Self {
+            args: ClientConfigArgs::default(),
+            origins: vec![Origin::Default; SETTINGS.len()],
+        }
+    }
+}
+
+impl LoadedArgs {
+    /// Get the arguments read
+    pub fn args(&self) -> &ClientConfigArgs {
+        &self.args
+    }
+
+    /// Get the arguments read, leaving where they were found
+    pub fn into_args(self) -> ClientConfigArgs {
+        self.args
+    }
+
+    /// Where the setting `name` was found, as named in the C# SDK or after its field: `None` if there is
+    /// no such setting.
+    pub fn origin(&self, name: &str) -> Option<&Origin> {
+        let (name, _) = setting(name)?;
+        let index = SETTINGS.iter().position(|(setting, _)| *setting == name)?;
+        self.origins.get(index)
+    }
+
+    /// Every setting, as named in the C# SDK, with where it was found.
+    pub fn origins(&self) -> impl Iterator<Item = (&'static str, &Origin)> {
+        SETTINGS.iter().map(|(name, _)| *name).zip(&self.origins)
+    }
+
+    /// Build the configuration from the arguments read.
+    ///
+    /// An error names the settings it is about, and where they were found.
+    pub fn into_config(self) -> Result<ClientConfig, ConfigError> {
+        ClientConfig::read_args(self.args.clone()).or_else(|Blamed { settings, error }| {
+            let blame = self.blame(settings);
+            Err(error).context(InvalidSnafu { blame })
+        })
+    }
+
+    /// The settings `names`, which an error is about, with where they were found: those left to their
+    /// defaults only when none of them was set anywhere.
+    fn blame(&self, names: &[&str]) -> String {
+        let blamed = SETTINGS
+            .iter()
+            .zip(&self.origins)
+            .filter(|((name, _), _)| names.contains(name))
+            .collect::<Vec<_>>();
+        let set = blamed.iter().any(|(_, origin)| **origin != Origin::Default);
+        let blamed = blamed
+            .into_iter()
+            .filter(|(_, origin)| !set || **origin != Origin::Default)
+            .map(|((name, _), origin)| format!("`{name}` from {origin}"))
+            .collect::<Vec<_>>();
+        if blamed.is_empty() {
+            return String::from("with every setting left to its default");
+        }
+        blamed.join(", ")
+    }
+
+    /// Set the settings of the file tree `tree`, found at `origin`.
+    fn apply_tree(&mut self, tree: Value, origin: &Origin) -> Result<(), ConfigError> {
+        let Value::Object(mut tree) = tree else {
+            return Err(self.invalid_file(origin, "the settings are not a mapping"));
+        };
+        // The `GrpcClient` section when there is one: the rest of an `appsettings.json` is not ours.
+        let section = tree
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(SECTION))
+            .cloned();
+        let settings = match section.and_then(|section| tree.remove(&section)) {
+            Some(Value::Object(settings)) => settings,
+            Some(_) => return Err(self.invalid_file(origin, "`GrpcClient` is not a mapping")),
+            None => std::mem::replace(&mut tree, Map::new()),
+        };
+
+        for (key, value) in settings {
+            let Some((name, field)) = setting(&key) else {
+                tracing::debug!("Ignoring `{key}` from {origin}: it is not a client setting");
+                continue;
+            };
+            let Some(value) = text(value) else {
+                continue;
+            };
+            self.apply(name, field, value, origin.clone())?;
+        }
+        Ok(())
+    }
+
+    fn invalid_file(&self, origin: &Origin, msg: &str) -> ConfigError {
+        let path = match origin {
+            Origin::File(path) | Origin::Profile { path, .. } => path.display().to_string(),
+            origin => origin.to_string(),
+        };
+        InvalidFileSnafu { path, msg }.build()
+    }
+
+    /// Set the setting `name` to `value`, found at `origin`.
+    fn apply(
+        &mut self,
+        name: &'static str,
+        field: Field,
+        value: String,
+        origin: Origin,
+    ) -> Result<(), ConfigError> {
+        match field {
+            Field::Text(field) => *field(&mut self.args) = value,
+            Field::Flag(field) => {
+                *field(&mut self.args) = crate::utils::parse_bool(&value).ok_or_else(|| {
+                    InvalidBooleanSnafu {
+                        name,
+                        value,
+                        origin: origin.clone(),
+                    }
+                    .build()
+                })?;
+            }
+        }
+        if let Some(index) = SETTINGS.iter().position(|(setting, _)| *setting == name) {
+            self.origins[index] = origin;
+        }
+        Ok(())
+    }
+}
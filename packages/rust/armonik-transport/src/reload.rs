@@ -0,0 +1,309 @@
+//! Reading the TLS material again once its files changed, for certificates rotated under a running
+//! client, as cert-manager does.
+//!
+//! The files are checked on each handshake, and the material swapped whole for the handshakes that
+//! follow. Connections already established keep what they were established with, so the channels
+//! keep working however the files change.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::{ResolvesClientCert, WebPkiServerVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::sign::CertifiedKey;
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use snafu::ResultExt;
+
+use crate::config::{read_certificates, read_private_key, ConfigError, InvalidIdentitySnafu};
+use crate::utils::FileStamp;
+
+/// Something read from files, and read again from them once they changed.
+struct Watched<T> {
+    paths: Vec<PathBuf>,
+    load: Box<dyn Fn() -> Result<T, ConfigError> + Send + Sync>,
+    current: Mutex<(Vec<Option<FileStamp>>, T)>,
+}
+
+impl<T: Clone> Watched<T> {
+    /// Read from `paths` with `load`, failing if it does.
+    fn new(
+        paths: Vec<PathBuf>,
+        load: impl Fn() -> Result<T, ConfigError> + Send + Sync + 'static,
+    ) -> Result<Self, ConfigError> {
+        // Stamped before reading: a change while reading is seen by the next check.
+        let stamps = stamps(&paths);
+        let value = load()?;
+        Ok(Self {
+            paths,
+            load: Box::new(load),
+            current: Mutex::new((stamps, value)),
+        })
+    }
+
+    /// The value as of the files now, read again if any of them changed.
+    ///
+    /// Files that cannot be read, as when only some of them were replaced yet, leave the previous value
+    /// in use: the next check tries again.
+    fn get(&self) -> T {
+        let mut current = self.current.lock().unwrap_or_else(|p| p.into_inner());
+
+        let stamps = stamps(&self.paths);
+        if stamps != current.0 {
+            match (self.load)() {
+                Ok(value) => {
+                    tracing::info!("Read {} again", self.describe());
+                    *current = (stamps, value);
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "Could not read {} again, keeping the previous ones: {err}",
+                        self.describe()
+                    );
+                }
+            }
+        }
+
+        current.1.clone()
+    }
+
+    fn describe(&self) -> String {
+        self.paths
+            .iter()
+            .map(|path| format!("`{}`", path.display()))
+            .collect::<Vec<_>>()
+            .join(" and ")
+    }
+}
+
+impl<T> std::fmt::Debug for Watched<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watched")
+            .field("paths", &self.paths)
+            .finish_non_exhaustive()
+    }
+}
+
+fn stamps(paths: &[PathBuf]) -> Vec<Option<FileStamp>> {
+    paths.iter().map(|path| FileStamp::of(path)).collect()
+}
+
+/// The client certificate chain and key, read again from their files once they changed.
+#[derive(Debug)]
+pub(crate) struct ReloadingIdentity {
+    key: Watched<Arc<CertifiedKey>>,
+}
+
+impl ReloadingIdentity {
+    /// Read the certificate chain at `cert` and the key at `key`, which have to match.
+    pub(crate) fn new(
+        cert: PathBuf,
+        key: PathBuf,
+        provider: Arc<CryptoProvider>,
+    ) -> Result<Self, ConfigError> {
+        let paths = vec![cert.clone(), key.clone()];
+        Ok(Self {
+            key: Watched::new(paths, move || {
+                let certified = CertifiedKey::from_der(
+                    read_certificates(&cert)?,
+                    read_private_key(&key)?,
+                    &provider,
+                )
+                .context(InvalidIdentitySnafu {})?;
+                Ok(Arc::new(certified))
+            })?,
+        })
+    }
+}
+
+impl ResolvesClientCert for ReloadingIdentity {
+    fn resolve(
+        &self,
+        _root_hint_subjects: &[&[u8]],
+        _sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.get())
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+/// Verifies the server against Certificate Authorities read again from their file once it changed.
+#[derive(Debug)]
+pub(crate) struct ReloadingVerifier {
+    verifier: Watched<Arc<WebPkiServerVerifier>>,
+}
+
+impl ReloadingVerifier {
+    /// Read the Certificate Authorities at `ca_cert`, to trust along with the system ones if
+    /// `native_roots` is set.
+    pub(crate) fn new(
+        ca_cert: PathBuf,
+        native_roots: bool,
+        provider: Arc<CryptoProvider>,
+    ) -> Result<Self, ConfigError> {
+        Ok(Self {
+            verifier: Watched::new(vec![ca_cert.clone()], move || {
+                crate::trust::verifier(read_certificates(&ca_cert)?, native_roots, provider.clone())
+            })?,
+        })
+    }
+}
+
+impl ServerCertVerifier for ReloadingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verifier.get().verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )
+    }
+
+    // The signatures are checked with the public key of the certificate just verified: what changes
+    // with the Certificate Authority does not matter to them.
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verifier
+            .get()
+            .verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verifier
+            .get()
+            .verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.verifier.get().supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, IsCa, KeyPair};
+
+    /// A fresh directory to put the files of one test in.
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "armonik-transport-reload-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Replace the file at `path` as cert-manager does: a new file moved over the old one.
+    fn replace(path: &Path, contents: &str) {
+        let new = path.with_extension("new");
+        std::fs::write(&new, contents).unwrap();
+        std::fs::rename(new, path).unwrap();
+    }
+
+    fn provider() -> Arc<CryptoProvider> {
+        Arc::new(rustls::crypto::ring::default_provider())
+    }
+
+    fn ca() -> CertifiedIssuer<'static, KeyPair> {
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        CertifiedIssuer::self_signed(params, KeyPair::generate().unwrap()).unwrap()
+    }
+
+    /// A certificate for `localhost` signed by `ca`, and its key.
+    fn leaf(ca: &CertifiedIssuer<'static, KeyPair>) -> (rcgen::Certificate, KeyPair) {
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec![String::from("localhost")])
+            .unwrap()
+            .signed_by(&key, ca)
+            .unwrap();
+        (cert, key)
+    }
+
+    #[test]
+    fn a_rotated_identity_is_used_for_the_next_handshakes() {
+        let dir = dir("identity");
+        let (cert_path, key_path) = (dir.join("tls.crt"), dir.join("tls.key"));
+        let ca = ca();
+        let (first, first_key) = leaf(&ca);
+        std::fs::write(&cert_path, first.pem()).unwrap();
+        std::fs::write(&key_path, first_key.serialize_pem()).unwrap();
+
+        let identity =
+            ReloadingIdentity::new(cert_path.clone(), key_path.clone(), provider()).unwrap();
+        let resolved = identity.resolve(&[], &[]).unwrap();
+        assert_eq!(resolved.cert[0].as_ref(), first.der().as_ref());
+
+        let (second, second_key) = leaf(&ca);
+        replace(&key_path, &second_key.serialize_pem());
+        replace(&cert_path, &second.pem());
+        let resolved = identity.resolve(&[], &[]).unwrap();
+        assert_eq!(resolved.cert[0].as_ref(), second.der().as_ref());
+
+        // Half a rotation: the key no longer matches, so the previous pair stays in use.
+        replace(&key_path, &first_key.serialize_pem());
+        let resolved = identity.resolve(&[], &[]).unwrap();
+        assert_eq!(resolved.cert[0].as_ref(), second.der().as_ref());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_rotated_ca_cert_verifies_the_next_handshakes() {
+        let dir = dir("ca");
+        let ca_path = dir.join("ca.crt");
+        let (old_ca, new_ca) = (ca(), ca());
+        let (old_server, _) = leaf(&old_ca);
+        let (new_server, _) = leaf(&new_ca);
+        std::fs::write(&ca_path, old_ca.pem()).unwrap();
+
+        let verifier = ReloadingVerifier::new(ca_path.clone(), false, provider()).unwrap();
+        let verify = |server: &rcgen::Certificate| {
+            verifier.verify_server_cert(
+                server.der(),
+                &[],
+                &ServerName::try_from("localhost").unwrap(),
+                &[],
+                UnixTime::now(),
+            )
+        };
+        assert!(verify(&old_server).is_ok());
+        assert!(verify(&new_server).is_err());
+
+        replace(&ca_path, &new_ca.pem());
+        assert!(verify(&old_server).is_err());
+        assert!(verify(&new_server).is_ok());
+
+        // A file that is no certificate leaves the current one in use.
+        replace(&ca_path, "not a certificate");
+        assert!(verify(&new_server).is_ok());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
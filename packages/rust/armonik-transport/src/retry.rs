@@ -0,0 +1,516 @@
+//! Retrying the calls the control plane fails transiently, with exponential backoff.
+//!
+//! This sits below the generated clients, on raw HTTP requests: a retried call is the same request sent
+//! again, so its body is kept as it is sent, up to a limit, to be replayed.
+
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::body::{Bytes, Frame, SizeHint};
+use tonic::body::Body;
+use tonic::codegen::http;
+use tonic::Code;
+
+/// Above this, a request body is no longer kept to be replayed, and its call is not retried once sent.
+///
+/// The gRPC default for the largest message, so no unary call this crate makes is left out.
+const MAX_REPLAY_BYTES: usize = 4 * 1024 * 1024;
+
+/// When and how often a failed call is sent again
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+    /// Number of times a call is sent at most, the first included: `1` disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Bound on the delay between two attempts, however many there were
+    pub max_backoff: Duration,
+    /// Factor the delay grows by after each retry
+    pub backoff_multiplier: f64,
+    /// Status codes that are worth retrying on
+    pub retryable_codes: Vec<Code>,
+}
+
+impl Default for RetryPolicy {
+    /// No retries; the delays are those of the C# SDK, for when `max_attempts` is raised.
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            backoff_multiplier: 1.5,
+            retryable_codes: vec![
+                Code::Unavailable,
+                Code::ResourceExhausted,
+                Code::DeadlineExceeded,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a call may be sent more than once.
+    pub fn is_enabled(&self) -> bool {
+        self.max_attempts > 1
+    }
+
+    /// The delay before the `retry`-th retry, counted from 1.
+    ///
+    /// Drawn between half the exponential delay and the whole of it, so that clients failed by the same
+    /// outage do not all come back at once.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = i32::try_from(retry.saturating_sub(1)).unwrap_or(i32::MAX);
+        let backoff = (self.initial_backoff.as_secs_f64() * self.backoff_multiplier.powi(exponent))
+            .min(self.max_backoff.as_secs_f64());
+        // `min` lets NaN through, and `from_secs_f64` panics on it.
+        let backoff = if backoff.is_finite() {
+            backoff
+        } else {
+            self.max_backoff.as_secs_f64()
+        };
+        Duration::from_secs_f64(backoff * (0.5 + jitter() / 2.0))
+    }
+
+    fn is_retryable(&self, code: Code) -> bool {
+        self.retryable_codes.contains(&code)
+    }
+}
+
+/// A number in `[0, 1)`, different on each call.
+///
+/// The standard library has no random numbers, but it seeds each `RandomState` randomly, which is plenty
+/// to spread retries out.
+fn jitter() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    let bits = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Marks a request whose body is a client stream, as a request extension.
+///
+/// Such a call is retried only if none of its stream was sent: sending it again would mean the server
+/// sees part of it twice, or the stream having to be kept whole in memory. Without the mark, a request
+/// whose body is small enough is replayed whole.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ClientStreaming;
+
+/// Retries the calls of the inner service according to a [`RetryPolicy`].
+///
+/// A call is retried when it fails with one of the retryable codes, as long as its request can be sent
+/// again: see [`ClientStreaming`]. A call that got a response has it passed on as is, so only the
+/// failures a server reports before answering, which is how they are reported in practice, are retried.
+#[derive(Debug, Clone)]
+pub struct Retry<S> {
+    inner: S,
+    policy: Arc<RetryPolicy>,
+}
+
+impl<S> Retry<S> {
+    /// Retry the calls of `inner` according to `policy`.
+    pub fn new(inner: S, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy: Arc::new(policy),
+        }
+    }
+
+    /// Get the policy calls are retried according to
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Get the inner service
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B> tower_service::Service<http::Request<Body>> for Retry<S>
+where
+    S: tower_service::Service<http::Request<Body>, Response = http::Response<B>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    S::Error: Error + Send + Sync + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        if !self.policy.is_enabled() {
+            return Box::pin(self.inner.call(request));
+        }
+
+        // The service `poll_ready` was called on is the one to call first; the clone left in its place
+        // is for the next call.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let policy = self.policy.clone();
+
+        let (mut parts, body) = request.into_parts();
+        // The time the caller gave the call bounds all its attempts and the delays between them, not
+        // each of them: every retry is sent with what is left of it.
+        let deadline = grpc_timeout(&parts.headers)
+            .and_then(|timeout| tokio::time::Instant::now().checked_add(timeout));
+        let limit = if parts.extensions.get::<ClientStreaming>().is_some() {
+            0
+        } else {
+            MAX_REPLAY_BYTES
+        };
+        let replay = Arc::new(Mutex::new(Replay::new(body, limit)));
+
+        Box::pin(async move {
+            let mut attempt = 1;
+            loop {
+                let body = Body::new(ReplayBody {
+                    replay: replay.clone(),
+                    position: 0,
+                });
+                let outcome = inner
+                    .call(http::Request::from_parts(parts.clone(), body))
+                    .await;
+
+                let code = match &outcome {
+                    Ok(response) => response_code(response),
+                    Err(error) => Some(error_code(error)),
+                };
+                let Some(code) = code.filter(|code| policy.is_retryable(*code)) else {
+                    return outcome;
+                };
+                if attempt >= policy.max_attempts {
+                    tracing::debug!("Giving up on {} after {attempt} attempts", parts.uri.path());
+                    return outcome;
+                }
+                if !replay
+                    .lock()
+                    .unwrap_or_else(|p| p.into_inner())
+                    .can_replay()
+                {
+                    tracing::debug!(
+                        "Not retrying {}: its request was already sent and cannot be sent again",
+                        parts.uri.path()
+                    );
+                    return outcome;
+                }
+
+                let backoff = policy.backoff(attempt);
+                let remaining = deadline.map(|deadline| {
+                    deadline.saturating_duration_since(tokio::time::Instant::now())
+                });
+                if remaining.is_some_and(|remaining| remaining <= backoff) {
+                    tracing::debug!(
+                        "Giving up on {} after {attempt} attempts: its deadline passes before the next one",
+                        parts.uri.path()
+                    );
+                    return outcome;
+                }
+                tracing::debug!(
+                    "{} failed with {code:?} on attempt {attempt}, retrying in {backoff:?}",
+                    parts.uri.path()
+                );
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+                if let Some(deadline) = deadline {
+                    let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+                    parts
+                        .headers
+                        .insert(GRPC_TIMEOUT, encode_grpc_timeout(remaining));
+                }
+
+                std::future::poll_fn(|cx| inner.poll_ready(cx)).await?;
+            }
+        })
+    }
+}
+
+const GRPC_TIMEOUT: &str = "grpc-timeout";
+
+/// The timeout of the `grpc-timeout` header: up to 8 digits, then the unit.
+fn grpc_timeout(headers: &http::HeaderMap) -> Option<Duration> {
+    let value = headers.get(GRPC_TIMEOUT)?.to_str().ok()?;
+    let (amount, unit) = value.split_at(value.len().checked_sub(1)?);
+    if amount.is_empty() || amount.len() > 8 || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount = amount.parse::<u64>().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+/// `timeout` as a `grpc-timeout` header, in the finest unit it fits in 8 digits of, rounded up.
+fn encode_grpc_timeout(timeout: Duration) -> http::HeaderValue {
+    const MAX: u128 = 99_999_999;
+    let nanos = timeout.as_nanos();
+    let (amount, unit) = [
+        (1, "n"),
+        (1_000, "u"),
+        (1_000_000, "m"),
+        (1_000_000_000, "S"),
+        (60_000_000_000, "M"),
+        (3_600_000_000_000, "H"),
+    ]
+    .into_iter()
+    .map(|(scale, unit)| (nanos.div_ceil(scale), unit))
+    .find(|(amount, _)| *amount <= MAX)
+    .unwrap_or((MAX, "H"));
+    http::HeaderValue::from_str(&format!("{amount}{unit}")).expect("digits and a letter")
+}
+
+/// The code of a failure the server reported before answering, if it is one.
+///
+/// A gRPC server that fails a call straight away answers with the status in the headers and no body,
+/// which is the only place a status can be read without waiting on the body. A proxy failing in front
+/// of it answers an HTTP status instead, mapped as the gRPC spec maps them.
+fn response_code<B>(response: &http::Response<B>) -> Option<Code> {
+    if let Some(status) = tonic::Status::from_header_map(response.headers()) {
+        return Some(status.code()).filter(|code| *code != Code::Ok);
+    }
+
+    match response.status() {
+        http::StatusCode::OK => None,
+        http::StatusCode::BAD_REQUEST => Some(Code::Internal),
+        http::StatusCode::UNAUTHORIZED => Some(Code::Unauthenticated),
+        http::StatusCode::FORBIDDEN => Some(Code::PermissionDenied),
+        http::StatusCode::NOT_FOUND => Some(Code::Unimplemented),
+        http::StatusCode::TOO_MANY_REQUESTS
+        | http::StatusCode::BAD_GATEWAY
+        | http::StatusCode::SERVICE_UNAVAILABLE
+        | http::StatusCode::GATEWAY_TIMEOUT => Some(Code::Unavailable),
+        _ => Some(Code::Unknown),
+    }
+}
+
+/// The code `tonic` reports a call that got no response with.
+///
+/// Mirrors `tonic::Status::from_error` for the errors worth telling apart: it takes the error by value,
+/// and this one has to be handed back to the caller as is.
+fn error_code(error: &(dyn Error + 'static)) -> Code {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(status) = error.downcast_ref::<tonic::Status>() {
+            return status.code();
+        }
+        if error.is::<tonic::TimeoutExpired>() {
+            return Code::Cancelled;
+        }
+        if error.is::<tonic::ConnectError>() {
+            return Code::Unavailable;
+        }
+        source = error.source();
+    }
+    Code::Unknown
+}
+
+/// A request body as it is sent, kept so that it can be sent again.
+struct Replay {
+    /// What is left of the original body
+    source: Body,
+    /// The data frames taken from `source` so far
+    frames: Vec<Bytes>,
+    /// The size of `frames`
+    length: usize,
+    /// Past this, `frames` are dropped
+    limit: usize,
+    /// Whether anything was taken from `source`
+    started: bool,
+    /// Whether `source` is exhausted
+    ended: bool,
+    /// Whether something was sent that is not in `frames`
+    lost: bool,
+}
+
+impl Replay {
+    fn new(source: Body, limit: usize) -> Self {
+        Self {
+            source,
+            frames: Vec::new(),
+            length: 0,
+            limit,
+            started: false,
+            ended: false,
+            lost: false,
+        }
+    }
+
+    /// Whether the body can be sent again from the start: either none of it was sent, or all of it was
+    /// kept.
+    fn can_replay(&self) -> bool {
+        !self.started || (self.ended && !self.lost)
+    }
+}
+
+/// One attempt at sending a [`Replay`]: what was kept first, then the rest of the source.
+struct ReplayBody {
+    replay: Arc<Mutex<Replay>>,
+    position: usize,
+}
+
+impl hyper::body::Body for ReplayBody {
+    type Data = Bytes;
+    type Error = tonic::Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let replay = self.replay.clone();
+        let mut replay = replay.lock().unwrap_or_else(|p| p.into_inner());
+
+        if let Some(frame) = replay.frames.get(self.position) {
+            let frame = frame.clone();
+            self.position += 1;
+            return Poll::Ready(Some(Ok(Frame::data(frame))));
+        }
+        if replay.ended {
+            return Poll::Ready(None);
+        }
+
+        let frame = std::task::ready!(Pin::new(&mut replay.source).poll_frame(cx));
+        replay.started = true;
+        match &frame {
+            Some(Ok(frame)) => match frame.data_ref() {
+                Some(data) if !replay.lost && replay.length + data.len() <= replay.limit => {
+                    replay.length += data.len();
+                    replay.frames.push(data.clone());
+                    self.position += 1;
+                }
+                Some(_) => {
+                    replay.lost = true;
+                    replay.frames = Vec::new();
+                }
+                // Trailers: gRPC requests have none, so they are passed on rather than kept.
+                None => replay.lost = true,
+            },
+            Some(Err(_)) => replay.lost = true,
+            None => replay.ended = true,
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        let replay = self.replay.lock().unwrap_or_else(|p| p.into_inner());
+        replay.ended && self.position >= replay.frames.len()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let replay = self.replay.lock().unwrap_or_else(|p| p.into_inner());
+        if replay.ended && !replay.lost {
+            let remaining: usize = replay.frames[self.position.min(replay.frames.len())..]
+                .iter()
+                .map(Bytes::len)
+                .sum();
+            SizeHint::with_exact(remaining as u64)
+        } else {
+            SizeHint::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            backoff_multiplier: 2.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn backoffs_grow_exponentially_within_half_of_their_value() {
+        let policy = policy();
+        for (retry, full) in [(1, 100), (2, 200), (3, 400), (4, 800)] {
+            let backoff = policy.backoff(retry);
+            assert!(
+                backoff >= Duration::from_millis(full / 2)
+                    && backoff <= Duration::from_millis(full),
+                "retry {retry}: {backoff:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn backoffs_stop_growing_at_the_maximum() {
+        let policy = policy();
+        assert!(policy.backoff(10) <= Duration::from_secs(1));
+        assert!(policy.backoff(u32::MAX) <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn the_default_policy_does_not_retry() {
+        assert!(!RetryPolicy::default().is_enabled());
+    }
+
+    #[test]
+    fn a_status_in_the_headers_is_read_as_the_outcome() {
+        let response = http::Response::builder()
+            .header("grpc-status", "14")
+            .body(())
+            .unwrap();
+        assert_eq!(response_code(&response), Some(Code::Unavailable));
+
+        let response = http::Response::builder()
+            .header("grpc-status", "0")
+            .body(())
+            .unwrap();
+        assert_eq!(response_code(&response), None);
+
+        // A proper answer: the status will be in the trailers, and the call is not failed.
+        let response = http::Response::builder().body(()).unwrap();
+        assert_eq!(response_code(&response), None);
+    }
+
+    #[test]
+    fn grpc_timeouts_are_written_as_they_are_read() {
+        for (timeout, value) in [
+            (Duration::from_nanos(250), "250n"),
+            (Duration::from_millis(1500), "1500000u"),
+            (Duration::from_secs(300), "300000m"),
+            (Duration::new(200_000, 1), "200001S"),
+        ] {
+            let header = encode_grpc_timeout(timeout);
+            assert_eq!(header, value);
+
+            let mut headers = http::HeaderMap::new();
+            headers.insert(GRPC_TIMEOUT, header);
+            assert!(grpc_timeout(&headers).unwrap() >= timeout, "{value}");
+        }
+        // Longer than the header can tell: as long as it can.
+        assert_eq!(encode_grpc_timeout(Duration::MAX), "99999999H");
+    }
+
+    #[test]
+    fn a_proxy_failing_is_read_as_unavailable() {
+        let response = http::Response::builder()
+            .status(http::StatusCode::SERVICE_UNAVAILABLE)
+            .body(())
+            .unwrap();
+        assert_eq!(response_code(&response), Some(Code::Unavailable));
+    }
+}
@@ -0,0 +1,567 @@
+//! What it takes to serve: the address to listen on, TLS and the client certificates it verifies, and
+//! how connections are kept alive.
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use snafu::ResultExt;
+
+use crate::config::{
+    optional_duration, read_certificates, read_private_key, ConfigError, EnvSnafu,
+    IncompatibleOptionsSnafu, InvalidAddressSnafu, InvalidClientCaSnafu, InvalidIntegerSnafu,
+    InvalidServerTlsSnafu,
+};
+
+/// Options for serving gRPC services
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ServerConfig {
+    /// Address to listen on
+    pub address: SocketAddr,
+    /// TLS identity of the server: certificate chain, the server certificate first, + key, defaults to
+    /// none: no TLS
+    pub identity: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    /// CA certificates the certificates of clients are verified against, defaults to none: clients are
+    /// not asked for any
+    pub client_ca: Vec<CertificateDer<'static>>,
+    /// Refuse clients that present no certificate, rather than only those that present one `client_ca`
+    /// did not issue, defaults to false
+    pub require_client_cert: bool,
+    /// TCP keepalive duration, defaults to no keepalive
+    pub tcp_keepalive: Option<Duration>,
+    /// Interval between TCP keepalive probes, defaults to OS default
+    pub tcp_keepalive_interval: Option<Duration>,
+    /// Number of TCP keepalive retries, defaults to OS default
+    pub tcp_keepalive_retries: Option<u32>,
+    /// Enable Nagle's algorithm (disable TCP_NODELAY), defaults to false
+    pub tcp_nagle_algorithm: bool,
+    /// HTTP/2 PING frame interval, defaults to no keepalive
+    pub http2_keep_alive_interval: Option<Duration>,
+    /// HTTP/2 PING timeout, defaults to 20s
+    pub http2_keep_alive_timeout: Option<Duration>,
+    /// Number of calls a connection may carry at once, defaults to no limit
+    pub max_concurrent_streams: Option<u32>,
+}
+
+impl ServerConfig {
+    /// Serve on `address`, in plain text, with every other option at its default
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            identity: None,
+            client_ca: Vec::new(),
+            require_client_cert: false,
+            tcp_keepalive: None,
+            tcp_keepalive_interval: None,
+            tcp_keepalive_retries: None,
+            tcp_nagle_algorithm: false,
+            http2_keep_alive_interval: None,
+            http2_keep_alive_timeout: None,
+            max_concurrent_streams: None,
+        }
+    }
+}
+
+impl Clone for ServerConfig {
+    fn clone(&self) -> Self {
+        Self {
+            address: self.address,
+            identity: self
+                .identity
+                .as_ref()
+                .map(|(chain, key)| (chain.clone(), key.clone_key())),
+            client_ca: self.client_ca.clone(),
+            require_client_cert: self.require_client_cert,
+            tcp_keepalive: self.tcp_keepalive,
+            tcp_keepalive_interval: self.tcp_keepalive_interval,
+            tcp_keepalive_retries: self.tcp_keepalive_retries,
+            tcp_nagle_algorithm: self.tcp_nagle_algorithm,
+            http2_keep_alive_interval: self.http2_keep_alive_interval,
+            http2_keep_alive_timeout: self.http2_keep_alive_timeout,
+            max_concurrent_streams: self.max_concurrent_streams,
+        }
+    }
+}
+
+/// Options for serving gRPC services (as given in the environment)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ServerConfigArgs {
+    /// Address to listen on, as an IP and a port (e.g. `0.0.0.0:1080` or `[::]:1080`)
+    pub address: String,
+    /// Path to the certificate file of the server in pem format, followed by its intermediate
+    /// certificates if any
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cert_pem: String,
+    /// Path to the key file of the server in pem format
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub key_pem: String,
+    /// Path to the Certificate Authority file the certificates of clients are verified against, in pem
+    /// format, which may hold several of them
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub client_ca_cert: String,
+    /// Refuse clients that present no certificate, defaults to false
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub require_client_cert: bool,
+    /// TCP keepalive duration (e.g. `30s`), defaults to no keepalive
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tcp_keepalive: String,
+    /// Interval between TCP keepalive probes (e.g. `5s`), defaults to OS default
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tcp_keepalive_interval: String,
+    /// Number of TCP keepalive retries, defaults to OS default
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tcp_keepalive_retries: String,
+    /// Enable Nagle's algorithm (disable TCP_NODELAY), defaults to false
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tcp_nagle_algorithm: bool,
+    /// HTTP/2 PING frame interval (e.g. `20s`), defaults to no keepalive
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub http2_keep_alive_interval: String,
+    /// HTTP/2 PING timeout (e.g. `10s`), defaults to 20s
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub http2_keep_alive_timeout: String,
+    /// Number of calls a connection may carry at once, defaults to no limit
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_concurrent_streams: String,
+}
+
+impl ServerConfigArgs {
+    /// Read the `GrpcServer__*` variables, each named as its field is in `PascalCase`.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        use crate::utils::{read_env, read_env_bool};
+        let ctx = EnvSnafu {};
+        Ok(Self {
+            address: read_env("GrpcServer__Address").context(ctx)?,
+            cert_pem: read_env("GrpcServer__CertPem").context(ctx)?,
+            key_pem: read_env("GrpcServer__KeyPem").context(ctx)?,
+            client_ca_cert: read_env("GrpcServer__ClientCaCert").context(ctx)?,
+            require_client_cert: read_env_bool("GrpcServer__RequireClientCert").context(ctx)?,
+            tcp_keepalive: read_env("GrpcServer__TcpKeepalive").context(ctx)?,
+            tcp_keepalive_interval: read_env("GrpcServer__TcpKeepaliveInterval").context(ctx)?,
+            tcp_keepalive_retries: read_env("GrpcServer__TcpKeepaliveRetries").context(ctx)?,
+            tcp_nagle_algorithm: read_env_bool("GrpcServer__TcpNagleAlgorithm").context(ctx)?,
+            http2_keep_alive_interval: read_env("GrpcServer__Http2KeepAliveInterval")
+                .context(ctx)?,
+            http2_keep_alive_timeout: read_env("GrpcServer__Http2KeepAliveTimeout").context(ctx)?,
+            max_concurrent_streams: read_env("GrpcServer__MaxConcurrentStreams").context(ctx)?,
+        })
+    }
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_config_args(ServerConfigArgs::from_env()?)
+    }
+
+    pub fn from_config_args(args: ServerConfigArgs) -> Result<Self, ConfigError> {
+        let _span = tracing::debug_span!(
+            "ServerConfig",
+            args.address,
+            args.cert_pem,
+            args.key_pem,
+            args.client_ca_cert,
+            args.require_client_cert,
+            args.tcp_keepalive,
+            args.tcp_keepalive_interval,
+            args.tcp_keepalive_retries,
+            args.tcp_nagle_algorithm,
+            args.http2_keep_alive_interval,
+            args.http2_keep_alive_timeout,
+            args.max_concurrent_streams,
+        );
+
+        let ServerConfigArgs {
+            address,
+            cert_pem,
+            key_pem,
+            client_ca_cert,
+            require_client_cert,
+            tcp_keepalive,
+            tcp_keepalive_interval,
+            tcp_keepalive_retries,
+            tcp_nagle_algorithm,
+            http2_keep_alive_interval,
+            http2_keep_alive_timeout,
+            max_concurrent_streams,
+        } = args;
+
+        if address.is_empty() {
+            return IncompatibleOptionsSnafu {
+                msg: String::from(
+                    "`GrpcServer__Address` has to be set to the address to listen on, as in \
+                     `0.0.0.0:1080`",
+                ),
+            }
+            .fail();
+        }
+        let address = address
+            .parse::<SocketAddr>()
+            .context(InvalidAddressSnafu { value: address })?;
+
+        let identity = match (cert_pem.as_str(), key_pem.as_str()) {
+            ("", "") => None,
+            ("", _) | (_, "") => return IncompatibleOptionsSnafu {
+                msg: format!("`GrpcServer__CertPem={cert_pem}` and `GrpcServer__KeyPem={key_pem}` must be either both empty or both set"),
+            }
+            .fail(),
+            (cert_path, key_path) => Some((
+                read_certificates(Path::new(cert_path))?,
+                read_private_key(Path::new(key_path))?,
+            )),
+        };
+
+        let client_ca = if client_ca_cert.is_empty() {
+            Vec::new()
+        } else if identity.is_none() {
+            return IncompatibleOptionsSnafu {
+                msg: format!(
+                    "`GrpcServer__ClientCaCert={client_ca_cert}` verifies the certificates of \
+                     clients, which only TLS carries: `GrpcServer__CertPem` and `GrpcServer__KeyPem` \
+                     have to be set as well"
+                ),
+            }
+            .fail();
+        } else {
+            read_certificates(Path::new(&client_ca_cert))?
+        };
+        if require_client_cert && client_ca.is_empty() {
+            return IncompatibleOptionsSnafu {
+                msg: String::from(
+                    "`GrpcServer__RequireClientCert` is set, but no certificate could be verified: \
+                     `GrpcServer__ClientCaCert` is not",
+                ),
+            }
+            .fail();
+        }
+
+        let tcp_keepalive_retries = if tcp_keepalive_retries.is_empty() {
+            None
+        } else {
+            Some(
+                tcp_keepalive_retries
+                    .parse::<u32>()
+                    .context(InvalidIntegerSnafu {
+                        setting: "GrpcServer__TcpKeepaliveRetries",
+                        value: tcp_keepalive_retries,
+                    })?,
+            )
+        };
+
+        let max_concurrent_streams = if max_concurrent_streams.is_empty() {
+            None
+        } else {
+            let max = max_concurrent_streams
+                .parse::<u32>()
+                .context(InvalidIntegerSnafu {
+                    setting: "GrpcServer__MaxConcurrentStreams",
+                    value: max_concurrent_streams.clone(),
+                })?;
+            if max == 0 {
+                return IncompatibleOptionsSnafu {
+                    msg: String::from(
+                        "`GrpcServer__MaxConcurrentStreams=0` would refuse every call; leave it \
+                         empty for no limit",
+                    ),
+                }
+                .fail();
+            }
+            Some(max)
+        };
+
+        Ok(Self {
+            address,
+            identity,
+            client_ca,
+            require_client_cert,
+            tcp_keepalive: optional_duration("GrpcServer__TcpKeepalive", tcp_keepalive)?,
+            tcp_keepalive_interval: optional_duration(
+                "GrpcServer__TcpKeepaliveInterval",
+                tcp_keepalive_interval,
+            )?,
+            tcp_keepalive_retries,
+            tcp_nagle_algorithm,
+            http2_keep_alive_interval: optional_duration(
+                "GrpcServer__Http2KeepAliveInterval",
+                http2_keep_alive_interval,
+            )?,
+            http2_keep_alive_timeout: optional_duration(
+                "GrpcServer__Http2KeepAliveTimeout",
+                http2_keep_alive_timeout,
+            )?,
+            max_concurrent_streams,
+        })
+    }
+
+    /// The TLS configuration connections are accepted with, `None` to serve in plain text.
+    ///
+    /// Clients are asked for a certificate issued by one of `client_ca` if there is any, and refused
+    /// without one if `require_client_cert` is set. HTTP/2 is the only protocol offered.
+    pub fn tls_config(&self) -> Result<Option<rustls::ServerConfig>, ConfigError> {
+        let Some((chain, key)) = &self.identity else {
+            return Ok(None);
+        };
+
+        // Get the default crypto provider or fallback to the ring crypto provider
+        let provider = rustls::crypto::CryptoProvider::get_default()
+            .cloned()
+            .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider()));
+
+        let tls = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .context(InvalidServerTlsSnafu {})?;
+        let tls = if self.client_ca.is_empty() {
+            tls.with_no_client_auth()
+        } else {
+            let mut roots = rustls::RootCertStore::empty();
+            // A certificate that is not a valid anchor is left out, and the build below fails for want
+            // of any.
+            roots.add_parsable_certificates(self.client_ca.iter().cloned());
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if self.require_client_cert {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            tls.with_client_cert_verifier(verifier.build().context(InvalidClientCaSnafu {})?)
+        };
+        let mut tls = tls
+            .with_single_cert(chain.clone(), key.clone_key())
+            .context(InvalidServerTlsSnafu {})?;
+        tls.alpn_protocols = vec![b"h2".to_vec()];
+        Ok(Some(tls))
+    }
+}
+
+/// A certificate a client presented, and the handshake verified against the client CA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PeerCertificate {
+    pub subject: String,
+    /// The common name of the subject, if it has one
+    pub common_name: Option<String>,
+    /// The DNS names and addresses the certificate is for
+    pub subject_alt_names: Vec<String>,
+    /// The SHA-256 digest of the certificate, in lowercase hexadecimal
+    pub fingerprint: String,
+    pub der: CertificateDer<'static>,
+}
+
+impl PeerCertificate {
+    /// Read what identifies the client out of `der`, `None` if it is not a certificate.
+    pub fn parse(der: CertificateDer<'static>) -> Option<Self> {
+        let (subject, common_name, subject_alt_names) = {
+            let (_, cert) = x509_parser::parse_x509_certificate(&der).ok()?;
+            let common_name = cert
+                .subject()
+                .iter_common_name()
+                .next()
+                .and_then(|name| name.as_str().ok())
+                .map(String::from);
+            (
+                cert.subject().to_string(),
+                common_name,
+                crate::diagnose::subject_alt_names(&cert),
+            )
+        };
+        let fingerprint = ring::digest::digest(&ring::digest::SHA256, &der)
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        Some(Self {
+            subject,
+            common_name,
+            subject_alt_names,
+            fingerprint,
+            der,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The minimum viable arguments: an address, and nothing else set.
+    fn args() -> ServerConfigArgs {
+        ServerConfigArgs {
+            address: String::from("127.0.0.1:1080"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_minimum_is_an_address_served_in_plain_text() {
+        let config = ServerConfig::from_config_args(args()).expect("an address is enough");
+
+        assert_eq!(config.address, "127.0.0.1:1080".parse().unwrap());
+        assert!(config.identity.is_none());
+        assert!(config.tls_config().unwrap().is_none());
+        assert_eq!(config.max_concurrent_streams, None);
+    }
+
+    #[test]
+    fn an_address_is_required_and_has_to_be_an_ip_and_a_port() {
+        for address in ["", "localhost:1080", "127.0.0.1"] {
+            let error = ServerConfig::from_config_args(ServerConfigArgs {
+                address: String::from(address),
+                ..args()
+            })
+            .expect_err(address);
+
+            assert!(
+                matches!(
+                    error,
+                    ConfigError::IncompatibleOptions { .. } | ConfigError::InvalidAddress { .. }
+                ),
+                "{error:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn options_are_read_in_their_units() {
+        let config = ServerConfig::from_config_args(ServerConfigArgs {
+            tcp_keepalive: String::from("30s"),
+            tcp_keepalive_retries: String::from("3"),
+            http2_keep_alive_interval: String::from("1m"),
+            http2_keep_alive_timeout: String::from("500ms"),
+            max_concurrent_streams: String::from("64"),
+            ..args()
+        })
+        .unwrap();
+
+        assert_eq!(config.tcp_keepalive, Some(Duration::from_secs(30)));
+        assert_eq!(config.tcp_keepalive_retries, Some(3));
+        assert_eq!(
+            config.http2_keep_alive_interval,
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            config.http2_keep_alive_timeout,
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(config.max_concurrent_streams, Some(64));
+    }
+
+    #[test]
+    fn a_value_that_cannot_be_read_names_its_server_variable() {
+        for (setting, args) in [
+            (
+                "GrpcServer__TcpKeepalive",
+                ServerConfigArgs {
+                    tcp_keepalive: String::from("soon"),
+                    ..args()
+                },
+            ),
+            (
+                "GrpcServer__Http2KeepAliveTimeout",
+                ServerConfigArgs {
+                    http2_keep_alive_timeout: String::from("soon"),
+                    ..args()
+                },
+            ),
+            (
+                "GrpcServer__TcpKeepaliveRetries",
+                ServerConfigArgs {
+                    tcp_keepalive_retries: String::from("many"),
+                    ..args()
+                },
+            ),
+        ] {
+            let error = ServerConfig::from_config_args(args).expect_err(setting);
+
+            assert!(
+                matches!(
+                    &error,
+                    ConfigError::InvalidDuration { setting: named, .. }
+                        | ConfigError::InvalidInteger { setting: named, .. } if *named == setting
+                ),
+                "{error:?}"
+            );
+            assert!(error.to_string().contains(setting), "{error}");
+        }
+    }
+
+    #[test]
+    fn no_concurrent_streams_at_all_is_rejected() {
+        let error = ServerConfig::from_config_args(ServerConfigArgs {
+            max_concurrent_streams: String::from("0"),
+            ..args()
+        })
+        .unwrap_err();
+
+        assert!(
+            error.to_string().contains("MaxConcurrentStreams"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn half_an_identity_is_rejected_and_names_both_variables() {
+        let error = ServerConfig::from_config_args(ServerConfigArgs {
+            cert_pem: String::from("server.pem"),
+            ..args()
+        })
+        .unwrap_err();
+
+        let message = error.to_string();
+        assert!(message.contains("GrpcServer__CertPem"), "{message}");
+        assert!(message.contains("GrpcServer__KeyPem"), "{message}");
+    }
+
+    #[test]
+    fn client_certificates_need_tls_and_a_ca_to_verify_them() {
+        let error = ServerConfig::from_config_args(ServerConfigArgs {
+            client_ca_cert: String::from("ca.pem"),
+            ..args()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("GrpcServer__CertPem"), "{error}");
+
+        let error = ServerConfig::from_config_args(ServerConfigArgs {
+            require_client_cert: true,
+            ..args()
+        })
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("GrpcServer__ClientCaCert"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn peer_certificates_name_their_subject_and_digest() {
+        let mut params =
+            rcgen::CertificateParams::new(vec![String::from("client.armonik.local")]).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "alice");
+        let cert = params
+            .self_signed(&rcgen::KeyPair::generate().unwrap())
+            .unwrap();
+
+        let peer = PeerCertificate::parse(cert.der().clone()).unwrap();
+
+        assert_eq!(peer.common_name.as_deref(), Some("alice"));
+        assert_eq!(peer.subject, "CN=alice");
+        assert_eq!(peer.subject_alt_names, ["client.armonik.local"]);
+        assert_eq!(peer.fingerprint.len(), 64);
+        assert!(peer
+            .fingerprint
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)));
+        assert_eq!(&peer.der, cert.der());
+
+        assert_eq!(
+            PeerCertificate::parse(CertificateDer::from(vec![0; 8])),
+            None
+        );
+    }
+}
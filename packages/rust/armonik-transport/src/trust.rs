@@ -0,0 +1,168 @@
+//! Which servers are trusted: the Certificate Authorities of a bundle, the system ones, or both, and the
+//! public keys a server may be pinned to on top of them.
+
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use snafu::ResultExt;
+
+use crate::config::{
+    ConfigError, InvalidCaCertSnafu, InvalidCaCertificateSnafu, NoSystemCaCertSnafu,
+};
+
+/// A verifier of servers against every certificate of `cacerts`, and against the system Certificate
+/// Authorities as well if `native_roots` is set.
+///
+/// Fails on a certificate of `cacerts` that is not a trust anchor, and when no system Certificate
+/// Authority is found with nothing else to trust.
+pub(crate) fn verifier(
+    cacerts: Vec<CertificateDer<'static>>,
+    native_roots: bool,
+    provider: Arc<CryptoProvider>,
+) -> Result<Arc<WebPkiServerVerifier>, ConfigError> {
+    let mut roots = RootCertStore::empty();
+    // A certificate named by the user that cannot be trusted is a mistake worth failing on.
+    for (index, cert) in cacerts.into_iter().enumerate() {
+        roots
+            .add(cert)
+            .context(InvalidCaCertificateSnafu { index: index + 1 })?;
+    }
+    if native_roots {
+        let native = rustls_native_certs::load_native_certs();
+        // Some unreadable system certificates are no reason to trust none of the others.
+        for err in native.errors {
+            tracing::warn!("Could not load a system Certificate Authority: {err}");
+        }
+        let (added, ignored) = roots.add_parsable_certificates(native.certs);
+        if ignored > 0 {
+            tracing::warn!(
+                "{ignored} system Certificate Authorities are not trust anchors, left out"
+            );
+        }
+        if added == 0 {
+            if roots.is_empty() {
+                return NoSystemCaCertSnafu {}.fail();
+            }
+            tracing::warn!(
+                "No system Certificate Authority was found, only the ones named are trusted"
+            );
+        }
+    }
+
+    WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+        .build()
+        .context(InvalidCaCertSnafu {})
+}
+
+/// The SHA-256 digest of the DER-encoded SubjectPublicKeyInfo of `cert`, which is what a pin names.
+///
+/// `None` if `cert` cannot be parsed.
+pub(crate) fn spki_sha256(cert: &CertificateDer<'_>) -> Option<[u8; 32]> {
+    let cert = webpki::EndEntityCert::try_from(cert).ok()?;
+    let digest = ring::digest::digest(
+        &ring::digest::SHA256,
+        cert.subject_public_key_info().as_ref(),
+    );
+    digest.as_ref().try_into().ok()
+}
+
+/// Verifies the server with another verifier, then requires one certificate of the chain it presented to
+/// have one of the pinned public keys.
+///
+/// The chain presented is the one checked: a pin on the key of an intermediate CA keeps trusting the
+/// server certificates it issues as they are renewed.
+///
+/// The handshake signatures are checked here whatever `inner` does with them: pinned on top of
+/// `GrpcClient__AllowUnsafeConnection`, whose verifier accepts any, a server would otherwise only have
+/// to present the pinned certificate, not to hold its key.
+#[derive(Debug)]
+pub(crate) struct PinnedVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<[u8; 32]>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedVerifier {
+    /// Verify with `inner`, then against `pins`: SHA-256 digests of SubjectPublicKeyInfos.
+    pub(crate) fn new(
+        inner: Arc<dyn ServerCertVerifier>,
+        pins: Vec<[u8; 32]>,
+        provider: Arc<CryptoProvider>,
+    ) -> Self {
+        Self {
+            inner,
+            pins,
+            provider,
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        let pinned = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(spki_sha256)
+            .any(|digest| self.pins.contains(&digest));
+        if pinned {
+            Ok(verified)
+        } else {
+            tracing::warn!("The server presented no certificate with a pinned public key");
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
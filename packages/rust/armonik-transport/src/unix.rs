@@ -0,0 +1,43 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use hyper_util::rt::TokioIo;
+use tonic::codegen::http::Uri;
+
+/// Dials a Unix domain socket, whatever URI it is given.
+///
+/// The URI of the channel is only there to fill the `:authority` of the requests.
+#[derive(Debug, Clone)]
+pub(crate) struct UnixConnector {
+    path: Arc<PathBuf>,
+}
+
+impl UnixConnector {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path: Arc::new(path),
+        }
+    }
+}
+
+impl tower_service::Service<Uri> for UnixConnector {
+    type Response = TokioIo<tokio::net::UnixStream>;
+    type Error = std::io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _uri: Uri) -> Self::Future {
+        let path = self.path.clone();
+        Box::pin(async move {
+            let stream = tokio::net::UnixStream::connect(path.as_ref()).await?;
+            tracing::debug!("Connected to unix:{}", path.display());
+            Ok(TokioIo::new(stream))
+        })
+    }
+}
@@ -1,9 +1,9 @@
-//! Reading configuration from the environment, and the one deliberately-insecure certificate
-//! verifier that `allow_unsafe_connection` selects.
+//! Reading configuration from the environment and noticing files change, and the one
+//! deliberately-insecure certificate verifier that `allow_unsafe_connection` selects.
 
 use snafu::Snafu;
 
-pub(crate) fn read_env(name: &str) -> Result<String, ReadEnvError> {
+pub fn read_env(name: &str) -> Result<String, ReadEnvError> {
     match std::env::var(name) {
         Ok(value) => Ok(value),
         Err(std::env::VarError::NotPresent) => Ok(String::new()),
@@ -15,12 +15,11 @@ pub(crate) fn read_env(name: &str) -> Result<String, ReadEnvError> {
     }
 }
 
-pub(crate) fn read_env_bool(name: &str) -> Result<bool, ReadEnvError> {
+pub fn read_env_bool(name: &str) -> Result<bool, ReadEnvError> {
     let value = read_env(name)?;
-    match value.as_ref() {
-        "0" | "false" | "no" | "disable" | "disallow" | "forbid" | "" => Ok(false),
-        "1" | "true" | "yes" | "enable" | "allow" | "authorize" => Ok(true),
-        _ => NotBooleanSnafu {
+    match parse_bool(&value) {
+        Some(value) => Ok(value),
+        None => NotBooleanSnafu {
             name: name.to_owned(),
             value,
         }
@@ -28,8 +27,18 @@ pub(crate) fn read_env_bool(name: &str) -> Result<bool, ReadEnvError> {
     }
 }
 
+/// The boolean `value` spells, in any of the spellings a boolean option accepts.
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "0" | "false" | "no" | "disable" | "disallow" | "forbid" | "" => Some(false),
+        "1" | "true" | "yes" | "enable" | "allow" | "authorize" => Some(true),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Snafu)]
 #[non_exhaustive]
+#[snafu(visibility(pub(crate)))]
 pub enum ReadEnvError {
     #[snafu(display(
         "Environment variable `{name}={value:?}` is not a valid unicode string [{location}]"
@@ -53,6 +62,32 @@ pub enum ReadEnvError {
     },
 }
 
+/// What a file looked like when it was read, to tell whether it changed since.
+///
+/// Modification times can be coarse, so they are not the only thing compared: a file replaced within
+/// the same tick still shows as changed if its length did, or, on Unix, if it was replaced by another
+/// file moved over it, as Kubernetes and cert-manager do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+    modified: Option<std::time::SystemTime>,
+    len: u64,
+    #[cfg(unix)]
+    inode: u64,
+}
+
+impl FileStamp {
+    /// The stamp of the file at `path` as it is now, `None` if it cannot be looked at.
+    pub(crate) fn of(path: &std::path::Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            #[cfg(unix)]
+            inode: std::os::unix::fs::MetadataExt::ino(&metadata),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct InsecureCertVerifier;
 
@@ -0,0 +1,101 @@
+//! `GrpcClient__Token*` reaching the server, as the headers of every call.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{call, config, dir, serve, HeaderService};
+
+#[tokio::test]
+async fn a_token_is_sent_as_a_bearer_token() {
+    let endpoint = serve(HeaderService::new("authorization")).await;
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.token = String::from("s3cr3t");
+    }))
+    .await
+    .expect("connecting should succeed");
+
+    let header = call(channel).await.expect("the call should succeed");
+    assert_eq!(header.as_ref(), b"Bearer s3cr3t");
+}
+
+#[tokio::test]
+async fn a_token_in_another_header_is_sent_as_is() {
+    let endpoint = serve(HeaderService::new("x-api-key")).await;
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.token = String::from("s3cr3t");
+        args.header_name = String::from("X-Api-Key");
+    }))
+    .await
+    .expect("connecting should succeed");
+
+    let header = call(channel).await.expect("the call should succeed");
+    assert_eq!(header.as_ref(), b"s3cr3t");
+}
+
+#[tokio::test]
+async fn a_token_file_is_read_again_once_it_changed() {
+    let dir = dir("refresh");
+    let path = dir.join("token");
+    std::fs::write(&path, "first\n").unwrap();
+    let endpoint = serve(HeaderService::new("authorization")).await;
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.token_file = path.display().to_string();
+    }))
+    .await
+    .expect("connecting should succeed");
+    let header = call(channel.clone())
+        .await
+        .expect("the call should succeed");
+    assert_eq!(header.as_ref(), b"Bearer first");
+
+    // Replaced as Kubernetes does, by a new file moved over the old one; a length of its own makes
+    // sure the change shows even where modification times are coarse.
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    std::fs::write(dir.join("token.new"), "rotated").unwrap();
+    std::fs::rename(dir.join("token.new"), &path).unwrap();
+
+    // Checked in the background, at most once a second: calls go on with the first token meanwhile.
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    loop {
+        let header = call(channel.clone())
+            .await
+            .expect("the call should succeed");
+        if header.as_ref() == b"Bearer rotated" {
+            break;
+        }
+        assert_eq!(header.as_ref(), b"Bearer first");
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "the token file was never read again"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    // A file missing for a moment leaves the last token in use.
+    std::fs::remove_file(&path).unwrap();
+    for _ in 0..3 {
+        let header = call(channel.clone())
+            .await
+            .expect("the call should succeed");
+        assert_eq!(header.as_ref(), b"Bearer rotated");
+        tokio::time::sleep(Duration::from_millis(600)).await;
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn no_token_sends_no_header() {
+    let endpoint = serve(HeaderService::new("authorization")).await;
+
+    let channel = armonik_transport::connect(config(&endpoint, |_| {}))
+        .await
+        .expect("connecting should succeed");
+
+    let header = call(channel).await.expect("the call should succeed");
+    assert!(header.is_empty());
+}
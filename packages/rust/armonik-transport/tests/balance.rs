@@ -0,0 +1,214 @@
+//! `GrpcClient__Endpoints` and the options around it: calls spread across several local servers, which
+//! go down and come back, or fail their health checks.
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use armonik_transport::reexports::tonic::Code;
+use common::{call_at, config, serve, serve_until, FlakyService, FLAKY_PATH};
+
+/// `count` servers that answer every call, with their endpoints.
+async fn servers(count: usize) -> (Vec<FlakyService>, Vec<String>) {
+    let mut services = Vec::new();
+    let mut endpoints = Vec::new();
+    for _ in 0..count {
+        let service = FlakyService::new(0, Code::Unavailable);
+        endpoints.push(serve(service.clone()).await);
+        services.push(service);
+    }
+    (services, endpoints)
+}
+
+/// An endpoint forwarding its connections to `endpoint`, and how many it was opened.
+async fn counted(endpoint: &str) -> (String, Arc<AtomicUsize>) {
+    let target = endpoint.trim_start_matches("http://").to_owned();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let opened = Arc::new(AtomicUsize::new(0));
+    let counted = opened.clone();
+    tokio::spawn(async move {
+        while let Ok((mut inbound, _)) = listener.accept().await {
+            counted.fetch_add(1, Ordering::SeqCst);
+            let target = target.clone();
+            tokio::spawn(async move {
+                if let Ok(mut outbound) = tokio::net::TcpStream::connect(target).await {
+                    let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                }
+            });
+        }
+    });
+    (format!("http://{address}"), opened)
+}
+
+/// Make `count` calls over `channel`, each of which has to succeed.
+async fn calls(channel: &armonik_transport::Channel, count: usize) {
+    for call in 0..count {
+        call_at(channel.clone(), FLAKY_PATH)
+            .await
+            .unwrap_or_else(|status| panic!("call {call} should succeed: {status}"));
+    }
+}
+
+#[tokio::test]
+async fn calls_are_spread_across_every_endpoint() {
+    let (services, endpoints) = servers(3).await;
+
+    let channel = armonik_transport::connect(config(&endpoints[0], |args| {
+        args.endpoints = endpoints[1..].join(",");
+    }))
+    .await
+    .expect("connecting should succeed");
+    calls(&channel, 30).await;
+
+    for (service, endpoint) in services.iter().zip(&endpoints) {
+        assert!(service.calls() > 0, "{endpoint} got no call");
+    }
+}
+
+#[tokio::test]
+async fn the_connections_made_when_connecting_are_those_calls_go_over() {
+    let (_, endpoints) = servers(2).await;
+    let (first, first_opened) = counted(&endpoints[0]).await;
+    let (second, second_opened) = counted(&endpoints[1]).await;
+
+    let channel = armonik_transport::connect(config(&first, |args| {
+        args.endpoints = second;
+    }))
+    .await
+    .expect("connecting should succeed");
+    calls(&channel, 10).await;
+
+    assert_eq!(first_opened.load(Ordering::SeqCst), 1);
+    assert_eq!(second_opened.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn an_endpoint_that_goes_down_is_left_out_until_it_is_back() {
+    let up = FlakyService::new(0, Code::Unavailable);
+    let up_endpoint = serve(up.clone()).await;
+    let down = FlakyService::new(0, Code::Unavailable);
+    let (shutdown, stopped) = tokio::sync::oneshot::channel::<()>();
+    let (down_endpoint, server) = serve_until(down.clone(), "127.0.0.1:0", async move {
+        let _ = stopped.await;
+    })
+    .await;
+
+    // The call that finds the endpoint down is retried on the other one.
+    let channel = armonik_transport::connect(config(&up_endpoint, |args| {
+        args.endpoints = down_endpoint.clone();
+        args.eviction_backoff = String::from("300ms");
+        args.retry_max_attempts = String::from("3");
+        args.retry_initial_backoff = String::from("10ms");
+    }))
+    .await
+    .expect("connecting should succeed");
+    calls(&channel, 10).await;
+    assert!(down.calls() > 0, "calls should be spread across both");
+
+    // Killed mid-run: no call fails, and none reaches it.
+    shutdown.send(()).expect("the server is running");
+    server.await.expect("the server should shut down");
+    let before = down.calls();
+    calls(&channel, 10).await;
+    assert_eq!(down.calls(), before);
+
+    // Back at the same address once the backoff is over, it gets calls again.
+    let address = down_endpoint.trim_start_matches("http://");
+    serve_until(down.clone(), address, std::future::pending()).await;
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    calls(&channel, 10).await;
+    assert!(down.calls() > before, "the endpoint should be back");
+}
+
+#[tokio::test]
+async fn an_endpoint_that_is_down_when_connecting_is_left_out_from_the_start() {
+    let (services, endpoints) = servers(1).await;
+    // Bound then released, so that nothing listens there.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind a port");
+    let down = format!("http://{}", listener.local_addr().expect("an address"));
+    drop(listener);
+
+    let channel = armonik_transport::connect(config(&down, |args| {
+        args.endpoints = endpoints[0].clone();
+        args.eviction_backoff = String::from("10s");
+    }))
+    .await
+    .expect("one endpoint is up");
+    calls(&channel, 5).await;
+
+    assert_eq!(services[0].calls(), 5);
+}
+
+#[tokio::test]
+async fn connecting_fails_when_every_endpoint_is_down() {
+    let mut endpoints = Vec::new();
+    for _ in 0..2 {
+        // Bound then released, so that nothing listens there.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind a port");
+        endpoints.push(format!(
+            "http://{}",
+            listener.local_addr().expect("an address")
+        ));
+    }
+
+    let error = armonik_transport::connect(config(&endpoints[0], |args| {
+        args.endpoints = endpoints[1].clone();
+    }))
+    .await
+    .expect_err("no endpoint is up");
+
+    assert!(
+        matches!(error, armonik_transport::ConnectionError::Transport { .. }),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn an_endpoint_failing_its_health_check_is_left_out() {
+    let healthy = FlakyService::new(0, Code::Unavailable);
+    let healthy_endpoint = serve(healthy.clone()).await;
+    let unhealthy = FlakyService::new(usize::MAX, Code::Unavailable);
+    let unhealthy_endpoint = serve(unhealthy.clone()).await;
+
+    let mut config = config(&healthy_endpoint, |args| {
+        args.endpoints = unhealthy_endpoint.clone();
+        args.health_check_interval = String::from("50ms");
+        args.eviction_backoff = String::from("10s");
+    });
+    config.health_check = Some(armonik_transport::HealthCheck::new(|channel| async move {
+        call_at(channel, FLAKY_PATH).await.is_ok()
+    }));
+    let channel = armonik_transport::connect(config)
+        .await
+        .expect("connecting should succeed");
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let before = unhealthy.calls();
+    assert!(before > 0, "the endpoint should have been checked");
+    // Without retries: a call sent to the unhealthy endpoint would fail.
+    calls(&channel, 10).await;
+
+    assert_eq!(unhealthy.calls(), before);
+}
+
+#[tokio::test]
+async fn the_addresses_a_name_resolves_to_are_each_an_endpoint() {
+    let (services, endpoints) = servers(1).await;
+    let port = endpoints[0].rsplit(':').next().expect("a port");
+
+    // `localhost` may resolve to `::1` as well, where nothing listens: that address is left out.
+    let channel = armonik_transport::connect(config(&format!("http://localhost:{port}"), |args| {
+        args.resolve_interval = String::from("50ms");
+        args.eviction_backoff = String::from("10s");
+    }))
+    .await
+    .expect("connecting should succeed");
+    calls(&channel, 3).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    calls(&channel, 3).await;
+
+    assert_eq!(services[0].calls(), 6);
+}
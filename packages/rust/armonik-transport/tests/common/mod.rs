@@ -1,10 +1,18 @@
-//! A gRPC service that answers slowly, and a raw client to call it with.
+//! gRPC services that answer slowly or fail on purpose, a raw client to call them with, and
+//! certificates made up for the tests speaking TLS.
 //!
 //! Hand-rolled rather than generated: this crate has no protos and deliberately no `protoc` in its
-//! build, so the codec moves opaque bytes and the service is one method that sleeps before replying.
+//! build, so the codec moves opaque bytes and each service is a method or two that sleep or fail before
+//! replying.
+
+// Each test binary compiles this module on its own, and uses only some of it.
+#![allow(dead_code)]
 
 use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Duration;
 
@@ -12,8 +20,11 @@ use armonik_transport::reexports::hyper;
 use armonik_transport::reexports::tonic::body::Body;
 use armonik_transport::reexports::tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
 use armonik_transport::reexports::tonic::server::NamedService;
-use armonik_transport::reexports::tonic::{Request, Response, Status};
+use armonik_transport::reexports::tonic::{Code, Request, Response, Status, Streaming};
 use bytes::{Buf, BufMut, Bytes};
+use rcgen::{
+    BasicConstraints, CertificateParams, CertifiedIssuer, DistinguishedName, DnType, IsCa, KeyPair,
+};
 use tower_service::Service;
 
 /// The one method the test service answers to.
@@ -120,16 +131,302 @@ impl Service<hyper::Request<Body>> for SlowService {
     }
 }
 
+/// A service that fails its first calls with a given code, then answers, counting every call it gets.
+///
+/// It has a unary method at [`FLAKY_PATH`], and a client-streaming one at [`STREAM_PATH`] which reads
+/// the first message of the stream before answering or failing.
+#[derive(Clone)]
+pub struct FlakyService {
+    failures: usize,
+    code: Code,
+    calls: Arc<AtomicUsize>,
+    timeouts: Arc<Mutex<Vec<Option<String>>>>,
+}
+
+impl FlakyService {
+    pub fn new(failures: usize, code: Code) -> Self {
+        Self {
+            failures,
+            code,
+            calls: Arc::default(),
+            timeouts: Arc::default(),
+        }
+    }
+
+    /// The number of calls received so far, across every clone.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// The `grpc-timeout` of each call received so far, in order, across every clone.
+    pub fn timeouts(&self) -> Vec<Option<String>> {
+        self.timeouts.lock().unwrap().clone()
+    }
+
+    /// The outcome of the next call: the first `failures` fail.
+    fn outcome(&self) -> Result<Response<Bytes>, Status> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        if call < self.failures {
+            Err(Status::new(
+                self.code,
+                format!("call {call} fails on purpose"),
+            ))
+        } else {
+            Ok(Response::new(Bytes::from_static(REPLY)))
+        }
+    }
+}
+
+/// The unary method of the flaky service.
+pub const FLAKY_PATH: &str = "/armonik_transport.test.Flaky/Call";
+
+/// The client-streaming method of the flaky service.
+pub const STREAM_PATH: &str = "/armonik_transport.test.Flaky/Stream";
+
+impl NamedService for FlakyService {
+    const NAME: &'static str = "armonik_transport.test.Flaky";
+}
+
+impl Service<Request<Bytes>> for FlakyService {
+    type Response = Response<Bytes>;
+    type Error = Status;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _request: Request<Bytes>) -> Self::Future {
+        let outcome = self.outcome();
+        Box::pin(async move { outcome })
+    }
+}
+
+impl Service<Request<Streaming<Bytes>>> for FlakyService {
+    type Response = Response<Bytes>;
+    type Error = Status;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Streaming<Bytes>>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            // Failing only once the first message is in makes sure it was sent.
+            request.into_inner().message().await?;
+            service.outcome()
+        })
+    }
+}
+
+impl Service<hyper::Request<Body>> for FlakyService {
+    type Response = hyper::Response<Body>;
+    type Error = std::convert::Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: hyper::Request<Body>) -> Self::Future {
+        let mut handler = self.clone();
+        self.timeouts.lock().unwrap().push(
+            request
+                .headers()
+                .get("grpc-timeout")
+                .map(|timeout| timeout.to_str().unwrap().to_owned()),
+        );
+        Box::pin(async move {
+            let mut grpc = armonik_transport::reexports::tonic::server::Grpc::new(BytesCodec);
+            Ok(if request.uri().path() == STREAM_PATH {
+                grpc.client_streaming(&mut handler, request).await
+            } else {
+                grpc.unary(&mut handler, request).await
+            })
+        })
+    }
+}
+
+/// A service that answers with the value of the header `name` it was called with, empty without one.
+#[derive(Clone)]
+pub struct HeaderService {
+    name: &'static str,
+}
+
+impl HeaderService {
+    pub fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+}
+
+// Under the name of the slow service, so that [`call`] reaches it.
+impl NamedService for HeaderService {
+    const NAME: &'static str = "armonik_transport.test.Slow";
+}
+
+impl Service<Request<Bytes>> for HeaderService {
+    type Response = Response<Bytes>;
+    type Error = Status;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Bytes>) -> Self::Future {
+        let value = request
+            .metadata()
+            .get(self.name)
+            .map(|value| Bytes::copy_from_slice(value.as_bytes()))
+            .unwrap_or_default();
+        Box::pin(async move { Ok(Response::new(value)) })
+    }
+}
+
+impl Service<hyper::Request<Body>> for HeaderService {
+    type Response = hyper::Response<Body>;
+    type Error = std::convert::Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: hyper::Request<Body>) -> Self::Future {
+        let mut handler = self.clone();
+        Box::pin(async move {
+            Ok(
+                armonik_transport::reexports::tonic::server::Grpc::new(BytesCodec)
+                    .unary(&mut handler, request)
+                    .await,
+            )
+        })
+    }
+}
+
+/// A stand-in for the `Versions` service of a control plane, answering `ListVersions` with
+/// [`CORE_VERSION`] and [`API_VERSION`].
+#[derive(Clone)]
+pub struct VersionsService;
+
+pub const CORE_VERSION: &str = "0.29.0";
+pub const API_VERSION: &str = "3.21.0";
+
+impl NamedService for VersionsService {
+    const NAME: &'static str = "armonik.api.grpc.v1.versions.Versions";
+}
+
+impl Service<Request<Bytes>> for VersionsService {
+    type Response = Response<Bytes>;
+    type Error = Status;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _request: Request<Bytes>) -> Self::Future {
+        // `ListVersionsResponse { core, api }`, encoded by hand: two length-delimited strings.
+        let mut reply = Vec::new();
+        for (tag, value) in [(0x0a, CORE_VERSION), (0x12, API_VERSION)] {
+            reply.push(tag);
+            reply.push(value.len() as u8);
+            reply.extend_from_slice(value.as_bytes());
+        }
+        Box::pin(async move { Ok(Response::new(Bytes::from(reply))) })
+    }
+}
+
+impl Service<hyper::Request<Body>> for VersionsService {
+    type Response = hyper::Response<Body>;
+    type Error = std::convert::Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: hyper::Request<Body>) -> Self::Future {
+        let mut handler = self.clone();
+        Box::pin(async move {
+            Ok(
+                armonik_transport::reexports::tonic::server::Grpc::new(BytesCodec)
+                    .unary(&mut handler, request)
+                    .await,
+            )
+        })
+    }
+}
+
 /// Serve `service` on an ephemeral loopback port and return its `http://` endpoint.
-pub async fn serve(service: SlowService) -> String {
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+pub async fn serve<S>(service: S) -> String
+where
+    S: Service<
+            hyper::Request<Body>,
+            Response = hyper::Response<Body>,
+            Error = std::convert::Infallible,
+        > + NamedService
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send + 'static,
+{
+    serve_until(service, "127.0.0.1:0", std::future::pending())
+        .await
+        .0
+}
+
+/// Serve `service` at `address` until `shutdown` resolves, then close the connections open to it.
+///
+/// Returns its `http://` endpoint, and the task serving it, which ends once it is down.
+pub async fn serve_until<S>(
+    service: S,
+    address: &str,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> (String, tokio::task::JoinHandle<()>)
+where
+    S: Service<
+            hyper::Request<Body>,
+            Response = hyper::Response<Body>,
+            Error = std::convert::Infallible,
+        > + NamedService
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send + 'static,
+{
+    let listener = tokio::net::TcpListener::bind(address)
         .await
         .expect("bind the test server");
     let address = listener.local_addr().expect("the test server's address");
 
-    tokio::spawn(async move {
+    let server = tokio::spawn(async move {
         let incoming =
             armonik_transport::reexports::tonic::transport::server::TcpIncoming::from(listener);
+        armonik_transport::reexports::tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_incoming_shutdown(incoming, shutdown)
+            .await
+            .expect("serve the test service");
+    });
+
+    (format!("http://{address}"), server)
+}
+
+/// Serve `service` on a Unix socket at `path` and return its `unix:` endpoint.
+#[cfg(unix)]
+pub fn serve_unix(service: SlowService, path: &std::path::Path) -> String {
+    let listener = tokio::net::UnixListener::bind(path).expect("bind the test socket");
+
+    tokio::spawn(async move {
+        let incoming =
+            armonik_transport::reexports::tonic::codegen::tokio_stream::wrappers::UnixListenerStream::new(
+                listener,
+            );
         armonik_transport::reexports::tonic::transport::Server::builder()
             .add_service(service)
             .serve_with_incoming(incoming)
@@ -137,28 +434,65 @@ pub async fn serve(service: SlowService) -> String {
             .expect("serve the test service");
     });
 
-    format!("http://{address}")
+    format!("unix://{}", path.display())
+}
+
+/// Make one unary call to the slow service over `channel`, returning whatever gRPC says about it.
+pub async fn call(channel: armonik_transport::Channel) -> Result<Bytes, Status> {
+    call_at(channel, METHOD_PATH).await
 }
 
-/// Make one unary call over `channel`, returning whatever gRPC says about it.
-pub async fn call(
-    channel: armonik_transport::reexports::tonic::transport::Channel,
+/// Make one unary call to the method at `path` over `channel`.
+pub async fn call_at(channel: armonik_transport::Channel, path: &str) -> Result<Bytes, Status> {
+    call_within(channel, path, None).await
+}
+
+/// Make one unary call to the method at `path` over `channel`, giving it `timeout` if any.
+pub async fn call_within(
+    channel: armonik_transport::Channel,
+    path: &str,
+    timeout: Option<Duration>,
+) -> Result<Bytes, Status> {
+    let path =
+        armonik_transport::reexports::tonic::codegen::http::uri::PathAndQuery::try_from(path)
+            .expect("a valid method path");
+
+    let mut grpc = armonik_transport::reexports::tonic::client::Grpc::new(channel);
+    // Generated clients always do this first, and `Grpc::unary` does not do it implicitly: skipping it
+    // trips `tower::Buffer`'s "send_item called without first calling poll_reserve" assertion.
+    grpc.ready()
+        .await
+        .map_err(|error| Status::unknown(format!("the channel was not ready: {error}")))?;
+
+    let mut request = Request::new(Bytes::from_static(b"ping"));
+    if let Some(timeout) = timeout {
+        request.set_timeout(timeout);
+    }
+    let response = grpc.unary(request, path, BytesCodec).await?;
+    Ok(response.into_inner())
+}
+
+/// Make one client-streaming call over `channel`, marked as one, sending `messages`.
+pub async fn call_stream(
+    channel: armonik_transport::Channel,
+    messages: Vec<Bytes>,
 ) -> Result<Bytes, Status> {
     let path = armonik_transport::reexports::tonic::codegen::http::uri::PathAndQuery::try_from(
-        METHOD_PATH,
+        STREAM_PATH,
     )
     .expect("a valid method path");
 
     let mut grpc = armonik_transport::reexports::tonic::client::Grpc::new(channel);
-    // Generated clients always do this first, and `Grpc::unary` does not do it implicitly: skipping it
-    // trips `tower::Buffer`'s "send_item called without first calling poll_reserve" assertion.
     grpc.ready()
         .await
         .map_err(|error| Status::unknown(format!("the channel was not ready: {error}")))?;
 
-    let response = grpc
-        .unary(Request::new(Bytes::from_static(b"ping")), path, BytesCodec)
-        .await?;
+    let mut request =
+        Request::new(armonik_transport::reexports::tonic::codegen::tokio_stream::iter(messages));
+    request
+        .extensions_mut()
+        .insert(armonik_transport::ClientStreaming);
+    let response = grpc.client_streaming(request, path, BytesCodec).await?;
     Ok(response.into_inner())
 }
 
@@ -174,8 +508,152 @@ pub fn config(
 ) -> armonik_transport::ClientConfig {
     let mut args = armonik_transport::ClientConfigArgs::default();
     args.endpoint = endpoint.to_owned();
-    args.allow_unsafe_connection = true;
+    // Refused for a Unix socket, which is spoken to in plain text.
+    args.allow_unsafe_connection = !endpoint.starts_with("unix:");
     set(&mut args);
     armonik_transport::ClientConfig::from_config_args(args)
         .expect("the configuration should be valid")
 }
+
+/// What a proxy started by [`serve_proxy`] was asked to tunnel to.
+pub type Tunnels = Arc<std::sync::Mutex<Vec<String>>>;
+
+/// Serve an HTTP proxy that answers `CONNECT` only, on an ephemeral loopback port, and return its
+/// `http://` URI and the targets it tunnelled to.
+///
+/// With `credentials`, as `user:password`, it requires them in basic authentication and answers 407
+/// without them.
+pub async fn serve_proxy(credentials: Option<&str>) -> (String, Tunnels) {
+    use base64::Engine as _;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind the test proxy");
+    let address = listener.local_addr().expect("the test proxy's address");
+    let expected = credentials.map(|credentials| {
+        format!(
+            "basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        )
+    });
+    let tunnels = Tunnels::default();
+
+    let recorded = tunnels.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let expected = expected.clone();
+            let recorded = recorded.clone();
+            tokio::spawn(async move {
+                // Buffered for reading the head line by line; whatever was read past it is still
+                // relayed, since the tunnel reads through the same buffer.
+                let mut client = tokio::io::BufReader::new(stream);
+                let mut head = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    if client.read_line(&mut line).await.unwrap_or(0) == 0 {
+                        return;
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push(line.trim_end().to_owned());
+                }
+
+                let target = match head.first().map(|line| line.split(' ').collect::<Vec<_>>()) {
+                    Some(parts) if parts.len() == 3 && parts[0] == "CONNECT" => parts[1].to_owned(),
+                    _ => {
+                        let _ = client
+                            .write_all(b"HTTP/1.1 405 Method Not Allowed\r\n\r\n")
+                            .await;
+                        return;
+                    }
+                };
+                let authorized = expected.as_ref().is_none_or(|expected| {
+                    head.iter().any(|line| {
+                        line.split_once(':').is_some_and(|(name, value)| {
+                            name.eq_ignore_ascii_case("proxy-authorization")
+                                && value.trim().eq_ignore_ascii_case(expected)
+                        })
+                    })
+                });
+                if !authorized {
+                    let _ = client
+                        .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                        .await;
+                    return;
+                }
+
+                let Ok(mut server) = tokio::net::TcpStream::connect(&target).await else {
+                    let _ = client.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").await;
+                    return;
+                };
+                recorded.lock().unwrap().push(target);
+                if client
+                    .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                    .await
+                    .is_ok()
+                {
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                }
+            });
+        }
+    });
+
+    (format!("http://{address}"), tunnels)
+}
+
+/// A fresh directory to put the files of one test in.
+pub fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("armonik-transport-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write `contents` to `name` in `dir`, returning its path as an option value.
+pub fn write(dir: &Path, name: &str, contents: impl AsRef<[u8]>) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path.display().to_string()
+}
+
+/// The parameters of a certificate named `name`, for `subject_alt_names`.
+///
+/// Named apart from each other: PKCS#12 readers link a certificate to its issuer by name.
+pub fn params(name: &str, subject_alt_names: Vec<String>) -> CertificateParams {
+    let mut params = CertificateParams::new(subject_alt_names).unwrap();
+    params.distinguished_name = DistinguishedName::new();
+    params.distinguished_name.push(DnType::CommonName, name);
+    params
+}
+
+pub fn ca_params(name: &str) -> CertificateParams {
+    let mut params = params(name, Vec::new());
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params
+}
+
+/// A root Certificate Authority.
+pub fn root() -> CertifiedIssuer<'static, KeyPair> {
+    CertifiedIssuer::self_signed(ca_params("root"), KeyPair::generate().unwrap()).unwrap()
+}
+
+/// An intermediate Certificate Authority, signed by `root`.
+pub fn intermediate(root: &CertifiedIssuer<'static, KeyPair>) -> CertifiedIssuer<'static, KeyPair> {
+    CertifiedIssuer::signed_by(
+        ca_params("intermediate"),
+        KeyPair::generate().unwrap(),
+        root,
+    )
+    .unwrap()
+}
+
+/// A certificate for `localhost`, signed by `issuer`, and its key.
+pub fn leaf(issuer: &CertifiedIssuer<'static, KeyPair>) -> (rcgen::Certificate, KeyPair) {
+    let key = KeyPair::generate().unwrap();
+    let cert = params("localhost", vec![String::from("localhost")])
+        .signed_by(&key, issuer)
+        .unwrap();
+    (cert, key)
+}
@@ -0,0 +1,114 @@
+//! `diagnose`: each step of connecting reported on its own, against servers that are not there, do not
+//! speak what the endpoint says, or are not a control plane.
+
+mod common;
+
+use std::time::Duration;
+
+use armonik_transport::{diagnose, Outcome, Report, Step};
+use common::{config, serve, SlowService, VersionsService, API_VERSION, CORE_VERSION};
+
+/// The outcome of `step`, which has to have been gone through.
+fn outcome(report: &Report, step: Step) -> &Outcome {
+    &report
+        .step(step)
+        .unwrap_or_else(|| panic!("{step} should be reported:\n{report}"))
+        .outcome
+}
+
+/// The hint of the step that failed, which has to have one.
+fn hint(report: &Report) -> &str {
+    match &report.first_failure().map(|step| &step.outcome) {
+        Some(Outcome::Failed {
+            hint: Some(hint), ..
+        }) => hint,
+        _ => panic!("a step should have failed with a hint:\n{report}"),
+    }
+}
+
+#[tokio::test]
+async fn every_step_passes_against_a_control_plane() {
+    let endpoint = serve(VersionsService).await;
+
+    let report = diagnose(config(&endpoint, |_| ())).await;
+
+    assert!(report.is_ok(), "{report}");
+    for step in [Step::Uri, Step::Dns, Step::Tcp, Step::Http2, Step::Rpc] {
+        assert_eq!(outcome(&report, step), &Outcome::Passed, "{report}");
+    }
+    assert!(
+        matches!(outcome(&report, Step::Tls), Outcome::Skipped { .. }),
+        "{report}"
+    );
+    let versions = report.versions.as_ref().expect("the versions");
+    assert_eq!(
+        (versions.core.as_str(), versions.api.as_str()),
+        (CORE_VERSION, API_VERSION)
+    );
+    assert!(report.to_string().contains(CORE_VERSION), "{report}");
+}
+
+#[tokio::test]
+async fn a_port_nothing_listens_on_fails_the_tcp_step_and_skips_the_rest() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+
+    let report = diagnose(config(&endpoint, |_| ())).await;
+
+    assert!(
+        matches!(outcome(&report, Step::Tcp), Outcome::Failed { .. }),
+        "{report}"
+    );
+    assert!(hint(&report).contains("nothing listens"), "{report}");
+    for step in [Step::Tls, Step::Http2, Step::Rpc] {
+        assert!(
+            matches!(outcome(&report, step), Outcome::Skipped { .. }),
+            "{report}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_host_that_does_not_resolve_fails_the_dns_step() {
+    let report = diagnose(config("http://armonik.invalid:5001", |args| {
+        args.connect_timeout = String::from("5s");
+    }))
+    .await;
+
+    assert!(
+        matches!(outcome(&report, Step::Dns), Outcome::Failed { .. }),
+        "{report}"
+    );
+}
+
+#[tokio::test]
+async fn an_https_endpoint_to_a_plaintext_server_is_told_apart() {
+    let endpoint = serve(VersionsService).await;
+    let endpoint = endpoint.replacen("http://", "https://", 1);
+
+    let report = diagnose(config(&endpoint, |_| ())).await;
+
+    assert!(
+        matches!(outcome(&report, Step::Tls), Outcome::Failed { .. }),
+        "{report}"
+    );
+    assert!(
+        hint(&report).contains("plaintext server but https endpoint"),
+        "{report}"
+    );
+}
+
+#[tokio::test]
+async fn a_grpc_server_that_is_not_a_control_plane_fails_the_call_only() {
+    let endpoint = serve(SlowService::new(Duration::ZERO)).await;
+
+    let report = diagnose(config(&endpoint, |_| ())).await;
+
+    assert_eq!(outcome(&report, Step::Http2), &Outcome::Passed, "{report}");
+    assert!(
+        matches!(outcome(&report, Step::Rpc), Outcome::Failed { .. }),
+        "{report}"
+    );
+    assert!(hint(&report).contains("Versions service"), "{report}");
+}
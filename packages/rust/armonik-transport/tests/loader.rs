@@ -0,0 +1,245 @@
+//! `ConfigLoader`: the same settings read from profiles, files, the environment and overrides, with the
+//! later ones winning and errors pointing back at where a value came from.
+
+mod common;
+
+use std::path::PathBuf;
+
+use armonik_transport::{ConfigError, ConfigLoader, Origin};
+use common::{dir, write};
+
+/// Every message in the chain, joined.
+fn chain(error: &ConfigError) -> String {
+    let mut rendered = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        rendered.push_str(" | ");
+        rendered.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    rendered
+}
+
+#[test]
+fn an_appsettings_file_is_read_from_its_grpc_client_section() {
+    let dir = dir("appsettings");
+    let path: PathBuf = write(
+        &dir,
+        "appsettings.json",
+        r#"{
+            "Logging": { "LogLevel": { "Default": "Information" } },
+            "GrpcClient": {
+                "Endpoint": "https://armonik.example.com:5001",
+                "AllowUnsafeConnection": true,
+                "RetryMaxAttempts": 3,
+                "Endpoints": ["https://cp-1:5001", "https://cp-2:5001"]
+            }
+        }"#,
+    )
+    .into();
+
+    let loaded = ConfigLoader::new()
+        .profiles_file(dir.join("none.toml"))
+        .file(&path)
+        .without_env()
+        .load()
+        .expect("a valid file");
+
+    assert_eq!(loaded.args().endpoint, "https://armonik.example.com:5001");
+    assert!(loaded.args().allow_unsafe_connection);
+    assert_eq!(loaded.args().retry_max_attempts, "3");
+    assert_eq!(
+        loaded.args().endpoints,
+        "https://cp-1:5001,https://cp-2:5001"
+    );
+    assert_eq!(loaded.origin("Endpoint"), Some(&Origin::File(path.clone())));
+    assert_eq!(
+        loaded.origin("retry_max_attempts"),
+        Some(&Origin::File(path))
+    );
+    assert_eq!(loaded.origin("Timeout"), Some(&Origin::Default));
+    assert_eq!(loaded.origin("NotASetting"), None);
+}
+
+#[test]
+#[serial_test::serial]
+fn each_source_overrides_the_ones_before_it() {
+    let dir = dir("layers");
+    let profiles: PathBuf = write(
+        &dir,
+        "config.toml",
+        r#"
+        current_profile = "dev"
+
+        [profiles.dev]
+        endpoint = "http://dev:5001"
+        timeout = "10s"
+        user_agent = "profile"
+        token = "from-profile"
+
+        [profiles.prod]
+        endpoint = "https://prod:5001"
+        "#,
+    )
+    .into();
+    let file: PathBuf = write(
+        &dir,
+        "client.yaml",
+        "GrpcClient:\n  Timeout: 20s\n  UserAgent: file\n  Token: from-file\n",
+    )
+    .into();
+
+    std::env::set_var("GrpcClient__UserAgent", "env");
+    std::env::set_var("GrpcClient__Token", "from-env");
+    let loaded = ConfigLoader::new()
+        .profiles_file(&profiles)
+        .file(&file)
+        .set("Token", "overridden")
+        .load();
+    std::env::remove_var("GrpcClient__UserAgent");
+    std::env::remove_var("GrpcClient__Token");
+    let loaded = loaded.expect("valid sources");
+
+    let args = loaded.args();
+    assert_eq!(args.endpoint, "http://dev:5001");
+    assert_eq!(args.timeout, "20s");
+    assert_eq!(args.user_agent, "env");
+    assert_eq!(args.token, "overridden");
+    assert_eq!(
+        loaded.origin("Endpoint"),
+        Some(&Origin::Profile {
+            path: profiles.clone(),
+            name: String::from("dev"),
+        })
+    );
+    assert_eq!(loaded.origin("Timeout"), Some(&Origin::File(file)));
+    assert_eq!(
+        loaded.origin("UserAgent"),
+        Some(&Origin::Env(String::from("GrpcClient__UserAgent")))
+    );
+    assert_eq!(loaded.origin("Token"), Some(&Origin::Override));
+
+    // Another profile, asked for by name.
+    let prod = ConfigLoader::new()
+        .profiles_file(&profiles)
+        .profile("prod")
+        .without_env()
+        .load()
+        .expect("the profile exists");
+    assert_eq!(prod.args().endpoint, "https://prod:5001");
+    assert_eq!(prod.args().timeout, "");
+}
+
+#[test]
+fn a_profile_that_does_not_exist_is_reported_with_the_file() {
+    let dir = dir("unknown-profile");
+    let profiles: PathBuf = write(&dir, "config.toml", "[profiles.dev]\nendpoint = \"x\"\n").into();
+
+    let error = ConfigLoader::new()
+        .profiles_file(&profiles)
+        .profile("staging")
+        .without_env()
+        .load()
+        .expect_err("no such profile");
+
+    assert!(
+        matches!(error, ConfigError::UnknownProfile { .. }),
+        "{error:?}"
+    );
+    assert!(chain(&error).contains("staging"), "{}", chain(&error));
+    assert!(
+        chain(&error).contains(&profiles.display().to_string()),
+        "{}",
+        chain(&error)
+    );
+}
+
+#[test]
+fn an_invalid_value_is_blamed_on_the_setting_and_where_it_was_found() {
+    let dir = dir("blame");
+    let file: PathBuf = write(
+        &dir,
+        "client.toml",
+        "[GrpcClient]\nEndpoint = \"http://localhost:5001\"\nTimeout = \"soon\"\n",
+    )
+    .into();
+
+    let error = ConfigLoader::new()
+        .profiles_file(dir.join("none.toml"))
+        .file(&file)
+        .without_env()
+        .load()
+        .expect("the file is readable")
+        .into_config()
+        .expect_err("`soon` is not a duration");
+
+    let rendered = chain(&error);
+    assert!(rendered.contains("`Timeout` from"), "{rendered}");
+    assert!(rendered.contains(&file.display().to_string()), "{rendered}");
+    assert!(!rendered.contains("`Endpoint` from"), "{rendered}");
+}
+
+#[test]
+fn values_quoted_by_an_error_blame_nothing_else() {
+    // Set to values short enough to be found anywhere in the error, its source locations included.
+    let error = ConfigLoader::new()
+        .profiles_file(dir("blame-values").join("none.toml"))
+        .without_env()
+        .set("Endpoint", "http://localhost:443")
+        .set("TcpKeepaliveRetries", "10")
+        .set("Http2MaxHeaderListSize", "443")
+        .set("RetryMaxAttempts", "1")
+        .set("Timeout", "10")
+        .load()
+        .expect("the overrides are settings")
+        .into_config()
+        .expect_err("`10` has no unit");
+
+    let rendered = chain(&error);
+    assert!(
+        rendered.contains("`Timeout` from an override"),
+        "{rendered}"
+    );
+    for innocent in [
+        "Endpoint",
+        "TcpKeepaliveRetries",
+        "Http2MaxHeaderListSize",
+        "RetryMaxAttempts",
+    ] {
+        assert!(
+            !rendered.contains(&format!("`{innocent}` from")),
+            "{rendered}"
+        );
+    }
+}
+
+#[test]
+fn a_flag_that_is_not_a_boolean_names_where_it_was_found() {
+    let error = ConfigLoader::new()
+        .profiles_file(dir("flag").join("none.toml"))
+        .without_env()
+        .set("allow_unsafe_connection", "perhaps")
+        .load()
+        .expect_err("`perhaps` is not a boolean");
+
+    assert!(
+        matches!(error, ConfigError::InvalidBoolean { .. }),
+        "{error:?}"
+    );
+    assert!(chain(&error).contains("an override"), "{}", chain(&error));
+}
+
+#[test]
+fn an_override_of_a_setting_that_does_not_exist_is_rejected() {
+    let error = ConfigLoader::new()
+        .profiles_file(dir("unknown-setting").join("none.toml"))
+        .without_env()
+        .set("Endpiont", "http://localhost:5001")
+        .load()
+        .expect_err("a typo");
+
+    assert!(
+        matches!(error, ConfigError::UnknownSetting { .. }),
+        "{error:?}"
+    );
+}
@@ -0,0 +1,97 @@
+//! `GrpcClient__Proxy`, `HTTP_PROXY` and `NO_PROXY`: calls tunnelled through a local stand-in for a
+//! corporate proxy, which records what it was asked to tunnel to.
+//!
+//! The environment is process-global, hence one test at a time.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{call, config, serve, serve_proxy, SlowService, REPLY};
+
+/// The `host:port` a proxy is asked to tunnel to for `endpoint`.
+fn target(endpoint: &str) -> String {
+    endpoint.trim_start_matches("http://").to_owned()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn calls_are_tunnelled_through_the_proxy() {
+    let endpoint = serve(SlowService::new(Duration::ZERO)).await;
+    let (proxy, tunnels) = serve_proxy(None).await;
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| args.proxy = proxy))
+        .await
+        .expect("connecting through the proxy should succeed");
+
+    assert_eq!(call(channel).await.expect("the call should succeed"), REPLY);
+    assert_eq!(*tunnels.lock().unwrap(), vec![target(&endpoint)]);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn the_credentials_of_the_proxy_uri_are_sent_to_it() {
+    let endpoint = serve(SlowService::new(Duration::ZERO)).await;
+    let (proxy, tunnels) = serve_proxy(Some("build@farm:s3cr3t")).await;
+    let with = |credentials: &str| proxy.replacen("http://", &format!("http://{credentials}@"), 1);
+
+    // `@` percent-encoded in the user name, as a URI has it.
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.proxy = with("build%40farm:s3cr3t")
+    }))
+    .await
+    .expect("the right credentials should be let through");
+    assert_eq!(call(channel).await.expect("the call should succeed"), REPLY);
+
+    let error = armonik_transport::connect(config(&endpoint, |args| {
+        args.proxy = with("build%40farm:wrong")
+    }))
+    .await
+    .expect_err("the wrong credentials should be refused");
+    let mut rendered = error.to_string();
+    let mut source = std::error::Error::source(&error);
+    while let Some(cause) = source {
+        rendered.push_str(" | ");
+        rendered.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    assert!(rendered.contains("407"), "{rendered}");
+    assert!(!rendered.contains("s3cr3t"), "{rendered}");
+    assert!(!rendered.contains("wrong"), "{rendered}");
+    assert_eq!(tunnels.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn the_proxy_of_the_environment_is_used_unless_no_proxy_lists_the_host() {
+    let endpoint = serve(SlowService::new(Duration::ZERO)).await;
+    let (proxy, tunnels) = serve_proxy(None).await;
+
+    std::env::set_var("HTTP_PROXY", &proxy);
+    let proxied = armonik_transport::connect(config(&endpoint, |_| ())).await;
+    std::env::set_var("NO_PROXY", "example.com,127.0.0.0/8");
+    let direct = armonik_transport::connect(config(&endpoint, |_| ())).await;
+    std::env::remove_var("HTTP_PROXY");
+    std::env::remove_var("NO_PROXY");
+
+    let proxied = proxied.expect("connecting through the proxy should succeed");
+    assert_eq!(call(proxied).await.expect("the call should succeed"), REPLY);
+    let direct = direct.expect("connecting straight to the server should succeed");
+    assert_eq!(call(direct).await.expect("the call should succeed"), REPLY);
+    assert_eq!(*tunnels.lock().unwrap(), vec![target(&endpoint)]);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn a_proxy_of_the_environment_that_is_not_valid_is_left_out() {
+    let endpoint = serve(SlowService::new(Duration::ZERO)).await;
+
+    // Set for other tools, which may speak SOCKS: connecting straight to the server is what was done
+    // before the environment was read at all.
+    std::env::set_var("HTTP_PROXY", "socks5://proxy:1080");
+    let result = armonik_transport::connect(config(&endpoint, |_| ())).await;
+    std::env::remove_var("HTTP_PROXY");
+
+    let channel = result.expect("connecting straight to the server should succeed");
+    assert_eq!(call(channel).await.expect("the call should succeed"), REPLY);
+}
@@ -0,0 +1,167 @@
+//! `GrpcClient__Retry*` reaching the channel.
+//!
+//! Against a local server that fails its first calls on purpose, counting how many calls it actually
+//! got: what the caller sees alone would not tell a retried call from one that succeeded first time.
+
+mod common;
+
+use armonik_transport::reexports::tonic::Code;
+use bytes::Bytes;
+use std::time::Duration;
+
+use common::{call_at, call_stream, call_within, config, serve, FlakyService, FLAKY_PATH};
+
+/// Connect to `endpoint` retrying up to `max_attempts` times, with delays short enough for a test.
+async fn connect(endpoint: &str, max_attempts: u32) -> armonik_transport::Channel {
+    armonik_transport::connect(config(endpoint, |args| {
+        args.retry_max_attempts = max_attempts.to_string();
+        args.retry_initial_backoff = String::from("10ms");
+        args.retry_max_backoff = String::from("50ms");
+    }))
+    .await
+    .expect("connecting should succeed")
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried_until_the_call_succeeds() {
+    let service = FlakyService::new(2, Code::Unavailable);
+    let endpoint = serve(service.clone()).await;
+
+    let answer = call_at(connect(&endpoint, 3).await, FLAKY_PATH)
+        .await
+        .expect("the third attempt should succeed");
+
+    assert_eq!(answer.as_ref(), common::REPLY);
+    assert_eq!(service.calls(), 3);
+}
+
+#[tokio::test]
+async fn the_last_failure_is_returned_once_attempts_run_out() {
+    let service = FlakyService::new(10, Code::ResourceExhausted);
+    let endpoint = serve(service.clone()).await;
+
+    let status = call_at(connect(&endpoint, 3).await, FLAKY_PATH)
+        .await
+        .expect_err("every attempt fails");
+
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(service.calls(), 3);
+}
+
+#[tokio::test]
+async fn a_code_that_is_not_retryable_fails_the_call_straight_away() {
+    let service = FlakyService::new(1, Code::InvalidArgument);
+    let endpoint = serve(service.clone()).await;
+
+    let status = call_at(connect(&endpoint, 5).await, FLAKY_PATH)
+        .await
+        .expect_err("the failure is not transient");
+
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn retryable_codes_replace_the_default_ones() {
+    let service = FlakyService::new(1, Code::Unavailable);
+    let endpoint = serve(service.clone()).await;
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.retry_max_attempts = String::from("3");
+        args.retry_initial_backoff = String::from("10ms");
+        args.retryable_codes = String::from("Aborted");
+    }))
+    .await
+    .expect("connecting should succeed");
+    let status = call_at(channel, FLAKY_PATH)
+        .await
+        .expect_err("`Unavailable` is not listed");
+
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn retries_are_off_by_default() {
+    let service = FlakyService::new(1, Code::Unavailable);
+    let endpoint = serve(service.clone()).await;
+
+    let channel = armonik_transport::connect(config(&endpoint, |_| {}))
+        .await
+        .expect("connecting should succeed");
+    let status = call_at(channel, FLAKY_PATH)
+        .await
+        .expect_err("the one attempt fails");
+
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn a_client_stream_is_not_sent_again_once_it_started() {
+    // The server reads the first chunk before failing, so it was sent: sending the stream again could
+    // only deliver it twice.
+    let service = FlakyService::new(1, Code::Unavailable);
+    let endpoint = serve(service.clone()).await;
+
+    let status = call_stream(
+        connect(&endpoint, 5).await,
+        vec![Bytes::from_static(b"first"), Bytes::from_static(b"second")],
+    )
+    .await
+    .expect_err("the stream is not retried");
+
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn retries_are_sent_with_what_is_left_of_the_deadline_and_stop_once_it_passes() {
+    let service = FlakyService::new(100, Code::Unavailable);
+    let endpoint = serve(service.clone()).await;
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.retry_max_attempts = String::from("100");
+        args.retry_initial_backoff = String::from("100ms");
+        args.retry_max_backoff = String::from("100ms");
+    }))
+    .await
+    .expect("connecting should succeed");
+
+    let started = std::time::Instant::now();
+    let status = call_within(channel, FLAKY_PATH, Some(Duration::from_millis(500)))
+        .await
+        .expect_err("every attempt fails");
+
+    assert_eq!(status.code(), Code::Unavailable);
+    assert!(
+        started.elapsed() < Duration::from_secs(1),
+        "{:?}",
+        started.elapsed()
+    );
+    // At least 50ms between attempts, none of them after the deadline.
+    let calls = service.calls();
+    assert!((2..=10).contains(&calls), "{calls} calls");
+
+    let timeouts = service
+        .timeouts()
+        .into_iter()
+        .map(|timeout| {
+            let timeout = timeout.expect("every attempt has a deadline");
+            let (amount, unit) = timeout.split_at(timeout.len() - 1);
+            let amount = amount.parse::<u64>().unwrap();
+            match unit {
+                "m" => Duration::from_millis(amount),
+                "u" => Duration::from_micros(amount),
+                "n" => Duration::from_nanos(amount),
+                unit => panic!("{unit} is no unit for less than a second"),
+            }
+        })
+        .collect::<Vec<_>>();
+    assert!(timeouts[0] <= Duration::from_millis(500), "{timeouts:?}");
+    assert!(
+        timeouts
+            .windows(2)
+            .all(|pair| pair[1] + Duration::from_millis(50) <= pair[0]),
+        "{timeouts:?}"
+    );
+}
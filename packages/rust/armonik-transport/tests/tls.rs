@@ -0,0 +1,448 @@
+//! Certificate chains, bundles of Certificate Authorities, PKCS#12 identities and pinned keys, against a
+//! server speaking TLS with certificates made up for each test.
+
+mod common;
+
+use std::time::Duration;
+
+use std::sync::Arc;
+
+use armonik_transport::reexports::rustls;
+use armonik_transport::reexports::tonic::codegen::tokio_stream::{
+    wrappers::TcpListenerStream, StreamExt,
+};
+use armonik_transport::reexports::tonic::transport::Server;
+use armonik_transport::{diagnose, Outcome, Step};
+use base64::Engine as _;
+use common::{call, config, dir, intermediate, leaf, root, serve_proxy, write, SlowService, REPLY};
+use rcgen::{CertifiedIssuer, KeyPair, PublicKeyData as _};
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+use rustls::server::WebPkiClientVerifier;
+
+/// The pin of the public key of `key`, as `GrpcClient__PinnedSpki` takes it.
+fn pin(key: &KeyPair) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, &key.subject_public_key_info());
+    format!(
+        "sha256/{}",
+        base64::engine::general_purpose::STANDARD.encode(digest)
+    )
+}
+
+/// Serve the slow service over TLS with `chain` and `key`, requiring a client certificate issued by
+/// `client_ca` if any, and return its `https://` endpoint.
+async fn serve_tls(
+    chain: Vec<CertificateDer<'static>>,
+    key: &KeyPair,
+    client_ca: Option<&CertifiedIssuer<'static, KeyPair>>,
+) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind the test server");
+    let address = listener.local_addr().expect("the test server's address");
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let tls = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let tls = match client_ca {
+        None => tls.with_no_client_auth(),
+        Some(client_ca) => {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.add(client_ca.der().clone()).unwrap();
+            let verifier = WebPkiClientVerifier::builder_with_provider(roots.into(), provider)
+                .build()
+                .unwrap();
+            tls.with_client_cert_verifier(verifier)
+        }
+    };
+    let mut tls = tls
+        .with_single_cert(chain, PrivatePkcs8KeyDer::from(key.serialize_der()).into())
+        .expect("a valid server TLS configuration");
+    tls.alpn_protocols = vec![b"h2".to_vec()];
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(tls));
+
+    tokio::spawn(async move {
+        // A failed handshake is what some tests are after: it ends that connection, not the server.
+        let incoming = TcpListenerStream::new(listener)
+            .then(move |tcp| {
+                let acceptor = acceptor.clone();
+                async move { acceptor.accept(tcp?).await }
+            })
+            .filter_map(|tls| tls.ok().map(Ok::<_, std::io::Error>));
+        Server::builder()
+            .add_service(SlowService::new(Duration::ZERO))
+            .serve_with_incoming(incoming)
+            .await
+            .expect("serve the test service");
+    });
+
+    // The certificates name `localhost`, not the address.
+    format!("https://localhost:{}", address.port())
+}
+
+/// A server whose certificate is issued by an intermediate of `root`, presenting both, and the pin of
+/// the intermediate.
+async fn serve_chain(root: &CertifiedIssuer<'static, KeyPair>) -> (String, String) {
+    let intermediate = intermediate(root);
+    let (server, key) = leaf(&intermediate);
+    let endpoint = serve_tls(
+        vec![server.der().clone(), intermediate.der().clone()],
+        &key,
+        None,
+    )
+    .await;
+    (endpoint, pin(intermediate.key()))
+}
+
+#[tokio::test]
+async fn every_certificate_authority_of_a_bundle_is_trusted() {
+    let dir = dir("bundle");
+    let (other, root) = (self::root(), self::root());
+    let (endpoint, _) = serve_chain(&root).await;
+    // The root of the server comes second: reading the first certificate alone would miss it.
+    let ca_cert = write(&dir, "ca.pem", format!("{}{}", other.pem(), root.pem()));
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert;
+    }))
+    .await
+    .expect("connecting should succeed");
+
+    let reply = call(channel).await.expect("the call should succeed");
+    assert_eq!(reply.as_ref(), REPLY);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_server_of_no_certificate_authority_of_the_bundle_is_refused() {
+    let dir = dir("untrusted");
+    let (endpoint, _) = serve_chain(&root()).await;
+    let ca_cert = write(&dir, "ca.pem", format!("{}{}", root().pem(), root().pem()));
+
+    let result = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert;
+    }))
+    .await;
+
+    assert!(result.is_err(), "the handshake should fail");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_certificate_of_the_bundle_that_cannot_be_read_is_refused() {
+    let dir = dir("unreadable");
+    let root = root();
+    let (endpoint, _) = serve_chain(&root).await;
+    // Its PEM is fine, not the DER it wraps: leaving it out would trust less than asked for, silently.
+    let broken = "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+    let ca_cert = write(&dir, "ca.pem", format!("{}{broken}", root.pem()));
+
+    let error = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert;
+    }))
+    .await
+    .expect_err("the bundle should be refused");
+    let mut rendered = error.to_string();
+    let mut source = std::error::Error::source(&error);
+    while let Some(cause) = source {
+        rendered.push_str(" | ");
+        rendered.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    assert!(
+        rendered.contains("Certificate 2 of the Certificate Authorities"),
+        "{rendered}"
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn the_system_certificate_authorities_can_be_trusted_along_with_a_bundle() {
+    let dir = dir("native");
+    let root = root();
+    let (endpoint, _) = serve_chain(&root).await;
+    let ca_cert = write(&dir, "ca.pem", root.pem());
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert;
+        args.trust_native_roots = true;
+    }))
+    .await
+    .expect("connecting should succeed");
+
+    let reply = call(channel).await.expect("the call should succeed");
+    assert_eq!(reply.as_ref(), REPLY);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn the_client_presents_its_whole_chain() {
+    let dir = dir("client-chain");
+    let (server_root, client_root) = (root(), root());
+    let (server, server_key) = leaf(&server_root);
+    // The server knows the root of the client only: the intermediate has to come from the client.
+    let endpoint = serve_tls(vec![server.der().clone()], &server_key, Some(&client_root)).await;
+    let client_intermediate = intermediate(&client_root);
+    let (client, client_key) = leaf(&client_intermediate);
+    let ca_cert = write(&dir, "ca.pem", server_root.pem());
+
+    let chain = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert.clone();
+        args.cert_pem = write(
+            &dir,
+            "chain.pem",
+            format!("{}{}", client.pem(), client_intermediate.pem()),
+        );
+        args.key_pem = write(&dir, "client.key", client_key.serialize_pem());
+    }))
+    .await
+    .expect("connecting should succeed");
+    let reply = call(chain).await.expect("the call should succeed");
+    assert_eq!(reply.as_ref(), REPLY);
+
+    // The client certificate alone does not lead to a root the server knows.
+    let alone = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert.clone();
+        args.cert_pem = write(&dir, "client.pem", client.pem());
+        args.key_pem = write(&dir, "client.key", client_key.serialize_pem());
+    }))
+    .await;
+    let refused = match alone {
+        Ok(channel) => call(channel).await.is_err(),
+        Err(_) => true,
+    };
+    assert!(refused, "the server should refuse the client certificate");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn mutual_tls_runs_end_to_end_through_a_proxy() {
+    let dir = dir("proxy");
+    let (server_root, client_root) = (root(), root());
+    let (server, server_key) = leaf(&server_root);
+    let endpoint = serve_tls(vec![server.der().clone()], &server_key, Some(&client_root)).await;
+    let (client, client_key) = leaf(&client_root);
+    let (proxy, tunnels) = serve_proxy(Some("user:password")).await;
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = write(&dir, "ca.pem", server_root.pem());
+        args.cert_pem = write(&dir, "client.pem", client.pem());
+        args.key_pem = write(&dir, "client.key", client_key.serialize_pem());
+        args.proxy = proxy.replacen("http://", "http://user:password@", 1);
+    }))
+    .await
+    .expect("connecting through the proxy should succeed");
+
+    let reply = call(channel).await.expect("the call should succeed");
+    assert_eq!(reply.as_ref(), REPLY);
+    // The proxy was asked for the server by the name its certificate is verified against.
+    assert_eq!(
+        *tunnels.lock().unwrap(),
+        vec![endpoint.trim_start_matches("https://").to_owned()]
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// The hint of the step of `report` that failed, which has to have one.
+fn failure_hint(report: &armonik_transport::Report) -> (Step, String) {
+    match report.first_failure() {
+        Some(step) => match &step.outcome {
+            Outcome::Failed {
+                hint: Some(hint), ..
+            } => (step.step, hint.clone()),
+            _ => panic!("the failure should have a hint:\n{report}"),
+        },
+        None => panic!("a step should have failed:\n{report}"),
+    }
+}
+
+#[tokio::test]
+async fn a_diagnosis_shows_the_server_chain_and_why_it_is_not_trusted() {
+    let dir = dir("diagnose-untrusted");
+    let (endpoint, _) = serve_chain(&root()).await;
+    let ca_cert = write(&dir, "ca.pem", root().pem());
+
+    let report = diagnose(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert;
+    }))
+    .await;
+
+    let (step, hint) = failure_hint(&report);
+    assert_eq!(step, Step::Tls, "{report}");
+    assert!(hint.contains("GrpcClient__CaCert"), "{report}");
+    let tls = report.tls.as_ref().expect("the TLS report");
+    assert_eq!(tls.chain.len(), 2, "{report}");
+    assert_eq!(
+        tls.chain[0].subject_alt_names,
+        vec!["localhost"],
+        "{report}"
+    );
+    assert!(tls.chain[1].subject.contains("intermediate"), "{report}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_diagnosis_names_the_names_a_certificate_is_for() {
+    let dir = dir("diagnose-name");
+    let root = root();
+    let (endpoint, _) = serve_chain(&root).await;
+    let ca_cert = write(&dir, "ca.pem", root.pem());
+    let by_address = endpoint.replacen("localhost", "127.0.0.1", 1);
+
+    let report = diagnose(config(&by_address, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert.clone();
+    }))
+    .await;
+    let (step, hint) = failure_hint(&report);
+    assert_eq!(step, Step::Tls, "{report}");
+    assert!(hint.contains("`localhost`"), "{report}");
+    assert!(hint.contains("GrpcClient__OverrideTargetName"), "{report}");
+
+    // Which is what the hint says to do.
+    let report = diagnose(config(&by_address, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert;
+        args.override_target_name = String::from("localhost");
+    }))
+    .await;
+    assert_eq!(
+        report.step(Step::Http2).map(|step| &step.outcome),
+        Some(&Outcome::Passed),
+        "{report}"
+    );
+    let tls = report.tls.as_ref().expect("the TLS report");
+    assert_eq!(tls.alpn.as_deref(), Some("h2"), "{report}");
+    assert!(tls.version.is_some(), "{report}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_diagnosis_tells_a_server_expecting_tls_from_a_plaintext_endpoint() {
+    let (endpoint, _) = serve_chain(&root()).await;
+    let plaintext = endpoint.replacen("https://", "http://", 1);
+
+    let report = diagnose(config(&plaintext, |_| ())).await;
+
+    let (step, hint) = failure_hint(&report);
+    assert_eq!(step, Step::Http2, "{report}");
+    assert!(hint.contains("https://"), "{report}");
+}
+
+#[tokio::test]
+async fn a_diagnosis_says_when_the_server_requires_a_client_certificate() {
+    let (server_root, client_root) = (root(), root());
+    let (server, server_key) = leaf(&server_root);
+    let endpoint = serve_tls(vec![server.der().clone()], &server_key, Some(&client_root)).await;
+
+    let report = diagnose(config(&endpoint, |_| ())).await;
+
+    // Refused in the handshake with TLS 1.2, on the first read after it with TLS 1.3.
+    let (step, hint) = failure_hint(&report);
+    assert!(matches!(step, Step::Tls | Step::Http2), "{report}");
+    assert!(hint.contains("GrpcClient__CertPem"), "{report}");
+}
+
+#[cfg(feature = "pkcs12")]
+#[tokio::test]
+async fn the_client_identity_can_be_read_from_a_pkcs12_file() {
+    let dir = dir("pkcs12");
+    let (server_root, client_root) = (root(), root());
+    let (server, server_key) = leaf(&server_root);
+    let endpoint = serve_tls(vec![server.der().clone()], &server_key, Some(&client_root)).await;
+    let client_intermediate = intermediate(&client_root);
+    let (client, client_key) = leaf(&client_intermediate);
+
+    let chain = [client.der(), client_intermediate.der()]
+        .into_iter()
+        .map(|cert| p12_keystore::Certificate::from_der(cert).unwrap());
+    let mut store = p12_keystore::KeyStore::new();
+    store.add_entry(
+        "client",
+        p12_keystore::KeyStoreEntry::PrivateKeyChain(p12_keystore::PrivateKeyChain::new(
+            client_key.serialize_der(),
+            b"client",
+            chain,
+        )),
+    );
+    let cert_p12 = write(
+        &dir,
+        "client.p12",
+        store.writer("p4ssw0rd").write().unwrap(),
+    );
+    let ca_cert = write(&dir, "ca.pem", server_root.pem());
+
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert;
+        args.cert_p12 = cert_p12;
+        args.cert_p12_password = String::from("p4ssw0rd");
+    }))
+    .await
+    .expect("connecting should succeed");
+
+    let reply = call(channel).await.expect("the call should succeed");
+    assert_eq!(reply.as_ref(), REPLY);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_pinned_key_of_the_chain_is_required_on_top_of_the_certificate_authority() {
+    let dir = dir("pinned");
+    let root = root();
+    let (endpoint, intermediate_pin) = serve_chain(&root).await;
+    let ca_cert = write(&dir, "ca.pem", root.pem());
+
+    // The key of the intermediate is in the chain the server presents.
+    let other = KeyPair::generate().unwrap();
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert.clone();
+        args.pinned_spki = format!("{}, {}", pin(&other), intermediate_pin);
+    }))
+    .await
+    .expect("connecting should succeed");
+    let reply = call(channel).await.expect("the call should succeed");
+    assert_eq!(reply.as_ref(), REPLY);
+
+    // A server trusted by its Certificate Authority, but with none of the keys pinned.
+    let result = armonik_transport::connect(config(&endpoint, |args| {
+        args.allow_unsafe_connection = false;
+        args.ca_cert = ca_cert.clone();
+        args.pinned_spki = pin(&other);
+    }))
+    .await;
+    assert!(result.is_err(), "the handshake should fail");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_pinned_key_is_checked_even_without_verifying_the_server() {
+    let (server, key) = leaf(&root());
+    let endpoint = serve_tls(vec![server.der().clone()], &key, None).await;
+
+    // No Certificate Authority at all: the pin is what the server is trusted by.
+    let channel = armonik_transport::connect(config(&endpoint, |args| {
+        args.pinned_spki = pin(&key);
+    }))
+    .await
+    .expect("connecting should succeed");
+    let reply = call(channel).await.expect("the call should succeed");
+    assert_eq!(reply.as_ref(), REPLY);
+
+    let result = armonik_transport::connect(config(&endpoint, |args| {
+        args.pinned_spki = pin(&KeyPair::generate().unwrap());
+    }))
+    .await;
+    assert!(result.is_err(), "the handshake should fail");
+}
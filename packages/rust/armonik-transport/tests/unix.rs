@@ -0,0 +1,78 @@
+//! `unix:` endpoints, served and dialled over a real socket.
+#![cfg(unix)]
+
+mod common;
+
+use std::time::Duration;
+
+use armonik_transport::ConnectionError;
+use common::{call, config, dir, serve_unix, SlowService};
+
+#[tokio::test]
+async fn a_unix_endpoint_is_dialled_eagerly_and_answers() {
+    let dir = dir("eager");
+    let endpoint = serve_unix(SlowService::new(Duration::ZERO), &dir.join("server.sock"));
+
+    let channel = armonik_transport::connect(config(&endpoint, |_| {}))
+        .await
+        .expect("connecting should succeed");
+
+    let answer = call(channel).await.expect("the call should complete");
+    assert_eq!(answer.as_ref(), common::REPLY);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_lazy_channel_reaches_a_socket_bound_after_it_was_built() {
+    // The worker's case: the agent may start listening after the channel to it is built.
+    let dir = dir("lazy");
+    let path = dir.join("server.sock");
+
+    let channel =
+        armonik_transport::connect_lazy(config(&format!("unix:{}", path.display()), |_| {}))
+            .await
+            .expect("a lazy channel is built whether or not the socket exists");
+    serve_unix(SlowService::new(Duration::ZERO), &path);
+
+    let answer = call(channel).await.expect("the call should complete");
+    assert_eq!(answer.as_ref(), common::REPLY);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_missing_socket_is_reported_with_its_path() {
+    let dir = dir("missing");
+    let path = dir.join("nobody.sock");
+
+    let error = armonik_transport::connect(config(&format!("unix://{}", path.display()), |_| {}))
+        .await
+        .expect_err("there is no socket to connect to");
+
+    assert!(
+        matches!(error, ConnectionError::UnixSocket { .. }),
+        "{error:?}"
+    );
+    assert!(error.to_string().contains("nobody.sock"), "{error}");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_regular_file_is_not_mistaken_for_a_socket() {
+    let dir = dir("file");
+    let path = dir.join("regular.sock");
+    std::fs::write(&path, b"").unwrap();
+
+    let error = armonik_transport::connect(config(&format!("unix:{}", path.display()), |_| {}))
+        .await
+        .expect_err("a regular file is not a socket");
+
+    assert!(
+        matches!(error, ConnectionError::NotASocket { .. }),
+        "{error:?}"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
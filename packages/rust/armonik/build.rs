@@ -48,6 +48,10 @@ const PROTO_FILES: &[&str] = &[
 /// what makes `include = ["protos/**"]` vendor them into the published crate.
 const PROTO_ROOT: &str = "protos/V1";
 
+/// The file the descriptors of the protos are written to, in `OUT_DIR`, as
+/// `tonic::include_file_descriptor_set!` looks for it.
+const DESCRIPTOR_SET: &str = "armonik_descriptor.bin";
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let proto_files = PROTO_FILES
         .iter()
@@ -64,8 +68,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=protos");
     println!("cargo:rerun-if-changed=build.rs");
 
+    // Every message and service compiled, imports included, for `api::FILE_DESCRIPTOR_SET`.
+    let descriptor_set = std::path::PathBuf::from(std::env::var("OUT_DIR")?).join(DESCRIPTOR_SET);
+
     tonic_prost_build::configure()
         .use_arc_self(true)
+        .file_descriptor_set_path(descriptor_set)
         .build_client(cfg!(feature = "_gen-client"))
         .build_server(cfg!(feature = "_gen-server"))
         // Both slices have to hold the same type, and `proto_files` is `Vec<String>`.
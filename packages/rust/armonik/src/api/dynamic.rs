@@ -0,0 +1,73 @@
+//! Messages of the API handled by name rather than through their Rust types, for tools that forward,
+//! log or inspect calls without knowing in advance which ones they will see.
+//!
+//! ```
+//! use armonik::api::dynamic;
+//! use prost::Message;
+//!
+//! let request = armonik::api::v3::sessions::GetSessionRequest {
+//!     session_id: String::from("session-id"),
+//! };
+//! let method = dynamic::method("/armonik.api.grpc.v1.sessions.Sessions/GetSession").unwrap();
+//! let message = dynamic::decode(method.input().full_name(), request.encode_to_vec().as_slice()).unwrap();
+//!
+//! assert_eq!(message.get_field_by_name("session_id").unwrap().as_str(), Some("session-id"));
+//! ```
+//!
+//! With the `serde` feature, the messages decoded serialize as the JSON mapping of Protobuf.
+
+use std::sync::OnceLock;
+
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use snafu::{OptionExt, ResultExt, Snafu};
+
+/// The descriptors of every message, enum and service of the API, and of the protos they import.
+pub fn descriptor_pool() -> &'static DescriptorPool {
+    static POOL: OnceLock<DescriptorPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        DescriptorPool::decode(super::FILE_DESCRIPTOR_SET)
+            .expect("the descriptor set embedded at build time is valid")
+    })
+}
+
+/// The descriptor of the message named `name`, in full: `armonik.api.grpc.v1.sessions.SessionRaw`.
+pub fn message(name: &str) -> Option<MessageDescriptor> {
+    descriptor_pool().get_message_by_name(name)
+}
+
+/// The descriptor of the method a call is made to, from the path of the call:
+/// `/armonik.api.grpc.v1.sessions.Sessions/GetSession`.
+pub fn method(path: &str) -> Option<MethodDescriptor> {
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+    descriptor_pool()
+        .get_service_by_name(service)?
+        .methods()
+        .find(|candidate| candidate.name() == method)
+}
+
+/// Decode `bytes` as the message named `name`, in full.
+pub fn decode(name: &str, bytes: impl prost::bytes::Buf) -> Result<DynamicMessage, DecodeError> {
+    let descriptor = message(name).context(UnknownMessageSnafu { name })?;
+    DynamicMessage::decode(descriptor, bytes).context(InvalidMessageSnafu { name })
+}
+
+/// Error decoding a message by name
+#[derive(Debug, Snafu)]
+#[non_exhaustive]
+pub enum DecodeError {
+    #[snafu(display("No message of the API is named {name} [{location}]"))]
+    #[non_exhaustive]
+    UnknownMessage {
+        name: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Could not decode a {name} [{location}]"))]
+    #[non_exhaustive]
+    InvalidMessage {
+        source: prost::DecodeError,
+        name: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+}
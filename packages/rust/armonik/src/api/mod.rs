@@ -1 +1,10 @@
 pub mod v3;
+
+/// The encoded `google.protobuf.FileDescriptorSet` of every proto of the API, along with those it imports.
+///
+/// What the reflection service of the `reflection` feature serves, and what the `dynamic` feature decodes
+/// messages with; other tools can load it into a `prost_types::FileDescriptorSet`.
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("armonik_descriptor");
+
+#[cfg(feature = "dynamic")]
+pub mod dynamic;
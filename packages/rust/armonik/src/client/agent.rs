@@ -9,12 +9,13 @@ use crate::api::v3;
 use crate::utils::IntoCollection;
 use crate::TaskOptions;
 
-use super::{GrpcCall, GrpcCallStream};
+use super::{CallOptions, GrpcCall, GrpcCallStream};
 
 /// The ResultsService provides methods for interacting with results.
 #[derive(Clone)]
 pub struct Agent<T> {
     inner: v3::agent::agent_client::AgentClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Agent<T>
@@ -28,9 +29,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::agent::agent_client::AgentClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Create the metadata of multiple results at once.
     /// Data have to be uploaded separately.
     pub async fn create_results_metadata(
@@ -119,7 +127,7 @@ where
             create_tasks::Response::Error {
                 communication_token: _,
                 error,
-            } => Err(tonic::Status::internal(error)).context(super::GrpcSnafu {}),
+            } => super::TaskCreationSnafu { details: error }.fail(),
         }
     }
 
@@ -133,17 +141,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Agent {
         async fn call(self, request: create_results_metadata::Request) -> Result<create_results_metadata::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .create_results_meta_data(request),
-                tracing::debug_span!("Agent::create_results_metadata")
-            );
+            let call = self.inner.create_results_meta_data(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -152,12 +167,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: create_results::Request) -> Result<create_results::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .create_results(request),
-                tracing::debug_span!("Agent::create_results")
-            );
+            let call = self.inner.create_results(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -166,12 +176,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: notify_result_data::Request) -> Result<notify_result_data::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .notify_result_data(request),
-                tracing::debug_span!("Agent::notify_result_data")
-            );
+            let call = self.inner.notify_result_data(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -180,12 +185,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: submit_tasks::Request) -> Result<submit_tasks::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .submit_tasks(request),
-                tracing::debug_span!("Agent::submit_tasks")
-            );
+            let call = self.inner.submit_tasks(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -194,12 +194,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get_resource_data::Request) -> Result<get_resource_data::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_resource_data(request),
-                tracing::debug_span!("Agent::get_resource_data")
-            );
+            let call = self.inner.get_resource_data(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -208,12 +203,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get_common_data::Request) -> Result<get_common_data::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_common_data(request),
-                tracing::debug_span!("Agent::get_common_data")
-            );
+            let call = self.inner.get_common_data(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -222,12 +212,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get_direct_data::Request) -> Result<get_direct_data::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_direct_data(request),
-                tracing::debug_span!("Agent::get_direct_data")
-            );
+            let call = self.inner.get_direct_data(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -249,16 +234,30 @@ where
     type Error = super::RequestError;
 
     async fn call(self, request: S) -> Result<Self::Response, Self::Error> {
+        let options = self.options.clone();
+        self.call_with(request, options).await
+    }
+
+    async fn call_with(
+        self,
+        request: S,
+        options: CallOptions,
+    ) -> Result<Self::Response, Self::Error> {
         let span = tracing::debug_span!("Agent::create_tasks");
         let stream = tracing_futures::Instrument::instrument(
             request.map(Into::into),
             tracing::trace_span!(parent: &span, "stream"),
         );
         let call = tracing_futures::Instrument::instrument(
-            self.inner.create_task(stream),
-            tracing::trace_span!("rpc"),
+            self.inner
+                .create_task(span.in_scope(|| super::client_streaming(stream, &options))),
+            tracing::trace_span!(parent: &span, "rpc"),
         );
-        Ok(call.await.context(super::GrpcSnafu {})?.into_inner().into())
+        let call = options
+            .run(async move { Ok(call.await.context(super::GrpcSnafu {})?.into_inner().into()) });
+        #[cfg(feature = "metrics")]
+        let call = super::metrics::Call::start("Agent", "create_tasks").unary(call);
+        call.await
     }
 }
 
@@ -3,12 +3,13 @@ use snafu::ResultExt;
 use crate::api::v3;
 use crate::auth::{current_user, User};
 
-use super::GrpcCall;
+use super::{CallOptions, GrpcCall};
 
 /// Service for authentication management.
 #[derive(Clone)]
 pub struct Auth<T> {
     inner: v3::auth::authentication_client::AuthenticationClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Auth<T>
@@ -22,9 +23,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::auth::authentication_client::AuthenticationClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Get current user
     pub async fn current_user(&mut self) -> Result<User, super::RequestError> {
         Ok(self.call(current_user::Request {}).await?.user)
@@ -40,17 +48,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Auth {
         async fn call(self, request: current_user::Request) -> Result<current_user::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_current_user(request),
-                tracing::debug_span!("Auth::current_user")
-            );
+            let call = self.inner.get_current_user(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
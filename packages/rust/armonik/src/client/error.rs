@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use snafu::Snafu;
+use tonic_types::{BadRequest, ErrorDetails, RetryInfo, StatusExt};
+
+/// Error of a call made through the clients
+///
+/// Either the call failed, with the status the server or the transport ended it with, or it went
+/// through and ArmoniK answered with an error of its own, as `create_tasks` does for tasks it could not
+/// create.
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+#[non_exhaustive]
+pub enum RequestError {
+    #[snafu(display("Grpc request error [{location}]"))]
+    #[non_exhaustive]
+    Grpc {
+        #[snafu(source(from(tonic::Status, Box::new)))]
+        source: Box<tonic::Status>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Tasks could not be created: {details} [{location}]"))]
+    #[non_exhaustive]
+    TaskCreation {
+        details: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Task failed: {details} [{location}]"))]
+    #[non_exhaustive]
+    TaskFailed {
+        details: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+}
+
+impl RequestError {
+    /// Status the call failed with, `None` for errors ArmoniK answered with.
+    pub fn status(&self) -> Option<&tonic::Status> {
+        match self {
+            Self::Grpc { source, .. } => Some(source),
+            Self::TaskCreation { .. } | Self::TaskFailed { .. } => None,
+        }
+    }
+
+    /// Code of the status the call failed with, `None` for errors ArmoniK answered with.
+    pub fn code(&self) -> Option<tonic::Code> {
+        self.status().map(tonic::Status::code)
+    }
+
+    /// Whether what the call was about does not exist: a session, a task, a result...
+    pub fn is_not_found(&self) -> bool {
+        self.code() == Some(tonic::Code::NotFound)
+    }
+
+    /// Whether the caller is not allowed to make the call.
+    pub fn is_permission_denied(&self) -> bool {
+        self.code() == Some(tonic::Code::PermissionDenied)
+    }
+
+    /// Whether the request was rejected as malformed, the fields at fault being in
+    /// [`bad_request`](Self::bad_request) when the server gave them.
+    pub fn is_invalid_argument(&self) -> bool {
+        self.code() == Some(tonic::Code::InvalidArgument)
+    }
+
+    /// Whether sending the same call again may succeed.
+    ///
+    /// It may when the call failed with one of the codes [`RetryPolicy`](super::RetryPolicy) retries on
+    /// by default, `Unavailable`, `ResourceExhausted` and `DeadlineExceeded`, or when the server said so
+    /// with a [`RetryInfo`].
+    pub fn is_retryable(&self) -> bool {
+        let Some(status) = self.status() else {
+            return false;
+        };
+        matches!(
+            status.code(),
+            tonic::Code::Unavailable
+                | tonic::Code::ResourceExhausted
+                | tonic::Code::DeadlineExceeded
+        ) || status.get_details_retry_info().is_some()
+    }
+
+    /// Delay the server asked to wait before retrying, if it gave one.
+    pub fn retry_delay(&self) -> Option<Duration> {
+        self.retry_info()?.retry_delay
+    }
+
+    /// The `google.rpc.RetryInfo` the server sent along with the status, if any.
+    pub fn retry_info(&self) -> Option<RetryInfo> {
+        self.status()?.get_details_retry_info()
+    }
+
+    /// The `google.rpc.BadRequest` the server sent along with the status, if any: the fields of the
+    /// request that were rejected, and why.
+    pub fn bad_request(&self) -> Option<BadRequest> {
+        self.status()?.get_details_bad_request()
+    }
+
+    /// All the `google.rpc` details the server sent along with the status, empty if it sent none.
+    pub fn error_details(&self) -> ErrorDetails {
+        self.status()
+            .map(StatusExt::get_error_details)
+            .unwrap_or_default()
+    }
+}
@@ -5,12 +5,13 @@ use crate::api::v3;
 use crate::events::subscribe;
 use crate::utils::IntoCollection;
 
-use super::GrpcCall;
+use super::{CallOptions, GrpcCall};
 
 /// Service for authentication management.
 #[derive(Clone)]
 pub struct Events<T> {
     inner: v3::events::events_client::EventsClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Events<T>
@@ -24,9 +25,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::events::events_client::EventsClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Get current user
     pub async fn subscribe(
         &mut self,
@@ -40,31 +48,19 @@ where
         impl Stream<Item = Result<subscribe::Response, super::RequestError>> + 'static,
         super::RequestError,
     > {
-        let span = tracing::debug_span!("Events::subscribe");
-        let call = tracing_futures::Instrument::instrument(
-            self.inner.get_events(subscribe::Request {
-                session_id: session_id.into(),
-                task_filters: task_filters
-                    .into_iter()
-                    .map(IntoCollection::into_collect)
-                    .collect(),
-                result_filters: result_filters
-                    .into_iter()
-                    .map(IntoCollection::into_collect)
-                    .collect(),
-                returned_events: returned_events.into_collect(),
-            }),
-            tracing::trace_span!(parent: &span, "init"),
-        );
-        let stream = call
-            .await
-            .context(super::GrpcSnafu {})?
-            .into_inner()
-            .map(|response| response.map(Into::into).context(super::GrpcSnafu {}));
-        Ok(tracing_futures::Instrument::instrument(
-            stream,
-            tracing::trace_span!(parent: &span, "stream"),
-        ))
+        self.call(subscribe::Request {
+            session_id: session_id.into(),
+            task_filters: task_filters
+                .into_iter()
+                .map(IntoCollection::into_collect)
+                .collect(),
+            result_filters: result_filters
+                .into_iter()
+                .map(IntoCollection::into_collect)
+                .collect(),
+            returned_events: returned_events.into_collect(),
+        })
+        .await
     }
 
     /// Perform a gRPC call from a raw request.
@@ -77,6 +73,18 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 impl<T> GrpcCall<subscribe::Request> for &'_ mut Events<T>
@@ -90,18 +98,36 @@ where
         futures::stream::BoxStream<'static, Result<subscribe::Response, super::RequestError>>;
     type Error = super::RequestError;
 
-    async fn call(
+    async fn call(self, request: subscribe::Request) -> Result<Self::Response, Self::Error> {
+        let options = self.options.clone();
+        self.call_with(request, options).await
+    }
+
+    async fn call_with(
         self,
-        subscribe::Request {
-            session_id,
-            task_filters,
-            result_filters,
-            returned_events,
-        }: subscribe::Request,
+        request: subscribe::Request,
+        options: CallOptions,
     ) -> Result<Self::Response, Self::Error> {
-        self.subscribe(session_id, task_filters, result_filters, returned_events)
-            .await
-            .map(futures::stream::StreamExt::boxed)
+        let span = tracing::debug_span!("Events::subscribe");
+        let call = tracing_futures::Instrument::instrument(
+            self.inner
+                .get_events(span.in_scope(|| options.request(request))),
+            tracing::trace_span!(parent: &span, "init"),
+        );
+        let call = options.run_stream(async move {
+            let stream = call
+                .await
+                .context(super::GrpcSnafu {})?
+                .into_inner()
+                .map(|response| response.map(Into::into).context(super::GrpcSnafu {}));
+            Ok(tracing_futures::Instrument::instrument(
+                stream,
+                tracing::trace_span!(parent: &span, "stream"),
+            ))
+        });
+        #[cfg(feature = "metrics")]
+        let call = super::metrics::Call::start("Events", "subscribe").streaming(call, None);
+        call.await
     }
 }
 
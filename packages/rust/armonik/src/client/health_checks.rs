@@ -3,12 +3,13 @@ use snafu::ResultExt;
 use crate::api::v3;
 use crate::health_checks::check;
 
-use super::GrpcCall;
+use super::{CallOptions, GrpcCall};
 
 /// Service for authentication management.
 #[derive(Clone)]
 pub struct HealthChecks<T> {
     inner: v3::health_checks::health_checks_service_client::HealthChecksServiceClient<T>,
+    options: CallOptions,
 }
 
 impl<T> HealthChecks<T>
@@ -24,9 +25,16 @@ where
             inner: v3::health_checks::health_checks_service_client::HealthChecksServiceClient::new(
                 channel,
             ),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Checks the health of the cluster. This can be used to verify that the cluster is up and running.
     pub async fn check(
         &mut self,
@@ -44,17 +52,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     HealthChecks {
         async fn call(self, request: check::Request) -> Result<check::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .check_health(request),
-                tracing::debug_span!("HealthChecks::check")
-            );
+            let call = self.inner.check_health(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -64,6 +79,25 @@ super::impl_call! {
     }
 }
 
+/// `config`, with endpoints checked with [`HealthChecks::check`] unless it has a check of its own: an
+/// endpoint one of whose services is unhealthy is left out.
+pub(super) fn with_default_check(mut config: super::ClientConfig) -> super::ClientConfig {
+    if config.health_check.is_none() {
+        config.health_check = Some(super::HealthCheck::new(|channel| async move {
+            match HealthChecks::with_channel(channel).check().await {
+                Ok(services) => services
+                    .iter()
+                    .all(|service| service.health != crate::health_checks::Status::Unhealthy),
+                Err(err) => {
+                    tracing::debug!("Health check failed: {err}");
+                    false
+                }
+            }
+        }));
+    }
+    config
+}
+
 #[cfg(test)]
 #[serial_test::serial(health_checks)]
 mod tests {
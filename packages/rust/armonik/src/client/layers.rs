@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use http_body_util::BodyExt;
+use tonic::body::Body;
+use tonic::codegen::http::{self, HeaderMap};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// The `grpc-status` of a response, as a code.
+fn code(headers: &HeaderMap) -> Option<tonic::Code> {
+    headers
+        .get("grpc-status")
+        .map(|status| tonic::Code::from_bytes(status.as_bytes()))
+}
+
+/// Log every call: its method once it starts, and how it ended once it does.
+#[derive(Debug, Clone, Default)]
+pub struct LogLayer {
+    _private: (),
+}
+
+impl LogLayer {
+    /// Log every call
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S> tower_layer::Layer<S> for LogLayer {
+    type Service = Log<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Log { inner }
+    }
+}
+
+/// Logs every call, as set up by [`LogLayer`].
+///
+/// A call ends when its status comes: with the response for a failure, after the whole response stream
+/// otherwise.
+#[derive(Debug, Clone)]
+pub struct Log<S> {
+    inner: S,
+}
+
+impl<S, B> tower_service::Service<http::Request<Body>> for Log<S>
+where
+    S: tower_service::Service<http::Request<Body>, Response = http::Response<B>>,
+    S::Error: std::fmt::Display,
+    S::Future: Send + 'static,
+    B: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+    B::Error: Into<tonic::codegen::StdError>,
+{
+    type Response = http::Response<Body>;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let method = request.uri().path().to_owned();
+        let started = Instant::now();
+        tracing::debug!(method, "Call started");
+        let call = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = match call.await {
+                Ok(response) => response,
+                Err(err) => {
+                    tracing::warn!(method, elapsed = ?started.elapsed(), "Call failed: {err}");
+                    return Err(err);
+                }
+            };
+
+            // A failure comes with the response, as a trailers-only response.
+            if let Some(code) = code(response.headers()) {
+                ended(&method, started, code);
+                return Ok(response.map(Body::new));
+            }
+
+            Ok(response.map(|body| {
+                Body::new(body.map_frame(move |frame| {
+                    if let Some(code) = frame.trailers_ref().and_then(code) {
+                        ended(&method, started, code);
+                    }
+                    frame
+                }))
+            }))
+        })
+    }
+}
+
+fn ended(method: &str, started: Instant, code: tonic::Code) {
+    let elapsed = started.elapsed();
+    if code == tonic::Code::Ok {
+        tracing::info!(method, ?elapsed, "Call succeeded");
+    } else {
+        tracing::warn!(method, ?elapsed, ?code, "Call failed");
+    }
+}
+
+/// Limit how many calls of a method run at once, with a limit for each method.
+///
+/// A call waits for its turn before it is sent, and keeps it until its response is dropped, streaming
+/// responses included. Limits are shared by every client layered with the same `ConcurrencyLimitLayer`,
+/// and by their clones.
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyLimitLayer {
+    limits: Arc<Limits>,
+}
+
+#[derive(Debug, Default)]
+struct Limits {
+    /// Limit of the methods that have none of their own, if any
+    default: Option<usize>,
+    /// Limits by method path, as `/armonik.api.grpc.v1.tasks.Tasks/GetTask`
+    methods: HashMap<String, usize>,
+    /// Calls running for each method that has a limit
+    running: Mutex<HashMap<String, Arc<tokio::sync::Semaphore>>>,
+}
+
+impl Limits {
+    /// The turns to wait for before calling `method`, if it has a limit.
+    fn of(&self, method: &str) -> Option<Arc<tokio::sync::Semaphore>> {
+        let limit = self.methods.get(method).copied().or(self.default)?;
+        let mut running = self.running.lock().unwrap_or_else(|p| p.into_inner());
+        Some(
+            running
+                .entry(method.to_owned())
+                .or_insert_with(|| Arc::new(tokio::sync::Semaphore::new(limit)))
+                .clone(),
+        )
+    }
+}
+
+impl ConcurrencyLimitLayer {
+    /// Do not limit any method, until given limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let at most `limit` calls run at once for each method that has no limit of its own.
+    ///
+    /// # Panics
+    ///
+    /// When `limit` is 0, which no call would ever get past, or when a service was already layered with
+    /// these limits.
+    pub fn with_default(mut self, limit: usize) -> Self {
+        assert_ne!(limit, 0, "a concurrency limit of 0 lets no call through");
+        Arc::get_mut(&mut self.limits)
+            .expect("limits are set before layering")
+            .default = Some(limit);
+        self
+    }
+
+    /// Let at most `limit` calls of `method` run at once.
+    ///
+    /// `method` is the path of its calls, as `/armonik.api.grpc.v1.tasks.Tasks/GetTask`.
+    ///
+    /// # Panics
+    ///
+    /// When `limit` is 0, which no call would ever get past, or when a service was already layered with
+    /// these limits.
+    pub fn with_limit(mut self, method: impl Into<String>, limit: usize) -> Self {
+        assert_ne!(limit, 0, "a concurrency limit of 0 lets no call through");
+        Arc::get_mut(&mut self.limits)
+            .expect("limits are set before layering")
+            .methods
+            .insert(method.into(), limit);
+        self
+    }
+}
+
+impl<S> tower_layer::Layer<S> for ConcurrencyLimitLayer {
+    type Service = ConcurrencyLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConcurrencyLimit {
+            inner,
+            limits: self.limits.clone(),
+        }
+    }
+}
+
+/// Limits how many calls of a method run at once, as set up by [`ConcurrencyLimitLayer`].
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit<S> {
+    inner: S,
+    limits: Arc<Limits>,
+}
+
+impl<S, B> tower_service::Service<http::Request<Body>> for ConcurrencyLimit<S>
+where
+    S: tower_service::Service<http::Request<Body>, Response = http::Response<B>>,
+    S: Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+    B::Error: Into<tonic::codegen::StdError>,
+{
+    type Response = http::Response<Body>;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    /// Ready at once: a call waits for its turn, then for a clone of the inner service to be ready, so
+    /// that calls waiting for their turn hold nothing of it, such as the buffer slots of a `Channel`.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let semaphore = self.limits.of(request.uri().path());
+        let mut inner = self.inner.clone();
+        Box::pin(async move {
+            let permit = match semaphore {
+                Some(semaphore) => Some(
+                    semaphore
+                        .acquire_owned()
+                        .await
+                        .expect("the semaphore is never closed"),
+                ),
+                None => None,
+            };
+            std::future::poll_fn(|cx| inner.poll_ready(cx)).await?;
+            let response = inner.call(request).await?;
+            Ok(response.map(|body| {
+                Body::new(body.map_frame(move |frame| {
+                    let _turn = &permit;
+                    frame
+                }))
+            }))
+        })
+    }
+}
+
+/// Set some headers on every call, as metadata that is the same for all of them.
+#[derive(Debug, Clone, Default)]
+pub struct StaticHeadersLayer {
+    headers: HeaderMap,
+}
+
+impl StaticHeadersLayer {
+    /// Set `headers` on every call, replacing the values a call has for them
+    pub fn new(headers: HeaderMap) -> Self {
+        Self { headers }
+    }
+}
+
+impl<S> tower_layer::Layer<S> for StaticHeadersLayer {
+    type Service = StaticHeaders<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        StaticHeaders {
+            inner,
+            headers: self.headers.clone(),
+        }
+    }
+}
+
+/// Sets some headers on every call, as set up by [`StaticHeadersLayer`].
+#[derive(Debug, Clone)]
+pub struct StaticHeaders<S> {
+    inner: S,
+    headers: HeaderMap,
+}
+
+impl<S> tower_service::Service<http::Request<Body>> for StaticHeaders<S>
+where
+    S: tower_service::Service<http::Request<Body>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        let headers = request.headers_mut();
+        for name in self.headers.keys() {
+            headers.remove(name);
+        }
+        for (name, value) in &self.headers {
+            headers.append(name, value.clone());
+        }
+        self.inner.call(request)
+    }
+}
@@ -0,0 +1,243 @@
+//! Metrics of the calls made through the clients, recorded through the [`metrics`] facade.
+//!
+//! Every call is counted, and timed, by service, method and the status code it ended with; streaming
+//! calls carrying data, as `Results::upload` and `Results::download`, count the bytes and chunks they
+//! carry as well. Nothing is kept here: an exporter has to be installed as the global recorder, be it a
+//! Prometheus text endpoint or anything else.
+//!
+//! Services and methods are named as the clients name them: `Results` and `download`, not
+//! `armonik.api.grpc.v1.results.Results` and `DownloadResultData`.
+
+use std::future::Future;
+#[cfg(feature = "client")]
+use std::pin::Pin;
+#[cfg(feature = "client")]
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+#[cfg(feature = "client")]
+use futures::stream::BoxStream;
+#[cfg(feature = "client")]
+use futures::{Stream, StreamExt};
+
+/// Calls made, by `service`, `method` and `code`, the status they ended with.
+pub const CALLS: &str = "armonik_client_calls_total";
+/// How long calls took, by `service`, `method` and `code`, from the call to its status.
+pub const CALL_DURATION: &str = "armonik_client_call_duration_seconds";
+/// Bytes of data sent in streaming calls, as `Results::upload`, by `service` and `method`.
+pub const SENT_BYTES: &str = "armonik_client_sent_bytes_total";
+/// Chunks of data sent in streaming calls, as `Results::upload`, by `service` and `method`.
+pub const SENT_CHUNKS: &str = "armonik_client_sent_chunks_total";
+/// Bytes of data received in streaming calls, as `Results::download`, by `service` and `method`.
+pub const RECEIVED_BYTES: &str = "armonik_client_received_bytes_total";
+/// Chunks of data received in streaming calls, as `Results::download`, by `service` and `method`.
+pub const RECEIVED_CHUNKS: &str = "armonik_client_received_chunks_total";
+
+/// Describe the metrics of the clients to the recorder installed, with their units.
+///
+/// Metrics are recorded whether or not they are described: this only gives an exporter the help text
+/// it shows along with them, as the `# HELP` lines of Prometheus.
+pub fn describe_metrics() {
+    metrics::describe_counter!(
+        CALLS,
+        "Calls made to ArmoniK, by the status they ended with"
+    );
+    metrics::describe_histogram!(
+        CALL_DURATION,
+        metrics::Unit::Seconds,
+        "How long calls to ArmoniK took, until their status"
+    );
+    metrics::describe_counter!(
+        SENT_BYTES,
+        metrics::Unit::Bytes,
+        "Bytes of data sent to ArmoniK in streaming calls"
+    );
+    metrics::describe_counter!(
+        SENT_CHUNKS,
+        "Chunks of data sent to ArmoniK in streaming calls"
+    );
+    metrics::describe_counter!(
+        RECEIVED_BYTES,
+        metrics::Unit::Bytes,
+        "Bytes of data received from ArmoniK in streaming calls"
+    );
+    metrics::describe_counter!(
+        RECEIVED_CHUNKS,
+        "Chunks of data received from ArmoniK in streaming calls"
+    );
+}
+
+/// The status code a call ended with, for the errors calls fail with.
+pub(crate) trait StatusCode {
+    fn status_code(&self) -> tonic::Code;
+}
+
+impl StatusCode for super::RequestError {
+    fn status_code(&self) -> tonic::Code {
+        // Errors ArmoniK answered with come from calls that went through.
+        self.code().unwrap_or(tonic::Code::Ok)
+    }
+}
+
+/// A call being made, recorded once its status is known.
+#[derive(Debug, Clone)]
+pub(crate) struct Call {
+    service: &'static str,
+    method: &'static str,
+    started: Instant,
+}
+
+impl Call {
+    /// Start the call of `method` of `service`, named as their client is: `Results`, `download`.
+    pub(crate) fn start(service: &'static str, method: &'static str) -> Self {
+        Self {
+            service,
+            method,
+            started: Instant::now(),
+        }
+    }
+
+    /// Record the call as ended with `code`.
+    fn end(&self, code: tonic::Code) {
+        let labels = [
+            ("service", self.service),
+            ("method", self.method),
+            ("code", code_label(code)),
+        ];
+        metrics::counter!(CALLS, &labels).increment(1);
+        metrics::histogram!(CALL_DURATION, &labels).record(self.started.elapsed());
+    }
+
+    /// Run `call`, whose response comes all at once, recording it once it is over.
+    pub(crate) async fn unary<R, E: StatusCode>(
+        self,
+        call: impl Future<Output = Result<R, E>>,
+    ) -> Result<R, E> {
+        let result = call.await;
+        self.end(match &result {
+            Ok(_) => tonic::Code::Ok,
+            Err(err) => err.status_code(),
+        });
+        result
+    }
+
+    #[cfg(feature = "client")]
+    /// Run `call`, whose response is a stream, recording it once the stream is over.
+    ///
+    /// The stream is over at its end, at its first error, or when it is dropped before either, which
+    /// counts as [`tonic::Code::Cancelled`]. Items `received` measures are counted as chunks of data.
+    pub(crate) async fn streaming<T: Send + 'static>(
+        self,
+        call: impl Future<
+            Output = Result<
+                BoxStream<'static, Result<T, super::RequestError>>,
+                super::RequestError,
+            >,
+        >,
+        received: Option<fn(&T) -> usize>,
+    ) -> Result<BoxStream<'static, Result<T, super::RequestError>>, super::RequestError> {
+        match call.await {
+            Ok(stream) => Ok(Recorded {
+                stream,
+                call: Some(self),
+                received,
+            }
+            .boxed()),
+            Err(err) => {
+                self.end(err.status_code());
+                Err(err)
+            }
+        }
+    }
+
+    #[cfg(feature = "client")]
+    /// Count the chunks of data of `stream`, as sent by the call: `sent` measures them, and is `None`
+    /// for items that are no data.
+    pub(crate) fn sending<S>(
+        &self,
+        stream: S,
+        sent: impl Fn(&S::Item) -> Option<usize>,
+    ) -> impl Stream<Item = S::Item>
+    where
+        S: Stream,
+    {
+        let labels = [("service", self.service), ("method", self.method)];
+        stream.inspect(move |item| {
+            if let Some(len) = sent(item) {
+                metrics::counter!(SENT_CHUNKS, &labels).increment(1);
+                metrics::counter!(SENT_BYTES, &labels).increment(len as u64);
+            }
+        })
+    }
+}
+
+/// The label of `code`, as the gRPC specification spells it.
+fn code_label(code: tonic::Code) -> &'static str {
+    match code {
+        tonic::Code::Ok => "OK",
+        tonic::Code::Cancelled => "CANCELLED",
+        tonic::Code::Unknown => "UNKNOWN",
+        tonic::Code::InvalidArgument => "INVALID_ARGUMENT",
+        tonic::Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+        tonic::Code::NotFound => "NOT_FOUND",
+        tonic::Code::AlreadyExists => "ALREADY_EXISTS",
+        tonic::Code::PermissionDenied => "PERMISSION_DENIED",
+        tonic::Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+        tonic::Code::FailedPrecondition => "FAILED_PRECONDITION",
+        tonic::Code::Aborted => "ABORTED",
+        tonic::Code::OutOfRange => "OUT_OF_RANGE",
+        tonic::Code::Unimplemented => "UNIMPLEMENTED",
+        tonic::Code::Internal => "INTERNAL",
+        tonic::Code::Unavailable => "UNAVAILABLE",
+        tonic::Code::DataLoss => "DATA_LOSS",
+        tonic::Code::Unauthenticated => "UNAUTHENTICATED",
+    }
+}
+
+#[cfg(feature = "client")]
+/// A streaming response, recording its call once it is over.
+struct Recorded<T> {
+    stream: BoxStream<'static, Result<T, super::RequestError>>,
+    /// `None` once recorded
+    call: Option<Call>,
+    received: Option<fn(&T) -> usize>,
+}
+
+#[cfg(feature = "client")]
+impl<T> Stream for Recorded<T> {
+    type Item = Result<T, super::RequestError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let next = self.stream.poll_next_unpin(cx);
+        let Poll::Ready(item) = &next else {
+            return next;
+        };
+        let this = &mut *self;
+        if let Some(call) = &this.call {
+            match item {
+                Some(Ok(item)) => {
+                    if let Some(received) = this.received {
+                        let labels = [("service", call.service), ("method", call.method)];
+                        metrics::counter!(RECEIVED_CHUNKS, &labels).increment(1);
+                        metrics::counter!(RECEIVED_BYTES, &labels).increment(received(item) as u64);
+                    }
+                }
+                Some(Err(err)) => call.end(err.status_code()),
+                None => call.end(tonic::Code::Ok),
+            }
+            if !matches!(item, Some(Ok(_))) {
+                this.call = None;
+            }
+        }
+        next
+    }
+}
+
+#[cfg(feature = "client")]
+impl<T> Drop for Recorded<T> {
+    fn drop(&mut self) {
+        if let Some(call) = self.call.take() {
+            call.end(tonic::Code::Cancelled);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 //! ArmoniK clients for all the services
 
-use snafu::{ResultExt, Snafu};
+use snafu::ResultExt;
 
 // Re-exported here, so a caller reaches them through the client rather than through the transport
 // crate.
@@ -8,8 +8,13 @@ use snafu::{ResultExt, Snafu};
 use armonik_transport::ConfigSnafu;
 #[cfg(feature = "_gen-client")]
 pub use armonik_transport::{
-    ClientConfig, ClientConfigArgs, ConfigError, ConnectionError, ReadEnvError,
+    CertificateFiles, Channel, ClientConfig, ClientConfigArgs, ConfigError, ConfigLoader,
+    ConnectionError, Credentials, HealthCheck, LoadedArgs, Origin, Proxy, ReadEnvError,
+    RetryPolicy, TokenSource,
 };
+// Connection diagnostics, for when `Client::new` fails: which step of connecting went wrong.
+#[cfg(feature = "_gen-client")]
+pub use armonik_transport::{diagnose, Report};
 
 #[cfg(feature = "worker")]
 mod agent;
@@ -17,10 +22,17 @@ mod agent;
 mod applications;
 #[cfg(feature = "client")]
 mod auth;
+mod error;
 #[cfg(feature = "client")]
 mod events;
 #[cfg(feature = "client")]
 mod health_checks;
+mod layers;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+mod options;
+#[cfg(feature = "client")]
+mod pagination;
 #[cfg(feature = "client")]
 mod partitions;
 #[cfg(feature = "client")]
@@ -30,9 +42,15 @@ mod sessions;
 #[cfg(feature = "client")]
 mod submitter;
 #[cfg(feature = "client")]
+mod subscription;
+#[cfg(feature = "client")]
+mod task_graph;
+#[cfg(feature = "client")]
 mod tasks;
 #[cfg(feature = "client")]
 mod versions;
+#[cfg(feature = "client")]
+mod wait;
 #[cfg(feature = "agent")]
 mod worker;
 
@@ -42,10 +60,22 @@ pub use agent::Agent;
 pub use applications::Applications;
 #[cfg(feature = "client")]
 pub use auth::Auth;
+use error::GrpcSnafu;
+pub use error::RequestError;
+#[cfg(any(feature = "client", feature = "worker"))]
+use error::TaskCreationSnafu;
+#[cfg(feature = "client")]
+use error::TaskFailedSnafu;
 #[cfg(feature = "client")]
 pub use events::Events;
 #[cfg(feature = "client")]
 pub use health_checks::HealthChecks;
+pub use layers::{
+    ConcurrencyLimit, ConcurrencyLimitLayer, Log, LogLayer, StaticHeaders, StaticHeadersLayer,
+};
+pub use options::CallOptions;
+#[cfg(feature = "client")]
+pub use pagination::ListStream;
 #[cfg(feature = "client")]
 pub use partitions::Partitions;
 #[cfg(feature = "client")]
@@ -56,6 +86,10 @@ pub use sessions::Sessions;
 #[allow(deprecated)]
 pub use submitter::Submitter;
 #[cfg(feature = "client")]
+pub use subscription::ResilientSubscription;
+#[cfg(feature = "client")]
+pub use task_graph::{SubmittedGraph, TaskGraph, TaskGraphError, TaskNode};
+#[cfg(feature = "client")]
 pub use tasks::Tasks;
 #[cfg(feature = "client")]
 pub use versions::Versions;
@@ -64,18 +98,27 @@ pub use worker::Worker;
 
 /// ArmoniK Client
 #[derive(Clone)]
-pub struct Client<T = tonic::transport::Channel> {
+pub struct Client<T = Channel> {
     channel: T,
 }
 
-impl Client<tonic::transport::Channel> {
+impl Client<Channel> {
     /// Create a new client using the configuration from the environment variables
+    ///
+    /// When this fails, [`diagnose`] goes through the steps of connecting one by one, and tells which one
+    /// went wrong.
     pub async fn new() -> Result<Self, ConnectionError> {
         Self::with_config(ClientConfig::from_env().context(ConfigSnafu {})?).await
     }
 
     /// Create a new client with the specified client configuration
+    ///
+    /// When calls are spread across several endpoints, those are health checked with
+    /// [`HealthChecks::check`] unless the configuration has a check of its own: an endpoint one of whose
+    /// services is unhealthy is left out.
     pub async fn with_config(config: ClientConfig) -> Result<Self, ConnectionError> {
+        #[cfg(feature = "client")]
+        let config = health_checks::with_default_check(config);
         let endpoint = config.endpoint.to_string();
         tracing_futures::Instrument::instrument(
             async move {
@@ -142,6 +185,21 @@ where
         Self { channel }
     }
 
+    /// Put `layer` between the services and the channel of this client: every call of every service goes
+    /// through it.
+    ///
+    /// Any [`tower_layer::Layer`] will do, as long as what it builds still is a gRPC service; this module
+    /// has a few, as [`LogLayer`], [`ConcurrencyLimitLayer`] and [`StaticHeadersLayer`]. Layers put on
+    /// top of each other are gone through from the last one put to the first, then the channel.
+    pub fn with_layer<L>(self, layer: L) -> Client<L::Service>
+    where
+        L: tower_layer::Layer<T>,
+    {
+        Client {
+            channel: layer.layer(self.channel),
+        }
+    }
+
     #[cfg(feature = "worker")]
     /// Create a borrowed [`Agent`]
     pub fn agent(&mut self) -> Agent<&mut Self> {
@@ -333,6 +391,22 @@ pub trait GrpcCall<Request> {
 
     /// Perform a gRPC call from a raw request.
     async fn call(self, request: Request) -> Result<Self::Response, Self::Error>;
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the service.
+    ///
+    /// The clients of this crate honour `options`; by default, they are ignored and the call is made
+    /// as [`call`](Self::call) makes it.
+    async fn call_with(
+        self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<Self::Response, Self::Error>
+    where
+        Self: Sized,
+    {
+        let _ = options;
+        self.call(request).await
+    }
 }
 
 /// Perform a gRPC call from a raw request.
@@ -346,6 +420,22 @@ where
 
     /// Perform a gRPC call from a raw request.
     async fn call(self, request: Stream) -> Result<Self::Response, Self::Error>;
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the service.
+    ///
+    /// The clients of this crate honour `options`; by default, they are ignored and the call is made
+    /// as [`call`](Self::call) makes it.
+    async fn call_with(
+        self,
+        request: Stream,
+        options: CallOptions,
+    ) -> Result<Self::Response, Self::Error>
+    where
+        Self: Sized,
+    {
+        let _ = options;
+        self.call(request).await
+    }
 }
 
 impl<Stream, Request, T> GrpcCall<Stream> for T
@@ -360,29 +450,36 @@ where
     async fn call(self, request: Stream) -> Result<Self::Response, Self::Error> {
         <T as GrpcCallStream<Request, Stream>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the service.
+    async fn call_with(
+        self,
+        request: Stream,
+        options: CallOptions,
+    ) -> Result<Self::Response, Self::Error> {
+        <T as GrpcCallStream<Request, Stream>>::call_with(self, request, options).await
+    }
 }
 
-#[derive(Debug, Snafu)]
-#[non_exhaustive]
-pub enum RequestError {
-    #[snafu(display("Grpc request error [{location}]"))]
-    #[non_exhaustive]
-    Grpc {
-        #[snafu(source(from(tonic::Status, Box::new)))]
-        source: Box<tonic::Status>,
-        #[snafu(implicit)]
-        location: snafu::Location,
-    },
+/// Wrap the stream of a client-streaming call into a request marked as such, so that the call is not
+/// retried once any of the stream was sent.
+#[cfg(any(feature = "client", feature = "worker"))]
+fn client_streaming<S>(stream: S, options: &CallOptions) -> tonic::Request<S> {
+    let mut request = options.request(stream);
+    request
+        .extensions_mut()
+        .insert(armonik_transport::ClientStreaming);
+    request
 }
 
 macro_rules! impl_call {
-    (@one $Client:ident($self:ident, $request:ident: $Request:ty) -> Result<$Response:ty> $block:block) => {
+    (@one $Client:ident($self:ident, $request:ident: $method:ident::$Request:ident) -> Result<$Response:ty> $block:block) => {
         crate::client::impl_call! {
-            @one $Client($self, $request: $Request) -> Result<$Response, crate::client::RequestError> $block
+            @one $Client($self, $request: $method::$Request) -> Result<$Response, crate::client::RequestError> $block
         }
     };
-    (@one $Client:ident($self:ident, $request:ident: $Request:ty) -> Result<$Response:ty, $Error:ty> $block:block) => {
-        impl<T> $crate::client::GrpcCall<$Request> for &'_ mut $Client<T>
+    (@one $Client:ident($self:ident, $request:ident: $method:ident::$Request:ident) -> Result<$Response:ty, $Error:ty> $block:block) => {
+        impl<T> $crate::client::GrpcCall<$method::$Request> for &'_ mut $Client<T>
         where
             T: tonic::client::GrpcService<tonic::body::Body>,
             T::Error: Into<tonic::codegen::StdError>,
@@ -392,13 +489,32 @@ macro_rules! impl_call {
             type Response = $Response;
             type Error = $Error;
 
-            async fn call($self, $request: $Request) -> Result<Self::Response, Self::Error> $block
+            async fn call($self, $request: $method::$Request) -> Result<Self::Response, Self::Error> {
+                let options = $self.options.clone();
+                $crate::client::GrpcCall::call_with($self, $request, options).await
+            }
+
+            async fn call_with(
+                $self,
+                $request: $method::$Request,
+                options: $crate::client::CallOptions,
+            ) -> Result<Self::Response, Self::Error> {
+                // The request is built within the span of the call, so that the server traces it under
+                // that span rather than under the span of the caller.
+                let span = tracing::debug_span!(concat!(stringify!($Client), "::", stringify!($method)));
+                let $request = span.in_scope(|| options.request($request));
+                let call = options.run(tracing_futures::Instrument::instrument(async move $block, span));
+                #[cfg(feature = "metrics")]
+                let call = $crate::client::metrics::Call::start(stringify!($Client), stringify!($method))
+                    .unary(call);
+                call.await
+            }
         }
     };
-    ($Client:ident {$(async fn call($self:ident, $request:ident: $Request:ty) -> Result<$($Result:ty),*> $block:block)*}) => {
+    ($Client:ident {$(async fn call($self:ident, $request:ident: $method:ident::$Request:ident) -> Result<$($Result:ty),*> $block:block)*}) => {
         $(
             crate::client::impl_call! {
-                @one $Client($self, $request: $Request) -> Result<$($Result),*> $block
+                @one $Client($self, $request: $method::$Request) -> Result<$($Result),*> $block
             }
         )*
     };
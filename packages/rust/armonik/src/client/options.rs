@@ -0,0 +1,214 @@
+use std::future::Future;
+#[cfg(feature = "client")]
+use std::pin::Pin;
+#[cfg(feature = "client")]
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::Either;
+#[cfg(feature = "client")]
+use futures::stream::BoxStream;
+#[cfg(feature = "client")]
+use futures::{Stream, StreamExt};
+use snafu::IntoError;
+use tokio_util::sync::CancellationToken;
+use tonic::metadata::{KeyAndValueRef, MetadataMap};
+
+/// Options of a single call: a deadline, metadata sent along with it, and a way to cancel it.
+///
+/// Given to a service with `with_options`, they apply to every call made through it. This is how the
+/// typed methods, such as `Tasks::get` or `Results::download`, take options: none of them has a
+/// parameter for them, and services are cheap to get from the client, so a call with options of its
+/// own is written `client.tasks().with_options(options).get(task_id)`.
+///
+/// Given to [`GrpcCall::call_with`](super::GrpcCall::call_with), they apply to that call only.
+///
+/// A deadline shorter than [`ClientConfig::timeout`](super::ClientConfig::timeout) is what bounds the
+/// call; a longer one, or none, leaves the channel-wide timeout in place.
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    deadline: Option<Duration>,
+    metadata: MetadataMap,
+    cancellation: Option<CancellationToken>,
+}
+
+impl CallOptions {
+    /// Options that change nothing: no deadline, no metadata, no cancellation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bound the call to `deadline`, sent to the server as `grpc-timeout`.
+    ///
+    /// For a streaming response, the deadline bounds the whole stream, not only its first message.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Send `metadata` along with the call, on top of what the call sends by itself.
+    pub fn with_metadata(mut self, metadata: MetadataMap) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Cancel the call once `token` is: it then fails with [`tonic::Code::Cancelled`], and a streaming
+    /// response ends with that error.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Get the deadline of the call, if any
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Get the metadata sent along with the call
+    pub fn metadata(&self) -> &MetadataMap {
+        &self.metadata
+    }
+
+    /// Get mutable metadata sent along with the call
+    pub fn metadata_mut(&mut self) -> &mut MetadataMap {
+        &mut self.metadata
+    }
+
+    /// Get the token cancelling the call, if any
+    pub fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    /// `request` as a `tonic` request, with the deadline and metadata of these options.
+    ///
+    /// With the `opentelemetry` feature, the trace context of the current span, the span of the call,
+    /// is sent along too, in place of any the metadata of these options has.
+    pub(crate) fn request<B>(&self, request: impl tonic::IntoRequest<B>) -> tonic::Request<B> {
+        let mut request = request.into_request();
+        if let Some(deadline) = self.deadline {
+            request.set_timeout(deadline);
+        }
+        let metadata = request.metadata_mut();
+        for entry in self.metadata.iter() {
+            match entry {
+                KeyAndValueRef::Ascii(key, value) => {
+                    metadata.append(key.clone(), value.clone());
+                }
+                KeyAndValueRef::Binary(key, value) => {
+                    metadata.append_bin(key.clone(), value.clone());
+                }
+            }
+        }
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::inject(metadata);
+        request
+    }
+
+    /// Run `call`, failing it once the deadline passed or the call was cancelled.
+    ///
+    /// The deadline is enforced here as well as by the server: a channel that is not a
+    /// [`Channel`](super::Channel), like a service called in-process, does not enforce `grpc-timeout`.
+    pub(crate) async fn run<R, E>(&self, call: impl Future<Output = Result<R, E>>) -> Result<R, E>
+    where
+        E: From<super::RequestError>,
+    {
+        let call = std::pin::pin!(call);
+        let ended = std::pin::pin!(self.ended());
+        match futures::future::select(call, ended).await {
+            Either::Left((result, _)) => result,
+            Either::Right((status, _)) => Err(failed(status).into()),
+        }
+    }
+
+    /// Run `call`, whose response is a stream, failing it once the deadline passed or the call was
+    /// cancelled: the deadline bounds the whole stream, which then ends with the error.
+    #[cfg(feature = "client")]
+    pub(crate) async fn run_stream<S, T>(
+        &self,
+        call: impl Future<Output = Result<S, super::RequestError>>,
+    ) -> Result<BoxStream<'static, Result<T, super::RequestError>>, super::RequestError>
+    where
+        S: Stream<Item = Result<T, super::RequestError>> + Send + 'static,
+        T: 'static,
+    {
+        let call = std::pin::pin!(call);
+        let mut ended = Box::pin(self.ended());
+        match futures::future::select(call, ended.as_mut()).await {
+            Either::Left((result, _)) => Ok(Bounded {
+                stream: Box::pin(result?),
+                ended: Some(ended),
+            }
+            .boxed()),
+            Either::Right((status, _)) => Err(failed(status)),
+        }
+    }
+
+    /// Resolves to the status the call fails with once it is over, never if it has no deadline and
+    /// cannot be cancelled.
+    fn ended(&self) -> impl Future<Output = tonic::Status> + Send + 'static {
+        let deadline = self.deadline;
+        let cancellation = self.cancellation.clone();
+        async move {
+            let expired = async {
+                match deadline {
+                    Some(deadline) => {
+                        tokio::time::sleep(deadline).await;
+                        tonic::Status::deadline_exceeded(format!(
+                            "the call did not complete within its deadline of {deadline:?}"
+                        ))
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            let cancelled = async {
+                match cancellation {
+                    Some(token) => {
+                        token.cancelled_owned().await;
+                        tonic::Status::cancelled("the call was cancelled")
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            let (expired, cancelled) = (std::pin::pin!(expired), std::pin::pin!(cancelled));
+            futures::future::select(expired, cancelled)
+                .await
+                .factor_first()
+                .0
+        }
+    }
+}
+
+fn failed(status: tonic::Status) -> super::RequestError {
+    super::GrpcSnafu {}.into_error(status)
+}
+
+/// A streaming response that ends with an error once its call is over.
+#[cfg(feature = "client")]
+struct Bounded<S> {
+    stream: Pin<Box<S>>,
+    /// `None` once the stream has ended this way
+    ended: Option<Pin<Box<dyn Future<Output = tonic::Status> + Send>>>,
+}
+
+#[cfg(feature = "client")]
+impl<S, T> Stream for Bounded<S>
+where
+    S: Stream<Item = Result<T, super::RequestError>>,
+{
+    type Item = Result<T, super::RequestError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Some(ended) = self.ended.as_mut() else {
+            return Poll::Ready(None);
+        };
+        if let Poll::Ready(status) = ended.as_mut().poll(cx) {
+            self.ended = None;
+            return Poll::Ready(Some(Err(failed(status))));
+        }
+        let next = self.stream.as_mut().poll_next(cx);
+        if let Poll::Ready(None) = next {
+            self.ended = None;
+        }
+        next
+    }
+}
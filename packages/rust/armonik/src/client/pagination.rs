@@ -0,0 +1,123 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use futures::{FutureExt, Stream};
+
+use crate::{Page, PagedRequest};
+
+use super::RequestError;
+
+type Item<R> = <<R as PagedRequest>::Response as Page>::Item;
+type Fetch<R> = BoxFuture<'static, Result<<R as PagedRequest>::Response, RequestError>>;
+
+/// Every item of a listing, fetched page after page.
+///
+/// The next page is asked for as soon as the current one is handed out, so that it is usually there by
+/// the time the current one is consumed. The listing stops once `total` items were listed, according to
+/// the latest page, or at the first empty page.
+///
+/// Pages are read at different times: an item inserted or removed concurrently can shift the others from
+/// one page to the next, so that one is listed twice or not at all. Sorting on a field that only grows,
+/// like the creation date, keeps the items already listed where they are.
+#[must_use = "streams do nothing unless polled"]
+pub struct ListStream<R: PagedRequest> {
+    /// Sends the request for one page
+    fetch: Box<dyn FnMut(R) -> Fetch<R> + Send>,
+    /// The request for the next page to fetch
+    request: R,
+    /// The page being fetched
+    pending: Option<Fetch<R>>,
+    /// The page fetched ahead, waiting for the current one to be consumed
+    ready: Option<R::Response>,
+    /// Why the page fetched ahead could not be
+    error: Option<RequestError>,
+    /// The rest of the current page
+    items: std::vec::IntoIter<Item<R>>,
+    /// The number of items handed out or about to be, up to the current page
+    listed: i64,
+}
+
+impl<R> ListStream<R>
+where
+    R: PagedRequest + Send + 'static,
+    R::Response: Send + 'static,
+{
+    /// List every item answered to `request`, starting from its page, with `fetch` sending the request
+    /// for one page.
+    pub(crate) fn new<F, Fut>(mut fetch: F, request: R) -> Self
+    where
+        F: FnMut(R) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<R::Response, RequestError>> + Send + 'static,
+    {
+        let pending = Some(fetch(request.clone()).boxed());
+        let listed = i64::from(request.page()) * i64::from(request.page_size());
+
+        Self {
+            fetch: Box::new(move |request| fetch(request).boxed()),
+            request,
+            pending,
+            ready: None,
+            error: None,
+            items: Vec::new().into_iter(),
+            listed,
+        }
+    }
+}
+
+// Nothing is pinned in place: the page being fetched is boxed.
+impl<R: PagedRequest> Unpin for ListStream<R> {}
+
+impl<R: PagedRequest> Stream for ListStream<R> {
+    type Item = Result<Item<R>, RequestError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        // Driven on every poll, items in hand or not, so that the next page keeps coming while the
+        // current one is consumed.
+        if let Some(pending) = &mut this.pending {
+            if let Poll::Ready(outcome) = pending.poll_unpin(cx) {
+                this.pending = None;
+                match outcome {
+                    Ok(page) => this.ready = Some(page),
+                    Err(error) => this.error = Some(error),
+                }
+            }
+        }
+
+        loop {
+            if let Some(item) = this.items.next() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+
+            let Some(page) = this.ready.take() else {
+                // The items fetched before a failure are handed out first, and the listing ends with it.
+                if let Some(error) = this.error.take() {
+                    return Poll::Ready(Some(Err(error)));
+                }
+                return match this.pending {
+                    Some(_) => Poll::Pending,
+                    None => Poll::Ready(None),
+                };
+            };
+
+            let total = page.total();
+            let items = page.into_items();
+            this.listed += i64::try_from(items.len()).unwrap_or(i64::MAX);
+            if !items.is_empty() && this.listed < i64::from(total) {
+                let page = this.request.page() + 1;
+                this.request.set_page(page);
+                let mut pending = (this.fetch)(this.request.clone());
+                // Polled once straight away, so that the request is on its way before the items of the
+                // current page are handed out.
+                match pending.poll_unpin(cx) {
+                    Poll::Ready(Ok(page)) => this.ready = Some(page),
+                    Poll::Ready(Err(error)) => this.error = Some(error),
+                    Poll::Pending => this.pending = Some(pending),
+                }
+            }
+            this.items = items.into_iter();
+        }
+    }
+}
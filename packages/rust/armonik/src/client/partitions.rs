@@ -4,11 +4,12 @@ use crate::api::v3;
 use crate::partitions::{get, list, Raw};
 use crate::utils::IntoCollection;
 
-use super::GrpcCall;
+use super::{CallOptions, GrpcCall};
 
 #[derive(Clone)]
 pub struct Partitions<T> {
     inner: v3::partitions::partitions_client::PartitionsClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Partitions<T>
@@ -22,9 +23,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::partitions::partitions_client::PartitionsClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     pub async fn list(
         &mut self,
         filters: impl IntoIterator<Item = impl IntoIterator<Item = crate::partitions::filter::Field>>,
@@ -44,6 +52,38 @@ where
         .await
     }
 
+    /// Get every partition matching the filters, page after page.
+    ///
+    /// Pages of `page_size` are fetched one after the other, the next while the current one is consumed:
+    /// see [`ListStream`](super::ListStream).
+    pub fn list_stream(
+        &self,
+        filters: impl IntoIterator<Item = impl IntoIterator<Item = crate::partitions::filter::Field>>,
+        sort: crate::partitions::Sort,
+        page_size: i32,
+    ) -> super::ListStream<list::Request>
+    where
+        T: Clone + Send + Sync + 'static,
+        T::Future: Send,
+    {
+        let client = self.clone();
+        super::ListStream::new(
+            move |request| {
+                let mut client = client.clone();
+                async move { client.call(request).await }
+            },
+            list::Request {
+                filters: filters
+                    .into_iter()
+                    .map(crate::utils::IntoCollection::into_collect)
+                    .collect(),
+                sort,
+                page: 0,
+                page_size,
+            },
+        )
+    }
+
     pub async fn get(
         &mut self,
         partition_id: impl Into<String>,
@@ -66,17 +106,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Partitions {
         async fn call(self, request: list::Request) -> Result<list::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .list_partitions(request),
-                tracing::debug_span!("Partitions::list")
-            );
+            let call = self.inner.list_partitions(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -85,12 +132,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get::Request) -> Result<get::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_partition(request),
-                tracing::debug_span!("Partitions::get")
-            );
+            let call = self.inner.get_partition(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -10,12 +10,13 @@ use crate::results::{
 };
 use crate::utils::IntoCollection;
 
-use super::{GrpcCall, GrpcCallStream};
+use super::{CallOptions, GrpcCall, GrpcCallStream};
 
 /// The ResultsService provides methods for interacting with results.
 #[derive(Clone)]
 pub struct Results<T> {
     inner: v3::results::results_client::ResultsClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Results<T>
@@ -29,9 +30,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::results::results_client::ResultsClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Get a results list using pagination, filters and sorting.
     pub async fn list(
         &mut self,
@@ -52,6 +60,38 @@ where
         .await
     }
 
+    /// Get every result matching the filters, page after page.
+    ///
+    /// Pages of `page_size` are fetched one after the other, the next while the current one is consumed:
+    /// see [`ListStream`](super::ListStream).
+    pub fn list_stream(
+        &self,
+        filters: impl IntoIterator<Item = impl IntoIterator<Item = filter::Field>>,
+        sort: Sort,
+        page_size: i32,
+    ) -> super::ListStream<list::Request>
+    where
+        T: Clone + Send + Sync + 'static,
+        T::Future: Send,
+    {
+        let client = self.clone();
+        super::ListStream::new(
+            move |request| {
+                let mut client = client.clone();
+                async move { client.call(request).await }
+            },
+            list::Request {
+                filters: filters
+                    .into_iter()
+                    .map(crate::utils::IntoCollection::into_collect)
+                    .collect(),
+                sort,
+                page: 0,
+                page_size,
+            },
+        )
+    }
+
     /// Get the id of the task that should produce the result.
     pub async fn get(&mut self, result_id: impl Into<String>) -> Result<Raw, super::RequestError> {
         Ok(self
@@ -138,6 +178,7 @@ where
         <S as futures::Stream>::Item: Into<Vec<u8>>,
     {
         let span = tracing::debug_span!("Results::upload");
+        let options = self.options.clone();
         let session_id: String = session_id.into();
         let result_id: String = result_id.into();
 
@@ -147,8 +188,13 @@ where
                 result_id,
             },
         )]);
+        let data = data.map(Into::<Vec<u8>>::into);
+        #[cfg(feature = "metrics")]
+        let metrics = super::metrics::Call::start("Results", "upload");
+        #[cfg(feature = "metrics")]
+        let data = metrics.sending(data, |chunk| Some(chunk.len()));
         let request = request.chain(data.map(|chunk| {
-            v3::results::UploadResultDataRequest::from(upload::Request::DataChunk(chunk.into()))
+            v3::results::UploadResultDataRequest::from(upload::Request::DataChunk(chunk))
         }));
         let stream = tracing_futures::Instrument::instrument(
             request,
@@ -156,16 +202,22 @@ where
         );
 
         let call = tracing_futures::Instrument::instrument(
-            self.inner.upload_result_data(stream),
+            self.inner
+                .upload_result_data(span.in_scope(|| super::client_streaming(stream, &options))),
             tracing::trace_span!(parent: &span, "rpc"),
         );
 
-        Ok(call
-            .await
-            .context(super::GrpcSnafu {})?
-            .into_inner()
-            .result
-            .map_or_else(Default::default, Into::into))
+        let call = options.run(async move {
+            Ok(call
+                .await
+                .context(super::GrpcSnafu {})?
+                .into_inner()
+                .result
+                .map_or_else(Default::default, Into::into))
+        });
+        #[cfg(feature = "metrics")]
+        let call = metrics.unary(call);
+        call.await
     }
 
     /// Retrieve data.
@@ -177,27 +229,13 @@ where
         impl futures::Stream<Item = Result<Vec<u8>, super::RequestError>> + 'static,
         super::RequestError,
     > {
-        let span = tracing::debug_span!("Results::download");
-        let call = tracing_futures::Instrument::instrument(
-            self.inner.download_result_data(download::Request {
+        let stream = self
+            .call(download::Request {
                 session_id: session_id.into(),
                 result_id: result_id.into(),
-            }),
-            tracing::trace_span!(parent: &span, "rpc"),
-        );
-        let stream = call
-            .await
-            .context(super::GrpcSnafu {})?
-            .into_inner()
-            .map(|response| {
-                response
-                    .map(|response| response.data_chunk)
-                    .context(super::GrpcSnafu {})
-            });
-        Ok(tracing_futures::Instrument::instrument(
-            stream,
-            tracing::trace_span!(parent: &span, "stream"),
-        ))
+            })
+            .await?;
+        Ok(stream.map(|response| response.map(|response| response.data_chunk)))
     }
 
     /// Delete data from multiple results.
@@ -232,17 +270,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Results {
         async fn call(self, request: list::Request) -> Result<list::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .list_results(request),
-                tracing::debug_span!("Results::list")
-            );
+            let call = self.inner.list_results(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -251,12 +296,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get::Request) -> Result<get::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_result(request),
-                tracing::debug_span!("Results::get")
-            );
+            let call = self.inner.get_result(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -265,12 +305,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get_owner_task_id::Request) -> Result<get_owner_task_id::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_owner_task_id(request),
-                tracing::debug_span!("Results::get_owner_task_id")
-            );
+            let call = self.inner.get_owner_task_id(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -279,12 +314,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: create_metadata::Request) -> Result<create_metadata::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .create_results_meta_data(request),
-                tracing::debug_span!("Results::create_metadata")
-            );
+            let call = self.inner.create_results_meta_data(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -293,12 +323,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: create::Request) -> Result<create::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .create_results(request),
-                tracing::debug_span!("Results::create")
-            );
+            let call = self.inner.create_results(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -307,12 +332,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: delete_data::Request) -> Result<delete_data::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .delete_results_data(request),
-                tracing::debug_span!("Results::delete_data")
-            );
+            let call = self.inner.delete_results_data(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -321,12 +341,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get_service_configuration::Request) -> Result<get_service_configuration::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_service_configuration(request),
-                tracing::debug_span!("Results::get_service_configuration")
-            );
+            let call = self.inner.get_service_configuration(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -358,22 +373,38 @@ where
     type Error = super::RequestError;
 
     async fn call(self, request: download::Request) -> Result<Self::Response, Self::Error> {
+        let options = self.options.clone();
+        self.call_with(request, options).await
+    }
+
+    async fn call_with(
+        self,
+        request: download::Request,
+        options: CallOptions,
+    ) -> Result<Self::Response, Self::Error> {
         let span = tracing::debug_span!("Results::download");
         let call = tracing_futures::Instrument::instrument(
-            self.inner.download_result_data(request),
+            self.inner
+                .download_result_data(span.in_scope(|| options.request(request))),
             tracing::trace_span!(parent: &span, "rpc"),
         );
-        let stream = call
-            .await
-            .context(super::GrpcSnafu {})?
-            .into_inner()
-            .map(|response| response.map(Into::into).context(super::GrpcSnafu {}));
-        Ok(futures::stream::StreamExt::boxed(
-            tracing_futures::Instrument::instrument(
+        let call = options.run_stream(async move {
+            let stream = call
+                .await
+                .context(super::GrpcSnafu {})?
+                .into_inner()
+                .map(|response| response.map(Into::into).context(super::GrpcSnafu {}));
+            Ok(tracing_futures::Instrument::instrument(
                 stream,
                 tracing::trace_span!(parent: &span, "stream"),
-            ),
-        ))
+            ))
+        });
+        #[cfg(feature = "metrics")]
+        let call = super::metrics::Call::start("Results", "download").streaming(
+            call,
+            Some(|response: &download::Response| response.data_chunk.len()),
+        );
+        call.await
     }
 }
 
@@ -389,16 +420,37 @@ where
     type Error = super::RequestError;
 
     async fn call(self, request: S) -> Result<Self::Response, Self::Error> {
+        let options = self.options.clone();
+        self.call_with(request, options).await
+    }
+
+    async fn call_with(
+        self,
+        request: S,
+        options: CallOptions,
+    ) -> Result<Self::Response, Self::Error> {
         let span = tracing::debug_span!("Results::upload");
+        #[cfg(feature = "metrics")]
+        let metrics = super::metrics::Call::start("Results", "upload");
+        #[cfg(feature = "metrics")]
+        let request = metrics.sending(request, |request| match request {
+            upload::Request::DataChunk(chunk) => Some(chunk.len()),
+            upload::Request::Identifier { .. } => None,
+        });
         let stream = tracing_futures::Instrument::instrument(
             request.map(Into::into),
             tracing::trace_span!(parent: &span, "stream"),
         );
         let call = tracing_futures::Instrument::instrument(
-            self.inner.upload_result_data(stream),
+            self.inner
+                .upload_result_data(span.in_scope(|| super::client_streaming(stream, &options))),
             tracing::trace_span!(parent: &span, "rpc"),
         );
-        Ok(call.await.context(super::GrpcSnafu {})?.into_inner().into())
+        let call = options
+            .run(async move { Ok(call.await.context(super::GrpcSnafu {})?.into_inner().into()) });
+        #[cfg(feature = "metrics")]
+        let call = metrics.unary(call);
+        call.await
     }
 }
 
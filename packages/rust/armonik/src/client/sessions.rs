@@ -8,12 +8,13 @@ use crate::sessions::{
 use crate::utils::IntoCollection;
 use crate::TaskOptions;
 
-use super::GrpcCall;
+use super::{CallOptions, GrpcCall};
 
 /// Service for handling sessions
 #[derive(Clone)]
 pub struct Sessions<T> {
     inner: v3::sessions::sessions_client::SessionsClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Sessions<T>
@@ -27,9 +28,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::sessions::sessions_client::SessionsClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Get a sessions list using pagination, filters and sorting.
     pub async fn list(
         &mut self,
@@ -52,6 +60,40 @@ where
         .await
     }
 
+    /// Get every session matching the filters, page after page.
+    ///
+    /// Pages of `page_size` are fetched one after the other, the next while the current one is consumed:
+    /// see [`ListStream`](super::ListStream).
+    pub fn list_stream(
+        &self,
+        filters: impl IntoIterator<Item = impl IntoIterator<Item = filter::Field>>,
+        sort: Sort,
+        with_task_options: bool,
+        page_size: i32,
+    ) -> super::ListStream<list::Request>
+    where
+        T: Clone + Send + Sync + 'static,
+        T::Future: Send,
+    {
+        let client = self.clone();
+        super::ListStream::new(
+            move |request| {
+                let mut client = client.clone();
+                async move { client.call(request).await }
+            },
+            list::Request {
+                filters: filters
+                    .into_iter()
+                    .map(crate::utils::IntoCollection::into_collect)
+                    .collect(),
+                sort,
+                with_task_options,
+                page: 0,
+                page_size,
+            },
+        )
+    }
+
     /// Get a session by its id.
     pub async fn get(&mut self, session_id: impl Into<String>) -> Result<Raw, super::RequestError> {
         Ok(self
@@ -182,17 +224,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Sessions {
         async fn call(self, request: list::Request) -> Result<list::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .list_sessions(request),
-                tracing::debug_span!("Sessions::list")
-            );
+            let call = self.inner.list_sessions(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -201,12 +250,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get::Request) -> Result<get::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_session(request),
-                tracing::debug_span!("Sessions::get")
-            );
+            let call = self.inner.get_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -215,12 +259,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: cancel::Request) -> Result<cancel::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .cancel_session(request),
-                tracing::debug_span!("Sessions::cancel")
-            );
+            let call = self.inner.cancel_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -229,12 +268,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: create::Request) -> Result<create::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .create_session(request),
-                tracing::debug_span!("Sessions::create")
-            );
+            let call = self.inner.create_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -243,12 +277,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: pause::Request) -> Result<pause::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .pause_session(request),
-                tracing::debug_span!("Sessions::pause")
-            );
+            let call = self.inner.pause_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -258,12 +287,7 @@ super::impl_call! {
 
 
         async fn call(self, request: resume::Request) -> Result<resume::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .resume_session(request),
-                tracing::debug_span!("Sessions::resume")
-            );
+            let call = self.inner.resume_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -272,12 +296,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: close::Request) -> Result<close::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .close_session(request),
-                tracing::debug_span!("Sessions::close")
-            );
+            let call = self.inner.close_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -286,12 +305,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: purge::Request) -> Result<purge::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .purge_session(request),
-                tracing::debug_span!("Sessions::purge")
-            );
+            let call = self.inner.purge_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -300,12 +314,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: delete::Request) -> Result<delete::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .delete_session(request),
-                tracing::debug_span!("Sessions::delete")
-            );
+            let call = self.inner.delete_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -314,12 +323,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: stop_submission::Request) -> Result<stop_submission::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .stop_submission(request),
-                tracing::debug_span!("Sessions::stop_submission")
-            );
+            let call = self.inner.stop_submission(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
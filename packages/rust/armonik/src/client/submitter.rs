@@ -15,12 +15,13 @@ use crate::submitter::{
 use crate::utils::IntoCollection;
 use crate::{Configuration, Output, ResultStatus, TaskOptions, TaskRequest, TaskStatus};
 
-use super::{GrpcCall, GrpcCallStream};
+use super::{CallOptions, GrpcCall, GrpcCallStream};
 
 #[derive(Clone)]
 #[deprecated]
 pub struct Submitter<T> {
     inner: v3::submitter::submitter_client::SubmitterClient<T>,
+    options: CallOptions,
 }
 
 #[allow(deprecated)]
@@ -35,9 +36,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::submitter::submitter_client::SubmitterClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     pub async fn get_service_configuration(
         &mut self,
     ) -> Result<Configuration, super::RequestError> {
@@ -85,9 +93,7 @@ where
 
         match response {
             create_tasks::Response::Status(statuses) => Ok(statuses),
-            create_tasks::Response::Error(msg) => {
-                Err(tonic::Status::internal(msg)).context(super::GrpcSnafu {})
-            }
+            create_tasks::Response::Error(details) => super::TaskCreationSnafu { details }.fail(),
         }
     }
 
@@ -99,9 +105,7 @@ where
 
         match response {
             create_tasks::Response::Status(statuses) => Ok(statuses),
-            create_tasks::Response::Error(msg) => {
-                Err(tonic::Status::internal(msg)).context(super::GrpcSnafu {})
-            }
+            create_tasks::Response::Error(details) => super::TaskCreationSnafu { details }.fail(),
         }
     }
 
@@ -138,22 +142,29 @@ where
         super::RequestError,
     > {
         let span = tracing::debug_span!("Submitter::try_get_result");
+        let options = self.options.clone();
         let call = tracing_futures::Instrument::instrument(
-            self.inner.try_get_result_stream(try_get_result::Request {
-                session_id: session_id.into(),
-                result_id: result_id.into(),
-            }),
+            self.inner.try_get_result_stream(span.in_scope(|| {
+                options.request(try_get_result::Request {
+                    session_id: session_id.into(),
+                    result_id: result_id.into(),
+                })
+            })),
             tracing::trace_span!(parent: &span, "rpc"),
         );
-        let stream = call
+        options
+            .run_stream(async move {
+                let stream = call
+                    .await
+                    .context(super::GrpcSnafu {})?
+                    .into_inner()
+                    .map(|item| item.map(Into::into).context(super::GrpcSnafu {}));
+                Ok(tracing_futures::Instrument::instrument(
+                    stream,
+                    tracing::trace_span!(parent: &span, "stream"),
+                ))
+            })
             .await
-            .context(super::GrpcSnafu {})?
-            .into_inner()
-            .map(|item| item.map(Into::into).context(super::GrpcSnafu {}));
-        Ok(tracing_futures::Instrument::instrument(
-            stream,
-            tracing::trace_span!(parent: &span, "stream"),
-        ))
     }
 
     pub async fn try_get_task_output(
@@ -170,9 +181,7 @@ where
 
         match response {
             Output::Ok => Ok(()),
-            Output::Error { details } => {
-                Err(tonic::Status::internal(details)).context(super::GrpcSnafu {})
-            }
+            Output::Error { details } => super::TaskFailedSnafu { details }.fail(),
         }
     }
 
@@ -245,17 +254,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Submitter {
         async fn call(self, request: get_service_configuration::Request) -> Result<get_service_configuration::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_service_configuration(request),
-                tracing::debug_span!("Submitter::get_service_configuration")
-            );
+            let call = self.inner.get_service_configuration(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -264,12 +280,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: create_session::Request) -> Result<create_session::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .create_session(request),
-                tracing::debug_span!("Submitter::create_session")
-            );
+            let call = self.inner.create_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -278,12 +289,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: cancel_session::Request) -> Result<cancel_session::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .cancel_session(request),
-                tracing::debug_span!("Submitter::cancel_session")
-            );
+            let call = self.inner.cancel_session(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -292,12 +298,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: create_tasks::SmallRequest) -> Result<create_tasks::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .create_small_tasks(request),
-                tracing::debug_span!("Submitter::create_tasks")
-            );
+            let call = self.inner.create_small_tasks(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -306,12 +307,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: list_tasks::Request) -> Result<list_tasks::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .list_tasks(request),
-                tracing::debug_span!("Submitter::list_tasks")
-            );
+            let call = self.inner.list_tasks(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -320,12 +316,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: list_sessions::Request) -> Result<list_sessions::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .list_sessions(request),
-                tracing::debug_span!("Submitter::list_sessions")
-            );
+            let call = self.inner.list_sessions(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -334,12 +325,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: count_tasks::Request) -> Result<count_tasks::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .count_tasks(request),
-                tracing::debug_span!("Submitter::count_tasks")
-            );
+            let call = self.inner.count_tasks(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -348,7 +334,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: try_get_result::Request) -> Result<futures::stream::BoxStream<'static, Result<try_get_result::Response, tonic::Status>>> {
-            let span = tracing::debug_span!("Submitter::try_get_result");
+            let span = tracing::Span::current();
             let call = tracing_futures::Instrument::instrument(
                 self
                     .inner
@@ -369,12 +355,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: try_get_task_output::Request) -> Result<try_get_task_output::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .try_get_task_output(request),
-                tracing::debug_span!("Submitter::try_get_task_output")
-            );
+            let call = self.inner.try_get_task_output(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -383,12 +364,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: wait_for_availability::Request) -> Result<wait_for_availability::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .wait_for_availability(request),
-                tracing::debug_span!("Submitter::wait_for_availability")
-            );
+            let call = self.inner.wait_for_availability(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -397,12 +373,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: wait_for_completion::Request) -> Result<wait_for_completion::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .wait_for_completion(request),
-                tracing::debug_span!("Submitter::wait_for_completion")
-            );
+            let call = self.inner.wait_for_completion(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -411,12 +382,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: cancel_tasks::Request) -> Result<cancel_tasks::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .cancel_tasks(request),
-                tracing::debug_span!("Submitter::cancel_tasks")
-            );
+            let call = self.inner.cancel_tasks(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -425,12 +391,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: task_status::Request) -> Result<task_status::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_task_status(request),
-                tracing::debug_span!("Submitter::task_status")
-            );
+            let call = self.inner.get_task_status(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -439,12 +400,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: result_status::Request) -> Result<result_status::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_result_status(request),
-                tracing::debug_span!("Submitter::result_status")
-            );
+            let call = self.inner.get_result_status(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -466,16 +422,30 @@ where
     type Error = super::RequestError;
 
     async fn call(self, request: S) -> Result<Self::Response, Self::Error> {
+        let options = self.options.clone();
+        self.call_with(request, options).await
+    }
+
+    async fn call_with(
+        self,
+        request: S,
+        options: CallOptions,
+    ) -> Result<Self::Response, Self::Error> {
         let span = tracing::debug_span!("Submitter::create_tasks");
         let stream = tracing_futures::Instrument::instrument(
             request.map(Into::into),
             tracing::trace_span!(parent: &span, "stream"),
         );
         let call = tracing_futures::Instrument::instrument(
-            self.inner.create_large_tasks(stream),
+            self.inner
+                .create_large_tasks(span.in_scope(|| super::client_streaming(stream, &options))),
             tracing::trace_span!(parent: &span, "rpc"),
         );
-        Ok(call.await.context(super::GrpcSnafu {})?.into_inner().into())
+        let call = options
+            .run(async move { Ok(call.await.context(super::GrpcSnafu {})?.into_inner().into()) });
+        #[cfg(feature = "metrics")]
+        let call = super::metrics::Call::start("Submitter", "create_tasks").unary(call);
+        call.await
     }
 }
 
@@ -530,11 +500,12 @@ mod tests {
         let mut client = Client::new().await.unwrap().into_submitter();
         match client.create_small_tasks("session-id", None, []).await {
             Ok(_) => (),
-            Err(crate::client::RequestError::Grpc { source, .. }) => {
-                if source.code() != tonic::Code::Internal || !source.message().is_empty() {
-                    panic!("{source:?}")
+            Err(crate::client::RequestError::TaskCreation { details, .. }) => {
+                if !details.is_empty() {
+                    panic!("{details:?}")
                 }
             }
+            Err(err) => panic!("{err:?}"),
         }
         let after = Client::get_nb_request("Submitter", "CreateSmallTasks").await;
         assert_eq!(after - before, 1);
@@ -551,11 +522,12 @@ mod tests {
             .await
         {
             Ok(_) => (),
-            Err(crate::client::RequestError::Grpc { source, .. }) => {
-                if source.code() != tonic::Code::Internal || !source.message().is_empty() {
-                    panic!("{source:?}")
+            Err(crate::client::RequestError::TaskCreation { details, .. }) => {
+                if !details.is_empty() {
+                    panic!("{details:?}")
                 }
             }
+            Err(err) => panic!("{err:?}"),
         }
         let after = Client::get_nb_request("Submitter", "CreateLargeTasks").await;
         assert_eq!(after - before, 1);
@@ -761,11 +733,12 @@ mod tests {
             .await
         {
             Ok(_) => (),
-            Err(crate::client::RequestError::Grpc { source, .. }) => {
-                if source.code() != tonic::Code::Internal || !source.message().is_empty() {
-                    panic!("{source:?}")
+            Err(crate::client::RequestError::TaskCreation { details, .. }) => {
+                if !details.is_empty() {
+                    panic!("{details:?}")
                 }
             }
+            Err(err) => panic!("{err:?}"),
         }
         let after = Client::get_nb_request("Submitter", "CreateSmallTasks").await;
         assert_eq!(after - before, 1);
@@ -782,11 +755,12 @@ mod tests {
             .await
         {
             Ok(_) => (),
-            Err(crate::client::RequestError::Grpc { source, .. }) => {
-                if source.code() != tonic::Code::Internal || !source.message().is_empty() {
-                    panic!("{source:?}")
+            Err(crate::client::RequestError::TaskCreation { details, .. }) => {
+                if !details.is_empty() {
+                    panic!("{details:?}")
                 }
             }
+            Err(err) => panic!("{err:?}"),
         }
         let after = Client::get_nb_request("Submitter", "CreateLargeTasks").await;
         assert_eq!(after - before, 1);
@@ -0,0 +1,467 @@
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+
+use crate::events::{self, subscribe, EventsEnum};
+use crate::{
+    results, tasks, FilterDate, FilterDateOperator, FilterStatus, FilterStatusOperator,
+    FilterString, FilterStringOperator, ResultStatus, TaskStatus,
+};
+
+use super::{Client, RequestError, RetryPolicy};
+
+/// Events of a session, subscribed to again whenever the stream breaks.
+///
+/// A subscription to the events service lasts as long as its gRPC stream: a load balancer timeout or a
+/// restart of the control plane ends it. This one subscribes again with the same request, backing off
+/// according to its [`RetryPolicy`] whenever a stream breaks or ends without having delivered anything.
+///
+/// Updates sent while no stream was open are not lost: once subscribed again, the tasks and results the
+/// request is about are listed, and those whose status differs from the one last delivered are reported
+/// as `TaskStatusUpdate` and `ResultStatusUpdate`. A status that was never delivered is reported as well,
+/// even if it was reached before the subscription started. Updates bringing nothing new, such as one
+/// already reported from a listing, are left out.
+///
+/// Tasks that ended and results that completed are forgotten a minute after their status was delivered,
+/// so that a long subscription does not hold every task of the session. Listings then leave out the
+/// tasks and results that ended more than a minute before the stream broke; this also leaves out the
+/// results aborted or deleted while no stream was open.
+///
+/// The stream ends once `max_attempts` subscriptions in a row broke or ended without delivering
+/// anything, with the error of the last one if any.
+#[must_use = "streams do nothing unless polled"]
+pub struct ResilientSubscription {
+    inner: BoxStream<'static, Result<subscribe::Response, RequestError>>,
+}
+
+impl ResilientSubscription {
+    /// Subscribe to the events `request` asks for, trying again for as long as it takes
+    pub fn new<T>(client: Client<T>, request: subscribe::Request) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+        T: tonic::client::GrpcService<tonic::body::Body>,
+        T::Future: Send,
+        T::Error: Into<tonic::codegen::StdError>,
+        T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+        <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+    {
+        let mut policy = RetryPolicy::default();
+        policy.max_attempts = u32::MAX;
+        Self::with_retry_policy(client, request, policy)
+    }
+
+    /// Subscribe to the events `request` asks for, trying again according to `policy`
+    pub fn with_retry_policy<T>(
+        client: Client<T>,
+        request: subscribe::Request,
+        policy: RetryPolicy,
+    ) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+        T: tonic::client::GrpcService<tonic::body::Body>,
+        T::Future: Send,
+        T::Error: Into<tonic::codegen::StdError>,
+        T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+        <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+    {
+        let state = State {
+            client,
+            request,
+            policy,
+            stream: None,
+            failures: 0,
+            last_error: None,
+            subscribed: false,
+            broken_at: SystemTime::now(),
+            done: false,
+            tasks: HashMap::new(),
+            results: HashMap::new(),
+            settled: VecDeque::new(),
+            ready: VecDeque::new(),
+        };
+
+        Self {
+            inner: futures::stream::unfold(state, |mut state| async move {
+                let item = state.next().await?;
+                Some((item, state))
+            })
+            .boxed(),
+        }
+    }
+}
+
+impl Stream for ResilientSubscription {
+    type Item = Result<subscribe::Response, RequestError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl std::fmt::Debug for ResilientSubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResilientSubscription")
+            .finish_non_exhaustive()
+    }
+}
+
+/// How long a task or result in a final status is remembered after its status was delivered
+const SETTLED_FOR: Duration = Duration::from_secs(60);
+
+/// Task statuses a task does not leave
+const SETTLED_TASKS: [TaskStatus; 5] = [
+    TaskStatus::Completed,
+    TaskStatus::Error,
+    TaskStatus::Timeout,
+    TaskStatus::Cancelled,
+    TaskStatus::Retried,
+];
+
+/// Result statuses after which a result is only deleted
+const SETTLED_RESULTS: [ResultStatus; 3] = [
+    ResultStatus::Completed,
+    ResultStatus::Aborted,
+    ResultStatus::Deleted,
+];
+
+/// A task or result whose status was delivered in a final status
+enum Settled {
+    Task(String),
+    Result(String),
+}
+
+struct State<T> {
+    client: Client<T>,
+    request: subscribe::Request,
+    policy: RetryPolicy,
+    /// The stream currently subscribed to
+    stream: Option<BoxStream<'static, Result<subscribe::Response, RequestError>>>,
+    /// How many subscriptions in a row broke or ended before delivering anything
+    failures: u32,
+    last_error: Option<RequestError>,
+    /// Whether a subscription ever succeeded: the next ones have to catch up
+    subscribed: bool,
+    /// When the last stream broke
+    broken_at: SystemTime,
+    done: bool,
+    /// Status of each task, as last delivered
+    tasks: HashMap<String, TaskStatus>,
+    /// Status of each result, as last delivered
+    results: HashMap<String, ResultStatus>,
+    /// Tasks and results delivered in a final status, with when, to forget once settled for long
+    settled: VecDeque<(tokio::time::Instant, Settled)>,
+    /// Updates found by listing, to deliver
+    ready: VecDeque<subscribe::Response>,
+}
+
+impl<T> State<T>
+where
+    T: Clone + Send + Sync + 'static,
+    T: tonic::client::GrpcService<tonic::body::Body>,
+    T::Future: Send,
+    T::Error: Into<tonic::codegen::StdError>,
+    T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    /// Whether the request asks for events of kind `kind`
+    fn returns(&self, kind: EventsEnum) -> bool {
+        self.request.returned_events.is_empty() || self.request.returned_events.contains(&kind)
+    }
+
+    /// Record the update, returning whether it brings anything new
+    fn record(&mut self, update: &events::Update) -> bool {
+        self.forget_settled();
+        let new = self.remember(update);
+        if new {
+            let settled = match update {
+                events::Update::TaskStatusUpdate(events::TaskStatusUpdate { task_id, status })
+                | events::Update::NewTask(events::NewTask {
+                    task_id, status, ..
+                }) if SETTLED_TASKS.contains(status) => Some(Settled::Task(task_id.clone())),
+                events::Update::ResultStatusUpdate(events::ResultStatusUpdate {
+                    result_id,
+                    status,
+                })
+                | events::Update::NewResult(events::NewResult {
+                    result_id, status, ..
+                }) if SETTLED_RESULTS.contains(status) => Some(Settled::Result(result_id.clone())),
+                _ => None,
+            };
+            if let Some(settled) = settled {
+                self.settled
+                    .push_back((tokio::time::Instant::now(), settled));
+            }
+        }
+        new
+    }
+
+    /// Forget the tasks and results delivered in a final status for long enough not to be listed again
+    fn forget_settled(&mut self) {
+        while let Some((at, _)) = self.settled.front() {
+            if at.elapsed() < SETTLED_FOR {
+                break;
+            }
+            match self.settled.pop_front() {
+                Some((_, Settled::Task(task_id))) => {
+                    self.tasks.remove(&task_id);
+                }
+                Some((_, Settled::Result(result_id))) => {
+                    self.results.remove(&result_id);
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Remember the status the update delivers, returning whether it differs from the one known
+    fn remember(&mut self, update: &events::Update) -> bool {
+        match update {
+            events::Update::TaskStatusUpdate(events::TaskStatusUpdate { task_id, status }) => self
+                .tasks
+                .insert(task_id.clone(), status.clone())
+                .is_none_or(|previous| previous != *status),
+            events::Update::ResultStatusUpdate(events::ResultStatusUpdate {
+                result_id,
+                status,
+            }) => self
+                .results
+                .insert(result_id.clone(), status.clone())
+                .is_none_or(|previous| previous != *status),
+            events::Update::NewTask(events::NewTask {
+                task_id, status, ..
+            }) => {
+                let new = !self.tasks.contains_key(task_id);
+                if new {
+                    self.tasks.insert(task_id.clone(), status.clone());
+                }
+                new
+            }
+            events::Update::NewResult(events::NewResult {
+                result_id, status, ..
+            }) => {
+                let new = !self.results.contains_key(result_id);
+                if new {
+                    self.results.insert(result_id.clone(), status.clone());
+                }
+                new
+            }
+            events::Update::ResultOwnerUpdate(_) | events::Update::Invalid => true,
+        }
+    }
+
+    /// Queue `update`, found by listing, if it brings anything new
+    fn catch_up(&mut self, update: events::Update) {
+        if self.record(&update) {
+            self.ready.push_back(subscribe::Response {
+                session_id: self.request.session_id.clone(),
+                update,
+            });
+        }
+    }
+
+    /// List the tasks and results the request is about, reporting the statuses that were missed
+    ///
+    /// Those that ended before the stream broke, minus the time they are remembered for, are left out:
+    /// they were delivered already, if at all, and may be forgotten.
+    async fn list(&mut self) -> Result<(), RequestError> {
+        let session_id = self.request.session_id.clone();
+        let since = FilterDate {
+            value: self
+                .broken_at
+                .checked_sub(SETTLED_FOR)
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+                .into(),
+            operator: FilterDateOperator::AfterOrEqual,
+        };
+
+        if self.returns(EventsEnum::TaskStatusUpdate) {
+            let session = tasks::filter::Field {
+                field: tasks::Field::Summary(tasks::SummaryField::SessionId),
+                condition: tasks::filter::Condition::String(FilterString {
+                    value: session_id.clone(),
+                    operator: FilterStringOperator::Equal,
+                }),
+            };
+            let mut requested = self.request.task_filters.or.clone();
+            if requested.is_empty() {
+                requested.push(Default::default());
+            }
+            let mut filters = Vec::with_capacity(2 * requested.len());
+            for mut and in requested {
+                and.and.push(session.clone());
+
+                let mut running = and.clone();
+                running.and.extend(
+                    SETTLED_TASKS
+                        .into_iter()
+                        .map(|status| tasks::filter::Field {
+                            field: tasks::Field::Summary(tasks::SummaryField::Status),
+                            condition: tasks::filter::Condition::Status(FilterStatus {
+                                value: status,
+                                operator: FilterStatusOperator::NotEqual,
+                            }),
+                        }),
+                );
+                filters.push(running);
+
+                and.and.push(tasks::filter::Field {
+                    field: tasks::Field::Summary(tasks::SummaryField::EndedAt),
+                    condition: tasks::filter::Condition::Date(since.clone()),
+                });
+                filters.push(and);
+            }
+
+            let mut listed = self.client.clone().into_tasks().list_stream(
+                filters,
+                Default::default(),
+                false,
+                100,
+            );
+            while let Some(task) = listed.next().await {
+                let task = task?;
+                self.catch_up(events::Update::TaskStatusUpdate(events::TaskStatusUpdate {
+                    task_id: task.task_id,
+                    status: task.status,
+                }));
+            }
+        }
+
+        if self.returns(EventsEnum::ResultStatusUpdate) {
+            let session = results::filter::Field {
+                field: results::Field::SessionId,
+                condition: results::filter::Condition::String(FilterString {
+                    value: session_id,
+                    operator: FilterStringOperator::Equal,
+                }),
+            };
+            let mut requested = self.request.result_filters.or.clone();
+            if requested.is_empty() {
+                requested.push(Default::default());
+            }
+            let mut filters = Vec::with_capacity(2 * requested.len());
+            for mut and in requested {
+                and.and.push(session.clone());
+
+                let mut running = and.clone();
+                running
+                    .and
+                    .extend(
+                        SETTLED_RESULTS
+                            .into_iter()
+                            .map(|status| results::filter::Field {
+                                field: results::Field::Status,
+                                condition: results::filter::Condition::Status(FilterStatus {
+                                    value: status,
+                                    operator: FilterStatusOperator::NotEqual,
+                                }),
+                            }),
+                    );
+                filters.push(running);
+
+                and.and.push(results::filter::Field {
+                    field: results::Field::CompletedAt,
+                    condition: results::filter::Condition::Date(since.clone()),
+                });
+                filters.push(and);
+            }
+
+            let mut listed =
+                self.client
+                    .clone()
+                    .into_results()
+                    .list_stream(filters, Default::default(), 100);
+            while let Some(result) = listed.next().await {
+                let result = result?;
+                self.catch_up(events::Update::ResultStatusUpdate(
+                    events::ResultStatusUpdate {
+                        result_id: result.result_id,
+                        status: result.status,
+                    },
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Subscribe again, listing what was missed since the previous subscription
+    async fn subscribe(&mut self) -> Result<(), RequestError> {
+        let request = self.request.clone();
+        let stream = self
+            .client
+            .events()
+            .subscribe(
+                request.session_id,
+                request.task_filters,
+                request.result_filters,
+                request.returned_events,
+            )
+            .await?;
+
+        // Subscribed before listing: an update sent in between is seen by one or the other.
+        if self.subscribed {
+            self.list().await?;
+        }
+        self.stream = Some(stream.boxed());
+        self.subscribed = true;
+        Ok(())
+    }
+
+    async fn next(&mut self) -> Option<Result<subscribe::Response, RequestError>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            if let Some(response) = self.ready.pop_front() {
+                return Some(Ok(response));
+            }
+
+            let Some(stream) = &mut self.stream else {
+                if self.failures > 0 {
+                    if self.failures >= self.policy.max_attempts {
+                        self.done = true;
+                        return self.last_error.take().map(Err);
+                    }
+                    tokio::time::sleep(self.policy.backoff(self.failures)).await;
+                }
+
+                match self.subscribe().await {
+                    Ok(()) => {}
+                    Err(err) => {
+                        tracing::debug!("Could not subscribe to the events: {err}");
+                        self.failures += 1;
+                        self.last_error = Some(err);
+                    }
+                }
+                continue;
+            };
+
+            match stream.next().await {
+                Some(Ok(response)) => {
+                    self.failures = 0;
+                    self.last_error = None;
+                    if self.record(&response.update) {
+                        return Some(Ok(response));
+                    }
+                }
+                Some(Err(err)) => {
+                    tracing::debug!("Events stream failed, subscribing again: {err}");
+                    self.stream = None;
+                    self.broken_at = SystemTime::now();
+                    self.failures += 1;
+                    self.last_error = Some(err);
+                }
+                None => {
+                    tracing::debug!("Events stream ended, subscribing again");
+                    self.stream = None;
+                    self.broken_at = SystemTime::now();
+                    self.failures += 1;
+                }
+            }
+        }
+    }
+}
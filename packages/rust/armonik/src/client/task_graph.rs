@@ -0,0 +1,366 @@
+use std::collections::{HashMap, HashSet};
+
+use snafu::{ResultExt, Snafu};
+
+use crate::{results, tasks, TaskOptions};
+
+use super::{Client, RequestError};
+
+/// A task of a [`TaskGraph`], whose data dependencies and expected outputs are named symbolically.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TaskNode {
+    /// Payload of the task.
+    pub payload: Vec<u8>,
+    /// Names of the results the task depends on: data of the graph, or outputs of other tasks.
+    pub data_dependencies: Vec<String>,
+    /// Names of the results the task produces.
+    pub expected_outputs: Vec<String>,
+    /// Options of the task, those of the submission if unset.
+    pub task_options: Option<TaskOptions>,
+}
+
+impl TaskNode {
+    /// Create a task with `payload`, depending on nothing and producing nothing yet
+    pub fn new(payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            payload: payload.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The ids a [`TaskGraph`] was given once submitted, by symbolic name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SubmittedGraph {
+    /// Id of each task.
+    pub tasks: HashMap<String, String>,
+    /// Id of each result: the data of the graph and the outputs of its tasks.
+    pub results: HashMap<String, String>,
+}
+
+/// A graph of tasks, submitted at once.
+///
+/// Tasks name the results they depend on and produce; the graph creates them, uploads the data and
+/// payloads, and submits the tasks with the ids the control plane gave to each name. The graph is checked
+/// before anything is sent: every dependency must be either data of the graph or the output of a task,
+/// every name must be declared once, and tasks cannot depend on one another in a cycle.
+///
+/// ```no_run
+/// # async fn example(client: &mut armonik::Client, session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// use armonik::client::{TaskGraph, TaskNode};
+///
+/// let mut square = TaskNode::new("square");
+/// square.data_dependencies = vec![String::from("x")];
+/// square.expected_outputs = vec![String::from("x2")];
+///
+/// let submitted = TaskGraph::new()
+///     .add_data("x", b"3".to_vec())
+///     .add_task("square", square)
+///     .submit(client, session_id)
+///     .await?;
+/// println!("x² will be in {}", submitted.results["x2"]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TaskGraph {
+    data: Vec<(String, Vec<u8>)>,
+    tasks: Vec<(String, TaskNode)>,
+}
+
+impl TaskGraph {
+    /// Create an empty graph
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add data the tasks can depend on, under `name`
+    pub fn add_data(&mut self, name: impl Into<String>, data: impl Into<Vec<u8>>) -> &mut Self {
+        self.data.push((name.into(), data.into()));
+        self
+    }
+
+    /// Add a task, under `name`
+    pub fn add_task(&mut self, name: impl Into<String>, task: TaskNode) -> &mut Self {
+        self.tasks.push((name.into(), task));
+        self
+    }
+
+    /// Check the graph, returning the indices of its tasks in an order where each task comes after those it
+    /// depends on
+    fn sorted(&self) -> Result<Vec<usize>, TaskGraphError> {
+        let mut task_names = HashSet::new();
+        for (name, _) in &self.tasks {
+            if !task_names.insert(name.as_str()) {
+                return DuplicateSnafu { name }.fail();
+            }
+        }
+
+        // Which task produces each result, `None` for the data of the graph
+        let mut producers = HashMap::new();
+        for (name, _) in &self.data {
+            if producers.insert(name.as_str(), None).is_some() {
+                return DuplicateSnafu { name }.fail();
+            }
+        }
+        for (index, (_, task)) in self.tasks.iter().enumerate() {
+            for name in &task.expected_outputs {
+                if producers.insert(name.as_str(), Some(index)).is_some() {
+                    return DuplicateSnafu { name }.fail();
+                }
+            }
+        }
+
+        let mut dependents = vec![Vec::new(); self.tasks.len()];
+        let mut pending = vec![0usize; self.tasks.len()];
+        for (index, (task_name, task)) in self.tasks.iter().enumerate() {
+            for name in &task.data_dependencies {
+                match producers.get(name.as_str()) {
+                    Some(Some(producer)) => {
+                        dependents[*producer].push(index);
+                        pending[index] += 1;
+                    }
+                    Some(None) => {}
+                    None => {
+                        return DanglingSnafu {
+                            task: task_name,
+                            name,
+                        }
+                        .fail()
+                    }
+                }
+            }
+        }
+
+        let mut sorted = (0..self.tasks.len())
+            .filter(|index| pending[*index] == 0)
+            .collect::<Vec<_>>();
+        let mut next = 0;
+        while let Some(&index) = sorted.get(next) {
+            next += 1;
+            for &dependent in &dependents[index] {
+                pending[dependent] -= 1;
+                if pending[dependent] == 0 {
+                    sorted.push(dependent);
+                }
+            }
+        }
+
+        if sorted.len() < self.tasks.len() {
+            let mut tasks = pending
+                .iter()
+                .enumerate()
+                .filter(|(_, pending)| **pending > 0)
+                .map(|(index, _)| self.tasks[index].0.clone())
+                .collect::<Vec<_>>();
+            tasks.sort();
+            return CycleSnafu { tasks }.fail();
+        }
+        Ok(sorted)
+    }
+
+    /// Check the graph without submitting it
+    pub fn validate(&self) -> Result<(), TaskGraphError> {
+        self.sorted().map(|_| ())
+    }
+
+    /// Submit the graph in `session_id`.
+    ///
+    /// Data and payloads no larger than the maximum chunk size of the results service are created with
+    /// their content; larger ones are created empty, then uploaded chunk by chunk. The tasks are then
+    /// submitted in a single call, each after those it depends on.
+    pub async fn submit<T>(
+        &self,
+        client: &mut Client<T>,
+        session_id: impl Into<String>,
+    ) -> Result<SubmittedGraph, TaskGraphError>
+    where
+        T: Clone,
+        T: tonic::client::GrpcService<tonic::body::Body>,
+        T::Error: Into<tonic::codegen::StdError>,
+        T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+        <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+    {
+        let sorted = self.sorted()?;
+        let session_id = session_id.into();
+
+        let chunk_size = client
+            .results()
+            .get_service_configuration()
+            .await
+            .context(RequestSnafu {})?
+            .data_chunk_max_size;
+        let chunk_size = usize::try_from(chunk_size).unwrap_or_default().max(1);
+
+        // Every result with content: the data, then the payload of each task, with the index of the task.
+        let payload_names = self
+            .tasks
+            .iter()
+            .map(|(name, _)| format!("{name}/payload"))
+            .collect::<Vec<_>>();
+        let contents =
+            self.data
+                .iter()
+                .map(|(name, data)| (None, name, data))
+                .chain(
+                    self.tasks.iter().enumerate().map(|(index, (_, task))| {
+                        (Some(index), &payload_names[index], &task.payload)
+                    }),
+                );
+        let (small, large): (Vec<_>, Vec<_>) =
+            contents.partition(|(_, _, data)| data.len() <= chunk_size);
+        let outputs = self
+            .tasks
+            .iter()
+            .flat_map(|(_, task)| &task.expected_outputs)
+            .map(|name| (None, name));
+
+        let mut ids = HashMap::new();
+        let mut payload_ids = vec![String::new(); self.tasks.len()];
+        let mut assign = |task: Option<usize>, name: &String, result_id: String| match task {
+            Some(index) => payload_ids[index] = result_id,
+            None => {
+                ids.insert(name.clone(), result_id);
+            }
+        };
+
+        if !small.is_empty() {
+            let created = client
+                .results()
+                .create(
+                    &session_id,
+                    small
+                        .iter()
+                        .map(|(_, name, data)| results::create::RequestItem {
+                            name: (*name).clone(),
+                            data: (*data).clone(),
+                            ..Default::default()
+                        }),
+                )
+                .await
+                .context(RequestSnafu {})?;
+            for ((task, name, _), result) in small.iter().zip(created) {
+                assign(*task, name, result.result_id);
+            }
+        }
+
+        let empty = large
+            .iter()
+            .map(|(task, name, _)| (*task, *name))
+            .chain(outputs)
+            .collect::<Vec<_>>();
+        let mut large_ids = Vec::with_capacity(large.len());
+        if !empty.is_empty() {
+            let created = client
+                .results()
+                .create_metadata(
+                    &session_id,
+                    empty
+                        .iter()
+                        .map(|(_, name)| results::create_metadata::RequestItem {
+                            name: (*name).clone(),
+                            ..Default::default()
+                        }),
+                )
+                .await
+                .context(RequestSnafu {})?;
+            for ((task, name), result) in empty.iter().zip(created) {
+                if large_ids.len() < large.len() {
+                    large_ids.push(result.result_id.clone());
+                }
+                assign(*task, name, result.result_id);
+            }
+        }
+
+        for ((_, _, data), result_id) in large.iter().zip(large_ids) {
+            let chunks = data
+                .chunks(chunk_size)
+                .map(<[u8]>::to_vec)
+                .collect::<Vec<_>>();
+            client
+                .results()
+                .upload(&session_id, result_id, futures::stream::iter(chunks))
+                .await
+                .context(RequestSnafu {})?;
+        }
+
+        let mut task_ids = HashMap::new();
+        if !sorted.is_empty() {
+            let submitted = client
+                .tasks()
+                .submit(
+                    &session_id,
+                    None,
+                    sorted.iter().map(|&index| {
+                        let task = &self.tasks[index].1;
+                        tasks::submit::RequestItem {
+                            payload_id: payload_ids[index].clone(),
+                            data_dependencies: task
+                                .data_dependencies
+                                .iter()
+                                .map(|name| ids[name].clone())
+                                .collect(),
+                            expected_output_keys: task
+                                .expected_outputs
+                                .iter()
+                                .map(|name| ids[name].clone())
+                                .collect(),
+                            task_options: task.task_options.clone(),
+                        }
+                    }),
+                )
+                .await
+                .context(RequestSnafu {})?;
+            task_ids.extend(
+                sorted
+                    .iter()
+                    .zip(submitted)
+                    .map(|(&index, item)| (self.tasks[index].0.clone(), item.task_id)),
+            );
+        }
+
+        Ok(SubmittedGraph {
+            tasks: task_ids,
+            results: ids,
+        })
+    }
+}
+
+/// Error checking or submitting a [`TaskGraph`]
+#[derive(Debug, Snafu)]
+#[non_exhaustive]
+pub enum TaskGraphError {
+    #[snafu(display("{name:?} is declared more than once in the task graph [{location}]"))]
+    #[non_exhaustive]
+    Duplicate {
+        name: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display(
+        "Task {task:?} depends on {name:?}, which is neither data of the graph nor the output of a task [{location}]"
+    ))]
+    #[non_exhaustive]
+    Dangling {
+        task: String,
+        name: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Tasks {tasks:?} depend on one another in a cycle [{location}]"))]
+    #[non_exhaustive]
+    Cycle {
+        tasks: Vec<String>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Could not submit the task graph [{location}]"))]
+    #[non_exhaustive]
+    Request {
+        source: RequestError,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+}
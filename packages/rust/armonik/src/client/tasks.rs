@@ -10,12 +10,13 @@ use crate::tasks::{
 use crate::utils::IntoCollection;
 use crate::{StatusCount, TaskOptions};
 
-use super::GrpcCall;
+use super::{CallOptions, GrpcCall};
 
 /// Service for handling tasks.
 #[derive(Clone)]
 pub struct Tasks<T> {
     inner: v3::tasks::tasks_client::TasksClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Tasks<T>
@@ -29,9 +30,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::tasks::tasks_client::TasksClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Get a tasks list using pagination, filters and sorting.
     pub async fn list(
         &mut self,
@@ -76,6 +84,74 @@ where
         .await
     }
 
+    /// Get every task summary matching the filters, page after page.
+    ///
+    /// Pages of `page_size` are fetched one after the other, the next while the current one is consumed:
+    /// see [`ListStream`](super::ListStream).
+    pub fn list_stream(
+        &self,
+        filters: impl IntoIterator<Item = impl IntoIterator<Item = filter::Field>>,
+        sort: Sort,
+        with_errors: bool,
+        page_size: i32,
+    ) -> super::ListStream<list::Request>
+    where
+        T: Clone + Send + Sync + 'static,
+        T::Future: Send,
+    {
+        let client = self.clone();
+        super::ListStream::new(
+            move |request| {
+                let mut client = client.clone();
+                async move { client.call(request).await }
+            },
+            list::Request {
+                filters: filters
+                    .into_iter()
+                    .map(crate::utils::IntoCollection::into_collect)
+                    .collect(),
+                sort,
+                with_errors,
+                page: 0,
+                page_size,
+            },
+        )
+    }
+
+    /// Get every task matching the filters, page after page.
+    ///
+    /// Pages of `page_size` are fetched one after the other, the next while the current one is consumed:
+    /// see [`ListStream`](super::ListStream).
+    pub fn list_detailed_stream(
+        &self,
+        filters: impl IntoIterator<Item = impl IntoIterator<Item = filter::Field>>,
+        sort: Sort,
+        with_errors: bool,
+        page_size: i32,
+    ) -> super::ListStream<list_detailed::Request>
+    where
+        T: Clone + Send + Sync + 'static,
+        T::Future: Send,
+    {
+        let client = self.clone();
+        super::ListStream::new(
+            move |request| {
+                let mut client = client.clone();
+                async move { client.call(request).await }
+            },
+            list_detailed::Request {
+                filters: filters
+                    .into_iter()
+                    .map(crate::utils::IntoCollection::into_collect)
+                    .collect(),
+                sort,
+                with_errors,
+                page: 0,
+                page_size,
+            },
+        )
+    }
+
     /// Get a task by its id.
     pub async fn get(&mut self, task_id: impl Into<String>) -> Result<Raw, super::RequestError> {
         Ok(self
@@ -147,17 +223,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Tasks {
         async fn call(self, request: list::Request) -> Result<list::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .list_tasks(request),
-                tracing::debug_span!("Tasks::list")
-            );
+            let call = self.inner.list_tasks(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -166,12 +249,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: list_detailed::Request) -> Result<list_detailed::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .list_tasks_detailed(request),
-                tracing::debug_span!("Tasks::list_detailed")
-            );
+            let call = self.inner.list_tasks_detailed(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -180,12 +258,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get::Request) -> Result<get::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_task(request),
-                tracing::debug_span!("Tasks::get")
-            );
+            let call = self.inner.get_task(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -194,12 +267,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: cancel::Request) -> Result<cancel::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .cancel_tasks(request),
-                tracing::debug_span!("Tasks::cancel")
-            );
+            let call = self.inner.cancel_tasks(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -208,12 +276,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: get_result_ids::Request) -> Result<get_result_ids::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .get_result_ids(request),
-                tracing::debug_span!("Tasks::get_result_ids")
-            );
+            let call = self.inner.get_result_ids(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -222,12 +285,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: count_status::Request) -> Result<count_status::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .count_tasks_by_status(request),
-                tracing::debug_span!("Tasks::count_status")
-            );
+            let call = self.inner.count_tasks_by_status(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -236,12 +294,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: submit::Request) -> Result<submit::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .submit_tasks(request),
-                tracing::debug_span!("Tasks::submit")
-            );
+            let call = self.inner.submit_tasks(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -3,11 +3,12 @@ use snafu::ResultExt;
 use crate::api::v3;
 use crate::versions::list;
 
-use super::GrpcCall;
+use super::{CallOptions, GrpcCall};
 
 #[derive(Clone)]
 pub struct Versions<T> {
     inner: v3::versions::versions_client::VersionsClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Versions<T>
@@ -21,9 +22,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::versions::versions_client::VersionsClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     pub async fn list(&mut self) -> Result<list::Response, super::RequestError> {
         self.call(list::Request {}).await
     }
@@ -38,17 +46,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Versions {
         async fn call(self, request: list::Request) -> Result<list::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .list_versions(request),
-                tracing::debug_span!("Versions::list")
-            );
+            let call = self.inner.list_versions(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
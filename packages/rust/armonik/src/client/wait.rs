@@ -0,0 +1,247 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use snafu::IntoError;
+
+use crate::{events, results, tasks, FilterString, FilterStringOperator, ResultStatus};
+
+use super::{Client, Events, GrpcSnafu, RequestError, Results};
+
+/// How often results are listed once events cannot be relied upon
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many results are named in one filter, be it of a listing or of a subscription
+const PAGE_SIZE: usize = 100;
+
+/// Whether a result reached a status it cannot leave while being waited for
+fn is_final(status: &ResultStatus) -> bool {
+    matches!(status, ResultStatus::Completed | ResultStatus::Aborted)
+}
+
+/// Filters matching the results of `result_ids`
+fn filters<'a>(
+    result_ids: impl IntoIterator<Item = &'a String>,
+) -> Vec<Vec<results::filter::Field>> {
+    result_ids
+        .into_iter()
+        .map(|result_id| {
+            vec![results::filter::Field {
+                field: results::Field::ResultId,
+                condition: results::filter::Condition::String(FilterString {
+                    value: result_id.clone(),
+                    operator: FilterStringOperator::Equal,
+                }),
+            }]
+        })
+        .collect()
+}
+
+/// State of a [`Results::wait`] stream
+struct Wait<T> {
+    results: Results<T>,
+    events: Events<T>,
+    session_id: String,
+    pending: HashSet<String>,
+    ready: VecDeque<Result<(String, ResultStatus), RequestError>>,
+    stream: Option<BoxStream<'static, Result<events::subscribe::Response, RequestError>>>,
+    started: bool,
+    /// Whether the results were listed once, telling those that are not of the session
+    listed: bool,
+}
+
+impl<T> Wait<T>
+where
+    T: Clone + Send + Sync + 'static,
+    T: tonic::client::GrpcService<tonic::body::Body>,
+    T::Future: Send,
+    T::Error: Into<tonic::codegen::StdError>,
+    T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    /// Mark `result_id` as ready if it is waited for and reached a final status
+    fn update(&mut self, result_id: String, status: ResultStatus) {
+        if is_final(&status) && self.pending.remove(&result_id) {
+            self.ready.push_back(Ok((result_id, status)));
+        }
+    }
+
+    /// Follow the status updates of the results still waited for, or of every result of the session
+    /// once they are too many to be named in one filter
+    async fn subscribe(&mut self) {
+        let filters = if self.pending.len() <= PAGE_SIZE {
+            filters(&self.pending)
+        } else {
+            Vec::new()
+        };
+        match self
+            .events
+            .subscribe(
+                &self.session_id,
+                tasks::filter::Or::default(),
+                filters,
+                [events::EventsEnum::ResultStatusUpdate],
+            )
+            .await
+        {
+            Ok(events) => self.stream = Some(events.boxed()),
+            Err(err) => {
+                tracing::debug!("Could not subscribe to the results, listing them instead: {err}")
+            }
+        }
+    }
+
+    /// List the results still waited for, to catch up with what no event reported, naming a page of
+    /// them at a time.
+    ///
+    /// Those the first listing does not return are not results of the session, and no event will ever
+    /// be about them: each is yielded as a `NotFound` error, and no longer waited for.
+    async fn snapshot(&mut self) -> Result<(), RequestError> {
+        let session = results::filter::Field {
+            field: results::Field::SessionId,
+            condition: results::filter::Condition::String(FilterString {
+                value: self.session_id.clone(),
+                operator: FilterStringOperator::Equal,
+            }),
+        };
+        let pending = self.pending.iter().cloned().collect::<Vec<_>>();
+        let mut found = HashSet::new();
+        for result_ids in pending.chunks(PAGE_SIZE) {
+            let mut filters = filters(result_ids);
+            for and in &mut filters {
+                and.push(session.clone());
+            }
+            let mut listed =
+                self.results
+                    .list_stream(filters, Default::default(), PAGE_SIZE as i32);
+            while let Some(result) = listed.next().await {
+                let result = result?;
+                found.insert(result.result_id.clone());
+                self.update(result.result_id, result.status);
+            }
+        }
+
+        if !self.listed {
+            self.listed = true;
+            for result_id in pending {
+                if !found.contains(&result_id) && self.pending.remove(&result_id) {
+                    let status = tonic::Status::not_found(format!(
+                        "Result {result_id} is not a result of session {}",
+                        self.session_id
+                    ));
+                    self.ready.push_back(Err(GrpcSnafu {}.into_error(status)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn next(&mut self) -> Option<Result<(String, ResultStatus), RequestError>> {
+        loop {
+            if let Some(ready) = self.ready.pop_front() {
+                return Some(ready);
+            }
+            if self.pending.is_empty() {
+                return None;
+            }
+
+            if let Some(stream) = &mut self.stream {
+                match stream.next().await {
+                    Some(Ok(events::subscribe::Response {
+                        update: events::Update::ResultStatusUpdate(update),
+                        ..
+                    })) => self.update(update.result_id, update.status),
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => {
+                        tracing::debug!("Events stream failed, subscribing again: {err}");
+                        self.stream = None;
+                    }
+                    None => {
+                        tracing::debug!("Events stream ended, subscribing again");
+                        self.stream = None;
+                    }
+                }
+                continue;
+            }
+
+            if self.started {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            self.started = true;
+            // Subscribed before listing: a result completed in between is seen by one or the other.
+            self.subscribe().await;
+            if let Err(err) = self.snapshot().await {
+                // Subscribed again along with the next listing, which the events cannot stand in for.
+                self.stream = None;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+impl<T> Results<T>
+where
+    T: Clone + Send + Sync + 'static,
+    T: tonic::client::GrpcService<tonic::body::Body>,
+    T::Future: Send,
+    T::Error: Into<tonic::codegen::StdError>,
+    T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    /// Wait for results of `session_id`, yielding each one as it becomes `Completed` or `Aborted`.
+    ///
+    /// Result status updates are followed through `events`, and the results are listed once subscribed,
+    /// for those that reached their status beforehand. Whenever the events stream fails or ends, or cannot
+    /// be subscribed to, the results are listed and subscribed to again every second. A listing that
+    /// fails is yielded as an error and tried again a second later: the stream ends once every result was
+    /// yielded, or when it is dropped.
+    ///
+    /// An id that is not a result of `session_id` is yielded as a `NotFound` error once the results are
+    /// first listed, and no longer waited for.
+    pub fn wait(
+        &self,
+        events: Events<T>,
+        session_id: impl Into<String>,
+        result_ids: impl IntoIterator<Item = impl Into<String>>,
+    ) -> impl Stream<Item = Result<(String, ResultStatus), RequestError>> + Send + 'static {
+        let wait = Wait {
+            results: self.clone(),
+            events,
+            session_id: session_id.into(),
+            pending: result_ids.into_iter().map(Into::into).collect(),
+            ready: VecDeque::new(),
+            stream: None,
+            started: false,
+            listed: false,
+        };
+
+        futures::stream::unfold(wait, |mut wait| async move {
+            let item = wait.next().await?;
+            Some((item, wait))
+        })
+    }
+}
+
+impl<T> Client<T>
+where
+    T: Clone + Send + Sync + 'static,
+    T: tonic::client::GrpcService<tonic::body::Body>,
+    T::Future: Send,
+    T::Error: Into<tonic::codegen::StdError>,
+    T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    /// Wait for results of `session_id`, yielding each one as it becomes `Completed` or `Aborted`.
+    ///
+    /// A shortcut for [`Results::wait`], with the events of this client.
+    pub fn wait_for_results(
+        &self,
+        session_id: impl Into<String>,
+        result_ids: impl IntoIterator<Item = impl Into<String>>,
+    ) -> impl Stream<Item = Result<(String, ResultStatus), RequestError>> + Send + 'static {
+        self.clone()
+            .into_results()
+            .wait(self.clone().into_events(), session_id, result_ids)
+    }
+}
@@ -4,11 +4,12 @@ use crate::api::v3;
 use crate::worker::{health_check, process};
 use crate::Output;
 
-use super::GrpcCall;
+use super::{CallOptions, GrpcCall};
 
 #[derive(Clone)]
 pub struct Worker<T> {
     inner: v3::worker::worker_client::WorkerClient<T>,
+    options: CallOptions,
 }
 
 impl<T> Worker<T>
@@ -22,9 +23,16 @@ where
     pub fn with_channel(channel: T) -> Self {
         Self {
             inner: v3::worker::worker_client::WorkerClient::new(channel),
+            options: CallOptions::default(),
         }
     }
 
+    /// Use `options` for every call made through this client, unless given options of its own
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     pub async fn health_check(&mut self) -> Result<health_check::Response, super::RequestError> {
         self.call(health_check::Request {}).await
     }
@@ -46,17 +54,24 @@ where
     {
         <&mut Self as GrpcCall<Request>>::call(self, request).await
     }
+
+    /// Perform a gRPC call from a raw request, with options of its own instead of those of the client.
+    pub async fn call_with<Request>(
+        &mut self,
+        request: Request,
+        options: CallOptions,
+    ) -> Result<<&mut Self as GrpcCall<Request>>::Response, <&mut Self as GrpcCall<Request>>::Error>
+    where
+        for<'a> &'a mut Self: GrpcCall<Request>,
+    {
+        <&mut Self as GrpcCall<Request>>::call_with(self, request, options).await
+    }
 }
 
 super::impl_call! {
     Worker {
         async fn call(self, request: health_check::Request) -> Result<health_check::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .health_check(request),
-                tracing::debug_span!("Worker::health_check")
-            );
+            let call = self.inner.health_check(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
@@ -65,12 +80,7 @@ super::impl_call! {
         }
 
         async fn call(self, request: process::Request) -> Result<process::Response> {
-            let call = tracing_futures::Instrument::instrument(
-                self
-                    .inner
-                    .process(request),
-                tracing::debug_span!("Worker::process")
-            );
+            let call = self.inner.process(request);
             Ok(call
                 .await
                 .context(super::GrpcSnafu{})?
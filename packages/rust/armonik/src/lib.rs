@@ -3,9 +3,16 @@
 pub mod api;
 #[cfg(feature = "_gen-client")]
 pub mod client;
+#[cfg(feature = "mock")]
+pub mod mock;
 mod objects;
 #[cfg(feature = "_gen-server")]
 pub mod server;
+#[cfg(feature = "opentelemetry")]
+mod telemetry;
+// Shadows the `worker` module of the glob below, which it re-exports: the objects stay where they were,
+// next to what it takes to write a worker with them.
+pub mod worker;
 
 /// The transport layer: configuration parsing, TLS and the connection itself.
 #[cfg(feature = "_gen-client")]
@@ -22,6 +29,8 @@ pub mod reexports {
     #[cfg(feature = "_gen-client")]
     pub use armonik_transport::reexports::{hyper, hyper_rustls, rustls};
     pub use prost;
+    #[cfg(feature = "dynamic")]
+    pub use prost_reflect;
     pub use prost_types;
     #[cfg(feature = "serde")]
     pub use serde;
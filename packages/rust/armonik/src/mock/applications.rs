@@ -0,0 +1,55 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::applications;
+use crate::server::{ApplicationsService, RequestContext};
+
+use super::query::{self, Filters};
+use super::ControlPlane;
+
+impl ApplicationsService for ControlPlane {
+    async fn list(
+        self: Arc<Self>,
+        request: applications::list::Request,
+        _context: RequestContext,
+    ) -> Result<applications::list::Response, tonic::Status> {
+        self.count("Applications", "ListApplications");
+
+        // Applications are not registered anywhere: they are those the tasks were submitted with.
+        let mut applications = self
+            .state()
+            .tasks
+            .values()
+            .map(|task| {
+                (
+                    task.options.application_name.clone(),
+                    task.options.application_version.clone(),
+                    task.options.application_namespace.clone(),
+                    task.options.application_service.clone(),
+                )
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|(name, version, namespace, service)| applications::Raw {
+                name,
+                version,
+                namespace,
+                service,
+            })
+            .filter(|application| request.filters.matches(application))
+            .collect::<Vec<_>>();
+        query::sort(
+            &mut applications,
+            &request.sort.fields,
+            &request.sort.direction,
+        );
+        let (applications, total) = query::page(applications, request.page, request.page_size)?;
+
+        Ok(applications::list::Response {
+            applications,
+            page: request.page,
+            page_size: request.page_size,
+            total,
+        })
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::Arc;
+
+use crate::auth;
+use crate::server::{AuthService, RequestContext};
+
+use super::ControlPlane;
+
+impl AuthService for ControlPlane {
+    async fn current_user(
+        self: Arc<Self>,
+        _request: auth::current_user::Request,
+        _context: RequestContext,
+    ) -> Result<auth::current_user::Response, tonic::Status> {
+        self.count("Authentication", "GetCurrentUser");
+
+        Ok(auth::current_user::Response {
+            user: self.state().user.clone(),
+        })
+    }
+}
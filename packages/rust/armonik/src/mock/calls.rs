@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// How many times each RPC of the mock was called.
+///
+/// RPCs are named the way the protos name them, service and method alike: `("Sessions",
+/// "CreateSession")`, `("Results", "UploadResultData")`. Calls are counted as they come in, whether they
+/// succeed or not.
+#[derive(Debug, Default)]
+pub struct Calls {
+    counts: Mutex<BTreeMap<(&'static str, &'static str), usize>>,
+}
+
+impl Calls {
+    /// Get how many times `service`/`rpc` was called
+    pub fn get(&self, service: &str, rpc: &str) -> usize {
+        self.lock()
+            .iter()
+            .find(|((s, r), _)| *s == service && *r == rpc)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    /// Get how many calls were made, to any RPC
+    pub fn total(&self) -> usize {
+        self.lock().values().sum()
+    }
+
+    /// Get every count, by service then by RPC, as the C# mock serves them on `/calls.json`
+    pub fn snapshot(&self) -> BTreeMap<String, BTreeMap<String, usize>> {
+        let mut snapshot = BTreeMap::<String, BTreeMap<String, usize>>::new();
+        for ((service, rpc), count) in self.lock().iter() {
+            snapshot
+                .entry(String::from(*service))
+                .or_default()
+                .insert(String::from(*rpc), *count);
+        }
+        snapshot
+    }
+
+    /// Set every count back to 0
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    pub(super) fn count(&self, service: &'static str, rpc: &'static str) {
+        *self.lock().entry((service, rpc)).or_default() += 1;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<(&'static str, &'static str), usize>> {
+        // A count is bumped in one go: a panic elsewhere cannot leave it half-written.
+        self.counts
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
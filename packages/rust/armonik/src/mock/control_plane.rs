@@ -0,0 +1,518 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use tokio::sync::broadcast;
+
+use crate::{auth, events, partitions, results, sessions, tasks};
+use crate::{ResultStatus, SessionStatus, TaskStatus};
+
+use super::Calls;
+
+/// How many events a subscriber can lag behind before it misses some
+const EVENTS_CAPACITY: usize = 4096;
+
+/// An update, with the task or the result it is about as it was right after it
+#[derive(Debug, Clone)]
+pub(super) struct Event {
+    pub(super) session_id: String,
+    pub(super) update: events::Update,
+    pub(super) task: Option<tasks::Raw>,
+    pub(super) result: Option<results::Raw>,
+}
+
+/// The state of an in-memory control plane, shared by every service of the mock.
+///
+/// Sessions, tasks and results go through the transitions the control plane takes them through: a
+/// session is created running, can be paused, resumed, closed, cancelled, purged and deleted; a task is
+/// submitted once its payload and data dependencies are completed; a result completes once its data is
+/// uploaded. What the compute plane does, a worker processing the tasks, is left to the test, through
+/// [`start_task`](Self::start_task), [`complete_task`](Self::complete_task) and
+/// [`fail_task`](Self::fail_task), or to a local cluster with the `local` feature.
+///
+/// Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct ControlPlane {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: Mutex<State>,
+    calls: Calls,
+}
+
+#[derive(Debug)]
+pub(super) struct State {
+    pub(super) sessions: BTreeMap<String, sessions::Raw>,
+    pub(super) tasks: BTreeMap<String, tasks::Raw>,
+    pub(super) results: BTreeMap<String, results::Raw>,
+    pub(super) data: HashMap<String, Vec<u8>>,
+    pub(super) partitions: BTreeMap<String, partitions::Raw>,
+    pub(super) user: auth::User,
+    pub(super) events: broadcast::Sender<Event>,
+    next_id: u64,
+}
+
+impl Default for ControlPlane {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ControlPlane {
+    /// Create an empty control plane, with a single `default` partition
+    pub fn new() -> Self {
+        let partition = partitions::Raw {
+            partition_id: String::from("default"),
+            pod_max: 100,
+            priority: 1,
+            ..Default::default()
+        };
+        let state = State {
+            sessions: Default::default(),
+            tasks: Default::default(),
+            results: Default::default(),
+            data: Default::default(),
+            partitions: BTreeMap::from([(partition.partition_id.clone(), partition)]),
+            user: auth::User {
+                username: String::from("anonymous"),
+                ..Default::default()
+            },
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            next_id: 0,
+        };
+
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(state),
+                calls: Calls::default(),
+            }),
+        }
+    }
+
+    /// Get how many times each RPC was called
+    pub fn calls(&self) -> &Calls {
+        &self.inner.calls
+    }
+
+    /// Add a partition, or replace the one with the same id
+    pub fn add_partition(&self, partition: partitions::Raw) {
+        self.state()
+            .partitions
+            .insert(partition.partition_id.clone(), partition);
+    }
+
+    /// Set the user the `Authentication` service answers with
+    pub fn set_user(&self, user: auth::User) {
+        self.state().user = user;
+    }
+
+    /// Get the data of a result, if it was uploaded
+    pub fn result_data(&self, result_id: &str) -> Option<Vec<u8>> {
+        self.state().data.get(result_id).cloned()
+    }
+
+    /// Start processing a submitted task, as a worker would.
+    pub fn start_task(&self, task_id: &str) -> Result<tasks::Raw, tonic::Status> {
+        let mut state = self.state();
+        state.start_task(task_id)?;
+        state.task(task_id).cloned()
+    }
+
+    /// Complete a task, as a worker would, with the data of its expected outputs.
+    ///
+    /// The task is started first if it was not already. Each output must be one the task is expected to
+    /// produce, and not completed yet; those left out stay as they are, to be uploaded separately. Nothing
+    /// changes unless the task can complete with all of them.
+    pub fn complete_task(
+        &self,
+        task_id: &str,
+        outputs: impl IntoIterator<Item = (impl Into<String>, impl Into<Vec<u8>>)>,
+    ) -> Result<tasks::Raw, tonic::Status> {
+        let mut state = self.state();
+        let outputs = outputs
+            .into_iter()
+            .map(|(result_id, data)| (result_id.into(), data.into()))
+            .collect::<Vec<(String, Vec<u8>)>>();
+        let expected = &state.task(task_id)?.expected_output_ids;
+        let mut given = HashSet::new();
+        for (result_id, _) in &outputs {
+            if !expected.contains(result_id) {
+                return Err(tonic::Status::invalid_argument(format!(
+                    "Result {result_id} is not an expected output of task {task_id}"
+                )));
+            }
+            if !given.insert(result_id) {
+                return Err(tonic::Status::invalid_argument(format!(
+                    "Result {result_id} is given more than once"
+                )));
+            }
+            let result = state.result(result_id)?;
+            if result.status != ResultStatus::Created {
+                return Err(tonic::Status::failed_precondition(format!(
+                    "Result {result_id} is {:?}",
+                    result.status
+                )));
+            }
+        }
+
+        if state.task(task_id)?.status != TaskStatus::Processing {
+            state.start_task(task_id)?;
+        }
+        for (result_id, data) in outputs {
+            state.complete_result(&result_id, data)?;
+        }
+        state.end_task(task_id, TaskStatus::Completed, tasks::Output::Success);
+        state.task(task_id).cloned()
+    }
+
+    /// Fail a task, as a worker would: its expected outputs that are not completed are aborted.
+    pub fn fail_task(
+        &self,
+        task_id: &str,
+        message: impl Into<String>,
+    ) -> Result<tasks::Raw, tonic::Status> {
+        let mut state = self.state();
+        if state.task(task_id)?.status != TaskStatus::Processing {
+            state.start_task(task_id)?;
+        }
+
+        state.end_task(
+            task_id,
+            TaskStatus::Error,
+            tasks::Output::Error(message.into()),
+        );
+        state.abort_outputs(task_id);
+        state.task(task_id).cloned()
+    }
+
+    pub(super) fn count(&self, service: &'static str, rpc: &'static str) {
+        self.inner.calls.count(service, rpc);
+    }
+
+    pub(super) fn state(&self) -> MutexGuard<'_, State> {
+        // Every change is made under the lock and checked before it starts: a panic cannot leave the state
+        // half-changed.
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Get the current time
+pub(super) fn now() -> prost_types::Timestamp {
+    std::time::SystemTime::now().into()
+}
+
+/// Get the time elapsed between `from` and `to`
+pub(super) fn elapsed(
+    from: &prost_types::Timestamp,
+    to: &prost_types::Timestamp,
+) -> prost_types::Duration {
+    let mut duration = prost_types::Duration {
+        seconds: to.seconds - from.seconds,
+        nanos: to.nanos - from.nanos,
+    };
+    duration.normalize();
+    duration
+}
+
+/// Whether a task reached a status it cannot leave
+pub(super) fn is_final(status: &TaskStatus) -> bool {
+    matches!(
+        status,
+        TaskStatus::Completed
+            | TaskStatus::Error
+            | TaskStatus::Timeout
+            | TaskStatus::Cancelled
+            | TaskStatus::Retried
+    )
+}
+
+impl State {
+    /// Generate a new id, shaped like the ones of the control plane, and ordered by creation
+    pub(super) fn new_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("00000000-0000-4000-8000-{:012x}", self.next_id)
+    }
+
+    pub(super) fn session(&self, session_id: &str) -> Result<&sessions::Raw, tonic::Status> {
+        self.sessions
+            .get(session_id)
+            .ok_or_else(|| tonic::Status::not_found(format!("Session {session_id} not found")))
+    }
+
+    pub(super) fn session_mut(
+        &mut self,
+        session_id: &str,
+    ) -> Result<&mut sessions::Raw, tonic::Status> {
+        self.sessions
+            .get_mut(session_id)
+            .ok_or_else(|| tonic::Status::not_found(format!("Session {session_id} not found")))
+    }
+
+    /// Get a session tasks and results can still be added to
+    pub(super) fn open_session(&self, session_id: &str) -> Result<&sessions::Raw, tonic::Status> {
+        let session = self.session(session_id)?;
+        match session.status {
+            SessionStatus::Running | SessionStatus::Paused => Ok(session),
+            _ => Err(tonic::Status::failed_precondition(format!(
+                "Session {session_id} is {:?}",
+                session.status
+            ))),
+        }
+    }
+
+    pub(super) fn task(&self, task_id: &str) -> Result<&tasks::Raw, tonic::Status> {
+        self.tasks
+            .get(task_id)
+            .ok_or_else(|| tonic::Status::not_found(format!("Task {task_id} not found")))
+    }
+
+    pub(super) fn result(&self, result_id: &str) -> Result<&results::Raw, tonic::Status> {
+        self.results
+            .get(result_id)
+            .ok_or_else(|| tonic::Status::not_found(format!("Result {result_id} not found")))
+    }
+
+    /// Send an update to the subscribers of its session
+    pub(super) fn emit(&self, session_id: &str, update: events::Update) {
+        let (task, result) = match &update {
+            events::Update::TaskStatusUpdate(events::TaskStatusUpdate { task_id, .. })
+            | events::Update::NewTask(events::NewTask { task_id, .. }) => {
+                (self.tasks.get(task_id).cloned(), None)
+            }
+            events::Update::ResultStatusUpdate(events::ResultStatusUpdate {
+                result_id, ..
+            })
+            | events::Update::ResultOwnerUpdate(events::ResultOwnerUpdate { result_id, .. })
+            | events::Update::NewResult(events::NewResult { result_id, .. }) => {
+                (None, self.results.get(result_id).cloned())
+            }
+            events::Update::Invalid => (None, None),
+        };
+
+        // Nobody listening is no failure.
+        let _ = self.events.send(Event {
+            session_id: session_id.to_owned(),
+            update,
+            task,
+            result,
+        });
+    }
+
+    /// Add a result to a session, with no data yet
+    pub(super) fn add_result(
+        &mut self,
+        session_id: &str,
+        name: String,
+        manual_deletion: bool,
+    ) -> results::Raw {
+        let result = results::Raw {
+            session_id: session_id.to_owned(),
+            name,
+            status: ResultStatus::Created,
+            created_at: Some(now()),
+            result_id: self.new_id(),
+            manual_deletion,
+            ..Default::default()
+        };
+        self.results
+            .insert(result.result_id.clone(), result.clone());
+        self.emit(
+            session_id,
+            events::Update::NewResult(events::NewResult {
+                result_id: result.result_id.clone(),
+                owner_id: String::new(),
+                status: result.status.clone(),
+            }),
+        );
+        result
+    }
+
+    /// Set the data of a result that has none yet, completing it
+    pub(super) fn complete_result(
+        &mut self,
+        result_id: &str,
+        data: Vec<u8>,
+    ) -> Result<results::Raw, tonic::Status> {
+        let result = self.result(result_id)?;
+        if result.status != ResultStatus::Created {
+            return Err(tonic::Status::failed_precondition(format!(
+                "Result {result_id} is {:?}",
+                result.status
+            )));
+        }
+
+        let size = i64::try_from(data.len()).unwrap_or(i64::MAX);
+        self.data.insert(result_id.to_owned(), data);
+        if let Some(result) = self.results.get_mut(result_id) {
+            result.size = size;
+            result.completed_at = Some(now());
+        }
+        self.set_result_status(result_id, ResultStatus::Completed);
+        self.result(result_id).cloned()
+    }
+
+    /// Move a result to `status`, submitting the tasks waiting for it once it is completed
+    pub(super) fn set_result_status(&mut self, result_id: &str, status: ResultStatus) {
+        let Some(result) = self.results.get_mut(result_id) else {
+            return;
+        };
+        if result.status == status {
+            return;
+        }
+        result.status = status.clone();
+        let session_id = result.session_id.clone();
+
+        self.emit(
+            &session_id,
+            events::Update::ResultStatusUpdate(events::ResultStatusUpdate {
+                result_id: result_id.to_owned(),
+                status: status.clone(),
+            }),
+        );
+        if status == ResultStatus::Completed {
+            self.submit_ready_tasks();
+        }
+    }
+
+    /// Move a task to `status`, dating the move
+    pub(super) fn set_task_status(&mut self, task_id: &str, status: TaskStatus) {
+        let Some(task) = self.tasks.get_mut(task_id) else {
+            return;
+        };
+        if task.status == status {
+            return;
+        }
+
+        let now = now();
+        match status {
+            TaskStatus::Submitted => task.submitted_at = Some(now),
+            TaskStatus::Processing => {
+                task.received_at = Some(now);
+                task.acquired_at = Some(now);
+                task.fetched_at = Some(now);
+                task.started_at = Some(now);
+            }
+            ref status if is_final(status) => {
+                if task.started_at.is_some() {
+                    task.processed_at = Some(now);
+                }
+                task.ended_at = Some(now);
+                task.creation_to_end_duration =
+                    task.created_at.as_ref().map(|from| elapsed(from, &now));
+                task.processing_to_end_duration =
+                    task.started_at.as_ref().map(|from| elapsed(from, &now));
+                task.received_to_end_duration =
+                    task.received_at.as_ref().map(|from| elapsed(from, &now));
+            }
+            _ => {}
+        }
+        task.status = status.clone();
+        let session_id = task.session_id.clone();
+
+        self.emit(
+            &session_id,
+            events::Update::TaskStatusUpdate(events::TaskStatusUpdate {
+                task_id: task_id.to_owned(),
+                status,
+            }),
+        );
+    }
+
+    /// Submit the pending tasks whose payload and data dependencies are all completed
+    pub(super) fn submit_ready_tasks(&mut self) {
+        let ready = self
+            .tasks
+            .values()
+            .filter(|task| task.status == TaskStatus::Pending)
+            .filter(|task| {
+                std::iter::once(&task.payload_id)
+                    .chain(&task.data_dependencies)
+                    .all(|result_id| {
+                        self.results
+                            .get(result_id)
+                            .is_some_and(|result| result.status == ResultStatus::Completed)
+                    })
+            })
+            .map(|task| (task.task_id.clone(), task.session_id.clone()))
+            .collect::<Vec<_>>();
+
+        for (task_id, session_id) in ready {
+            let paused = self
+                .sessions
+                .get(&session_id)
+                .is_some_and(|session| session.status == SessionStatus::Paused);
+            self.set_task_status(&task_id, TaskStatus::Submitted);
+            if paused {
+                self.set_task_status(&task_id, TaskStatus::Paused);
+            }
+        }
+    }
+
+    pub(super) fn start_task(&mut self, task_id: &str) -> Result<(), tonic::Status> {
+        let task = self.task(task_id)?;
+        if !matches!(task.status, TaskStatus::Submitted | TaskStatus::Dispatched) {
+            return Err(tonic::Status::failed_precondition(format!(
+                "Task {task_id} is {:?}",
+                task.status
+            )));
+        }
+
+        self.set_task_status(task_id, TaskStatus::Processing);
+        Ok(())
+    }
+
+    /// End a task, submitting the subtasks it created if it completed and cancelling them otherwise
+    pub(super) fn end_task(&mut self, task_id: &str, status: TaskStatus, output: tasks::Output) {
+        if let Some(task) = self.tasks.get_mut(task_id) {
+            task.output = output;
+        }
+        self.set_task_status(task_id, status.clone());
+
+        let subtasks = self
+            .tasks
+            .values()
+            .filter(|task| task.created_by == task_id && task.status == TaskStatus::Creating)
+            .map(|task| task.task_id.clone())
+            .collect::<Vec<_>>();
+        for subtask_id in subtasks {
+            if status == TaskStatus::Completed {
+                self.set_task_status(&subtask_id, TaskStatus::Pending);
+            } else {
+                self.cancel_task(&subtask_id);
+            }
+        }
+        self.submit_ready_tasks();
+    }
+
+    /// Cancel a task that has not ended yet, aborting its expected outputs
+    pub(super) fn cancel_task(&mut self, task_id: &str) {
+        if self
+            .tasks
+            .get(task_id)
+            .is_some_and(|task| !is_final(&task.status))
+        {
+            self.set_task_status(task_id, TaskStatus::Cancelled);
+            self.abort_outputs(task_id);
+        }
+    }
+
+    /// Abort the expected outputs of a task that are not completed, nor delegated to a subtask
+    pub(super) fn abort_outputs(&mut self, task_id: &str) {
+        let outputs = self
+            .tasks
+            .get(task_id)
+            .map(|task| task.expected_output_ids.clone())
+            .unwrap_or_default();
+        for result_id in outputs {
+            if self.results.get(&result_id).is_some_and(|result| {
+                result.status == ResultStatus::Created && result.owner_task_id == task_id
+            }) {
+                self.set_result_status(&result_id, ResultStatus::Aborted);
+            }
+        }
+    }
+}
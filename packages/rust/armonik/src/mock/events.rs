@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use crate::events;
+use crate::server::{EventsService, RequestContext};
+
+use super::control_plane::Event;
+use super::query::Filters;
+use super::ControlPlane;
+
+/// Whether `event` is one `request` subscribed to
+fn subscribed(request: &events::subscribe::Request, event: &Event) -> bool {
+    let kind = match &event.update {
+        events::Update::Invalid => return false,
+        events::Update::TaskStatusUpdate(_) => events::EventsEnum::TaskStatusUpdate,
+        events::Update::ResultStatusUpdate(_) => events::EventsEnum::ResultStatusUpdate,
+        events::Update::ResultOwnerUpdate(_) => events::EventsEnum::ResultOwnerUpdate,
+        events::Update::NewTask(_) => events::EventsEnum::NewTask,
+        events::Update::NewResult(_) => events::EventsEnum::NewResult,
+    };
+
+    event.session_id == request.session_id
+        && (request.returned_events.is_empty() || request.returned_events.contains(&kind))
+        && event
+            .task
+            .as_ref()
+            .is_none_or(|task| request.task_filters.matches(task))
+        && event
+            .result
+            .as_ref()
+            .is_none_or(|result| request.result_filters.matches(result))
+}
+
+impl EventsService for ControlPlane {
+    async fn subscribe(
+        self: Arc<Self>,
+        request: events::subscribe::Request,
+        _context: RequestContext,
+    ) -> Result<
+        impl tonic::codegen::tokio_stream::Stream<
+                Item = Result<events::subscribe::Response, tonic::Status>,
+            > + Send,
+        tonic::Status,
+    > {
+        self.count("Events", "GetEvents");
+
+        let receiver = {
+            let state = self.state();
+            state.session(&request.session_id)?;
+            state.events.subscribe()
+        };
+
+        // Only what happens from now on is sent, as the control plane does.
+        Ok(futures::stream::unfold(
+            (receiver, request),
+            |(mut receiver, request)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) if subscribed(&request, &event) => {
+                            let response = events::subscribe::Response {
+                                session_id: event.session_id,
+                                update: event.update,
+                            };
+                            return Some((Ok(response), (receiver, request)));
+                        }
+                        Ok(_) => {}
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            let status = tonic::Status::data_loss(format!(
+                                "The subscription lagged behind, {missed} events were missed"
+                            ));
+                            return Some((Err(status), (receiver, request)));
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        ))
+    }
+}
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use crate::health_checks;
+use crate::server::{HealthChecksService, RequestContext};
+
+use super::ControlPlane;
+
+impl HealthChecksService for ControlPlane {
+    async fn check(
+        self: Arc<Self>,
+        _request: health_checks::check::Request,
+        _context: RequestContext,
+    ) -> Result<health_checks::check::Response, tonic::Status> {
+        self.count("HealthChecksService", "CheckHealth");
+
+        // The storages of the control plane, which the mock keeps in memory.
+        Ok(health_checks::check::Response {
+            services: ["database", "object", "queue"]
+                .into_iter()
+                .map(|name| health_checks::ServiceHealth {
+                    name: String::from(name),
+                    message: String::from("In memory"),
+                    health: health_checks::Status::Healthy,
+                })
+                .collect(),
+        })
+    }
+}
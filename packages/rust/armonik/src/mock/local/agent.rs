@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::agent::{
+    create_results, create_results_metadata, create_tasks, get_common_data, get_direct_data,
+    get_resource_data, notify_result_data, submit_tasks, ResultMetaData,
+};
+use crate::server::{AgentService, RequestContext};
+use crate::{results, tasks, ResultStatus};
+
+use super::super::control_plane::State;
+use super::super::ControlPlane;
+
+/// The task a communication token was handed out for
+#[derive(Debug, Clone)]
+pub(super) struct Assignment {
+    pub(super) session_id: String,
+    pub(super) task_id: String,
+    pub(super) data_folder: PathBuf,
+}
+
+/// The agent of a local cluster: what the worker calls back while it processes a task.
+///
+/// Every call carries the communication token of the task being processed, which ties it to the task
+/// and to its data folder. Clones share the same assignments.
+#[derive(Debug, Clone)]
+pub(super) struct LocalAgent {
+    pub(super) control_plane: ControlPlane,
+    assignments: Arc<Mutex<HashMap<String, Assignment>>>,
+}
+
+impl LocalAgent {
+    pub(super) fn new(control_plane: ControlPlane) -> Self {
+        Self {
+            control_plane,
+            assignments: Default::default(),
+        }
+    }
+
+    /// Accept calls with `token`, on behalf of the task of `assignment`
+    pub(super) fn assign(&self, token: String, assignment: Assignment) {
+        self.assignments().insert(token, assignment);
+    }
+
+    /// Stop accepting calls with `token`, once its task is processed
+    pub(super) fn release(&self, token: &str) {
+        self.assignments().remove(token);
+    }
+
+    fn assignments(&self) -> MutexGuard<'_, HashMap<String, Assignment>> {
+        self.assignments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get the assignment of `token`, checking the call is about its session
+    fn assignment(&self, token: &str, session_id: &str) -> Result<Assignment, tonic::Status> {
+        let Some(assignment) = self.assignments().get(token).cloned() else {
+            return Err(tonic::Status::permission_denied(format!(
+                "Communication token {token} is not the one of a task being processed"
+            )));
+        };
+        if !session_id.is_empty() && assignment.session_id != session_id {
+            return Err(tonic::Status::invalid_argument(format!(
+                "Task {} does not belong to session {session_id}",
+                assignment.task_id
+            )));
+        }
+        Ok(assignment)
+    }
+}
+
+/// Describe a result the way the agent does
+fn metadata(result: results::Raw) -> ResultMetaData {
+    ResultMetaData {
+        session_id: result.session_id,
+        result_id: result.result_id,
+        name: result.name,
+        status: result.status,
+        created_at: result.created_at,
+    }
+}
+
+impl State {
+    /// Add a result to the session of the task of `assignment`, created by that task
+    fn add_task_result(&mut self, assignment: &Assignment, name: String) -> results::Raw {
+        let mut result = self.add_result(&assignment.session_id, name, false);
+        if let Some(stored) = self.results.get_mut(&result.result_id) {
+            stored.created_by = assignment.task_id.clone();
+            result.created_by = assignment.task_id.clone();
+        }
+        result
+    }
+}
+
+impl AgentService for LocalAgent {
+    async fn create_results_metadata(
+        self: Arc<Self>,
+        request: create_results_metadata::Request,
+        _context: RequestContext,
+    ) -> Result<create_results_metadata::Response, tonic::Status> {
+        self.control_plane.count("Agent", "CreateResultsMetaData");
+        let assignment = self.assignment(&request.communication_token, &request.session_id)?;
+        let mut state = self.control_plane.state();
+        state.open_session(&assignment.session_id)?;
+
+        Ok(create_results_metadata::Response {
+            communication_token: request.communication_token,
+            results: request
+                .results
+                .into_iter()
+                .map(|item| metadata(state.add_task_result(&assignment, item.name)))
+                .collect(),
+        })
+    }
+
+    async fn create_results(
+        self: Arc<Self>,
+        request: create_results::Request,
+        _context: RequestContext,
+    ) -> Result<create_results::Response, tonic::Status> {
+        self.control_plane.count("Agent", "CreateResults");
+        let assignment = self.assignment(&request.communication_token, &request.session_id)?;
+        let mut state = self.control_plane.state();
+        state.open_session(&assignment.session_id)?;
+
+        Ok(create_results::Response {
+            communication_token: request.communication_token,
+            results: request
+                .results
+                .into_iter()
+                .map(|item| {
+                    let result = state.add_task_result(&assignment, item.name);
+                    state.complete_result(&result.result_id, item.data)
+                })
+                .map(|result| result.map(metadata))
+                .collect::<Result<_, tonic::Status>>()?,
+        })
+    }
+
+    async fn notify_result_data(
+        self: Arc<Self>,
+        request: notify_result_data::Request,
+        _context: RequestContext,
+    ) -> Result<notify_result_data::Response, tonic::Status> {
+        self.control_plane.count("Agent", "NotifyResultData");
+        let assignment = self.assignment(&request.communication_token, &request.session_id)?;
+
+        // The files are read before taking the lock: the state is not held across an await.
+        let mut data = Vec::with_capacity(request.result_ids.len());
+        for result_id in &request.result_ids {
+            let path = assignment.data_folder.join(result_id);
+            let content = tokio::fs::read(&path).await.map_err(|err| {
+                tonic::Status::not_found(format!(
+                    "Could not read the data of result {result_id} from {}: {err}",
+                    path.display()
+                ))
+            })?;
+            data.push((result_id, content));
+        }
+
+        let mut state = self.control_plane.state();
+        for (result_id, _) in &data {
+            if state.result(result_id)?.session_id != assignment.session_id {
+                return Err(tonic::Status::not_found(format!(
+                    "Result {result_id} not found in session {}",
+                    assignment.session_id
+                )));
+            }
+        }
+        for (result_id, content) in data {
+            state.complete_result(result_id, content)?;
+        }
+
+        Ok(notify_result_data::Response {
+            result_ids: request.result_ids,
+        })
+    }
+
+    async fn submit_tasks(
+        self: Arc<Self>,
+        request: submit_tasks::Request,
+        _context: RequestContext,
+    ) -> Result<submit_tasks::Response, tonic::Status> {
+        self.control_plane.count("Agent", "SubmitTasks");
+        let assignment = self.assignment(&request.communication_token, &request.session_id)?;
+
+        let items = request
+            .items
+            .into_iter()
+            .map(|item| tasks::submit::RequestItem {
+                expected_output_keys: item.expected_output_keys,
+                data_dependencies: item.data_dependencies,
+                payload_id: item.payload_id,
+                task_options: item.task_options,
+            })
+            .collect();
+        let submitted = self.control_plane.state().submit_tasks(
+            &assignment.session_id,
+            request.task_options,
+            items,
+            Some(&assignment.task_id),
+        )?;
+
+        Ok(submit_tasks::Response {
+            communication_token: request.communication_token,
+            items: submitted
+                .into_iter()
+                .map(|item| submit_tasks::ResponseItem {
+                    task_id: item.task_id,
+                    expected_output_ids: item.expected_output_ids,
+                    data_dependencies: item.data_dependencies,
+                    payload_id: item.payload_id,
+                })
+                .collect(),
+        })
+    }
+
+    async fn get_resource_data(
+        self: Arc<Self>,
+        request: get_resource_data::Request,
+        _context: RequestContext,
+    ) -> Result<get_resource_data::Response, tonic::Status> {
+        self.control_plane.count("Agent", "GetResourceData");
+        let assignment = self.assignment(&request.communication_token, "")?;
+
+        let data = {
+            let state = self.control_plane.state();
+            let result = state.result(&request.result_id)?;
+            if result.session_id != assignment.session_id
+                || result.status != ResultStatus::Completed
+            {
+                return Err(tonic::Status::not_found(format!(
+                    "Result {} is not a completed result of session {}",
+                    request.result_id, assignment.session_id
+                )));
+            }
+            state
+                .data
+                .get(&request.result_id)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let path = assignment.data_folder.join(&request.result_id);
+        tokio::fs::write(&path, data).await.map_err(|err| {
+            tonic::Status::internal(format!(
+                "Could not write the data of result {} to {}: {err}",
+                request.result_id,
+                path.display()
+            ))
+        })?;
+
+        Ok(get_resource_data::Response {
+            result_id: request.result_id,
+        })
+    }
+
+    async fn get_common_data(
+        self: Arc<Self>,
+        _request: get_common_data::Request,
+        _context: RequestContext,
+    ) -> Result<get_common_data::Response, tonic::Status> {
+        self.control_plane.count("Agent", "GetCommonData");
+        Err(tonic::Status::unimplemented(
+            "Common data are not supported by the ArmoniK agent",
+        ))
+    }
+
+    async fn get_direct_data(
+        self: Arc<Self>,
+        _request: get_direct_data::Request,
+        _context: RequestContext,
+    ) -> Result<get_direct_data::Response, tonic::Status> {
+        self.control_plane.count("Agent", "GetDirectData");
+        Err(tonic::Status::unimplemented(
+            "Direct data are not supported by the ArmoniK agent",
+        ))
+    }
+
+    async fn create_tasks(
+        self: Arc<Self>,
+        _request: impl tonic::codegen::tokio_stream::Stream<
+                Item = Result<create_tasks::Request, tonic::Status>,
+            > + Send
+            + 'static,
+        _context: RequestContext,
+    ) -> Result<create_tasks::Response, tonic::Status> {
+        self.control_plane.count("Agent", "CreateTask");
+        Err(tonic::Status::unimplemented(
+            "The local cluster only supports SubmitTasks to create subtasks",
+        ))
+    }
+}
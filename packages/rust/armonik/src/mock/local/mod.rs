@@ -0,0 +1,305 @@
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures::FutureExt;
+use snafu::ResultExt;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::client::{Agent, Channel, ClientConfig, ClientConfigArgs, ConnectionError};
+use crate::server::{AgentServiceExt, RequestContext, WorkerService};
+use crate::worker::process;
+use crate::{tasks, Client, Configuration, Output, TaskStatus};
+
+use super::control_plane::State;
+use super::{Calls, ControlPlane, MockError, MockServer, DATA_CHUNK_MAX_SIZE};
+
+mod agent;
+
+use agent::{Assignment, LocalAgent};
+
+/// Tell apart the data folders of every task processed by this process
+static DATA_FOLDERS: AtomicU64 = AtomicU64::new(0);
+
+/// A [`MockServer`] whose tasks are processed by a worker running in the same process.
+///
+/// Once submitted, a task is dispatched to the worker, then processed: the worker gets a
+/// [`process::Request`] with a real data folder holding the payload and the data dependencies of the
+/// task, and calls back an agent served next to the control plane. Results it creates and sends, and
+/// subtasks it submits, are honoured the way ArmoniK does: subtasks are only submitted once their parent
+/// completed, and cancelled if it failed.
+///
+/// Tasks go from `Submitted` to `Dispatched`, `Processing`, then `Completed` or `Error`. When a task
+/// fails, the expected outputs it did not produce nor delegate to a subtask are aborted.
+///
+/// ```no_run
+/// # use armonik::worker::{Processor, TaskHandler, WorkerWrapper};
+/// # struct Echo;
+/// # impl Processor for Echo {
+/// #     async fn process(&self, _task_handler: &TaskHandler) -> armonik::Output {
+/// #         armonik::Output::Ok
+/// #     }
+/// # }
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cluster = armonik::mock::LocalCluster::start(|agent| WorkerWrapper::new(Echo, agent)).await?;
+/// let client = cluster.client().await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Everything stops once this is dropped, including the tasks being processed.
+#[derive(Debug)]
+pub struct LocalCluster {
+    server: MockServer,
+    agent_address: SocketAddr,
+    agent_served: JoinHandle<()>,
+    dispatching: JoinHandle<()>,
+}
+
+impl LocalCluster {
+    /// Start an empty cluster, processing its tasks with the worker `worker` builds.
+    ///
+    /// `worker` is given a client to the agent of the cluster, for the worker to call back.
+    pub async fn start<W>(worker: impl FnOnce(Agent<Channel>) -> W) -> Result<Self, MockError>
+    where
+        W: WorkerService + Send + Sync + 'static,
+    {
+        Self::serve(ControlPlane::new(), worker).await
+    }
+
+    /// Serve `control_plane`, processing its tasks with the worker `worker` builds
+    pub async fn serve<W>(
+        control_plane: ControlPlane,
+        worker: impl FnOnce(Agent<Channel>) -> W,
+    ) -> Result<Self, MockError>
+    where
+        W: WorkerService + Send + Sync + 'static,
+    {
+        let server = MockServer::serve(control_plane.clone()).await?;
+
+        let agent = LocalAgent::new(control_plane);
+        let router = tonic::transport::Server::builder().add_service(agent.clone().agent_server());
+        let (agent_address, agent_served) = super::listen(router).await?;
+        tracing::debug!("Local agent listening on {agent_address}");
+
+        let mut args = ClientConfigArgs::default();
+        args.endpoint = format!("http://{agent_address}");
+        let config = ClientConfig::from_config_args(args)
+            .context(armonik_transport::ConfigSnafu {})
+            .context(super::ConnectSnafu {})?;
+        let channel = armonik_transport::connect(config)
+            .await
+            .context(super::ConnectSnafu {})?;
+
+        let worker = Arc::new(worker(Agent::with_channel(channel)));
+        let dispatching = tokio::spawn(dispatch(agent, worker));
+
+        Ok(Self {
+            server,
+            agent_address,
+            agent_served,
+            dispatching,
+        })
+    }
+
+    /// Get the server of the control plane
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Get the endpoint to reach the agent at
+    pub fn agent_endpoint(&self) -> String {
+        format!("http://{}", self.agent_address)
+    }
+
+    /// Connect a client to the control plane
+    pub async fn client(&self) -> Result<Client, ConnectionError> {
+        self.server.client().await
+    }
+
+    /// Get the control plane being served
+    pub fn control_plane(&self) -> &ControlPlane {
+        self.server.control_plane()
+    }
+
+    /// Get how many times each RPC was called, those of the agent included
+    pub fn calls(&self) -> &Calls {
+        self.server.calls()
+    }
+}
+
+impl Drop for LocalCluster {
+    fn drop(&mut self) {
+        self.dispatching.abort();
+        self.agent_served.abort();
+    }
+}
+
+impl State {
+    /// Dispatch the submitted tasks, returning their ids
+    fn dispatch_submitted(&mut self) -> Vec<String> {
+        let submitted = self
+            .tasks
+            .values()
+            .filter(|task| task.status == TaskStatus::Submitted)
+            .map(|task| task.task_id.clone())
+            .collect::<Vec<_>>();
+        for task_id in &submitted {
+            self.set_task_status(task_id, TaskStatus::Dispatched);
+        }
+        submitted
+    }
+}
+
+/// Process the tasks as they are submitted, concurrently
+async fn dispatch<W>(agent: LocalAgent, worker: Arc<W>)
+where
+    W: WorkerService + Send + Sync + 'static,
+{
+    // Subscribed before looking for tasks: none can be submitted in between unnoticed.
+    let mut events = agent.control_plane.state().events.subscribe();
+    let mut processing = JoinSet::new();
+
+    loop {
+        let submitted = agent.control_plane.state().dispatch_submitted();
+        for task_id in submitted {
+            processing.spawn(process_task(agent.clone(), worker.clone(), task_id));
+        }
+
+        tokio::select! {
+            event = events.recv() => {
+                // Lagging behind is no failure: the tasks are looked for in the state, not in the events.
+                if let Err(RecvError::Closed) = event {
+                    break;
+                }
+            }
+            Some(processed) = processing.join_next() => {
+                if let Err(err) = processed {
+                    tracing::error!("Could not process a task locally: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Process a dispatched task with `worker`, in a data folder of its own
+async fn process_task<W>(agent: LocalAgent, worker: Arc<W>, task_id: String)
+where
+    W: WorkerService + Send + Sync + 'static,
+{
+    let data_folder = std::env::temp_dir().join(format!(
+        "armonik-local-{}-{}",
+        std::process::id(),
+        DATA_FOLDERS.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let (request, inputs) = {
+        let mut state = agent.control_plane.state();
+        let Ok(task) = state.task(&task_id).cloned() else {
+            return;
+        };
+        let inputs = std::iter::once(&task.payload_id)
+            .chain(&task.data_dependencies)
+            .map(|result_id| {
+                let data = state.data.get(result_id).cloned().unwrap_or_default();
+                (result_id.clone(), data)
+            })
+            .collect::<Vec<_>>();
+        let request = process::Request {
+            communication_token: state.new_id(),
+            session_id: task.session_id,
+            task_id: task.task_id,
+            task_options: task.options,
+            expected_output_keys: task.expected_output_ids,
+            payload_id: task.payload_id,
+            data_dependencies: task.data_dependencies,
+            data_folder: data_folder.to_string_lossy().into_owned(),
+            configuration: Configuration {
+                data_chunk_max_size: DATA_CHUNK_MAX_SIZE,
+            },
+        };
+        (request, inputs)
+    };
+
+    let token = request.communication_token.clone();
+    agent.assign(
+        token.clone(),
+        Assignment {
+            session_id: request.session_id.clone(),
+            task_id: task_id.clone(),
+            data_folder: data_folder.clone(),
+        },
+    );
+
+    let output = match write_inputs(&data_folder, inputs).await {
+        Ok(()) => {
+            let started = agent.control_plane.state().start_task(&task_id);
+            match started {
+                Ok(()) => Some(run(worker, request).await),
+                // Cancelled in the meantime
+                Err(_) => None,
+            }
+        }
+        Err(err) => Some(tasks::Output::Error(format!(
+            "Could not prepare the data folder {}: {err}",
+            data_folder.display()
+        ))),
+    };
+
+    if let Some(output) = output {
+        let mut state = agent.control_plane.state();
+        // The task may have been cancelled while it was processed: it then stays cancelled.
+        if state.task(&task_id).is_ok_and(|task| {
+            matches!(task.status, TaskStatus::Dispatched | TaskStatus::Processing)
+        }) {
+            match output {
+                tasks::Output::Success => {
+                    state.end_task(&task_id, TaskStatus::Completed, output);
+                }
+                tasks::Output::Error(_) => {
+                    state.end_task(&task_id, TaskStatus::Error, output);
+                    state.abort_outputs(&task_id);
+                }
+            }
+        }
+    }
+
+    agent.release(&token);
+    if let Err(err) = tokio::fs::remove_dir_all(&data_folder).await {
+        tracing::debug!(
+            "Could not remove the data folder {}: {err}",
+            data_folder.display()
+        );
+    }
+}
+
+/// Create the data folder of a task, with the payload and the data dependencies in it
+async fn write_inputs(data_folder: &Path, inputs: Vec<(String, Vec<u8>)>) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(data_folder).await?;
+    for (result_id, data) in inputs {
+        tokio::fs::write(data_folder.join(result_id), data).await?;
+    }
+    Ok(())
+}
+
+/// Have `worker` process a task, a panic failing the task rather than the cluster
+async fn run<W>(worker: Arc<W>, request: process::Request) -> tasks::Output
+where
+    W: WorkerService + Send + Sync + 'static,
+{
+    let processed = AssertUnwindSafe(worker.process(request, RequestContext::default()))
+        .catch_unwind()
+        .await;
+
+    match processed {
+        Ok(Ok(process::Response { output: Output::Ok })) => tasks::Output::Success,
+        Ok(Ok(process::Response {
+            output: Output::Error { details },
+        })) => tasks::Output::Error(details),
+        Ok(Err(status)) => tasks::Output::Error(status.message().to_owned()),
+        Err(_) => tasks::Output::Error(String::from("The worker panicked")),
+    }
+}
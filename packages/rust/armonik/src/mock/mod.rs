@@ -0,0 +1,178 @@
+//! An ArmoniK control plane in memory, to test clients against without a deployment.
+//!
+//! [`MockServer`] serves every service of the control plane on an ephemeral local port: sessions,
+//! tasks, results, events, partitions, health checks, versions, authentication and applications. The
+//! state behind them is a [`ControlPlane`], which keeps sessions, tasks and results coherent with one
+//! another, counts the calls made to each RPC, and lets the test play the part of the workers.
+//!
+//! With the `local` feature, a `LocalCluster` plays that part instead: it dispatches the submitted tasks
+//! to a worker running in the same process, the way an ArmoniK deployment would, with no Kubernetes.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = armonik::mock::MockServer::start().await?;
+//! let mut sessions = server.client().await?.into_sessions();
+//!
+//! sessions.create(["default"], Default::default()).await?;
+//!
+//! assert_eq!(server.calls().get("Sessions", "CreateSession"), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::net::SocketAddr;
+
+use snafu::{ResultExt, Snafu};
+
+use crate::client::{ClientConfig, ClientConfigArgs, ConfigError, ConnectionError};
+use crate::server::{
+    ApplicationsServiceExt, AuthServiceExt, EventsServiceExt, HealthChecksServiceExt,
+    PartitionsServiceExt, ResultsServiceExt, SessionsServiceExt, TasksServiceExt,
+    VersionsServiceExt,
+};
+use crate::Client;
+
+mod applications;
+mod auth;
+mod calls;
+mod control_plane;
+mod events;
+mod health_checks;
+#[cfg(feature = "local")]
+mod local;
+mod partitions;
+mod query;
+mod results;
+mod sessions;
+mod tasks;
+mod versions;
+
+pub use calls::Calls;
+pub use control_plane::ControlPlane;
+#[cfg(feature = "local")]
+pub use local::LocalCluster;
+pub use results::DATA_CHUNK_MAX_SIZE;
+
+/// A [`ControlPlane`] served on a local port.
+///
+/// The server runs in the background until this is dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    address: SocketAddr,
+    control_plane: ControlPlane,
+    served: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Serve an empty control plane
+    pub async fn start() -> Result<Self, MockError> {
+        Self::serve(ControlPlane::new()).await
+    }
+
+    /// Serve `control_plane`, on an ephemeral port of the loopback interface
+    pub async fn serve(control_plane: ControlPlane) -> Result<Self, MockError> {
+        let router = tonic::transport::Server::builder()
+            .add_service(control_plane.clone().sessions_server())
+            .add_service(control_plane.clone().tasks_server())
+            .add_service(control_plane.clone().results_server())
+            .add_service(control_plane.clone().events_server())
+            .add_service(control_plane.clone().partitions_server())
+            .add_service(control_plane.clone().health_checks_server())
+            .add_service(control_plane.clone().versions_server())
+            .add_service(control_plane.clone().auth_server())
+            .add_service(control_plane.clone().applications_server());
+        let (address, served) = listen(router).await?;
+        tracing::debug!("Mock control plane listening on {address}");
+
+        Ok(Self {
+            address,
+            control_plane,
+            served,
+        })
+    }
+
+    /// Get the address the server listens on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Get the endpoint to reach the server at
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Get a client configuration to reach the server with
+    pub fn config(&self) -> Result<ClientConfig, ConfigError> {
+        let mut args = ClientConfigArgs::default();
+        args.endpoint = self.endpoint();
+        ClientConfig::from_config_args(args)
+    }
+
+    /// Connect a client to the server
+    pub async fn client(&self) -> Result<Client, ConnectionError> {
+        let config = self.config().context(armonik_transport::ConfigSnafu {})?;
+        Client::with_config(config).await
+    }
+
+    /// Get the control plane being served
+    pub fn control_plane(&self) -> &ControlPlane {
+        &self.control_plane
+    }
+
+    /// Get how many times each RPC was called
+    pub fn calls(&self) -> &Calls {
+        self.control_plane.calls()
+    }
+}
+
+/// Serve `router` in the background, on an ephemeral port of the loopback interface
+async fn listen(
+    router: tonic::transport::server::Router,
+) -> Result<(SocketAddr, tokio::task::JoinHandle<()>), MockError> {
+    let listener = tokio::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0))
+        .await
+        .context(BindSnafu {})?;
+    let address = listener.local_addr().context(BindSnafu {})?;
+
+    let served = tokio::spawn(async move {
+        if let Err(err) = router
+            .serve_with_incoming(
+                crate::reexports::tokio_stream::wrappers::TcpListenerStream::new(listener),
+            )
+            .await
+        {
+            tracing::error!("Mock server stopped: {err}");
+        }
+    });
+
+    Ok((address, served))
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // Not a graceful shutdown: that would wait for the event subscriptions, which never end.
+        self.served.abort();
+    }
+}
+
+/// Error starting a mock server
+#[derive(Debug, Snafu)]
+#[non_exhaustive]
+pub enum MockError {
+    #[snafu(display("Could not bind the mock server to a local port [{location}]"))]
+    #[non_exhaustive]
+    Bind {
+        source: std::io::Error,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[cfg(feature = "local")]
+    #[snafu(display("Could not connect the worker to the local agent [{location}]"))]
+    #[non_exhaustive]
+    Connect {
+        #[snafu(source(from(ConnectionError, Box::new)))]
+        source: Box<ConnectionError>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use crate::partitions;
+use crate::server::{PartitionsService, RequestContext};
+
+use super::query::{self, Filters};
+use super::ControlPlane;
+
+impl PartitionsService for ControlPlane {
+    async fn list(
+        self: Arc<Self>,
+        request: partitions::list::Request,
+        _context: RequestContext,
+    ) -> Result<partitions::list::Response, tonic::Status> {
+        self.count("Partitions", "ListPartitions");
+
+        let mut partitions = self
+            .state()
+            .partitions
+            .values()
+            .filter(|partition| request.filters.matches(partition))
+            .cloned()
+            .collect::<Vec<_>>();
+        query::sort(
+            &mut partitions,
+            std::slice::from_ref(&request.sort.field),
+            &request.sort.direction,
+        );
+        let (partitions, total) = query::page(partitions, request.page, request.page_size)?;
+
+        Ok(partitions::list::Response {
+            partitions,
+            page: request.page,
+            page_size: request.page_size,
+            total,
+        })
+    }
+
+    async fn get(
+        self: Arc<Self>,
+        request: partitions::get::Request,
+        _context: RequestContext,
+    ) -> Result<partitions::get::Response, tonic::Status> {
+        self.count("Partitions", "GetPartition");
+
+        let partition = self
+            .state()
+            .partitions
+            .get(&request.partition_id)
+            .cloned()
+            .ok_or_else(|| {
+                tonic::Status::not_found(format!("Partition {} not found", request.partition_id))
+            })?;
+
+        Ok(partitions::get::Response { partition })
+    }
+}
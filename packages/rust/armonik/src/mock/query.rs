@@ -0,0 +1,373 @@
+//! Filtering, sorting and paging of the listings, the way the control plane does them.
+
+use std::cmp::Ordering;
+
+use crate::{
+    applications, partitions, results, sessions, tasks, FilterArray, FilterArrayOperator,
+    FilterBoolean, FilterDate, FilterDateOperator, FilterDuration, FilterDurationOperator,
+    FilterNumber, FilterNumberOperator, FilterStatusOperator, FilterString, FilterStringOperator,
+    SortDirection, TaskOptionField, TaskOptions,
+};
+
+/// The value of one field of a listed object, as compared by the filters and the sort
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub(super) enum Value {
+    String(String),
+    Number(i64),
+    Boolean(bool),
+    Status(i32),
+    /// Seconds and nanoseconds since the epoch, so that dates compare
+    Date(Option<(i64, i32)>),
+    /// Seconds and nanoseconds, so that durations compare
+    Duration(Option<(i64, i32)>),
+    Array(Vec<String>),
+}
+
+impl Value {
+    pub(super) fn date(date: &Option<prost_types::Timestamp>) -> Self {
+        Self::Date(date.map(|date| (date.seconds, date.nanos)))
+    }
+
+    pub(super) fn duration(duration: &Option<prost_types::Duration>) -> Self {
+        Self::Duration(duration.map(|duration| (duration.seconds, duration.nanos)))
+    }
+}
+
+/// A condition of a filter, whichever service it comes from
+pub(super) enum Condition {
+    String(FilterString),
+    Number(FilterNumber),
+    Boolean(FilterBoolean),
+    Status(i32, FilterStatusOperator),
+    Date(FilterDate),
+    Duration(FilterDuration),
+    Array(FilterArray),
+}
+
+impl Condition {
+    /// Whether `value` satisfies the condition: a condition on a value of another kind never does.
+    fn test(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::String(filter), Value::String(value)) => {
+                let expected = filter.value.as_str();
+                match filter.operator {
+                    FilterStringOperator::Equal => value == expected,
+                    FilterStringOperator::NotEqual => value != expected,
+                    FilterStringOperator::Contains => value.contains(expected),
+                    FilterStringOperator::NotContains => !value.contains(expected),
+                    FilterStringOperator::StartsWith => value.starts_with(expected),
+                    FilterStringOperator::EndsWith => value.ends_with(expected),
+                }
+            }
+            (Self::Number(filter), Value::Number(value)) => {
+                let ordering = value.cmp(&filter.value);
+                match filter.operator {
+                    FilterNumberOperator::Equal => ordering.is_eq(),
+                    FilterNumberOperator::NotEqual => ordering.is_ne(),
+                    FilterNumberOperator::LessThan => ordering.is_lt(),
+                    FilterNumberOperator::LessThanOrEqual => ordering.is_le(),
+                    FilterNumberOperator::GreaterThanOrEqual => ordering.is_ge(),
+                    FilterNumberOperator::GreaterThan => ordering.is_gt(),
+                }
+            }
+            (Self::Boolean(filter), Value::Boolean(value)) => *value == filter.value,
+            (Self::Status(expected, operator), Value::Status(value)) => match operator {
+                FilterStatusOperator::Equal => value == expected,
+                FilterStatusOperator::NotEqual => value != expected,
+            },
+            (Self::Date(filter), Value::Date(value)) => {
+                // An unset date compares to nothing but `NotEqual`.
+                let Some(value) = value else {
+                    return filter.operator == FilterDateOperator::NotEqual;
+                };
+                let ordering = value.cmp(&(filter.value.seconds, filter.value.nanos));
+                match filter.operator {
+                    FilterDateOperator::Equal => ordering.is_eq(),
+                    FilterDateOperator::NotEqual => ordering.is_ne(),
+                    FilterDateOperator::Before => ordering.is_lt(),
+                    FilterDateOperator::BeforeOrEqual => ordering.is_le(),
+                    FilterDateOperator::AfterOrEqual => ordering.is_ge(),
+                    FilterDateOperator::After => ordering.is_gt(),
+                }
+            }
+            (Self::Duration(filter), Value::Duration(value)) => {
+                let Some(value) = value else {
+                    return filter.operator == FilterDurationOperator::NotEqual;
+                };
+                let ordering = value.cmp(&(filter.value.seconds, filter.value.nanos));
+                match filter.operator {
+                    FilterDurationOperator::Equal => ordering.is_eq(),
+                    FilterDurationOperator::NotEqual => ordering.is_ne(),
+                    FilterDurationOperator::ShorterThan => ordering.is_lt(),
+                    FilterDurationOperator::ShorterThanOrEqual => ordering.is_le(),
+                    FilterDurationOperator::LongerThanOrEqual => ordering.is_ge(),
+                    FilterDurationOperator::LongerThan => ordering.is_gt(),
+                }
+            }
+            (Self::Array(filter), Value::Array(values)) => {
+                let contains = values.contains(&filter.value);
+                match filter.operator {
+                    FilterArrayOperator::Contains => contains,
+                    FilterArrayOperator::NotContains => !contains,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// An object listed by one of the services
+pub(super) trait Listed {
+    /// The fields it is filtered and sorted on
+    type Field;
+
+    /// Get the value of `field`, if it has one
+    fn value(&self, field: &Self::Field) -> Option<Value>;
+}
+
+/// Filters of one of the services: a disjunction of conjunctions of conditions
+pub(super) trait Filters<T> {
+    /// Whether `item` is kept by the filters: no filter at all keeps everything.
+    fn matches(&self, item: &T) -> bool;
+}
+
+macro_rules! impl_filters {
+    ($($module:ident::$Item:ident { $($variant:ident$(($status:ident))?),* }),* $(,)?) => {
+        $(
+            impl From<$module::filter::Condition> for Condition {
+                fn from(value: $module::filter::Condition) -> Self {
+                    match value {
+                        $($module::filter::Condition::$variant(condition) => {
+                            impl_filters!(@condition $variant$(($status))? condition)
+                        })*
+                    }
+                }
+            }
+
+            impl Filters<$module::$Item> for $module::filter::Or {
+                fn matches(&self, item: &$module::$Item) -> bool {
+                    self.or.is_empty()
+                        || self.or.iter().any(|and| {
+                            and.and.iter().all(|field| {
+                                let condition = Condition::from(field.condition.clone());
+                                item.value(&field.field)
+                                    .is_some_and(|value| condition.test(&value))
+                            })
+                        })
+                }
+            }
+        )*
+    };
+    (@condition Status(status) $condition:ident) => {
+        Condition::Status($condition.value as i32, $condition.operator)
+    };
+    (@condition $variant:ident $condition:ident) => {
+        Condition::$variant($condition)
+    };
+}
+
+impl_filters! {
+    applications::Raw { String },
+    partitions::Raw { String, Number, Boolean, Array },
+    results::Raw { String, Date, Array, Status(status), Number },
+    sessions::Raw { String, Number, Boolean, Status(status), Date, Duration, Array },
+    tasks::Raw { String, Number, Boolean, Status(status), Date, Duration, Array },
+}
+
+/// Sort `items` on `fields`, the first one first
+pub(super) fn sort<T: Listed>(items: &mut [T], fields: &[T::Field], direction: &SortDirection) {
+    items.sort_by(|a, b| {
+        let ordering = fields
+            .iter()
+            .map(|field| {
+                a.value(field)
+                    .partial_cmp(&b.value(field))
+                    .unwrap_or(Ordering::Equal)
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal);
+        match direction {
+            SortDirection::Desc => ordering.reverse(),
+            SortDirection::Unspecified | SortDirection::Asc => ordering,
+        }
+    });
+}
+
+/// Get the page `page` of `items`, and how many they are in all
+pub(super) fn page<T>(
+    items: Vec<T>,
+    page: i32,
+    page_size: i32,
+) -> Result<(Vec<T>, i32), tonic::Status> {
+    if page < 0 {
+        return Err(tonic::Status::invalid_argument(format!(
+            "The page {page} is negative"
+        )));
+    }
+    if page_size <= 0 {
+        return Err(tonic::Status::invalid_argument(format!(
+            "The page size {page_size} is not positive"
+        )));
+    }
+
+    let total = i32::try_from(items.len()).unwrap_or(i32::MAX);
+    let skip = usize::try_from(i64::from(page) * i64::from(page_size)).unwrap_or(usize::MAX);
+    let page_size = usize::try_from(page_size).unwrap_or(usize::MAX);
+
+    Ok((
+        items.into_iter().skip(skip).take(page_size).collect(),
+        total,
+    ))
+}
+
+/// Get the value of a task option, by its field or by its key for the generic ones
+pub(super) fn option_value(options: &TaskOptions, field: &TaskOptionField) -> Option<Value> {
+    Some(match field {
+        TaskOptionField::Unspecified => return None,
+        TaskOptionField::MaxDuration => Value::duration(&Some(options.max_duration)),
+        TaskOptionField::MaxRetries => Value::Number(options.max_retries.into()),
+        TaskOptionField::Priority => Value::Number(options.priority.into()),
+        TaskOptionField::PartitionId => Value::String(options.partition_id.clone()),
+        TaskOptionField::ApplicationName => Value::String(options.application_name.clone()),
+        TaskOptionField::ApplicationVersion => Value::String(options.application_version.clone()),
+        TaskOptionField::ApplicationNamespace => {
+            Value::String(options.application_namespace.clone())
+        }
+        TaskOptionField::ApplicationService => Value::String(options.application_service.clone()),
+        TaskOptionField::ApplicationEngine => Value::String(options.engine_type.clone()),
+    })
+}
+
+pub(super) fn generic_option_value(options: &TaskOptions, key: &str) -> Option<Value> {
+    options.options.get(key).cloned().map(Value::String)
+}
+
+impl Listed for applications::Raw {
+    type Field = applications::Field;
+
+    fn value(&self, field: &Self::Field) -> Option<Value> {
+        Some(Value::String(match field {
+            applications::Field::Unspecified => return None,
+            applications::Field::Name => self.name.clone(),
+            applications::Field::Version => self.version.clone(),
+            applications::Field::Namespace => self.namespace.clone(),
+            applications::Field::Service => self.service.clone(),
+        }))
+    }
+}
+
+impl Listed for partitions::Raw {
+    type Field = partitions::Field;
+
+    fn value(&self, field: &Self::Field) -> Option<Value> {
+        Some(match field {
+            partitions::Field::Unspecified => return None,
+            partitions::Field::Id => Value::String(self.partition_id.clone()),
+            partitions::Field::ParentPartitionIds => {
+                Value::Array(self.parent_partition_ids.clone())
+            }
+            partitions::Field::PodReserved => Value::Number(self.pod_reserved),
+            partitions::Field::PodMax => Value::Number(self.pod_max),
+            partitions::Field::PreemptionPercentage => Value::Number(self.preemption_percentage),
+            partitions::Field::Priority => Value::Number(self.priority),
+        })
+    }
+}
+
+impl Listed for results::Raw {
+    type Field = results::Field;
+
+    fn value(&self, field: &Self::Field) -> Option<Value> {
+        Some(match field {
+            results::Field::Unspecified => return None,
+            results::Field::SessionId => Value::String(self.session_id.clone()),
+            results::Field::Name => Value::String(self.name.clone()),
+            results::Field::OwnerTaskId => Value::String(self.owner_task_id.clone()),
+            results::Field::Status => Value::Status(self.status.clone() as i32),
+            results::Field::CreatedAt => Value::date(&self.created_at),
+            results::Field::CompletedAt => Value::date(&self.completed_at),
+            results::Field::ResultId => Value::String(self.result_id.clone()),
+            results::Field::Size => Value::Number(self.size),
+            results::Field::CreatedBy => Value::String(self.created_by.clone()),
+            results::Field::OpaqueId => {
+                Value::String(String::from_utf8_lossy(&self.opaque_id).into_owned())
+            }
+            results::Field::ManualDeletion => Value::Boolean(self.manual_deletion),
+        })
+    }
+}
+
+impl Listed for sessions::Raw {
+    type Field = sessions::Field;
+
+    fn value(&self, field: &Self::Field) -> Option<Value> {
+        let field = match field {
+            sessions::Field::Raw(field) => field,
+            sessions::Field::TaskOption(field) => {
+                return option_value(&self.default_task_options, field)
+            }
+            sessions::Field::TaskOptionGeneric(key) => {
+                return generic_option_value(&self.default_task_options, key)
+            }
+        };
+        Some(match field {
+            sessions::RawField::Unspecified | sessions::RawField::Options => return None,
+            sessions::RawField::SessionId => Value::String(self.session_id.clone()),
+            sessions::RawField::Status => Value::Status(self.status.clone() as i32),
+            sessions::RawField::ClientSubmission => Value::Boolean(self.client_submission),
+            sessions::RawField::WorkerSubmission => Value::Boolean(self.worker_submission),
+            sessions::RawField::PartitionIds => Value::Array(self.partition_ids.clone()),
+            sessions::RawField::CreatedAt => Value::date(&self.created_at),
+            sessions::RawField::CancelledAt => Value::date(&self.cancelled_at),
+            sessions::RawField::ClosedAt => Value::date(&self.closed_at),
+            sessions::RawField::PurgedAt => Value::date(&self.purged_at),
+            sessions::RawField::DeletedAt => Value::date(&self.deleted_at),
+            sessions::RawField::Duration => Value::duration(&self.duration),
+        })
+    }
+}
+
+impl Listed for tasks::Raw {
+    type Field = tasks::Field;
+
+    fn value(&self, field: &Self::Field) -> Option<Value> {
+        let field = match field {
+            tasks::Field::Summary(field) => field,
+            tasks::Field::Option(field) => return option_value(&self.options, field),
+            tasks::Field::OptionGeneric(key) => return generic_option_value(&self.options, key),
+        };
+        Some(match field {
+            tasks::SummaryField::Unspecified => return None,
+            tasks::SummaryField::TaskId => Value::String(self.task_id.clone()),
+            tasks::SummaryField::SessionId => Value::String(self.session_id.clone()),
+            tasks::SummaryField::OwnerPodId => Value::String(self.owner_pod_id.clone()),
+            tasks::SummaryField::InitialTaskId => Value::String(self.initial_task_id.clone()),
+            tasks::SummaryField::Status => Value::Status(self.status.clone() as i32),
+            tasks::SummaryField::CreatedAt => Value::date(&self.created_at),
+            tasks::SummaryField::SubmittedAt => Value::date(&self.submitted_at),
+            tasks::SummaryField::StartedAt => Value::date(&self.started_at),
+            tasks::SummaryField::EndedAt => Value::date(&self.ended_at),
+            tasks::SummaryField::CreationToEndDuration => {
+                Value::duration(&self.creation_to_end_duration)
+            }
+            tasks::SummaryField::ProcessingToEndDuration => {
+                Value::duration(&self.processing_to_end_duration)
+            }
+            tasks::SummaryField::ReceivedToEndDuration => {
+                Value::duration(&self.received_to_end_duration)
+            }
+            tasks::SummaryField::PodTtl => Value::date(&self.pod_ttl),
+            tasks::SummaryField::PodHostname => Value::String(self.pod_hostname.clone()),
+            tasks::SummaryField::ReceivedAt => Value::date(&self.received_at),
+            tasks::SummaryField::AcquiredAt => Value::date(&self.acquired_at),
+            tasks::SummaryField::ProcessedAt => Value::date(&self.processed_at),
+            tasks::SummaryField::FetchedAt => Value::date(&self.fetched_at),
+            tasks::SummaryField::Error => Value::String(match &self.output {
+                tasks::Output::Success => String::new(),
+                tasks::Output::Error(message) => message.clone(),
+            }),
+            tasks::SummaryField::PayloadId => Value::String(self.payload_id.clone()),
+            tasks::SummaryField::CreatedBy => Value::String(self.created_by.clone()),
+        })
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+
+use crate::server::{RequestContext, ResultsService};
+use crate::{results, ResultStatus};
+
+use super::query::{self, Filters};
+use super::ControlPlane;
+
+/// The largest chunk of data the mock sends or expects, as the control plane advertises it
+pub const DATA_CHUNK_MAX_SIZE: i32 = 84_000;
+
+impl ResultsService for ControlPlane {
+    async fn list(
+        self: Arc<Self>,
+        request: results::list::Request,
+        _context: RequestContext,
+    ) -> Result<results::list::Response, tonic::Status> {
+        self.count("Results", "ListResults");
+        let state = self.state();
+
+        let mut results = state
+            .results
+            .values()
+            .filter(|result| request.filters.matches(result))
+            .cloned()
+            .collect::<Vec<_>>();
+        query::sort(
+            &mut results,
+            std::slice::from_ref(&request.sort.field),
+            &request.sort.direction,
+        );
+        let (results, total) = query::page(results, request.page, request.page_size)?;
+
+        Ok(results::list::Response {
+            results,
+            page: request.page,
+            page_size: request.page_size,
+            total,
+        })
+    }
+
+    async fn get(
+        self: Arc<Self>,
+        request: results::get::Request,
+        _context: RequestContext,
+    ) -> Result<results::get::Response, tonic::Status> {
+        self.count("Results", "GetResult");
+
+        Ok(results::get::Response {
+            result: self.state().result(&request.id)?.clone(),
+        })
+    }
+
+    async fn get_owner_task_id(
+        self: Arc<Self>,
+        request: results::get_owner_task_id::Request,
+        _context: RequestContext,
+    ) -> Result<results::get_owner_task_id::Response, tonic::Status> {
+        self.count("Results", "GetOwnerTaskId");
+        let state = self.state();
+
+        Ok(results::get_owner_task_id::Response {
+            result_task: request
+                .result_ids
+                .into_iter()
+                .map(|result_id| {
+                    let owner_task_id = state.result(&result_id)?.owner_task_id.clone();
+                    Ok((result_id, owner_task_id))
+                })
+                .collect::<Result<_, tonic::Status>>()?,
+            session_id: request.session_id,
+        })
+    }
+
+    async fn create_metadata(
+        self: Arc<Self>,
+        request: results::create_metadata::Request,
+        _context: RequestContext,
+    ) -> Result<results::create_metadata::Response, tonic::Status> {
+        self.count("Results", "CreateResultsMetaData");
+        let mut state = self.state();
+        state.open_session(&request.session_id)?;
+
+        Ok(results::create_metadata::Response {
+            results: request
+                .results
+                .into_iter()
+                .map(|item| state.add_result(&request.session_id, item.name, item.manual_deletion))
+                .collect(),
+        })
+    }
+
+    async fn create(
+        self: Arc<Self>,
+        request: results::create::Request,
+        _context: RequestContext,
+    ) -> Result<results::create::Response, tonic::Status> {
+        self.count("Results", "CreateResults");
+        let mut state = self.state();
+        state.open_session(&request.session_id)?;
+
+        Ok(results::create::Response {
+            results: request
+                .results
+                .into_iter()
+                .map(|item| {
+                    let result =
+                        state.add_result(&request.session_id, item.name, item.manual_deletion);
+                    state.complete_result(&result.result_id, item.data)
+                })
+                .collect::<Result<_, tonic::Status>>()?,
+        })
+    }
+
+    async fn import(
+        self: Arc<Self>,
+        request: results::import::Request,
+        _context: RequestContext,
+    ) -> Result<results::import::Response, tonic::Status> {
+        self.count("Results", "ImportResultsData");
+        let mut state = self.state();
+
+        for result_id in request.results.keys() {
+            let result = state.result(result_id)?;
+            if result.session_id != request.session_id {
+                return Err(tonic::Status::not_found(format!(
+                    "Result {result_id} not found in session {}",
+                    request.session_id
+                )));
+            }
+        }
+
+        // The data stays wherever the opaque id points to: the mock only knows it is there.
+        Ok(results::import::Response {
+            results: request
+                .results
+                .into_iter()
+                .map(|(result_id, opaque_id)| {
+                    let mut result = state.complete_result(&result_id, Vec::new())?;
+                    if let Some(stored) = state.results.get_mut(&result_id) {
+                        stored.opaque_id.clone_from(&opaque_id);
+                    }
+                    result.opaque_id = opaque_id;
+                    Ok((result_id, result))
+                })
+                .collect::<Result<_, tonic::Status>>()?,
+        })
+    }
+
+    async fn delete_data(
+        self: Arc<Self>,
+        request: results::delete_data::Request,
+        _context: RequestContext,
+    ) -> Result<results::delete_data::Response, tonic::Status> {
+        self.count("Results", "DeleteResultsData");
+        let mut state = self.state();
+
+        for result_id in &request.result_ids {
+            state.result(result_id)?;
+        }
+        for result_id in &request.result_ids {
+            state.data.remove(result_id);
+            state.set_result_status(result_id, ResultStatus::Deleted);
+        }
+
+        Ok(results::delete_data::Response {
+            session_id: request.session_id,
+            result_ids: request.result_ids,
+        })
+    }
+
+    async fn get_service_configuration(
+        self: Arc<Self>,
+        _request: results::get_service_configuration::Request,
+        _context: RequestContext,
+    ) -> Result<results::get_service_configuration::Response, tonic::Status> {
+        self.count("Results", "GetServiceConfiguration");
+
+        Ok(results::get_service_configuration::Response {
+            data_chunk_max_size: DATA_CHUNK_MAX_SIZE,
+        })
+    }
+
+    async fn download(
+        self: Arc<Self>,
+        request: results::download::Request,
+        _context: RequestContext,
+    ) -> Result<
+        impl tonic::codegen::tokio_stream::Stream<
+                Item = Result<results::download::Response, tonic::Status>,
+            > + Send,
+        tonic::Status,
+    > {
+        self.count("Results", "DownloadResultData");
+
+        let data = {
+            let state = self.state();
+            let result = state.result(&request.result_id)?;
+            if result.session_id != request.session_id {
+                return Err(tonic::Status::not_found(format!(
+                    "Result {} not found in session {}",
+                    request.result_id, request.session_id
+                )));
+            }
+            state.data.get(&request.result_id).cloned().ok_or_else(|| {
+                tonic::Status::not_found(format!("Result {} has no data", request.result_id))
+            })?
+        };
+
+        let chunks = data
+            .chunks(DATA_CHUNK_MAX_SIZE as usize)
+            .map(|chunk| {
+                Ok(results::download::Response {
+                    data_chunk: chunk.to_vec(),
+                })
+            })
+            .collect::<Vec<_>>();
+        // An empty result is still sent, as one empty chunk.
+        let chunks = if chunks.is_empty() {
+            vec![Ok(results::download::Response::default())]
+        } else {
+            chunks
+        };
+
+        Ok(futures::stream::iter(chunks))
+    }
+
+    async fn upload(
+        self: Arc<Self>,
+        request: impl tonic::codegen::tokio_stream::Stream<
+                Item = Result<results::upload::Request, tonic::Status>,
+            > + Send
+            + 'static,
+        _context: RequestContext,
+    ) -> Result<results::upload::Response, tonic::Status> {
+        self.count("Results", "UploadResultData");
+        let mut request = std::pin::pin!(request);
+
+        let Some(results::upload::Request::Identifier {
+            session_id,
+            result_id,
+        }) = request.next().await.transpose()?
+        else {
+            return Err(tonic::Status::invalid_argument(
+                "The upload does not start with the result to upload",
+            ));
+        };
+
+        let mut data = Vec::new();
+        while let Some(chunk) = request.next().await.transpose()? {
+            match chunk {
+                results::upload::Request::DataChunk(chunk) => data.extend(chunk),
+                results::upload::Request::Identifier { .. } => {
+                    return Err(tonic::Status::invalid_argument(
+                        "The result to upload is given twice",
+                    ))
+                }
+            }
+        }
+
+        let mut state = self.state();
+        if state.result(&result_id)?.session_id != session_id {
+            return Err(tonic::Status::not_found(format!(
+                "Result {result_id} not found in session {session_id}"
+            )));
+        }
+
+        Ok(results::upload::Response {
+            result: state.complete_result(&result_id, data)?,
+        })
+    }
+}
@@ -0,0 +1,306 @@
+use std::sync::Arc;
+
+use crate::server::{RequestContext, SessionsService};
+use crate::{sessions, SessionStatus, TaskStatus};
+
+use super::control_plane::{elapsed, now, State};
+use super::query::{self, Filters};
+use super::ControlPlane;
+
+impl State {
+    /// Move a session from one of `from` to `to`
+    fn transition(
+        &mut self,
+        session_id: &str,
+        from: &[SessionStatus],
+        to: SessionStatus,
+    ) -> Result<sessions::Raw, tonic::Status> {
+        let session = self.session_mut(session_id)?;
+        if !from.contains(&session.status) {
+            return Err(tonic::Status::failed_precondition(format!(
+                "Session {session_id} is {:?}, it cannot be {to:?}",
+                session.status
+            )));
+        }
+
+        let now = now();
+        match to {
+            SessionStatus::Cancelled => session.cancelled_at = Some(now),
+            SessionStatus::Closed => session.closed_at = Some(now),
+            SessionStatus::Purged => session.purged_at = Some(now),
+            SessionStatus::Deleted => session.deleted_at = Some(now),
+            _ => {}
+        }
+        if matches!(to, SessionStatus::Cancelled | SessionStatus::Closed) {
+            session.duration = session.created_at.as_ref().map(|from| elapsed(from, &now));
+        }
+        session.status = to;
+        Ok(session.clone())
+    }
+
+    /// Get the ids of the tasks of a session that are in `status`
+    fn session_tasks(&self, session_id: &str, status: Option<TaskStatus>) -> Vec<String> {
+        self.tasks
+            .values()
+            .filter(|task| task.session_id == session_id)
+            .filter(|task| status.as_ref().is_none_or(|status| task.status == *status))
+            .map(|task| task.task_id.clone())
+            .collect()
+    }
+}
+
+impl SessionsService for ControlPlane {
+    async fn list(
+        self: Arc<Self>,
+        request: sessions::list::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::list::Response, tonic::Status> {
+        self.count("Sessions", "ListSessions");
+        let state = self.state();
+
+        let mut sessions = state
+            .sessions
+            .values()
+            .filter(|session| request.filters.matches(session))
+            .cloned()
+            .collect::<Vec<_>>();
+        query::sort(
+            &mut sessions,
+            std::slice::from_ref(&request.sort.field),
+            &request.sort.direction,
+        );
+        if !request.with_task_options {
+            for session in &mut sessions {
+                session.default_task_options = Default::default();
+            }
+        }
+        let (sessions, total) = query::page(sessions, request.page, request.page_size)?;
+
+        Ok(sessions::list::Response {
+            sessions,
+            page: request.page,
+            page_size: request.page_size,
+            total,
+        })
+    }
+
+    async fn get(
+        self: Arc<Self>,
+        request: sessions::get::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::get::Response, tonic::Status> {
+        self.count("Sessions", "GetSession");
+
+        Ok(sessions::get::Response {
+            session: self.state().session(&request.session_id)?.clone(),
+        })
+    }
+
+    async fn cancel(
+        self: Arc<Self>,
+        request: sessions::cancel::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::cancel::Response, tonic::Status> {
+        self.count("Sessions", "CancelSession");
+        let mut state = self.state();
+
+        let session = state.transition(
+            &request.session_id,
+            &[SessionStatus::Running, SessionStatus::Paused],
+            SessionStatus::Cancelled,
+        )?;
+        for task_id in state.session_tasks(&request.session_id, None) {
+            state.cancel_task(&task_id);
+        }
+
+        Ok(sessions::cancel::Response { session })
+    }
+
+    async fn create(
+        self: Arc<Self>,
+        request: sessions::create::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::create::Response, tonic::Status> {
+        self.count("Sessions", "CreateSession");
+        let mut state = self.state();
+
+        let mut partition_ids = request.partition_ids;
+        if partition_ids.is_empty() {
+            partition_ids.extend(state.partitions.keys().next().cloned());
+        }
+        if let Some(partition_id) = partition_ids
+            .iter()
+            .find(|partition_id| !state.partitions.contains_key(*partition_id))
+        {
+            return Err(tonic::Status::invalid_argument(format!(
+                "Partition {partition_id} does not exist"
+            )));
+        }
+        let mut default_task_options = request.default_task_options;
+        if default_task_options.partition_id.is_empty() {
+            default_task_options.partition_id = partition_ids[0].clone();
+        } else if !partition_ids.contains(&default_task_options.partition_id) {
+            return Err(tonic::Status::invalid_argument(format!(
+                "Partition {} is not one of the session",
+                default_task_options.partition_id
+            )));
+        }
+
+        let session_id = state.new_id();
+        state.sessions.insert(
+            session_id.clone(),
+            sessions::Raw {
+                session_id: session_id.clone(),
+                status: SessionStatus::Running,
+                client_submission: true,
+                worker_submission: true,
+                partition_ids,
+                default_task_options,
+                created_at: Some(now()),
+                ..Default::default()
+            },
+        );
+
+        Ok(sessions::create::Response { session_id })
+    }
+
+    async fn pause(
+        self: Arc<Self>,
+        request: sessions::pause::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::pause::Response, tonic::Status> {
+        self.count("Sessions", "PauseSession");
+        let mut state = self.state();
+
+        let session = state.transition(
+            &request.session_id,
+            &[SessionStatus::Running],
+            SessionStatus::Paused,
+        )?;
+        for task_id in state.session_tasks(&request.session_id, Some(TaskStatus::Submitted)) {
+            state.set_task_status(&task_id, TaskStatus::Paused);
+        }
+
+        Ok(sessions::pause::Response { session })
+    }
+
+    async fn resume(
+        self: Arc<Self>,
+        request: sessions::resume::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::resume::Response, tonic::Status> {
+        self.count("Sessions", "ResumeSession");
+        let mut state = self.state();
+
+        let session = state.transition(
+            &request.session_id,
+            &[SessionStatus::Paused],
+            SessionStatus::Running,
+        )?;
+        for task_id in state.session_tasks(&request.session_id, Some(TaskStatus::Paused)) {
+            state.set_task_status(&task_id, TaskStatus::Submitted);
+        }
+
+        Ok(sessions::resume::Response { session })
+    }
+
+    async fn close(
+        self: Arc<Self>,
+        request: sessions::close::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::close::Response, tonic::Status> {
+        self.count("Sessions", "CloseSession");
+
+        let session = self.state().transition(
+            &request.session_id,
+            &[SessionStatus::Running, SessionStatus::Paused],
+            SessionStatus::Closed,
+        )?;
+
+        Ok(sessions::close::Response { session })
+    }
+
+    async fn purge(
+        self: Arc<Self>,
+        request: sessions::purge::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::purge::Response, tonic::Status> {
+        self.count("Sessions", "PurgeSession");
+        let mut state = self.state();
+
+        let session = state.transition(
+            &request.session_id,
+            &[SessionStatus::Closed, SessionStatus::Cancelled],
+            SessionStatus::Purged,
+        )?;
+        let result_ids = state
+            .results
+            .values()
+            .filter(|result| result.session_id == request.session_id)
+            .map(|result| result.result_id.clone())
+            .collect::<Vec<_>>();
+        for result_id in result_ids {
+            state.data.remove(&result_id);
+            state.set_result_status(&result_id, crate::ResultStatus::Deleted);
+        }
+
+        Ok(sessions::purge::Response { session })
+    }
+
+    async fn delete(
+        self: Arc<Self>,
+        request: sessions::delete::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::delete::Response, tonic::Status> {
+        self.count("Sessions", "DeleteSession");
+        let mut state = self.state();
+
+        let session = state.transition(
+            &request.session_id,
+            &[
+                SessionStatus::Running,
+                SessionStatus::Paused,
+                SessionStatus::Cancelled,
+                SessionStatus::Closed,
+                SessionStatus::Purged,
+            ],
+            SessionStatus::Deleted,
+        )?;
+        // Nothing of a deleted session is kept, the session itself included.
+        state.sessions.remove(&request.session_id);
+        state
+            .tasks
+            .retain(|_, task| task.session_id != request.session_id);
+        let State { results, data, .. } = &mut *state;
+        results.retain(|result_id, result| {
+            let kept = result.session_id != request.session_id;
+            if !kept {
+                data.remove(result_id);
+            }
+            kept
+        });
+
+        Ok(sessions::delete::Response { session })
+    }
+
+    async fn stop_submission(
+        self: Arc<Self>,
+        request: sessions::stop_submission::Request,
+        _context: RequestContext,
+    ) -> Result<sessions::stop_submission::Response, tonic::Status> {
+        self.count("Sessions", "StopSubmission");
+        let mut state = self.state();
+
+        let session = state.session_mut(&request.session_id)?;
+        if request.client {
+            session.client_submission = false;
+        }
+        if request.worker {
+            session.worker_submission = false;
+        }
+
+        Ok(sessions::stop_submission::Response {
+            session: session.clone(),
+        })
+    }
+}
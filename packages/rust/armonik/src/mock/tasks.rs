@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::server::{RequestContext, TasksService};
+use crate::{events, tasks, StatusCount, TaskOptions, TaskStatus};
+
+use super::control_plane::{now, State};
+use super::query::{self, Filters};
+use super::ControlPlane;
+
+/// Sum up a task the way `ListTasks` does
+fn summary(task: tasks::Raw) -> tasks::Summary {
+    let count = |ids: &[String]| i64::try_from(ids.len()).unwrap_or(i64::MAX);
+    tasks::Summary {
+        count_parent_task_ids: count(&task.parent_task_ids),
+        count_data_dependencies: count(&task.data_dependencies),
+        count_expected_output_ids: count(&task.expected_output_ids),
+        count_retry_of_ids: count(&task.retry_of_ids),
+        task_id: task.task_id,
+        session_id: task.session_id,
+        owner_pod_id: task.owner_pod_id,
+        initial_task_id: task.initial_task_id,
+        status: task.status,
+        status_message: task.status_message,
+        options: task.options,
+        created_at: task.created_at,
+        submitted_at: task.submitted_at,
+        received_at: task.received_at,
+        acquired_at: task.acquired_at,
+        fetched_at: task.fetched_at,
+        started_at: task.started_at,
+        processed_at: task.processed_at,
+        ended_at: task.ended_at,
+        creation_to_end_duration: task.creation_to_end_duration,
+        processing_to_end_duration: task.processing_to_end_duration,
+        received_to_end_duration: task.received_to_end_duration,
+        pod_ttl: task.pod_ttl,
+        output: task.output,
+        pod_hostname: task.pod_hostname,
+        payload_id: task.payload_id,
+        created_by: task.created_by,
+    }
+}
+
+impl State {
+    /// List the tasks matching the filters, sorted and paged
+    fn list_tasks(
+        &self,
+        filters: &tasks::filter::Or,
+        sort: &tasks::Sort,
+        with_errors: bool,
+        page: i32,
+        page_size: i32,
+    ) -> Result<(Vec<tasks::Raw>, i32), tonic::Status> {
+        let mut tasks = self
+            .tasks
+            .values()
+            .filter(|task| filters.matches(task))
+            .cloned()
+            .collect::<Vec<_>>();
+        query::sort(
+            &mut tasks,
+            std::slice::from_ref(&sort.field),
+            &sort.direction,
+        );
+        if !with_errors {
+            for task in &mut tasks {
+                task.output = tasks::Output::Success;
+            }
+        }
+        query::page(tasks, page, page_size)
+    }
+
+    /// Check that `result_id` names a result of `session_id`
+    fn session_result(&self, session_id: &str, result_id: &str) -> Result<(), tonic::Status> {
+        match self.results.get(result_id) {
+            Some(result) if result.session_id == session_id => Ok(()),
+            _ => Err(tonic::Status::not_found(format!(
+                "Result {result_id} not found in session {session_id}"
+            ))),
+        }
+    }
+
+    /// Submit tasks to a session, by a client or, when `parent_task_id` is set, by the worker processing
+    /// that task.
+    ///
+    /// Tasks submitted by a client wait for their payload and data dependencies. Those submitted by a
+    /// worker are only created: they wait for their parent to complete first.
+    pub(super) fn submit_tasks(
+        &mut self,
+        session_id: &str,
+        task_options: Option<TaskOptions>,
+        items: Vec<tasks::submit::RequestItem>,
+        parent_task_id: Option<&str>,
+    ) -> Result<Vec<tasks::submit::ResponseItem>, tonic::Status> {
+        let session = self.open_session(session_id)?.clone();
+        let (allowed, status) = match parent_task_id {
+            None => (session.client_submission, TaskStatus::Pending),
+            Some(_) => (session.worker_submission, TaskStatus::Creating),
+        };
+        if !allowed {
+            return Err(tonic::Status::failed_precondition(format!(
+                "Submission is stopped in session {session_id}"
+            )));
+        }
+        let parent_task_ids = match parent_task_id {
+            Some(parent_task_id) => {
+                let parent = self.task(parent_task_id)?;
+                let mut parent_task_ids = parent.parent_task_ids.clone();
+                parent_task_ids.push(parent.task_id.clone());
+                parent_task_ids
+            }
+            None => Vec::new(),
+        };
+
+        // Everything is checked before anything is changed: a submission is all or nothing.
+        let mut checked = Vec::with_capacity(items.len());
+        for item in items {
+            let mut options = item
+                .task_options
+                .clone()
+                .or_else(|| task_options.clone())
+                .unwrap_or_else(|| session.default_task_options.clone());
+            if options.partition_id.is_empty() {
+                options.partition_id = session.default_task_options.partition_id.clone();
+            }
+            if !session.partition_ids.contains(&options.partition_id) {
+                return Err(tonic::Status::invalid_argument(format!(
+                    "Partition {} is not one of session {session_id}",
+                    options.partition_id
+                )));
+            }
+
+            for result_id in std::iter::once(&item.payload_id)
+                .chain(&item.data_dependencies)
+                .chain(&item.expected_output_keys)
+            {
+                self.session_result(session_id, result_id)?;
+            }
+            if let Some(result_id) = item.expected_output_keys.iter().find(|result_id| {
+                self.results
+                    .get(*result_id)
+                    .is_some_and(|result| result.status != crate::ResultStatus::Created)
+            }) {
+                return Err(tonic::Status::failed_precondition(format!(
+                    "Result {result_id} cannot be the output of a new task: it is not pending"
+                )));
+            }
+
+            checked.push((item, options));
+        }
+
+        let mut response = Vec::with_capacity(checked.len());
+        for (item, options) in checked {
+            let task_id = self.new_id();
+            let task = tasks::Raw {
+                task_id: task_id.clone(),
+                session_id: session_id.to_owned(),
+                initial_task_id: task_id.clone(),
+                parent_task_ids: parent_task_ids.clone(),
+                data_dependencies: item.data_dependencies.clone(),
+                expected_output_ids: item.expected_output_keys.clone(),
+                status: status.clone(),
+                options,
+                created_at: Some(now()),
+                payload_id: item.payload_id.clone(),
+                created_by: parent_task_id.unwrap_or_default().to_owned(),
+                ..Default::default()
+            };
+            self.tasks.insert(task_id.clone(), task);
+            self.emit(
+                session_id,
+                events::Update::NewTask(events::NewTask {
+                    task_id: task_id.clone(),
+                    payload_id: item.payload_id.clone(),
+                    status: status.clone(),
+                    expected_output_keys: item.expected_output_keys.clone(),
+                    data_dependencies: item.data_dependencies.clone(),
+                    parent_task_ids: parent_task_ids.clone(),
+                    ..Default::default()
+                }),
+            );
+
+            for result_id in &item.expected_output_keys {
+                let Some(result) = self.results.get_mut(result_id) else {
+                    continue;
+                };
+                let previous_owner_id =
+                    std::mem::replace(&mut result.owner_task_id, task_id.clone());
+                self.emit(
+                    session_id,
+                    events::Update::ResultOwnerUpdate(events::ResultOwnerUpdate {
+                        result_id: result_id.clone(),
+                        previous_owner_id,
+                        current_owner_id: task_id.clone(),
+                    }),
+                );
+            }
+
+            response.push(tasks::submit::ResponseItem {
+                task_id,
+                expected_output_ids: item.expected_output_keys,
+                data_dependencies: item.data_dependencies,
+                payload_id: item.payload_id,
+            });
+        }
+        self.submit_ready_tasks();
+
+        Ok(response)
+    }
+}
+
+impl TasksService for ControlPlane {
+    async fn list(
+        self: Arc<Self>,
+        request: tasks::list::Request,
+        _context: RequestContext,
+    ) -> Result<tasks::list::Response, tonic::Status> {
+        self.count("Tasks", "ListTasks");
+
+        let (tasks, total) = self.state().list_tasks(
+            &request.filters,
+            &request.sort,
+            request.with_errors,
+            request.page,
+            request.page_size,
+        )?;
+
+        Ok(tasks::list::Response {
+            tasks: tasks.into_iter().map(summary).collect(),
+            page: request.page,
+            page_size: request.page_size,
+            total,
+        })
+    }
+
+    async fn list_detailed(
+        self: Arc<Self>,
+        request: tasks::list_detailed::Request,
+        _context: RequestContext,
+    ) -> Result<tasks::list_detailed::Response, tonic::Status> {
+        self.count("Tasks", "ListTasksDetailed");
+
+        let (tasks, total) = self.state().list_tasks(
+            &request.filters,
+            &request.sort,
+            request.with_errors,
+            request.page,
+            request.page_size,
+        )?;
+
+        Ok(tasks::list_detailed::Response {
+            tasks,
+            page: request.page,
+            page_size: request.page_size,
+            total,
+        })
+    }
+
+    async fn get(
+        self: Arc<Self>,
+        request: tasks::get::Request,
+        _context: RequestContext,
+    ) -> Result<tasks::get::Response, tonic::Status> {
+        self.count("Tasks", "GetTask");
+
+        Ok(tasks::get::Response {
+            task: self.state().task(&request.task_id)?.clone(),
+        })
+    }
+
+    async fn cancel(
+        self: Arc<Self>,
+        request: tasks::cancel::Request,
+        _context: RequestContext,
+    ) -> Result<tasks::cancel::Response, tonic::Status> {
+        self.count("Tasks", "CancelTasks");
+        let mut state = self.state();
+
+        for task_id in &request.task_ids {
+            state.task(task_id)?;
+        }
+        for task_id in &request.task_ids {
+            state.cancel_task(task_id);
+        }
+
+        Ok(tasks::cancel::Response {
+            tasks: request
+                .task_ids
+                .iter()
+                .filter_map(|task_id| state.tasks.get(task_id).cloned())
+                .map(summary)
+                .collect(),
+        })
+    }
+
+    async fn get_result_ids(
+        self: Arc<Self>,
+        request: tasks::get_result_ids::Request,
+        _context: RequestContext,
+    ) -> Result<tasks::get_result_ids::Response, tonic::Status> {
+        self.count("Tasks", "GetResultIds");
+        let state = self.state();
+
+        Ok(tasks::get_result_ids::Response {
+            task_results: request
+                .task_ids
+                .into_iter()
+                .map(|task_id| {
+                    let output_ids = state.task(&task_id)?.expected_output_ids.clone();
+                    Ok((task_id, output_ids))
+                })
+                .collect::<Result<_, tonic::Status>>()?,
+        })
+    }
+
+    async fn count_status(
+        self: Arc<Self>,
+        request: tasks::count_status::Request,
+        _context: RequestContext,
+    ) -> Result<tasks::count_status::Response, tonic::Status> {
+        self.count("Tasks", "CountTasksByStatus");
+
+        let mut counts = BTreeMap::<TaskStatus, i32>::new();
+        for task in self.state().tasks.values() {
+            if request.filters.matches(task) {
+                *counts.entry(task.status.clone()).or_default() += 1;
+            }
+        }
+
+        Ok(tasks::count_status::Response {
+            status: counts
+                .into_iter()
+                .map(|(status, count)| StatusCount { status, count })
+                .collect(),
+        })
+    }
+
+    async fn submit(
+        self: Arc<Self>,
+        request: tasks::submit::Request,
+        _context: RequestContext,
+    ) -> Result<tasks::submit::Response, tonic::Status> {
+        self.count("Tasks", "SubmitTasks");
+
+        Ok(tasks::submit::Response {
+            items: self.state().submit_tasks(
+                &request.session_id,
+                request.task_options,
+                request.items,
+                None,
+            )?,
+        })
+    }
+}
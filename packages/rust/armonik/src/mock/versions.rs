@@ -0,0 +1,21 @@
+use std::sync::Arc;
+
+use crate::server::{RequestContext, VersionsService};
+use crate::versions;
+
+use super::ControlPlane;
+
+impl VersionsService for ControlPlane {
+    async fn list(
+        self: Arc<Self>,
+        _request: versions::list::Request,
+        _context: RequestContext,
+    ) -> Result<versions::list::Response, tonic::Status> {
+        self.count("Versions", "ListVersions");
+
+        Ok(versions::list::Response {
+            core: String::from("mock"),
+            api: String::from(env!("CARGO_PKG_VERSION")),
+        })
+    }
+}
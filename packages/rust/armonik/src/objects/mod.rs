@@ -34,6 +34,7 @@ mod filters;
 mod init_keyed_data_stream;
 mod init_task_request;
 mod output;
+mod pagination;
 mod result_request;
 mod result_status;
 mod session;
@@ -72,6 +73,7 @@ pub use filters::*;
 pub use init_keyed_data_stream::InitKeyedDataStream;
 pub use init_task_request::InitTaskRequest;
 pub use output::Output;
+pub use pagination::{Page, PagedRequest};
 pub use result_request::ResultRequest;
 pub use result_status::ResultStatus;
 pub use session::Session;
@@ -0,0 +1,65 @@
+/// A request for one page of a listing, which can be sent again for the next one.
+///
+/// Implemented by every `list::Request` of the services that paginate their listings.
+pub trait PagedRequest: Clone {
+    /// What one page of the listing comes back as
+    type Response: Page;
+
+    /// Get the page number, starting at 0
+    fn page(&self) -> i32;
+    /// Set the page to ask for
+    fn set_page(&mut self, page: i32);
+    /// Get the number of items per page
+    fn page_size(&self) -> i32;
+}
+
+/// One page of a listing, as answered to a [`PagedRequest`].
+pub trait Page {
+    /// What is listed
+    type Item;
+
+    /// Get the number of items across every page, at the time this one was read
+    fn total(&self) -> i32;
+    /// Take the items of this page
+    fn into_items(self) -> Vec<Self::Item>;
+}
+
+macro_rules! impl_paged {
+    ($($module:ident::$rpc:ident { $items:ident: $Item:ty }),* $(,)?) => {
+        $(
+            impl PagedRequest for super::$module::$rpc::Request {
+                type Response = super::$module::$rpc::Response;
+
+                fn page(&self) -> i32 {
+                    self.page
+                }
+                fn set_page(&mut self, page: i32) {
+                    self.page = page;
+                }
+                fn page_size(&self) -> i32 {
+                    self.page_size
+                }
+            }
+
+            impl Page for super::$module::$rpc::Response {
+                type Item = $Item;
+
+                fn total(&self) -> i32 {
+                    self.total
+                }
+                fn into_items(self) -> Vec<Self::Item> {
+                    self.$items
+                }
+            }
+        )*
+    };
+}
+
+impl_paged! {
+    applications::list { applications: super::applications::Raw },
+    partitions::list { partitions: super::partitions::Raw },
+    results::list { results: super::results::Raw },
+    sessions::list { sessions: super::sessions::Raw },
+    tasks::list { tasks: super::tasks::Summary },
+    tasks::list_detailed { tasks: super::tasks::Raw },
+}
@@ -0,0 +1,128 @@
+//! Who calls: an [`Authenticator`] tells, from the context of a call, the user it is made as, before the
+//! call reaches its service. Services then check what that user is granted with
+//! [`RequestContext::require_permission`].
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tonic::body::Body;
+use tonic::codegen::http;
+
+use crate::auth;
+
+use super::RequestContext;
+
+/// Calls to the health service are let through as they are: probes rarely carry credentials.
+const HEALTH: &str = "/grpc.health.v1.Health/";
+
+/// Tell the user a call is made as, from what the caller presented: its certificate, the headers it
+/// sent...
+///
+/// Given to [`ArmoniKServerBuilder::authenticator`](super::ArmoniKServerBuilder::authenticator), it is
+/// run before every call but those of the health service.
+pub trait Authenticator: Send + Sync + 'static {
+    /// Get the user the call is made as, `None` to let it through anonymously, or the status to fail
+    /// it with, `Unauthenticated` typically.
+    fn authenticate(
+        &self,
+        context: &RequestContext,
+    ) -> impl Future<Output = Result<Option<auth::User>, tonic::Status>> + Send;
+}
+
+type Authenticate = Arc<
+    dyn Fn(RequestContext) -> BoxFuture<'static, Result<Option<auth::User>, tonic::Status>>
+        + Send
+        + Sync,
+>;
+
+/// Authenticates the calls to the services it wraps, with the [`Authenticator`] it was given if any.
+#[derive(Clone, Default)]
+pub(crate) struct AuthenticationLayer {
+    authenticate: Option<Authenticate>,
+}
+
+impl AuthenticationLayer {
+    pub(crate) fn new(authenticator: impl Authenticator) -> Self {
+        let authenticator = Arc::new(authenticator);
+        Self {
+            authenticate: Some(Arc::new(move |context| {
+                let authenticator = authenticator.clone();
+                async move { authenticator.authenticate(&context).await }.boxed()
+            })),
+        }
+    }
+}
+
+impl std::fmt::Debug for AuthenticationLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthenticationLayer")
+            .field("authenticator", &self.authenticate.is_some())
+            .finish()
+    }
+}
+
+impl<S> tower_layer::Layer<S> for AuthenticationLayer {
+    type Service = Authentication<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Authentication {
+            inner,
+            authenticate: self.authenticate.clone(),
+        }
+    }
+}
+
+/// A service whose calls are authenticated first.
+#[derive(Clone)]
+pub(crate) struct Authentication<S> {
+    inner: S,
+    authenticate: Option<Authenticate>,
+}
+
+impl<S> tower_service::Service<http::Request<Body>> for Authentication<S>
+where
+    S: tower_service::Service<
+            http::Request<Body>,
+            Response = http::Response<Body>,
+            Error = Infallible,
+        > + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        let authenticate = match &self.authenticate {
+            Some(authenticate) if !request.uri().path().starts_with(HEALTH) => authenticate.clone(),
+            _ => return self.inner.call(request).boxed(),
+        };
+        // The service driven ready is the one to call, and a clone takes its place.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        async move {
+            let context =
+                RequestContext::new(request.headers().clone(), request.extensions().clone());
+            match authenticate(context).await {
+                Ok(Some(user)) => {
+                    request.extensions_mut().insert(user);
+                }
+                Ok(None) => {}
+                Err(status) => return Ok(status.into_http()),
+            }
+            inner.call(request).await
+        }
+        .boxed()
+    }
+}
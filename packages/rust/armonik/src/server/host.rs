@@ -0,0 +1,540 @@
+//! Hosting services: listening on an address, with TLS and client certificates as configured, along with
+//! the standard `grpc.health.v1` service, until asked to shut down. With the `reflection` feature, the
+//! `grpc.reflection` services can be served along too.
+//!
+//! ```no_run
+//! use armonik::server::{ArmoniKServer, ServerConfig, SessionsService, TasksService};
+//!
+//! async fn host(
+//!     sessions: impl SessionsService + Send + Sync + 'static,
+//!     tasks: impl TasksService + Send + Sync + 'static,
+//!     shutdown: impl std::future::Future<Output = ()>,
+//! ) -> Result<(), Box<dyn std::error::Error>> {
+//!     ArmoniKServer::builder()
+//!         .sessions(sessions)
+//!         .tasks(tasks)
+//!         .serve_with_shutdown(&ServerConfig::from_env()?, shutdown)
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use armonik_transport::reexports::rustls::pki_types::CertificateDer;
+use armonik_transport::reexports::tokio_rustls::{server::TlsStream, TlsAcceptor};
+use armonik_transport::{ConfigError, ServerConfig};
+use futures::{Stream, StreamExt};
+use snafu::{ResultExt, Snafu};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tonic::body::Body;
+use tonic::codegen::http;
+use tonic::server::NamedService;
+use tonic::transport::server::{Connected, TcpConnectInfo, TcpIncoming};
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
+use super::authentication::AuthenticationLayer;
+use super::*;
+
+/// Time a client has to complete the TLS handshake once connected.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of connections through their TLS handshake that wait for `tonic` to take them.
+const HANDSHAKEN_BACKLOG: usize = 64;
+
+/// Services to serve, collected by [`ArmoniKServer::builder`].
+///
+/// Any subset of them: only those given are served, each reported as serving by the `grpc.health.v1`
+/// service added along with them.
+#[derive(Default)]
+pub struct ArmoniKServerBuilder {
+    routes: tonic::service::RoutesBuilder,
+    /// Names of the services, as the health service knows them
+    services: Vec<&'static str>,
+    #[cfg(feature = "reflection")]
+    reflection: bool,
+    authentication: AuthenticationLayer,
+}
+
+impl std::fmt::Debug for ArmoniKServerBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArmoniKServerBuilder")
+            .field("services", &self.services)
+            .field("authentication", &self.authentication)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ArmoniKServerBuilder {
+    /// Serve any gRPC service, generated by `tonic` or not, along with those of ArmoniK.
+    pub fn add_service<S>(mut self, service: S) -> Self
+    where
+        S: tower_service::Service<
+                http::Request<Body>,
+                Response = http::Response<Body>,
+                Error = Infallible,
+            > + NamedService
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        self.routes.add_service(service);
+        self.services.push(S::NAME);
+        self
+    }
+
+    /// Serve the applications of `service`.
+    pub fn applications(self, service: impl ApplicationsService + Send + Sync + 'static) -> Self {
+        self.add_service(service.applications_server())
+    }
+
+    /// Serve the authentication service `service`, telling clients who they are.
+    pub fn auth(self, service: impl AuthService + Send + Sync + 'static) -> Self {
+        self.add_service(service.auth_server())
+    }
+
+    /// Serve the events of `service`, streamed to the clients that subscribe.
+    pub fn events(self, service: impl EventsService + Send + Sync + 'static) -> Self {
+        self.add_service(service.events_server())
+    }
+
+    /// Serve the health checks of the ArmoniK components `service` knows of.
+    ///
+    /// Not to be mixed up with the `grpc.health.v1` service, served along with any service.
+    pub fn health_checks(self, service: impl HealthChecksService + Send + Sync + 'static) -> Self {
+        self.add_service(service.health_checks_server())
+    }
+
+    /// Serve the partitions of `service`.
+    pub fn partitions(self, service: impl PartitionsService + Send + Sync + 'static) -> Self {
+        self.add_service(service.partitions_server())
+    }
+
+    /// Serve the results of `service`.
+    pub fn results(self, service: impl ResultsService + Send + Sync + 'static) -> Self {
+        self.add_service(service.results_server())
+    }
+
+    /// Serve the sessions of `service`.
+    pub fn sessions(self, service: impl SessionsService + Send + Sync + 'static) -> Self {
+        self.add_service(service.sessions_server())
+    }
+
+    /// Serve the legacy submitter service `service`.
+    pub fn submitter(self, service: impl SubmitterService + Send + Sync + 'static) -> Self {
+        self.add_service(service.submitter_server())
+    }
+
+    /// Serve the tasks of `service`.
+    pub fn tasks(self, service: impl TasksService + Send + Sync + 'static) -> Self {
+        self.add_service(service.tasks_server())
+    }
+
+    /// Serve the versions `service` reports.
+    pub fn versions(self, service: impl VersionsService + Send + Sync + 'static) -> Self {
+        self.add_service(service.versions_server())
+    }
+
+    /// Serve the agent `service`, for workers to call back while they process tasks.
+    #[cfg(feature = "agent")]
+    pub fn agent(self, service: impl AgentService + Send + Sync + 'static) -> Self {
+        self.add_service(service.agent_server())
+    }
+
+    /// Serve the worker `service`, processing the tasks the agent hands it.
+    #[cfg(feature = "worker")]
+    pub fn worker(self, service: impl WorkerService + Send + Sync + 'static) -> Self {
+        self.add_service(service.worker_server())
+    }
+
+    /// Serve the `grpc.reflection` services too, v1 and v1alpha, describing the services given to the
+    /// builder along with the health service.
+    ///
+    /// Tools such as `grpcurl` can then list and call them without the protos at hand.
+    #[cfg(feature = "reflection")]
+    pub fn reflection(mut self) -> Self {
+        self.reflection = true;
+        self
+    }
+
+    /// Authenticate every call with `authenticator` before it reaches its service, but those of the
+    /// health service.
+    ///
+    /// The services then find the user a call is made as in its [`RequestContext`].
+    pub fn authenticator(mut self, authenticator: impl Authenticator) -> Self {
+        self.authentication = AuthenticationLayer::new(authenticator);
+        self
+    }
+
+    /// Listen on the address of `config`, ready to serve.
+    ///
+    /// Binding apart from serving tells the address listened on before the first call comes, which is
+    /// what port `0` needs.
+    pub async fn bind(self, config: &ServerConfig) -> Result<ArmoniKServer, ServeError> {
+        let tls = config
+            .tls_config()
+            .context(ConfigSnafu {})?
+            .map(|tls| TlsAcceptor::from(Arc::new(tls)));
+        let listener = tokio::net::TcpListener::bind(config.address)
+            .await
+            .context(BindSnafu {
+                address: config.address,
+            })?;
+        let address = listener.local_addr().context(BindSnafu {
+            address: config.address,
+        })?;
+
+        let Self {
+            mut routes,
+            services,
+            authentication,
+            ..
+        } = self.with_reflection()?;
+        let (health, health_server) = tonic_health::server::health_reporter();
+        for service in &services {
+            health
+                .set_service_status(service, ServingStatus::Serving)
+                .await;
+        }
+        routes.add_service(health_server);
+
+        Ok(ArmoniKServer {
+            listener,
+            address,
+            config: config.clone(),
+            tls,
+            routes: routes.routes(),
+            health,
+            services,
+            authentication,
+        })
+    }
+
+    /// Add the reflection services if asked to, describing the services added so far.
+    #[cfg(feature = "reflection")]
+    fn with_reflection(self) -> Result<Self, ServeError> {
+        use tonic_reflection::server::Builder;
+
+        if !self.reflection {
+            return Ok(self);
+        }
+
+        let names = self
+            .services
+            .iter()
+            .copied()
+            .chain([
+                "grpc.health.v1.Health",
+                "grpc.reflection.v1.ServerReflection",
+                "grpc.reflection.v1alpha.ServerReflection",
+            ])
+            .collect::<Vec<_>>();
+        let builder = || {
+            names.iter().fold(
+                Builder::configure()
+                    .register_encoded_file_descriptor_set(crate::api::FILE_DESCRIPTOR_SET)
+                    .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET),
+                |builder, name| builder.with_service_name(*name),
+            )
+        };
+        let v1 = builder().build_v1().context(ReflectionSnafu {})?;
+        let v1alpha = builder().build_v1alpha().context(ReflectionSnafu {})?;
+        Ok(self.add_service(v1).add_service(v1alpha))
+    }
+
+    #[cfg(not(feature = "reflection"))]
+    fn with_reflection(self) -> Result<Self, ServeError> {
+        Ok(self)
+    }
+
+    /// Serve on the address of `config` until the process ends or serving fails.
+    pub async fn serve(self, config: &ServerConfig) -> Result<(), ServeError> {
+        self.bind(config).await?.serve().await
+    }
+
+    /// Serve on the address of `config` until `signal` completes, then finish the calls in progress.
+    pub async fn serve_with_shutdown(
+        self,
+        config: &ServerConfig,
+        signal: impl Future<Output = ()>,
+    ) -> Result<(), ServeError> {
+        self.bind(config).await?.serve_with_shutdown(signal).await
+    }
+}
+
+/// Services listening on an address, ready to serve
+pub struct ArmoniKServer {
+    listener: tokio::net::TcpListener,
+    address: SocketAddr,
+    config: ServerConfig,
+    tls: Option<TlsAcceptor>,
+    routes: tonic::service::Routes,
+    health: HealthReporter,
+    services: Vec<&'static str>,
+    authentication: AuthenticationLayer,
+}
+
+impl std::fmt::Debug for ArmoniKServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArmoniKServer")
+            .field("address", &self.address)
+            .field("tls", &self.tls.is_some())
+            .field("services", &self.services)
+            .field("authentication", &self.authentication)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ArmoniKServer {
+    /// Collect the services to serve
+    pub fn builder() -> ArmoniKServerBuilder {
+        ArmoniKServerBuilder::default()
+    }
+
+    /// Get the address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Get the reporter of the `grpc.health.v1` service, to report a service as no longer serving, or
+    /// a dependency as unhealthy
+    pub fn health_reporter(&self) -> &HealthReporter {
+        &self.health
+    }
+
+    /// Serve until the process ends or serving fails.
+    pub async fn serve(self) -> Result<(), ServeError> {
+        self.serve_with_shutdown(std::future::pending()).await
+    }
+
+    /// Serve until `signal` completes, then finish the calls in progress.
+    ///
+    /// Every service is reported as not serving as soon as `signal` completes, so that health checks
+    /// take the server out before it stops accepting calls.
+    pub async fn serve_with_shutdown(
+        self,
+        signal: impl Future<Output = ()>,
+    ) -> Result<(), ServeError> {
+        let Self {
+            listener,
+            address,
+            config,
+            tls,
+            routes,
+            health,
+            services,
+            authentication,
+        } = self;
+        tracing::info!(
+            "Serving {} on {address}{}",
+            services.join(", "),
+            if tls.is_some() { " with TLS" } else { "" }
+        );
+
+        let signal = async move {
+            signal.await;
+            tracing::info!("Shutting down the server on {address}");
+            for service in std::iter::once("").chain(services) {
+                health
+                    .set_service_status(service, ServingStatus::NotServing)
+                    .await;
+            }
+        };
+        let incoming = TcpIncoming::from(listener)
+            .with_nodelay(Some(!config.tcp_nagle_algorithm))
+            .with_keepalive(config.tcp_keepalive)
+            .with_keepalive_interval(config.tcp_keepalive_interval)
+            .with_keepalive_retries(config.tcp_keepalive_retries);
+        let mut server = tonic::transport::Server::builder()
+            .http2_keepalive_interval(config.http2_keep_alive_interval)
+            .max_concurrent_streams(config.max_concurrent_streams);
+        if let Some(timeout) = config.http2_keep_alive_timeout {
+            server = server.http2_keepalive_timeout(Some(timeout));
+        }
+        let router = server.layer(authentication).add_routes(routes);
+
+        match tls {
+            None => router.serve_with_incoming_shutdown(incoming, signal).await,
+            Some(tls) => {
+                router
+                    .serve_with_incoming_shutdown(handshakes(incoming, tls), signal)
+                    .await
+            }
+        }
+        .context(ServeSnafu { address })
+    }
+}
+
+/// The connections of `incoming`, once their TLS handshake with `tls` succeeded.
+///
+/// Each handshake goes on in a task of its own, and the connections are handed over in the order their
+/// handshake completed: a client slow to complete its handshake holds up no other. One that fails its
+/// handshake, or takes too long to complete it, is dropped without failing the others: to `tonic`, any
+/// error of the stream that is not of the TCP connection stops the server.
+///
+/// Connections stop being accepted once the stream is dropped.
+fn handshakes(
+    incoming: TcpIncoming,
+    tls: TlsAcceptor,
+) -> impl Stream<Item = std::io::Result<TlsConnection>> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(HANDSHAKEN_BACKLOG);
+    let dropped = {
+        let sender = sender.clone();
+        async move { sender.closed().await }
+    };
+
+    tokio::spawn(async move {
+        let mut incoming = std::pin::pin!(incoming.take_until(dropped));
+        while let Some(tcp) = incoming.next().await {
+            let tcp = match tcp {
+                Ok(tcp) => tcp,
+                // Left for `tonic` to tell whether the listener can go on.
+                Err(err) => {
+                    if sender.send(Err(err)).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let tls = tls.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                let peer = tcp.peer_addr().ok();
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, tls.accept(tcp)).await {
+                    Ok(Ok(stream)) => {
+                        // Sent to no one once the server stopped, which drops the connection.
+                        let _ = sender.send(Ok(TlsConnection::new(stream))).await;
+                    }
+                    Ok(Err(err)) => tracing::debug!("TLS handshake with {peer:?} failed: {err}"),
+                    Err(_) => tracing::debug!("TLS handshake with {peer:?} timed out"),
+                }
+            });
+        }
+    });
+
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        let connection = receiver.recv().await?;
+        Some((connection, receiver))
+    })
+}
+
+/// A connection accepted through TLS, which tells the calls it carries who the client is.
+struct TlsConnection {
+    stream: TlsStream<tokio::net::TcpStream>,
+    info: TlsConnectInfo,
+}
+
+/// What is known of a client connected through TLS, in the extensions of each of its calls.
+#[derive(Debug, Clone)]
+pub(crate) struct TlsConnectInfo {
+    pub(crate) tcp: TcpConnectInfo,
+    /// The certificate chain the client presented, its own first, verified during the handshake
+    pub(crate) certificates: Option<Arc<Vec<CertificateDer<'static>>>>,
+}
+
+impl TlsConnection {
+    fn new(stream: TlsStream<tokio::net::TcpStream>) -> Self {
+        let (tcp, session) = stream.get_ref();
+        let info = TlsConnectInfo {
+            tcp: tcp.connect_info(),
+            certificates: session
+                .peer_certificates()
+                .map(|chain| Arc::new(chain.to_vec())),
+        };
+        Self { stream, info }
+    }
+}
+
+impl Connected for TlsConnection {
+    type ConnectInfo = TlsConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.info.clone()
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// Error hosting services
+#[derive(Debug, Snafu)]
+#[non_exhaustive]
+pub enum ServeError {
+    #[snafu(display("Invalid server configuration [{location}]"))]
+    #[non_exhaustive]
+    Config {
+        #[snafu(source(from(ConfigError, Box::new)))]
+        source: Box<ConfigError>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[cfg(feature = "reflection")]
+    #[snafu(display("Invalid descriptors for the reflection services [{location}]"))]
+    #[non_exhaustive]
+    Reflection {
+        source: tonic_reflection::server::Error,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Could not listen on {address} [{location}]"))]
+    #[non_exhaustive]
+    Bind {
+        source: std::io::Error,
+        address: SocketAddr,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Serving on {address} failed [{location}]"))]
+    #[non_exhaustive]
+    Serve {
+        source: tonic::transport::Error,
+        address: SocketAddr,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+}
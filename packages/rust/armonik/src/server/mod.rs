@@ -5,10 +5,14 @@ mod applications;
 #[cfg(feature = "server")]
 mod auth;
 #[cfg(feature = "server")]
+mod authentication;
+#[cfg(feature = "server")]
 mod events;
 #[cfg(feature = "server")]
 mod health_checks;
 #[cfg(feature = "server")]
+mod host;
+#[cfg(feature = "server")]
 mod partitions;
 mod request_context;
 #[cfg(feature = "server")]
@@ -31,10 +35,14 @@ pub use applications::{ApplicationsService, ApplicationsServiceExt};
 #[cfg(feature = "server")]
 pub use auth::{AuthService, AuthServiceExt};
 #[cfg(feature = "server")]
+pub use authentication::Authenticator;
+#[cfg(feature = "server")]
 pub use events::{EventsService, EventsServiceExt};
 #[cfg(feature = "server")]
 pub use health_checks::{HealthChecksService, HealthChecksServiceExt};
 #[cfg(feature = "server")]
+pub use host::{ArmoniKServer, ArmoniKServerBuilder, ServeError};
+#[cfg(feature = "server")]
 pub use partitions::{PartitionsService, PartitionsServiceExt};
 pub use request_context::RequestContext;
 #[cfg(feature = "server")]
@@ -47,6 +55,10 @@ pub use submitter::{SubmitterService, SubmitterServiceExt};
 pub use tasks::{TasksService, TasksServiceExt};
 #[cfg(feature = "server")]
 pub use versions::{VersionsService, VersionsServiceExt};
+// Re-exported here, so that a server is configured through the server rather than through the
+// transport crate, as a client is through the client.
+#[cfg(feature = "server")]
+pub use armonik_transport::{ConfigError, PeerCertificate, ServerConfig, ServerConfigArgs};
 #[cfg(feature = "worker")]
 pub use worker::{WorkerService, WorkerServiceExt};
 
@@ -87,11 +99,15 @@ macro_rules! impl_trait_methods {
             let request = request.into();
             tracing::trace!("Request: {request:?}");
             let context = crate::server::RequestContext::new(metadata_map.into_headers(), extensions);
+            let span = context.follow_trace(tracing::debug_span!(stringify!($inner)));
+            let cancellation = context.cancel_on_drop();
             let fut = tracing_futures::Instrument::instrument(
                 $inner($self, request, context),
-                tracing::debug_span!(stringify!($inner)),
+                span,
             );
-            match fut.await {
+            let res = fut.await;
+            cancellation.disarm();
+            match res {
                 Ok(res) => {
                     tracing::trace!("Response: {res:?}");
                     Ok(tonic::Response::new(res.into()))
@@ -107,7 +123,8 @@ macro_rules! impl_trait_methods {
         {
             let (metadata_map, extensions, request) = $request.into_parts();
             let context = crate::server::RequestContext::new(metadata_map.into_headers(), extensions);
-            let span = tracing::debug_span!(stringify!($inner));
+            let span = context.follow_trace(tracing::debug_span!(stringify!($inner)));
+            let cancellation = context.cancel_on_drop();
             let stream = tracing_futures::Instrument::instrument(
                 tonic::codegen::tokio_stream::StreamExt::map(request, |r| match r {
                     Ok(r) => {
@@ -129,7 +146,9 @@ macro_rules! impl_trait_methods {
                 ),
                 tracing::trace_span!(parent: &span, "rpc"),
             );
-            match fut.await {
+            let res = fut.await;
+            cancellation.disarm();
+            match res {
                 Ok(res) => {
                     tracing::trace!("Response: {res:?}");
                     Ok(tonic::Response::new(res.into()))
@@ -147,7 +166,8 @@ macro_rules! impl_trait_methods {
             let request = request.into();
             tracing::trace!("Request: {request:?}");
             let context = crate::server::RequestContext::new(metadata_map.into_headers(), extensions);
-            let span = tracing::debug_span!(stringify!($inner));
+            let span = context.follow_trace(tracing::debug_span!(stringify!($inner)));
+            let cancellation = context.cancel_on_drop();
             let fut = tracing_futures::Instrument::instrument(
                 $inner($self, request, context),
                 tracing::trace_span!(parent: &span, "rpc")
@@ -172,10 +192,14 @@ macro_rules! impl_trait_methods {
                     Ok(tonic::Response::new(
                         crate::server::ServerStream{
                             receiver: stream,
+                            cancellation: Some(cancellation),
                         },
                     ))
                 }
-                Err(err) => Err(err),
+                Err(err) => {
+                    cancellation.disarm();
+                    Err(err)
+                }
             }
         }
     };
@@ -185,6 +209,8 @@ pub struct ServerStream<T> {
     receiver: tracing_futures::Instrumented<
         futures::stream::BoxStream<'static, Result<T, tonic::Status>>,
     >,
+    /// Cancels the call if the stream is dropped before its end
+    cancellation: Option<tokio_util::sync::DropGuard>,
 }
 
 impl<T> crate::reexports::tokio_stream::Stream for ServerStream<T> {
@@ -194,7 +220,13 @@ impl<T> crate::reexports::tokio_stream::Stream for ServerStream<T> {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.receiver.inner_mut().as_mut().poll_next(cx)
+        let item = self.receiver.inner_mut().as_mut().poll_next(cx);
+        if let std::task::Poll::Ready(None) = item {
+            if let Some(cancellation) = self.cancellation.take() {
+                cancellation.disarm();
+            }
+        }
+        item
     }
 }
 
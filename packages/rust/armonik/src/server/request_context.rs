@@ -1,3 +1,12 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tonic::transport::server::TcpConnectInfo;
+
+#[cfg(feature = "server")]
+use super::host::TlsConnectInfo;
+use crate::auth;
 use crate::reexports::http::{Extensions, HeaderMap};
 
 /// Context of the request
@@ -7,12 +16,29 @@ pub struct RequestContext {
     headers: HeaderMap,
     /// Extensions of the request
     extensions: Extensions,
+    /// Trace context the caller sent along with the request
+    #[cfg(feature = "opentelemetry")]
+    trace_context: opentelemetry::Context,
+    /// Time the caller gave the call, from its `grpc-timeout`
+    timeout: Option<Duration>,
+    /// Instant the call has to be answered by, `timeout` after it was received
+    deadline: Option<Instant>,
+    /// Cancelled when the caller gives up on the call
+    cancellation: CancellationToken,
 }
 
 impl RequestContext {
     /// Create a new context from headers and extensions
+    ///
+    /// With the `opentelemetry` feature, the trace context the caller sent in the headers is read here.
     pub fn new(headers: HeaderMap, extensions: Extensions) -> Self {
+        let timeout = grpc_timeout(&headers);
         Self {
+            #[cfg(feature = "opentelemetry")]
+            trace_context: crate::telemetry::extract(&headers),
+            timeout,
+            deadline: timeout.and_then(|timeout| Instant::now().checked_add(timeout)),
+            cancellation: CancellationToken::new(),
             headers,
             extensions,
         }
@@ -35,4 +61,141 @@ impl RequestContext {
     pub fn extensions_mut(&mut self) -> &mut Extensions {
         &mut self.extensions
     }
+
+    /// Get the trace context the caller sent, empty if it sent none
+    #[cfg(feature = "opentelemetry")]
+    pub fn trace_context(&self) -> &opentelemetry::Context {
+        &self.trace_context
+    }
+
+    /// Get the address of the caller, if it called over TCP
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        let address = self
+            .extensions
+            .get::<TcpConnectInfo>()
+            .and_then(TcpConnectInfo::remote_addr);
+        #[cfg(feature = "server")]
+        let address = address.or_else(|| {
+            self.extensions
+                .get::<TlsConnectInfo>()
+                .and_then(|info| info.tcp.remote_addr())
+        });
+        address
+    }
+
+    /// Get the certificate chain the caller presented, its own certificate first, empty if it
+    /// presented none
+    ///
+    /// The chain was verified against the client CA of the server during the handshake, by an
+    /// [`ArmoniKServer`](crate::server::ArmoniKServer) serving TLS.
+    #[cfg(feature = "server")]
+    pub fn client_certificates(&self) -> Vec<armonik_transport::PeerCertificate> {
+        self.extensions
+            .get::<TlsConnectInfo>()
+            .and_then(|info| info.certificates.as_ref())
+            .map(|chain| {
+                chain
+                    .iter()
+                    .cloned()
+                    .filter_map(armonik_transport::PeerCertificate::parse)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the certificate the caller presented, if it presented one
+    #[cfg(feature = "server")]
+    pub fn client_certificate(&self) -> Option<armonik_transport::PeerCertificate> {
+        self.client_certificates().into_iter().next()
+    }
+
+    /// Get the time the caller gave the call, if it set a `grpc-timeout`
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Get the instant the caller stops waiting for the response, if it set a `grpc-timeout`
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Wait for the caller to give up on the call: cancel it, reach its deadline, or go away.
+    ///
+    /// The handler itself is dropped then; this is for the work it handed over, to other tasks for
+    /// instance. A call answered is never cancelled.
+    pub fn cancelled(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        self.cancellation.clone().cancelled_owned()
+    }
+
+    /// Check whether the caller gave up on the call
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Get a token cancelled when the caller gives up on the call
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.child_token()
+    }
+
+    /// Get the user the call was authenticated as, if any
+    ///
+    /// It is the [`auth::User`] in the extensions of the request, where the `Authenticator` of the
+    /// server puts it.
+    pub fn user(&self) -> Option<&auth::User> {
+        self.extensions.get::<auth::User>()
+    }
+
+    /// Check that the call was authenticated as a user granted `permission`, such as
+    /// `"Sessions:CreateSession"`, and get that user.
+    ///
+    /// Fails with `Unauthenticated` when no user is known, and `PermissionDenied` when the user is not
+    /// granted `permission`; either way, ready to be returned by the handler.
+    pub fn require_permission(&self, permission: &str) -> Result<&auth::User, tonic::Status> {
+        let Some(user) = self.user() else {
+            return Err(tonic::Status::unauthenticated(format!(
+                "{permission} requires an authenticated user"
+            )));
+        };
+        if !user.permissions.iter().any(|granted| granted == permission) {
+            return Err(tonic::Status::permission_denied(format!(
+                "{} is not granted {permission}",
+                user.username
+            )));
+        }
+        Ok(user)
+    }
+
+    /// Parent `span`, the span serving the request, on the trace context the caller sent.
+    pub(crate) fn follow_trace(&self, span: tracing::Span) -> tracing::Span {
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::set_parent(&span, &self.trace_context);
+        span
+    }
+
+    /// Cancel the call when the guard returned is dropped, unless it was disarmed first.
+    ///
+    /// Held for as long as the call goes on, so that its being dropped before the response is what
+    /// tells the caller gave up.
+    pub(crate) fn cancel_on_drop(&self) -> DropGuard {
+        self.cancellation.clone().drop_guard()
+    }
+}
+
+/// The timeout of the `grpc-timeout` header: up to 8 digits, then the unit.
+fn grpc_timeout(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get("grpc-timeout")?.to_str().ok()?;
+    let (amount, unit) = value.split_at(value.len().checked_sub(1)?);
+    if amount.is_empty() || amount.len() > 8 || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount = amount.parse::<u64>().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
 }
@@ -0,0 +1,74 @@
+//! W3C trace context crossing the wire: written into the metadata of every call, read back from the
+//! headers of every request served.
+//!
+//! The format is that of the global propagator, as set with
+//! [`opentelemetry::global::set_text_map_propagator`]: nothing is sent until the application sets one,
+//! usually `TraceContextPropagator` for `traceparent` and `tracestate`.
+
+#[cfg(any(feature = "_gen-client", feature = "_gen-server"))]
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Write the trace context of the current span into `metadata`.
+#[cfg(feature = "_gen-client")]
+pub(crate) fn inject(metadata: &mut tonic::metadata::MetadataMap) {
+    let context = tracing::Span::current().context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut MetadataInjector(metadata))
+    });
+}
+
+/// Read the trace context the caller sent in `headers`, empty if it sent none.
+#[cfg(feature = "_gen-server")]
+pub(crate) fn extract(headers: &tonic::codegen::http::HeaderMap) -> opentelemetry::Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        // Onto an empty context rather than the current one, which is whatever span the server is
+        // polled in: only what the caller sent counts.
+        propagator.extract_with_context(&opentelemetry::Context::new(), &HeaderExtractor(headers))
+    })
+}
+
+/// Parent `span` on `context`, if that one comes from a caller.
+///
+/// A request sent without trace context leaves the span where it is, under the span of whatever serves
+/// it, instead of making it the root of a trace of its own.
+#[cfg(feature = "_gen-server")]
+pub(crate) fn set_parent(span: &tracing::Span, context: &opentelemetry::Context) {
+    use opentelemetry::trace::TraceContextExt;
+
+    if context.span().span_context().is_valid() {
+        // Fails only for a span that is disabled, which has no trace to be part of anyway.
+        let _ = span.set_parent(context.clone());
+    }
+}
+
+#[cfg(feature = "_gen-client")]
+struct MetadataInjector<'a>(&'a mut tonic::metadata::MetadataMap);
+
+#[cfg(feature = "_gen-client")]
+impl opentelemetry::propagation::Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        let Ok(key) = tonic::metadata::MetadataKey::from_bytes(key.as_bytes()) else {
+            tracing::debug!("Trace context key `{key}` is not valid metadata, it is not sent");
+            return;
+        };
+        let Ok(value) = value.parse() else {
+            tracing::debug!("Trace context value of `{key}` is not valid metadata, it is not sent");
+            return;
+        };
+        self.0.insert(key, value);
+    }
+}
+
+#[cfg(feature = "_gen-server")]
+struct HeaderExtractor<'a>(&'a tonic::codegen::http::HeaderMap);
+
+#[cfg(feature = "_gen-server")]
+impl opentelemetry::propagation::Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key)?.to_str().ok()
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
@@ -0,0 +1,209 @@
+use snafu::ResultExt;
+
+use super::serve::{EnvSnafu, InvalidSocketTypeSnafu, MissingAddressSnafu, WorkerError};
+
+/// How a channel between the worker and the agent is reached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SocketType {
+    /// The address is the path of a Unix domain socket
+    #[default]
+    UnixDomainSocket,
+    /// The address is a URI, as in `http://localhost:10667`
+    Tcp,
+}
+
+impl std::str::FromStr for SocketType {
+    type Err = ();
+
+    /// The names of the C# `GrpcSocketType`, in any case; empty is the default.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "" | "unixdomainsocket" => Ok(Self::UnixDomainSocket),
+            "tcp" => Ok(Self::Tcp),
+            _ => Err(()),
+        }
+    }
+}
+
+/// One of the channels between the worker and the agent
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GrpcChannel {
+    /// Path of the socket, or URI for TCP
+    pub address: String,
+    /// How `address` is to be read
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub socket_type: SocketType,
+}
+
+impl GrpcChannel {
+    /// Read the channel from the `{prefix}__Address` and `{prefix}__SocketType` variables.
+    fn from_env(prefix: &str) -> Result<Self, WorkerError> {
+        let address_name = format!("{prefix}__Address");
+        let address = armonik_transport::read_env(&address_name).context(EnvSnafu {})?;
+        if address.is_empty() {
+            return MissingAddressSnafu { name: address_name }.fail();
+        }
+
+        let socket_type_name = format!("{prefix}__SocketType");
+        let socket_type = armonik_transport::read_env(&socket_type_name).context(EnvSnafu {})?;
+        let Ok(socket_type) = socket_type.parse() else {
+            return InvalidSocketTypeSnafu {
+                name: socket_type_name,
+                value: socket_type,
+            }
+            .fail();
+        };
+
+        Ok(Self {
+            address,
+            socket_type,
+        })
+    }
+}
+
+/// Options to configure the connections between the worker and the agent, mirroring the
+/// `ComputePlane` section of the C# SDK.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ComputePlane {
+    /// Channel used by the agent to send tasks to the worker
+    pub worker_channel: GrpcChannel,
+    /// Channel used by the worker to send requests to the agent
+    pub agent_channel: GrpcChannel,
+}
+
+impl ComputePlane {
+    /// Both channels, given explicitly.
+    pub fn new(worker_channel: GrpcChannel, agent_channel: GrpcChannel) -> Self {
+        Self {
+            worker_channel,
+            agent_channel,
+        }
+    }
+
+    /// Read both channels from the `ComputePlane__WorkerChannel__*` and
+    /// `ComputePlane__AgentChannel__*` environment variables, as set by the agent in a compute pod.
+    pub fn from_env() -> Result<Self, WorkerError> {
+        Ok(Self {
+            worker_channel: GrpcChannel::from_env("ComputePlane__WorkerChannel")?,
+            agent_channel: GrpcChannel::from_env("ComputePlane__AgentChannel")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The variables of both channels set at once, and cleared whatever the outcome.
+    fn with_vars<T>(vars: &[(&str, &str)], body: impl FnOnce() -> T) -> T {
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+        let outcome = body();
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+        outcome
+    }
+
+    #[test]
+    #[serial_test::serial(env)]
+    fn channels_default_to_unix_domain_sockets() {
+        // What the agent sets in a compute pod: addresses, and no socket type.
+        let compute_plane = with_vars(
+            &[
+                (
+                    "ComputePlane__WorkerChannel__Address",
+                    "/cache/armonik_worker.sock",
+                ),
+                (
+                    "ComputePlane__AgentChannel__Address",
+                    "/cache/armonik_agent.sock",
+                ),
+            ],
+            ComputePlane::from_env,
+        )
+        .expect("valid");
+
+        assert_eq!(
+            compute_plane.worker_channel,
+            GrpcChannel {
+                address: String::from("/cache/armonik_worker.sock"),
+                socket_type: SocketType::UnixDomainSocket,
+            }
+        );
+        assert_eq!(
+            compute_plane.agent_channel.address,
+            "/cache/armonik_agent.sock"
+        );
+    }
+
+    #[test]
+    #[serial_test::serial(env)]
+    fn socket_types_are_read_in_any_case() {
+        let compute_plane = with_vars(
+            &[
+                (
+                    "ComputePlane__WorkerChannel__Address",
+                    "http://0.0.0.0:10667",
+                ),
+                ("ComputePlane__WorkerChannel__SocketType", "tcp"),
+                (
+                    "ComputePlane__AgentChannel__Address",
+                    "/cache/armonik_agent.sock",
+                ),
+                ("ComputePlane__AgentChannel__SocketType", "UnixDomainSocket"),
+            ],
+            ComputePlane::from_env,
+        )
+        .expect("valid");
+
+        assert_eq!(compute_plane.worker_channel.socket_type, SocketType::Tcp);
+        assert_eq!(
+            compute_plane.agent_channel.socket_type,
+            SocketType::UnixDomainSocket
+        );
+    }
+
+    #[test]
+    #[serial_test::serial(env)]
+    fn a_missing_address_names_its_variable() {
+        let error = with_vars(
+            &[("ComputePlane__WorkerChannel__Address", "/tmp/worker.sock")],
+            ComputePlane::from_env,
+        )
+        .expect_err("the agent channel has no address");
+
+        assert!(
+            error
+                .to_string()
+                .contains("ComputePlane__AgentChannel__Address"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    #[serial_test::serial(env)]
+    fn an_unknown_socket_type_is_reported_with_its_value() {
+        let error = with_vars(
+            &[
+                ("ComputePlane__WorkerChannel__Address", "/tmp/worker.sock"),
+                ("ComputePlane__WorkerChannel__SocketType", "pipe"),
+                ("ComputePlane__AgentChannel__Address", "/tmp/agent.sock"),
+            ],
+            ComputePlane::from_env,
+        )
+        .expect_err("`pipe` is not a socket type");
+
+        let rendered = error.to_string();
+        assert!(
+            rendered.contains("ComputePlane__WorkerChannel__SocketType"),
+            "{rendered}"
+        );
+        assert!(rendered.contains("pipe"), "{rendered}");
+    }
+}
@@ -0,0 +1,25 @@
+//! ArmoniK objects related to the Worker service, and what it takes to write a worker on top of them.
+//!
+//! The objects are those of every other service. With the `worker` feature, this module also holds the
+//! SDK side: a [`TaskHandler`] over the request the agent sends, a [`Processor`] trait which is all an
+//! application has to implement, and [`run`] to serve it in a compute pod.
+
+pub use crate::objects::worker::{health_check, process};
+
+#[cfg(feature = "worker")]
+mod compute_plane;
+#[cfg(feature = "worker")]
+mod processor;
+#[cfg(feature = "worker")]
+mod serve;
+#[cfg(feature = "worker")]
+mod task_handler;
+
+#[cfg(feature = "worker")]
+pub use compute_plane::{ComputePlane, GrpcChannel, SocketType};
+#[cfg(feature = "worker")]
+pub use processor::{Processor, WorkerWrapper};
+#[cfg(feature = "worker")]
+pub use serve::{run, serve, WorkerError};
+#[cfg(feature = "worker")]
+pub use task_handler::{TaskHandler, TaskHandlerError};
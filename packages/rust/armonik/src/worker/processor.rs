@@ -0,0 +1,85 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::client::Agent;
+use crate::server::{RequestContext, WorkerService};
+use crate::Output;
+
+use super::{health_check, process, TaskHandler};
+
+/// The application side of a worker: what to do with one task.
+///
+/// This is all an application has to write. [`WorkerWrapper`] turns it into a [`WorkerService`],
+/// building a [`TaskHandler`] for each task the agent sends.
+pub trait Processor<T = crate::client::Channel>: Send + Sync + 'static {
+    /// Process one task.
+    ///
+    /// Failing the task is done by returning [`Output::Error`]: the details end up in the task status,
+    /// and the task is retried according to its options.
+    fn process(&self, task_handler: &TaskHandler<T>) -> impl Future<Output = Output> + Send;
+
+    /// Whether the worker is ready to process tasks, `Serving` unless overridden.
+    fn health_check(&self) -> impl Future<Output = health_check::Response> + Send {
+        std::future::ready(health_check::Response::Serving)
+    }
+}
+
+/// Bridge between a [`Processor`] and the [`WorkerService`] the agent calls.
+///
+/// Holds the client to the agent, which is handed to the [`TaskHandler`] of every task.
+pub struct WorkerWrapper<P, T = crate::client::Channel> {
+    processor: P,
+    agent: Agent<T>,
+}
+
+impl<P, T> WorkerWrapper<P, T> {
+    /// Wrap `processor`, calling back the agent through `agent`.
+    pub fn new(processor: P, agent: Agent<T>) -> Self {
+        Self { processor, agent }
+    }
+
+    /// Get the wrapped processor
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+}
+
+impl<P, T> WorkerService for WorkerWrapper<P, T>
+where
+    P: Processor<T>,
+    T: tonic::client::GrpcService<tonic::body::Body> + Clone + Send + Sync + 'static,
+    T::Future: Send,
+    T::Error: Into<tonic::codegen::StdError>,
+    T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    async fn health_check(
+        self: Arc<Self>,
+        _request: health_check::Request,
+        _context: RequestContext,
+    ) -> Result<health_check::Response, tonic::Status> {
+        Ok(self.processor.health_check().await)
+    }
+
+    async fn process(
+        self: Arc<Self>,
+        request: process::Request,
+        _context: RequestContext,
+    ) -> Result<process::Response, tonic::Status> {
+        let span = tracing::info_span!(
+            "TaskHandler",
+            session_id = request.session_id,
+            task_id = request.task_id
+        );
+        let task_handler = TaskHandler::new(self.agent.clone(), request);
+        let output =
+            tracing_futures::Instrument::instrument(self.processor.process(&task_handler), span)
+                .await;
+
+        if let Output::Error { details } = &output {
+            tracing::debug!("Task failed: {details}");
+        }
+
+        Ok(process::Response { output })
+    }
+}
@@ -0,0 +1,313 @@
+use std::future::Future;
+use std::path::PathBuf;
+
+use snafu::{ResultExt, Snafu};
+
+use crate::client::Agent;
+use crate::server::WorkerServiceExt;
+
+use super::{ComputePlane, GrpcChannel, Processor, SocketType, WorkerWrapper};
+
+/// Serve `processor` as configured by the `ComputePlane__*` environment variables, until the process is
+/// asked to terminate.
+///
+/// This is the whole of a worker's `main`: the worker channel is bound, the agent channel is connected,
+/// and tasks are processed until `SIGTERM` (or `Ctrl-C`), at which point the tasks being processed are
+/// finished before this returns.
+pub async fn run<P: Processor>(processor: P) -> Result<(), WorkerError> {
+    serve(processor, ComputePlane::from_env()?, termination()).await
+}
+
+/// Serve `processor` on the channels of `compute_plane`, until `shutdown` resolves.
+///
+/// The agent channel is connected lazily, as the agent may well start listening after the worker.
+pub async fn serve<P: Processor>(
+    processor: P,
+    compute_plane: ComputePlane,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<(), WorkerError> {
+    let ComputePlane {
+        worker_channel,
+        agent_channel,
+    } = compute_plane;
+
+    let agent = Agent::with_channel(agent_channel_connect(&agent_channel).await?);
+    let service = WorkerWrapper::new(processor, agent).worker_server();
+    let router = tonic::transport::Server::builder().add_service(service);
+
+    match worker_channel.socket_type {
+        SocketType::UnixDomainSocket => {
+            #[cfg(unix)]
+            {
+                let path = PathBuf::from(&worker_channel.address);
+                // A socket left over by a previous run would make the bind fail, and nothing but a
+                // previous worker puts one there.
+                match std::fs::remove_file(&path) {
+                    Ok(()) => tracing::debug!("Removed stale socket {}", path.display()),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => {
+                        return Err(err).context(BindSnafu {
+                            address: worker_channel.address,
+                        })
+                    }
+                }
+                let listener = tokio::net::UnixListener::bind(&path).context(BindSnafu {
+                    address: worker_channel.address.clone(),
+                })?;
+                tracing::info!("Worker listening on unix:{}", path.display());
+
+                let served = router
+                    .serve_with_incoming_shutdown(
+                        crate::reexports::tokio_stream::wrappers::UnixListenerStream::new(listener),
+                        shutdown,
+                    )
+                    .await;
+                let _ = std::fs::remove_file(&path);
+                served.context(ServeSnafu {})?;
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = (router, shutdown);
+                return UnsupportedSocketTypeSnafu {
+                    address: worker_channel.address,
+                }
+                .fail();
+            }
+        }
+        SocketType::Tcp => {
+            let address = tcp_address(&worker_channel.address)?;
+            let listener = tokio::net::TcpListener::bind(address)
+                .await
+                .context(BindSnafu {
+                    address: worker_channel.address.clone(),
+                })?;
+            tracing::info!("Worker listening on {address}");
+
+            router
+                .serve_with_incoming_shutdown(
+                    crate::reexports::tokio_stream::wrappers::TcpListenerStream::new(listener),
+                    shutdown,
+                )
+                .await
+                .context(ServeSnafu {})?;
+        }
+    }
+
+    tracing::info!("Worker stopped");
+    Ok(())
+}
+
+/// The address to listen on for the worker channel `address`.
+///
+/// An IP address is bound as it is. A host name, or no host at all, binds every IPv4 interface: the name
+/// is there for the agent to reach the worker by, not to pick an interface. The port has to be given,
+/// there being no port a worker is expected on.
+fn tcp_address(address: &str) -> Result<std::net::SocketAddr, WorkerError> {
+    let uri = tonic::codegen::http::Uri::try_from(address).context(InvalidAddressSnafu {
+        address: address.to_owned(),
+    })?;
+    let Some(port) = uri.port_u16() else {
+        return MissingPortSnafu {
+            address: address.to_owned(),
+        }
+        .fail();
+    };
+    let ip = uri
+        .host()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .and_then(|host| host.parse::<std::net::IpAddr>().ok())
+        .unwrap_or(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED));
+    Ok(std::net::SocketAddr::new(ip, port))
+}
+
+/// A lazily connected channel to the agent.
+async fn agent_channel_connect(
+    agent_channel: &GrpcChannel,
+) -> Result<crate::client::Channel, WorkerError> {
+    match agent_channel.socket_type {
+        SocketType::UnixDomainSocket => {
+            // `unix:` followed by the path reads the same whether it is relative or absolute.
+            let mut args = armonik_transport::ClientConfigArgs::default();
+            args.endpoint = format!("unix:{}", agent_channel.address);
+            let config = armonik_transport::ClientConfig::from_config_args(args).context(
+                AgentConfigSnafu {
+                    address: agent_channel.address.clone(),
+                },
+            )?;
+            armonik_transport::connect_lazy(config)
+                .await
+                .context(AgentConnectionSnafu {
+                    address: agent_channel.address.clone(),
+                })
+        }
+        SocketType::Tcp => Ok(tonic::transport::Endpoint::from_shared(
+            agent_channel.address.clone(),
+        )
+        .context(InvalidEndpointSnafu {
+            address: agent_channel.address.clone(),
+        })?
+        .connect_lazy()
+        .into()),
+    }
+}
+
+/// Resolves on `SIGTERM` or `Ctrl-C`, whichever comes first.
+async fn termination() {
+    #[cfg(unix)]
+    {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => tracing::info!("SIGTERM received, shutting down"),
+                    _ = tokio::signal::ctrl_c() => tracing::info!("Ctrl-C received, shutting down"),
+                }
+                return;
+            }
+            Err(err) => tracing::warn!("Could not listen for SIGTERM: {err}"),
+        }
+    }
+
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => tracing::info!("Ctrl-C received, shutting down"),
+        // Nothing to wait on, so waiting forever: shutting down straight away would stop a worker that
+        // was never asked to.
+        Err(err) => {
+            tracing::warn!("Could not listen for Ctrl-C: {err}");
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Everything that can go wrong while configuring or serving a worker.
+#[derive(Debug, Snafu)]
+#[non_exhaustive]
+#[snafu(visibility(pub(super)))]
+pub enum WorkerError {
+    #[snafu(display("Could not read environment variable [{location}]"))]
+    #[non_exhaustive]
+    Env {
+        #[snafu(source(from(armonik_transport::ReadEnvError, Box::new)))]
+        source: Box<armonik_transport::ReadEnvError>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("`{name}` has to be set [{location}]"))]
+    #[non_exhaustive]
+    MissingAddress {
+        name: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display(
+        "`{name}={value}` is not a valid socket type, expected `UnixDomainSocket` or `Tcp` [{location}]"
+    ))]
+    #[non_exhaustive]
+    InvalidSocketType {
+        name: String,
+        value: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Worker address is not a valid URI: `{address}` [{location}]"))]
+    #[non_exhaustive]
+    InvalidAddress {
+        address: String,
+        #[snafu(source(from(tonic::codegen::http::uri::InvalidUri, Box::new)))]
+        source: Box<tonic::codegen::http::uri::InvalidUri>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Worker address has no port to listen on: `{address}` [{location}]"))]
+    #[non_exhaustive]
+    MissingPort {
+        address: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Agent address is not a valid endpoint: `{address}` [{location}]"))]
+    #[non_exhaustive]
+    InvalidEndpoint {
+        address: String,
+        #[snafu(source(from(tonic::transport::Error, Box::new)))]
+        source: Box<tonic::transport::Error>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Agent address is not a valid socket path: `{address}` [{location}]"))]
+    #[non_exhaustive]
+    AgentConfig {
+        address: String,
+        #[snafu(source(from(armonik_transport::ConfigError, Box::new)))]
+        source: Box<armonik_transport::ConfigError>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Could not set up the channel to the agent at `{address}` [{location}]"))]
+    #[non_exhaustive]
+    AgentConnection {
+        address: String,
+        #[snafu(source(from(armonik_transport::ConnectionError, Box::new)))]
+        source: Box<armonik_transport::ConnectionError>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display(
+        "Unix domain sockets are not supported on this platform: `{address}` [{location}]"
+    ))]
+    #[non_exhaustive]
+    UnsupportedSocketType {
+        address: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Could not listen on `{address}` [{location}]"))]
+    #[non_exhaustive]
+    Bind {
+        address: String,
+        #[snafu(source(from(std::io::Error, Box::new)))]
+        source: Box<std::io::Error>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Worker server failed [{location}]"))]
+    #[non_exhaustive]
+    Serve {
+        #[snafu(source(from(tonic::transport::Error, Box::new)))]
+        source: Box<tonic::transport::Error>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tcp_address, WorkerError};
+
+    #[test]
+    fn the_worker_listens_on_the_ip_and_port_it_is_given() {
+        for (address, bound) in [
+            ("http://10.0.0.7:1080", "10.0.0.7:1080"),
+            ("127.0.0.1:1080", "127.0.0.1:1080"),
+            ("http://[::1]:1080", "[::1]:1080"),
+            ("http://worker.armonik:1080", "0.0.0.0:1080"),
+            ("http://0.0.0.0:1080", "0.0.0.0:1080"),
+        ] {
+            assert_eq!(
+                tcp_address(address).expect(address).to_string(),
+                bound,
+                "for {address}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_worker_address_without_a_port_is_rejected() {
+        for address in ["http://worker.armonik", "http://10.0.0.7"] {
+            let error = tcp_address(address).expect_err(address);
+            assert!(
+                matches!(error, WorkerError::MissingPort { .. }),
+                "{address}: {error:?}"
+            );
+        }
+    }
+}
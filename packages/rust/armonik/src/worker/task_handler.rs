@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use snafu::{ResultExt, Snafu};
+use tokio::sync::OnceCell;
+
+use crate::agent::{submit_tasks, ResultMetaData};
+use crate::client::{Agent, RequestError};
+use crate::{Configuration, TaskOptions};
+
+use super::process;
+
+/// Everything a worker needs to process one task: its inputs, and a way to talk back to the agent.
+///
+/// The communication token and the session id of the task are carried here, and passed on every agent
+/// call on the caller's behalf. Inputs are read lazily from the data folder shared with the agent, and
+/// read at most once.
+pub struct TaskHandler<T = crate::client::Channel> {
+    agent: Agent<T>,
+    communication_token: String,
+    session_id: String,
+    task_id: String,
+    task_options: TaskOptions,
+    expected_output_keys: Vec<String>,
+    configuration: Configuration,
+    data_folder: PathBuf,
+    payload_id: String,
+    payload: OnceCell<Vec<u8>>,
+    data_dependencies: HashMap<String, OnceCell<Vec<u8>>>,
+}
+
+impl<T> TaskHandler<T>
+where
+    T: tonic::client::GrpcService<tonic::body::Body> + Clone,
+    T::Error: Into<tonic::codegen::StdError>,
+    T::ResponseBody: tonic::codegen::Body<Data = tonic::codegen::Bytes> + Send + 'static,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    /// Build a handler for the task described by `request`, talking to the agent through `agent`.
+    pub fn new(agent: Agent<T>, request: process::Request) -> Self {
+        let process::Request {
+            communication_token,
+            session_id,
+            task_id,
+            task_options,
+            expected_output_keys,
+            payload_id,
+            data_dependencies,
+            data_folder,
+            configuration,
+        } = request;
+
+        Self {
+            agent,
+            communication_token,
+            session_id,
+            task_id,
+            task_options,
+            expected_output_keys,
+            configuration,
+            data_folder: PathBuf::from(data_folder),
+            payload_id,
+            payload: OnceCell::new(),
+            data_dependencies: data_dependencies
+                .into_iter()
+                .map(|id| (id, OnceCell::new()))
+                .collect(),
+        }
+    }
+
+    /// Id of the session the task belongs to
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Id of the task being processed
+    pub fn task_id(&self) -> &str {
+        &self.task_id
+    }
+
+    /// Options of the task being processed
+    pub fn task_options(&self) -> &TaskOptions {
+        &self.task_options
+    }
+
+    /// Ids of the results the task is expected to produce
+    pub fn expected_output_keys(&self) -> &[String] {
+        &self.expected_output_keys
+    }
+
+    /// Configuration sent by the agent
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    /// Folder shared with the agent, where inputs are read and outputs are written
+    pub fn data_folder(&self) -> &Path {
+        &self.data_folder
+    }
+
+    /// Id of the result holding the payload of the task
+    pub fn payload_id(&self) -> &str {
+        &self.payload_id
+    }
+
+    /// Ids of the data dependencies of the task
+    pub fn data_dependency_ids(&self) -> impl Iterator<Item = &str> {
+        self.data_dependencies.keys().map(String::as_str)
+    }
+
+    /// Payload of the task, read from the data folder on first access.
+    pub async fn payload(&self) -> Result<&[u8], TaskHandlerError> {
+        let payload = self
+            .payload
+            .get_or_try_init(|| read(self.data_folder.join(&self.payload_id)))
+            .await?;
+        Ok(payload)
+    }
+
+    /// Data dependency `result_id`, read from the data folder on first access.
+    ///
+    /// Only the ids the task was submitted with can be read: anything else is not guaranteed to be in
+    /// the data folder, so it is reported rather than attempted.
+    pub async fn data_dependency(&self, result_id: &str) -> Result<&[u8], TaskHandlerError> {
+        let Some(cell) = self.data_dependencies.get(result_id) else {
+            return UnknownDependencySnafu { result_id }.fail();
+        };
+        let data = cell
+            .get_or_try_init(|| read(self.data_folder.join(result_id)))
+            .await?;
+        Ok(data)
+    }
+
+    /// Write the data of result `result_id` in the data folder, and notify the agent it is available.
+    pub async fn send_result(
+        &self,
+        result_id: impl Into<String>,
+        data: impl AsRef<[u8]>,
+    ) -> Result<(), TaskHandlerError> {
+        self.send_results([(result_id, data)]).await
+    }
+
+    /// Write the data of several results in the data folder, and notify the agent in a single call.
+    pub async fn send_results(
+        &self,
+        results: impl IntoIterator<Item = (impl Into<String>, impl AsRef<[u8]>)>,
+    ) -> Result<(), TaskHandlerError> {
+        let mut result_ids = Vec::new();
+        for (result_id, data) in results {
+            let result_id = result_id.into();
+            let path = self.data_folder.join(&result_id);
+            tokio::fs::write(&path, data)
+                .await
+                .context(IoSnafu { path })?;
+            result_ids.push(result_id);
+        }
+
+        self.agent
+            .clone()
+            .notify_result_data(&self.communication_token, &self.session_id, result_ids)
+            .await
+            .context(RequestSnafu {})?;
+        Ok(())
+    }
+
+    /// Create the metadata of multiple results at once, in the session of the task.
+    /// Data have to be sent separately.
+    pub async fn create_results_metadata(
+        &self,
+        names: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Vec<ResultMetaData>, TaskHandlerError> {
+        self.agent
+            .clone()
+            .create_results_metadata(&self.communication_token, &self.session_id, names)
+            .await
+            .context(RequestSnafu {})
+    }
+
+    /// Create multiple results with their data, in the session of the task.
+    pub async fn create_results(
+        &self,
+        results: impl IntoIterator<Item = (impl Into<String>, impl Into<Vec<u8>>)>,
+    ) -> Result<Vec<ResultMetaData>, TaskHandlerError> {
+        self.agent
+            .clone()
+            .create_results(&self.communication_token, &self.session_id, results)
+            .await
+            .context(RequestSnafu {})
+    }
+
+    /// Submit subtasks in the session of the task.
+    ///
+    /// Payloads, data dependencies and expected outputs have to be created beforehand, with
+    /// [`create_results`](Self::create_results) or
+    /// [`create_results_metadata`](Self::create_results_metadata).
+    pub async fn submit_tasks(
+        &self,
+        task_options: Option<TaskOptions>,
+        items: impl IntoIterator<Item = submit_tasks::RequestItem>,
+    ) -> Result<Vec<submit_tasks::ResponseItem>, TaskHandlerError> {
+        self.agent
+            .clone()
+            .submit_tasks(
+                &self.communication_token,
+                &self.session_id,
+                task_options,
+                items,
+            )
+            .await
+            .context(RequestSnafu {})
+    }
+}
+
+impl<T> std::fmt::Debug for TaskHandler<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Neither the agent nor the data: one is opaque, the other can be arbitrarily large.
+        f.debug_struct("TaskHandler")
+            .field("session_id", &self.session_id)
+            .field("task_id", &self.task_id)
+            .field("task_options", &self.task_options)
+            .field("expected_output_keys", &self.expected_output_keys)
+            .field("configuration", &self.configuration)
+            .field("data_folder", &self.data_folder)
+            .field("payload_id", &self.payload_id)
+            .field(
+                "data_dependencies",
+                &self.data_dependencies.keys().collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+async fn read(path: PathBuf) -> Result<Vec<u8>, TaskHandlerError> {
+    tokio::fs::read(&path).await.context(IoSnafu { path })
+}
+
+/// Everything that can go wrong while processing a task through a [`TaskHandler`].
+#[derive(Debug, Snafu)]
+#[non_exhaustive]
+pub enum TaskHandlerError {
+    #[snafu(display("Could not access `{}` in the data folder [{location}]", path.display()))]
+    #[non_exhaustive]
+    Io {
+        path: PathBuf,
+        #[snafu(source(from(std::io::Error, Box::new)))]
+        source: Box<std::io::Error>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("`{result_id}` is not a data dependency of the task [{location}]"))]
+    #[non_exhaustive]
+    UnknownDependency {
+        result_id: String,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+    #[snafu(display("Agent request failed [{location}]"))]
+    #[non_exhaustive]
+    Request {
+        #[snafu(source(from(RequestError, Box::new)))]
+        source: Box<RequestError>,
+        #[snafu(implicit)]
+        location: snafu::Location,
+    },
+}
@@ -146,6 +146,15 @@ impl armonik::server::AgentService for Service {
         let mut token = None;
         loop {
             match request.next().await {
+                Some(Ok(agent::create_tasks::Request::InitRequest {
+                    communication_token,
+                    ..
+                })) if communication_token == "rpc-create-tasks-rejected" => {
+                    return Ok(agent::create_tasks::Response::Error {
+                        communication_token,
+                        error: String::from("rpc-create-tasks-error"),
+                    });
+                }
                 Some(Ok(agent::create_tasks::Request::InitTaskRequest {
                     communication_token,
                     ..
@@ -289,3 +298,27 @@ async fn create_tasks() {
         }
     }
 }
+
+#[tokio::test]
+async fn create_tasks_rejected() {
+    let mut client = armonik::Client::with_channel(Service::default().agent_server()).into_agent();
+
+    let err = client
+        .create_tasks(futures::stream::iter([
+            agent::create_tasks::Request::InitRequest {
+                communication_token: String::from("rpc-create-tasks-rejected"),
+                request: agent::create_tasks::InitRequest { task_options: None },
+            },
+        ]))
+        .await
+        .unwrap_err();
+
+    match &err {
+        armonik::client::RequestError::TaskCreation { details, .. } => {
+            assert_eq!(details, "rpc-create-tasks-error");
+        }
+        err => panic!("Expected TaskCreation, but got {err:?}"),
+    }
+    assert!(err.status().is_none());
+    assert!(!err.is_retryable());
+}
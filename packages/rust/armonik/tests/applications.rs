@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use armonik::{
     applications,
+    reexports::tokio_stream::StreamExt,
     server::{ApplicationsServiceExt, RequestContext},
 };
 
@@ -54,3 +55,48 @@ async fn list() {
     assert_eq!(response.total, 1337);
     assert_eq!(response.applications[0].name, "rpc-list-output");
 }
+
+#[tokio::test]
+async fn list_stream() {
+    let client =
+        armonik::Client::with_channel(Service::default().applications_server()).into_applications();
+
+    let applications = client
+        .list_stream(
+            armonik::applications::filter::Or::default(),
+            armonik::applications::Sort::default(),
+            1,
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap();
+
+    assert_eq!(applications.len(), 1337);
+    assert!(applications
+        .iter()
+        .all(|application| application.name == "rpc-list-output"));
+}
+
+#[tokio::test]
+async fn list_stream_failure() {
+    let client = armonik::Client::with_channel(
+        Service {
+            failure: Some(tonic::Status::unavailable("rpc-list-failure")),
+            ..Default::default()
+        }
+        .applications_server(),
+    )
+    .into_applications();
+
+    let applications = client
+        .list_stream(
+            armonik::applications::filter::Or::default(),
+            armonik::applications::Sort::default(),
+            12,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(applications.len(), 1);
+    assert!(applications[0].is_err());
+}
@@ -14,3 +14,67 @@ pub(crate) async fn unary_rpc_impl<Response>(
         response()
     }
 }
+
+/// A fresh directory to put the files of one test in.
+#[allow(unused)]
+pub(crate) fn dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("armonik-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write `contents` to `name` in `dir`, returning its path as an option value.
+#[allow(unused)]
+pub(crate) fn write(dir: &std::path::Path, name: &str, contents: impl AsRef<[u8]>) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path.display().to_string()
+}
+
+/// A Certificate Authority, named `name`.
+#[allow(unused)]
+pub(crate) fn ca(name: &str) -> rcgen::CertifiedIssuer<'static, rcgen::KeyPair> {
+    let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, name);
+    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    rcgen::CertifiedIssuer::self_signed(params, rcgen::KeyPair::generate().unwrap()).unwrap()
+}
+
+/// A certificate named `name` for `subject_alt_names`, issued by `issuer`, and its key.
+#[allow(unused)]
+pub(crate) fn issue(
+    name: &str,
+    subject_alt_names: &[&str],
+    issuer: &rcgen::CertifiedIssuer<'static, rcgen::KeyPair>,
+) -> (rcgen::Certificate, rcgen::KeyPair) {
+    let key = rcgen::KeyPair::generate().unwrap();
+    let mut params = rcgen::CertificateParams::new(
+        subject_alt_names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .unwrap();
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, name);
+    (params.signed_by(&key, issuer).unwrap(), key)
+}
+
+/// A certificate for `localhost` issued by `issuer`, and its key, written to `{name}.pem` and
+/// `{name}.key` in `dir`.
+#[allow(unused)]
+pub(crate) fn leaf(
+    dir: &std::path::Path,
+    name: &str,
+    issuer: &rcgen::CertifiedIssuer<'static, rcgen::KeyPair>,
+) -> (String, String) {
+    let (cert, key) = issue(name, &["localhost"], issuer);
+    (
+        write(dir, &format!("{name}.pem"), cert.pem()),
+        write(dir, &format!("{name}.key"), key.serialize_pem()),
+    )
+}
@@ -0,0 +1,141 @@
+use armonik::api::{dynamic, v3};
+use armonik::reexports::prost_reflect::Value;
+use prost::Message;
+
+const SERVICES: &[&str] = &[
+    "armonik.api.grpc.v1.agent.Agent",
+    "armonik.api.grpc.v1.applications.Applications",
+    "armonik.api.grpc.v1.auth.Authentication",
+    "armonik.api.grpc.v1.events.Events",
+    "armonik.api.grpc.v1.health_checks.HealthChecksService",
+    "armonik.api.grpc.v1.partitions.Partitions",
+    "armonik.api.grpc.v1.results.Results",
+    "armonik.api.grpc.v1.sessions.Sessions",
+    "armonik.api.grpc.v1.submitter.Submitter",
+    "armonik.api.grpc.v1.tasks.Tasks",
+    "armonik.api.grpc.v1.versions.Versions",
+    "armonik.api.grpc.v1.worker.Worker",
+];
+
+fn create_session_request() -> v3::sessions::CreateSessionRequest {
+    v3::sessions::CreateSessionRequest {
+        default_task_option: Some(v3::TaskOptions {
+            max_retries: 2,
+            priority: 1,
+            partition_id: String::from("part1"),
+            ..Default::default()
+        }),
+        partition_ids: vec![String::from("part1"), String::from("part2")],
+    }
+}
+
+#[test]
+fn every_service_is_described() {
+    let pool = dynamic::descriptor_pool();
+
+    for service in SERVICES {
+        assert!(
+            pool.get_service_by_name(service).is_some(),
+            "{service} is missing"
+        );
+    }
+    assert_eq!(
+        pool.services()
+            .filter(|service| service.package_name().starts_with("armonik."))
+            .count(),
+        SERVICES.len()
+    );
+}
+
+#[test]
+fn decode_by_name() {
+    let bytes = create_session_request().encode_to_vec();
+
+    let message = dynamic::decode(
+        "armonik.api.grpc.v1.sessions.CreateSessionRequest",
+        bytes.as_slice(),
+    )
+    .unwrap();
+
+    let partitions = message.get_field_by_name("partition_ids").unwrap();
+    assert_eq!(
+        partitions.as_list().unwrap(),
+        [
+            Value::String(String::from("part1")),
+            Value::String(String::from("part2"))
+        ]
+    );
+    let options = message.get_field_by_name("default_task_option").unwrap();
+    let options = options.as_message().unwrap();
+    assert_eq!(
+        options.get_field_by_name("max_retries").unwrap().as_i32(),
+        Some(2)
+    );
+    assert_eq!(message.encode_to_vec(), bytes);
+}
+
+#[test]
+fn method_by_path() {
+    let method = dynamic::method("/armonik.api.grpc.v1.sessions.Sessions/CreateSession").unwrap();
+
+    assert_eq!(
+        method.input().full_name(),
+        "armonik.api.grpc.v1.sessions.CreateSessionRequest"
+    );
+    assert_eq!(
+        method.output().full_name(),
+        "armonik.api.grpc.v1.sessions.CreateSessionReply"
+    );
+
+    assert!(dynamic::method("/armonik.api.grpc.v1.sessions.Sessions/Unknown").is_none());
+    assert!(dynamic::method("/armonik.api.grpc.v1.sessions.Unknown/CreateSession").is_none());
+    assert!(dynamic::method("armonik.api.grpc.v1.sessions.Sessions/CreateSession").is_none());
+    assert!(dynamic::method("/armonik.api.grpc.v1.sessions.Sessions").is_none());
+}
+
+#[test]
+fn unknown_message() {
+    let err = dynamic::decode("armonik.api.grpc.v1.sessions.Unknown", [].as_slice()).unwrap_err();
+
+    assert!(
+        matches!(&err, dynamic::DecodeError::UnknownMessage { name, .. } if name == "armonik.api.grpc.v1.sessions.Unknown"),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn invalid_message() {
+    // A length-delimited field announcing more bytes than there are.
+    let err = dynamic::decode(
+        "armonik.api.grpc.v1.sessions.CreateSessionRequest",
+        [0x12, 0x10, b'p'].as_slice(),
+    )
+    .unwrap_err();
+
+    assert!(
+        matches!(err, dynamic::DecodeError::InvalidMessage { .. }),
+        "{err:?}"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialize_as_json() {
+    let message = dynamic::decode(
+        "armonik.api.grpc.v1.sessions.CreateSessionRequest",
+        create_session_request().encode_to_vec().as_slice(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&message).unwrap(),
+        serde_json::json!({
+            "defaultTaskOption": {
+                "maxRetries": 2,
+                "priority": 1,
+                "partitionId": "part1",
+            },
+            "partitionIds": ["part1", "part2"],
+        })
+    );
+}
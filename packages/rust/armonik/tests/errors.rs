@@ -0,0 +1,109 @@
+use std::{sync::Arc, time::Duration};
+
+use armonik::{
+    client::RequestError,
+    server::{RequestContext, VersionsServiceExt},
+    versions,
+};
+use tonic_types::{ErrorDetails, FieldViolation, StatusExt};
+
+/// Fails every call with `failure`.
+#[derive(Debug, Clone)]
+struct Service {
+    failure: tonic::Status,
+}
+
+impl armonik::server::VersionsService for Service {
+    async fn list(
+        self: Arc<Self>,
+        _request: versions::list::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<versions::list::Response, tonic::Status> {
+        Err(self.failure.clone())
+    }
+}
+
+/// The error a call fails with, when the server fails it with `failure`.
+async fn failed_with(failure: tonic::Status) -> RequestError {
+    armonik::Client::with_channel(Service { failure }.versions_server())
+        .into_versions()
+        .list()
+        .await
+        .unwrap_err()
+}
+
+#[tokio::test]
+async fn not_found() {
+    let err = failed_with(tonic::Status::not_found("session-id")).await;
+
+    assert_eq!(err.code(), Some(tonic::Code::NotFound));
+    assert_eq!(err.status().unwrap().message(), "session-id");
+    assert!(err.is_not_found());
+    assert!(!err.is_retryable());
+    assert!(!err.is_permission_denied());
+    assert!(!err.is_invalid_argument());
+}
+
+#[tokio::test]
+async fn permission_denied() {
+    let err = failed_with(tonic::Status::permission_denied("list")).await;
+
+    assert!(err.is_permission_denied());
+    assert!(!err.is_not_found());
+    assert!(!err.is_retryable());
+}
+
+#[tokio::test]
+async fn invalid_argument_with_field_violations() {
+    let err = failed_with(tonic::Status::with_error_details(
+        tonic::Code::InvalidArgument,
+        "invalid request",
+        ErrorDetails::with_bad_request(vec![
+            FieldViolation::new("session_id", "is empty"),
+            FieldViolation::new("page_size", "is negative"),
+        ]),
+    ))
+    .await;
+
+    assert!(err.is_invalid_argument());
+    assert!(!err.is_retryable());
+    let violations = err.bad_request().unwrap().field_violations;
+    assert_eq!(violations.len(), 2);
+    assert_eq!(violations[0].field, "session_id");
+    assert_eq!(violations[0].description, "is empty");
+    assert_eq!(violations[1].field, "page_size");
+    assert!(err.error_details().bad_request().is_some());
+    assert!(err.retry_info().is_none());
+}
+
+#[tokio::test]
+async fn unavailable_is_retryable() {
+    let err = failed_with(tonic::Status::unavailable("control plane is restarting")).await;
+
+    assert!(err.is_retryable());
+    assert_eq!(err.retry_delay(), None);
+    assert!(err.bad_request().is_none());
+}
+
+#[tokio::test]
+async fn retry_info_makes_any_code_retryable() {
+    let err = failed_with(tonic::Status::with_error_details(
+        tonic::Code::Aborted,
+        "conflict",
+        ErrorDetails::with_retry_info(Some(Duration::from_millis(1500))),
+    ))
+    .await;
+
+    assert!(err.is_retryable());
+    assert_eq!(err.retry_delay(), Some(Duration::from_millis(1500)));
+}
+
+#[tokio::test]
+async fn no_details() {
+    let err = failed_with(tonic::Status::internal("internal")).await;
+
+    assert!(!err.is_retryable());
+    assert!(err.bad_request().is_none());
+    assert!(err.retry_info().is_none());
+    assert!(err.error_details().bad_request().is_none());
+}
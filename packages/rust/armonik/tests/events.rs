@@ -100,3 +100,44 @@ async fn subscribe() {
         panic!("Expected a cancel, but got a timeout");
     }
 }
+
+#[tokio::test(start_paused = true)]
+async fn subscribe_cancelled() {
+    let dropped = tokio_util::sync::CancellationToken::new();
+    let token = tokio_util::sync::CancellationToken::new();
+    let mut client = armonik::Client::with_channel(
+        Service {
+            failure: None,
+            wait: Some(tokio::time::Duration::from_secs(1)),
+            dropped: dropped.clone(),
+        }
+        .events_server(),
+    )
+    .into_events();
+
+    let mut response = client
+        .call_with(
+            events::subscribe::Request {
+                session_id: String::from("rpc-subscribe-input"),
+                ..Default::default()
+            },
+            armonik::client::CallOptions::new().with_cancellation(token.clone()),
+        )
+        .await
+        .unwrap();
+
+    let event = response.next().await.unwrap().unwrap();
+    assert_eq!(event.session_id, "rpc-subscribe-input");
+
+    token.cancel();
+    match response.next().await {
+        Some(Err(armonik::client::RequestError::Grpc { source, .. })) => {
+            assert_eq!(source.code(), tonic::Code::Cancelled)
+        }
+        event => panic!("expected the call to be cancelled, but got {event:?}"),
+    }
+    assert!(response.next().await.is_none());
+
+    std::mem::drop(response);
+    dropped.cancelled().await;
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use armonik::{
+    client::{ClientConfig, ClientConfigArgs},
+    server::{ArmoniKServer, RequestContext, ServerConfig, ServerConfigArgs},
+    versions,
+};
+use common::{ca, dir, leaf, write};
+use tonic_health::pb::{health_check_response::ServingStatus, HealthCheckRequest};
+
+mod common;
+
+const VERSIONS: &str = "armonik.api.grpc.v1.versions.Versions";
+
+#[derive(Debug, Clone, Default)]
+struct Service;
+
+impl armonik::server::VersionsService for Service {
+    async fn list(
+        self: Arc<Self>,
+        _request: versions::list::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<versions::list::Response, tonic::Status> {
+        Ok(versions::list::Response {
+            core: String::from("rpc-list-output"),
+            ..Default::default()
+        })
+    }
+}
+
+fn client_config(endpoint: String, set: impl FnOnce(&mut ClientConfigArgs)) -> ClientConfig {
+    let mut args = ClientConfigArgs::default();
+    args.endpoint = endpoint;
+    set(&mut args);
+    ClientConfig::from_config_args(args).unwrap()
+}
+
+/// Serve the versions service with `config` until the sender returned is dropped, or sent to.
+async fn serve(
+    config: ServerConfig,
+) -> (
+    std::net::SocketAddr,
+    tokio::sync::oneshot::Sender<()>,
+    tokio::task::JoinHandle<Result<(), armonik::server::ServeError>>,
+) {
+    let server = ArmoniKServer::builder()
+        .versions(Service)
+        .bind(&config)
+        .await
+        .unwrap();
+    let address = server.local_addr();
+    let (shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+    let served = tokio::spawn(server.serve_with_shutdown(async move {
+        let _ = signal.await;
+    }));
+    (address, shutdown, served)
+}
+
+#[tokio::test]
+async fn services_are_served_with_their_health_until_shutdown() {
+    let (address, shutdown, served) =
+        serve(ServerConfig::new("127.0.0.1:0".parse().unwrap())).await;
+    let config = client_config(format!("http://{address}"), |_| ());
+
+    let mut client = armonik::Client::with_config(config.clone()).await.unwrap();
+    let response = client.versions().list().await.unwrap();
+    assert_eq!(response.core, "rpc-list-output");
+
+    let mut health = tonic_health::pb::health_client::HealthClient::new(
+        armonik::transport::connect(config).await.unwrap(),
+    );
+    for (service, status) in [
+        ("", ServingStatus::Serving),
+        (VERSIONS, ServingStatus::Serving),
+    ] {
+        let response = health
+            .check(HealthCheckRequest {
+                service: String::from(service),
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().status(), status, "{service:?}");
+    }
+    let unknown = health
+        .check(HealthCheckRequest {
+            service: String::from("armonik.api.grpc.v1.sessions.Sessions"),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(unknown.code(), tonic::Code::NotFound);
+
+    shutdown.send(()).unwrap();
+    served.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn mutual_tls_refuses_clients_without_a_certificate_of_the_client_ca() {
+    let dir = dir("host-mtls");
+    let (server_ca, client_ca, other_ca) = (ca("server-ca"), ca("client-ca"), ca("other-ca"));
+    let (server_cert, server_key) = leaf(&dir, "server", &server_ca);
+    let (client_cert, client_key) = leaf(&dir, "client", &client_ca);
+    let (other_cert, other_key) = leaf(&dir, "other", &other_ca);
+    let server_ca = write(&dir, "server-ca.pem", server_ca.pem());
+
+    let mut args = ServerConfigArgs::default();
+    args.address = String::from("127.0.0.1:0");
+    args.cert_pem = server_cert;
+    args.key_pem = server_key;
+    args.client_ca_cert = write(&dir, "client-ca.pem", client_ca.pem());
+    args.require_client_cert = true;
+    let (address, _shutdown, _served) = serve(ServerConfig::from_config_args(args).unwrap()).await;
+    let endpoint = format!("https://localhost:{}", address.port());
+
+    for (cert, key) in [(String::new(), String::new()), (other_cert, other_key)] {
+        let config = client_config(endpoint.clone(), |args| {
+            args.ca_cert = server_ca.clone();
+            args.cert_pem = cert;
+            args.key_pem = key;
+        });
+        // Refused during the handshake, which the client may only learn of on its first call.
+        if let Ok(mut client) = armonik::Client::with_config(config).await {
+            client.versions().list().await.unwrap_err();
+        }
+    }
+
+    // The handshakes that failed did not stop the server.
+    let mut client = armonik::Client::with_config(client_config(endpoint, |args| {
+        args.ca_cert = server_ca.clone();
+        args.cert_pem = client_cert;
+        args.key_pem = client_key;
+    }))
+    .await
+    .unwrap();
+    let response = client.versions().list().await.unwrap();
+    assert_eq!(response.core, "rpc-list-output");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn clients_slow_to_handshake_hold_up_no_other() {
+    let dir = dir("host-slow-handshakes");
+    let server_ca = ca("server-ca");
+    let (server_cert, server_key) = leaf(&dir, "server", &server_ca);
+    let server_ca = write(&dir, "server-ca.pem", server_ca.pem());
+
+    let mut args = ServerConfigArgs::default();
+    args.address = String::from("127.0.0.1:0");
+    args.cert_pem = server_cert;
+    args.key_pem = server_key;
+    let (address, _shutdown, _served) = serve(ServerConfig::from_config_args(args).unwrap()).await;
+
+    // Connected, and never starting their handshake.
+    let mut idle = Vec::new();
+    for _ in 0..100 {
+        idle.push(tokio::net::TcpStream::connect(address).await.unwrap());
+    }
+
+    let mut client = armonik::Client::with_config(client_config(
+        format!("https://localhost:{}", address.port()),
+        |args| args.ca_cert = server_ca.clone(),
+    ))
+    .await
+    .unwrap();
+    // Well before the idle clients are timed out.
+    let response =
+        tokio::time::timeout(std::time::Duration::from_secs(5), client.versions().list())
+            .await
+            .expect("the call should not wait for the idle clients")
+            .unwrap();
+    assert_eq!(response.core, "rpc-list-output");
+
+    drop(idle);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn an_address_in_use_is_reported() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let err = ArmoniKServer::builder()
+        .versions(Service)
+        .bind(&ServerConfig::new(listener.local_addr().unwrap()))
+        .await
+        .unwrap_err();
+
+    assert!(
+        matches!(err, armonik::server::ServeError::Bind { .. }),
+        "{err:?}"
+    );
+}
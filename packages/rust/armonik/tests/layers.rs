@@ -0,0 +1,182 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use armonik::{
+    applications,
+    client::{ConcurrencyLimitLayer, LogLayer, StaticHeadersLayer},
+    reexports::tokio_stream::wrappers::TcpListenerStream,
+    server::{ApplicationsServiceExt, RequestContext, VersionsServiceExt},
+    versions,
+};
+
+mod common;
+
+#[derive(Debug, Clone, Default)]
+struct Service {
+    failure: Option<tonic::Status>,
+    wait: Option<tokio::time::Duration>,
+    seen: Arc<Seen>,
+}
+
+/// What the service saw of the calls it served
+#[derive(Debug, Default)]
+struct Seen {
+    headers: Mutex<Option<tonic::codegen::http::HeaderMap>>,
+    running: AtomicUsize,
+    most_running: AtomicUsize,
+}
+
+impl armonik::server::VersionsService for Service {
+    async fn list(
+        self: Arc<Self>,
+        _request: versions::list::Request,
+        context: RequestContext,
+    ) -> std::result::Result<versions::list::Response, tonic::Status> {
+        *self.seen.headers.lock().unwrap() = Some(context.headers().clone());
+        let running = self.seen.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.seen.most_running.fetch_max(running, Ordering::SeqCst);
+        let response = common::unary_rpc_impl(self.wait, self.failure.clone(), || {
+            Ok(versions::list::Response {
+                core: String::from("rpc-list-output"),
+                ..Default::default()
+            })
+        })
+        .await;
+        self.seen.running.fetch_sub(1, Ordering::SeqCst);
+        response
+    }
+}
+
+impl armonik::server::ApplicationsService for Service {
+    async fn list(
+        self: Arc<Self>,
+        request: applications::list::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<applications::list::Response, tonic::Status> {
+        Ok(applications::list::Response {
+            page: request.page,
+            page_size: request.page_size,
+            ..Default::default()
+        })
+    }
+}
+
+#[tokio::test]
+async fn static_headers() {
+    let service = Service::default();
+    let mut headers = tonic::codegen::http::HeaderMap::new();
+    headers.insert("x-tenant", "rpc-list-tenant".parse().unwrap());
+    let mut client = armonik::Client::with_channel(service.clone().versions_server())
+        .with_layer(StaticHeadersLayer::new(headers));
+
+    let response = client.versions().list().await.unwrap();
+
+    assert_eq!(response.core, "rpc-list-output");
+    let headers = service.seen.headers.lock().unwrap().take().unwrap();
+    assert_eq!(headers["x-tenant"], "rpc-list-tenant");
+}
+
+#[tokio::test]
+async fn log() {
+    let client = armonik::Client::with_channel(
+        Service {
+            failure: Some(tonic::Status::unavailable("rpc-list-failure")),
+            ..Default::default()
+        }
+        .versions_server(),
+    )
+    .with_layer(LogLayer::new());
+
+    let err = client.into_versions().list().await.unwrap_err();
+
+    match err {
+        armonik::client::RequestError::Grpc { source, .. } => {
+            assert_eq!(source.code(), tonic::Code::Unavailable);
+            assert_eq!(source.message(), "rpc-list-failure");
+        }
+        err => panic!("expected a gRPC error, but got {err:?}"),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn concurrency_limit() {
+    let service = Service {
+        wait: Some(tokio::time::Duration::from_secs(1)),
+        ..Default::default()
+    };
+    let client = armonik::Client::with_channel(service.clone().versions_server())
+        .with_layer(LogLayer::new())
+        .with_layer(
+            ConcurrencyLimitLayer::new()
+                .with_limit("/armonik.api.grpc.v1.versions.Versions/ListVersions", 2),
+        )
+        .into_versions();
+
+    let calls = (0..5).map(|_| {
+        let mut client = client.clone();
+        async move { client.list().await }
+    });
+    for response in futures::future::join_all(calls).await {
+        assert_eq!(response.unwrap().core, "rpc-list-output");
+    }
+
+    assert_eq!(service.seen.most_running.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn calls_waiting_for_their_turn_hold_up_no_other_method() {
+    let service = Service {
+        wait: Some(tokio::time::Duration::from_millis(500)),
+        ..Default::default()
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let router = tonic::transport::Server::builder()
+        .add_service(service.clone().versions_server())
+        .add_service(service.clone().applications_server());
+    tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+
+    // A `Channel` reserves a slot of its buffer for each call it is made ready for.
+    let channel = tonic::transport::Endpoint::from_shared(format!("http://{address}"))
+        .unwrap()
+        .buffer_size(2)
+        .connect()
+        .await
+        .unwrap();
+    let client = armonik::Client::with_channel(channel).with_layer(
+        ConcurrencyLimitLayer::new()
+            .with_limit("/armonik.api.grpc.v1.versions.Versions/ListVersions", 1),
+    );
+
+    let limited = (0..6)
+        .map(|_| {
+            let mut versions = client.clone().into_versions();
+            tokio::spawn(async move { versions.list().await })
+        })
+        .collect::<Vec<_>>();
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let other = tokio::time::timeout(
+        tokio::time::Duration::from_millis(300),
+        client.clone().into_applications().list(
+            applications::filter::Or::default(),
+            applications::Sort::default(),
+            0,
+            10,
+        ),
+    )
+    .await
+    .expect("the calls waiting for their turn should not hold up other methods");
+    assert_eq!(other.unwrap().page_size, 10);
+
+    for call in limited {
+        assert_eq!(call.await.unwrap().unwrap().core, "rpc-list-output");
+    }
+    assert_eq!(service.seen.most_running.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+#[should_panic(expected = "a concurrency limit of 0")]
+fn a_concurrency_limit_of_0_is_refused() {
+    let _ = ConcurrencyLimitLayer::new().with_default(0);
+}
@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use armonik::{
+    agent, events,
+    mock::LocalCluster,
+    reexports::tokio_stream::StreamExt,
+    results, tasks,
+    worker::{Processor, TaskHandler, WorkerWrapper},
+    Client, Output, ResultStatus, TaskStatus,
+};
+
+/// Uppercase the payload, in a subtask the output is delegated to
+struct Shout;
+
+impl Processor for Shout {
+    async fn process(&self, task_handler: &TaskHandler) -> Output {
+        let payload = task_handler.payload().await.unwrap().to_vec();
+        let output = task_handler.expected_output_keys()[0].clone();
+
+        match payload.strip_prefix(b"sub:") {
+            Some(word) => {
+                task_handler
+                    .send_result(output, word.to_ascii_uppercase())
+                    .await
+                    .unwrap();
+            }
+            None => {
+                let payload = task_handler
+                    .create_results([("sub-payload", [b"sub:".as_slice(), &payload].concat())])
+                    .await
+                    .unwrap()
+                    .remove(0);
+                task_handler
+                    .submit_tasks(
+                        None,
+                        [agent::submit_tasks::RequestItem {
+                            payload_id: payload.result_id,
+                            expected_output_keys: vec![output],
+                            ..Default::default()
+                        }],
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+        Output::Ok
+    }
+}
+
+/// Submit a subtask, then fail
+struct Fail;
+
+impl Processor for Fail {
+    async fn process(&self, task_handler: &TaskHandler) -> Output {
+        let mut created = task_handler
+            .create_results_metadata(["sub-payload", "sub-output"])
+            .await
+            .unwrap();
+        let output = created.pop().unwrap();
+        let payload = created.pop().unwrap();
+        task_handler
+            .submit_tasks(
+                None,
+                [agent::submit_tasks::RequestItem {
+                    payload_id: payload.result_id,
+                    expected_output_keys: vec![output.result_id],
+                    ..Default::default()
+                }],
+            )
+            .await
+            .unwrap();
+
+        Output::Error {
+            details: String::from("boom"),
+        }
+    }
+}
+
+/// Create a session on the default partition
+async fn session(client: &Client) -> String {
+    client
+        .clone()
+        .into_sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap()
+}
+
+/// Submit a task, returning its id and the id of its output
+async fn submit(client: &Client, session_id: &str, payload: &[u8]) -> (String, String) {
+    let mut results = client.clone().into_results();
+
+    let payload = results
+        .create(
+            session_id,
+            [results::create::RequestItem {
+                name: String::from("payload"),
+                data: payload.to_vec(),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0);
+    let output = results
+        .create_metadata(
+            session_id,
+            [results::create_metadata::RequestItem {
+                name: String::from("output"),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0);
+    let task_id = client
+        .clone()
+        .into_tasks()
+        .submit(
+            session_id,
+            None,
+            [tasks::submit::RequestItem {
+                payload_id: payload.result_id,
+                expected_output_keys: vec![output.result_id.clone()],
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0)
+        .task_id;
+
+    (task_id, output.result_id)
+}
+
+/// Wait for a result to be completed or aborted
+async fn wait_for(client: &Client, result_id: &str) -> results::Raw {
+    let mut results = client.clone().into_results();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let result = results.get(result_id).await.unwrap();
+            if result.status != ResultStatus::Created {
+                return result;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the result was not produced in time")
+}
+
+#[tokio::test]
+async fn tasks_are_processed_by_the_worker() {
+    let cluster = LocalCluster::start(|agent| WorkerWrapper::new(Shout, agent))
+        .await
+        .unwrap();
+    let client = cluster.client().await.unwrap();
+    let session_id = session(&client).await;
+
+    let updates = client
+        .clone()
+        .into_events()
+        .subscribe(
+            &session_id,
+            tasks::filter::Or::default(),
+            results::filter::Or::default(),
+            [events::EventsEnum::TaskStatusUpdate],
+        )
+        .await
+        .unwrap();
+    let (task_id, output_id) = submit(&client, &session_id, b"hello").await;
+
+    let output = wait_for(&client, &output_id).await;
+    assert_eq!(output.status, ResultStatus::Completed);
+    let data = client
+        .clone()
+        .into_results()
+        .download(&session_id, &output_id)
+        .await
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap()
+        .concat();
+    assert_eq!(data, b"HELLO");
+
+    let mut tasks = client.clone().into_tasks();
+    let task = tasks.get(&task_id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    let subtask = tasks.get(&output.owner_task_id).await.unwrap();
+    assert_eq!(subtask.status, TaskStatus::Completed);
+    assert_eq!(subtask.created_by, task_id);
+    assert_eq!(subtask.parent_task_ids, [task_id.as_str()]);
+    assert_eq!(cluster.calls().get("Agent", "SubmitTasks"), 1);
+    assert_eq!(cluster.calls().get("Agent", "NotifyResultData"), 1);
+
+    let statuses = updates
+        .map(|event| event.unwrap().update)
+        .filter_map(|update| match update {
+            events::Update::TaskStatusUpdate(update) if update.task_id == subtask.task_id => {
+                Some(update.status)
+            }
+            _ => None,
+        })
+        .take(5)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        statuses,
+        [
+            TaskStatus::Pending,
+            TaskStatus::Submitted,
+            TaskStatus::Dispatched,
+            TaskStatus::Processing,
+            TaskStatus::Completed,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn a_failed_task_cancels_its_subtasks() {
+    let cluster = LocalCluster::start(|agent| WorkerWrapper::new(Fail, agent))
+        .await
+        .unwrap();
+    let client = cluster.client().await.unwrap();
+
+    let session_id = session(&client).await;
+    let (task_id, output_id) = submit(&client, &session_id, b"payload").await;
+
+    let output = wait_for(&client, &output_id).await;
+    assert_eq!(output.status, ResultStatus::Aborted);
+
+    let mut tasks = client.clone().into_tasks();
+    let task = tasks.get(&task_id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Error);
+    assert_eq!(task.output, tasks::Output::Error(String::from("boom")));
+
+    let listed = tasks
+        .list(
+            tasks::filter::Or::default(),
+            Default::default(),
+            false,
+            0,
+            10,
+        )
+        .await
+        .unwrap();
+    assert_eq!(listed.total, 2);
+    let subtask = listed
+        .tasks
+        .into_iter()
+        .find(|subtask| subtask.task_id != task_id)
+        .unwrap();
+    assert_eq!(subtask.status, TaskStatus::Cancelled);
+}
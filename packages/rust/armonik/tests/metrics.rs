@@ -0,0 +1,193 @@
+use armonik::{client::metrics, events, mock::MockServer, results, tasks};
+use futures::StreamExt;
+use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+/// Create a session on `server`, on its default partition
+async fn session(server: &MockServer) -> String {
+    server
+        .client()
+        .await
+        .unwrap()
+        .into_sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap()
+}
+
+/// A metric recorded, as `(name, labels, value)` with sorted labels
+type Recorded = (String, Vec<(String, String)>, DebugValue);
+
+/// What was recorded since the last snapshot.
+///
+/// Taking it resets what was recorded.
+fn snapshot(snapshotter: &Snapshotter) -> Vec<Recorded> {
+    snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let key = key.key();
+            let mut labels = key
+                .labels()
+                .map(|label| (label.key().to_owned(), label.value().to_owned()))
+                .collect::<Vec<_>>();
+            labels.sort();
+            (key.name().to_owned(), labels, value)
+        })
+        .collect()
+}
+
+/// Value of the metric `name` with exactly `labels`, if it was recorded
+fn value<'a>(
+    snapshot: &'a [Recorded],
+    name: &str,
+    labels: &[(&str, &str)],
+) -> Option<&'a DebugValue> {
+    let mut labels = labels
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<Vec<_>>();
+    labels.sort();
+    snapshot
+        .iter()
+        .find(|(recorded, recorded_labels, _)| recorded == name && *recorded_labels == labels)
+        .map(|(_, _, value)| value)
+}
+
+fn counter(snapshot: &[Recorded], name: &str, labels: &[(&str, &str)]) -> u64 {
+    match value(snapshot, name, labels) {
+        Some(DebugValue::Counter(count)) => *count,
+        None => 0,
+        Some(value) => panic!("{name} is not a counter: {value:?}"),
+    }
+}
+
+#[tokio::test]
+async fn calls_are_counted_and_timed_by_method_and_code() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let _guard = ::metrics::set_default_local_recorder(&recorder);
+    let server = MockServer::start().await.unwrap();
+    let mut client = server.client().await.unwrap();
+
+    client.versions().list().await.unwrap();
+    client.versions().list().await.unwrap();
+    client.results().get("missing").await.unwrap_err();
+
+    let snapshot = snapshot(&snapshotter);
+    let ok = [("service", "Versions"), ("method", "list"), ("code", "OK")];
+    assert_eq!(counter(&snapshot, metrics::CALLS, &ok), 2);
+    assert_eq!(
+        counter(
+            &snapshot,
+            metrics::CALLS,
+            &[
+                ("service", "Results"),
+                ("method", "get"),
+                ("code", "NOT_FOUND")
+            ]
+        ),
+        1
+    );
+    match value(&snapshot, metrics::CALL_DURATION, &ok) {
+        Some(DebugValue::Histogram(durations)) => assert_eq!(durations.len(), 2),
+        value => panic!("expected durations, but got {value:?}"),
+    }
+}
+
+#[tokio::test]
+async fn upload_and_download_count_the_data_they_carry() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let _guard = ::metrics::set_default_local_recorder(&recorder);
+    let server = MockServer::start().await.unwrap();
+    let session_id = session(&server).await;
+    let mut client = server.client().await.unwrap();
+
+    let result = client
+        .results()
+        .create_metadata(
+            &session_id,
+            [results::create_metadata::RequestItem {
+                name: String::from("data"),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0);
+    client
+        .results()
+        .upload(
+            &session_id,
+            &result.result_id,
+            futures::stream::iter([b"abc".to_vec(), b"defg".to_vec()]),
+        )
+        .await
+        .unwrap();
+    let downloaded = client
+        .results()
+        .download(&session_id, &result.result_id)
+        .await
+        .unwrap()
+        .map(|chunk| chunk.unwrap())
+        .concat()
+        .await;
+    assert_eq!(downloaded, b"abcdefg");
+
+    let snapshot = snapshot(&snapshotter);
+    let upload = [("service", "Results"), ("method", "upload")];
+    assert_eq!(counter(&snapshot, metrics::SENT_CHUNKS, &upload), 2);
+    assert_eq!(counter(&snapshot, metrics::SENT_BYTES, &upload), 7);
+    let download = [("service", "Results"), ("method", "download")];
+    assert!(counter(&snapshot, metrics::RECEIVED_CHUNKS, &download) >= 1);
+    assert_eq!(counter(&snapshot, metrics::RECEIVED_BYTES, &download), 7);
+    for method in ["upload", "download"] {
+        assert_eq!(
+            counter(
+                &snapshot,
+                metrics::CALLS,
+                &[("service", "Results"), ("method", method), ("code", "OK")]
+            ),
+            1,
+            "{method}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_stream_dropped_before_its_end_is_cancelled() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let _guard = ::metrics::set_default_local_recorder(&recorder);
+    let server = MockServer::start().await.unwrap();
+    let session_id = session(&server).await;
+    let mut client = server.client().await.unwrap();
+
+    let events = client
+        .events()
+        .subscribe(
+            &session_id,
+            tasks::filter::Or::default(),
+            results::filter::Or::default(),
+            [events::EventsEnum::NewResult],
+        )
+        .await
+        .unwrap();
+    let cancelled = [
+        ("service", "Events"),
+        ("method", "subscribe"),
+        ("code", "CANCELLED"),
+    ];
+    assert_eq!(
+        counter(&snapshot(&snapshotter), metrics::CALLS, &cancelled),
+        0
+    );
+
+    drop(events);
+
+    assert_eq!(
+        counter(&snapshot(&snapshotter), metrics::CALLS, &cancelled),
+        1
+    );
+}
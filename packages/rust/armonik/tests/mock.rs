@@ -0,0 +1,416 @@
+use armonik::{
+    events, mock::MockServer, reexports::tokio_stream::StreamExt, results, sessions, tasks,
+    FilterString, FilterStringOperator, ResultStatus, SessionStatus, SortDirection, TaskStatus,
+};
+
+/// Create a session on `server`, on its default partition
+async fn session(server: &MockServer) -> String {
+    server
+        .client()
+        .await
+        .unwrap()
+        .into_sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap()
+}
+
+fn name_starts_with(prefix: &str) -> results::filter::Field {
+    results::filter::Field {
+        field: results::Field::Name,
+        condition: results::filter::Condition::String(FilterString {
+            value: String::from(prefix),
+            operator: FilterStringOperator::StartsWith,
+        }),
+    }
+}
+
+#[tokio::test]
+async fn calls_are_counted_per_rpc() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = server.client().await.unwrap();
+
+    client.versions().list().await.unwrap();
+    client.versions().list().await.unwrap();
+    client.health_checks().check().await.unwrap();
+
+    assert_eq!(server.calls().get("Versions", "ListVersions"), 2);
+    assert_eq!(server.calls().get("HealthChecksService", "CheckHealth"), 1);
+    assert_eq!(server.calls().get("Sessions", "CreateSession"), 0);
+    assert_eq!(server.calls().total(), 3);
+    assert_eq!(server.calls().snapshot()["Versions"]["ListVersions"], 2);
+
+    server.calls().reset();
+    assert_eq!(server.calls().total(), 0);
+}
+
+#[tokio::test]
+async fn a_task_is_submitted_once_its_inputs_are_completed() {
+    let server = MockServer::start().await.unwrap();
+    let session_id = session(&server).await;
+    let client = server.client().await.unwrap();
+    let mut results = client.clone().into_results();
+    let mut tasks = client.into_tasks();
+
+    let payload = results
+        .create(
+            &session_id,
+            [results::create::RequestItem {
+                name: String::from("payload"),
+                data: b"payload".to_vec(),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0);
+    let mut metadata = results
+        .create_metadata(
+            &session_id,
+            ["input", "output"].map(|name| results::create_metadata::RequestItem {
+                name: String::from(name),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+    let output = metadata.pop().unwrap();
+    let input = metadata.pop().unwrap();
+
+    let task_id = tasks
+        .submit(
+            &session_id,
+            None,
+            [tasks::submit::RequestItem {
+                payload_id: payload.result_id.clone(),
+                data_dependencies: vec![input.result_id.clone()],
+                expected_output_keys: vec![output.result_id.clone()],
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0)
+        .task_id;
+    let task = tasks.get(&task_id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Pending);
+    assert_eq!(
+        results.get(&output.result_id).await.unwrap().owner_task_id,
+        task_id
+    );
+
+    results
+        .upload(
+            &session_id,
+            &input.result_id,
+            futures::stream::iter([b"input".to_vec()]),
+        )
+        .await
+        .unwrap();
+    let task = tasks.get(&task_id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Submitted);
+
+    server
+        .control_plane()
+        .complete_task(&task_id, [(output.result_id.clone(), b"output".to_vec())])
+        .unwrap();
+    let task = tasks.get(&task_id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert!(task.ended_at.is_some());
+
+    let data = results
+        .download(&session_id, &output.result_id)
+        .await
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap()
+        .concat();
+    assert_eq!(data, b"output");
+    assert_eq!(server.calls().get("Results", "UploadResultData"), 1);
+    assert_eq!(server.calls().get("Results", "DownloadResultData"), 1);
+}
+
+#[tokio::test]
+async fn a_task_that_cannot_complete_all_its_outputs_completes_none() {
+    let server = MockServer::start().await.unwrap();
+    let session_id = session(&server).await;
+    let client = server.client().await.unwrap();
+    let mut results = client.clone().into_results();
+    let mut tasks = client.into_tasks();
+
+    let payload = results
+        .create(
+            &session_id,
+            [results::create::RequestItem {
+                name: String::from("payload"),
+                data: b"payload".to_vec(),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0);
+    let mut metadata = results
+        .create_metadata(
+            &session_id,
+            ["done", "pending"].map(|name| results::create_metadata::RequestItem {
+                name: String::from(name),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+    let pending = metadata.pop().unwrap();
+    let done = metadata.pop().unwrap();
+    let task_id = tasks
+        .submit(
+            &session_id,
+            None,
+            [tasks::submit::RequestItem {
+                payload_id: payload.result_id.clone(),
+                expected_output_keys: vec![done.result_id.clone(), pending.result_id.clone()],
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0)
+        .task_id;
+    results
+        .upload(
+            &session_id,
+            &done.result_id,
+            futures::stream::iter([b"done".to_vec()]),
+        )
+        .await
+        .unwrap();
+
+    let status = server
+        .control_plane()
+        .complete_task(
+            &task_id,
+            [
+                (pending.result_id.clone(), b"pending".to_vec()),
+                (done.result_id.clone(), b"again".to_vec()),
+            ],
+        )
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    assert_eq!(
+        results.get(&pending.result_id).await.unwrap().status,
+        ResultStatus::Created
+    );
+    assert_eq!(
+        tasks.get(&task_id).await.unwrap().status,
+        TaskStatus::Submitted
+    );
+}
+
+#[tokio::test]
+async fn a_failed_task_aborts_its_outputs() {
+    let server = MockServer::start().await.unwrap();
+    let session_id = session(&server).await;
+    let mut client = server.client().await.unwrap();
+
+    let mut created = client
+        .results()
+        .create_metadata(
+            &session_id,
+            ["payload", "output"].map(|name| results::create_metadata::RequestItem {
+                name: String::from(name),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+    let output = created.pop().unwrap();
+    let payload = created.pop().unwrap();
+    client
+        .results()
+        .upload(
+            &session_id,
+            &payload.result_id,
+            futures::stream::empty::<Vec<u8>>(),
+        )
+        .await
+        .unwrap();
+    let task_id = client
+        .tasks()
+        .submit(
+            &session_id,
+            None,
+            [tasks::submit::RequestItem {
+                payload_id: payload.result_id,
+                expected_output_keys: vec![output.result_id.clone()],
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0)
+        .task_id;
+
+    server.control_plane().fail_task(&task_id, "boom").unwrap();
+
+    let task = client.tasks().get(&task_id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Error);
+    assert_eq!(task.output, tasks::Output::Error(String::from("boom")));
+    let output = client.results().get(&output.result_id).await.unwrap();
+    assert_eq!(output.status, ResultStatus::Aborted);
+}
+
+#[tokio::test]
+async fn events_are_sent_as_they_happen() {
+    let server = MockServer::start().await.unwrap();
+    let session_id = session(&server).await;
+    let mut client = server.client().await.unwrap();
+
+    let events = client
+        .events()
+        .subscribe(
+            &session_id,
+            tasks::filter::Or::default(),
+            results::filter::Or::default(),
+            [
+                events::EventsEnum::NewResult,
+                events::EventsEnum::ResultStatusUpdate,
+            ],
+        )
+        .await
+        .unwrap();
+    let result = client
+        .results()
+        .create_metadata(
+            &session_id,
+            [results::create_metadata::RequestItem {
+                name: String::from("result"),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0);
+    client
+        .results()
+        .upload(
+            &session_id,
+            &result.result_id,
+            futures::stream::iter([b"data".to_vec()]),
+        )
+        .await
+        .unwrap();
+
+    let updates = events
+        .take(2)
+        .map(|event| event.unwrap().update)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        updates,
+        [
+            events::Update::NewResult(events::NewResult {
+                result_id: result.result_id.clone(),
+                owner_id: String::new(),
+                status: ResultStatus::Created,
+            }),
+            events::Update::ResultStatusUpdate(events::ResultStatusUpdate {
+                result_id: result.result_id,
+                status: ResultStatus::Completed,
+            }),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn sessions_go_through_their_lifecycle() {
+    let server = MockServer::start().await.unwrap();
+    let session_id = session(&server).await;
+    let mut sessions = server.client().await.unwrap().into_sessions();
+
+    assert_eq!(
+        sessions.pause(&session_id).await.unwrap().status,
+        SessionStatus::Paused
+    );
+    assert_eq!(
+        sessions.resume(&session_id).await.unwrap().status,
+        SessionStatus::Running
+    );
+    assert!(sessions.purge(&session_id).await.is_err());
+    assert_eq!(
+        sessions.close(&session_id).await.unwrap().status,
+        SessionStatus::Closed
+    );
+    assert!(sessions.resume(&session_id).await.is_err());
+    assert_eq!(
+        sessions.purge(&session_id).await.unwrap().status,
+        SessionStatus::Purged
+    );
+    assert_eq!(
+        sessions.delete(&session_id).await.unwrap().status,
+        SessionStatus::Deleted
+    );
+    assert!(sessions.get(&session_id).await.is_err());
+}
+
+#[tokio::test]
+async fn listings_are_filtered_sorted_and_paged() {
+    let server = MockServer::start().await.unwrap();
+    let session_id = session(&server).await;
+    let mut client = server.client().await.unwrap();
+
+    client
+        .results()
+        .create_metadata(
+            &session_id,
+            ["kept-1", "other", "kept-3", "kept-2"].map(|name| {
+                results::create_metadata::RequestItem {
+                    name: String::from(name),
+                    ..Default::default()
+                }
+            }),
+        )
+        .await
+        .unwrap();
+    let sort = results::Sort {
+        field: results::Field::Name,
+        direction: SortDirection::Desc,
+    };
+
+    let page = client
+        .results()
+        .list([[name_starts_with("kept")]], sort.clone(), 0, 2)
+        .await
+        .unwrap();
+    assert_eq!(page.total, 3);
+    let names = page
+        .results
+        .iter()
+        .map(|result| result.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["kept-3", "kept-2"]);
+
+    let listed = client
+        .clone()
+        .into_results()
+        .list_stream([[name_starts_with("kept")]], sort, 2)
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 3);
+    assert_eq!(server.calls().get("Results", "ListResults"), 3);
+
+    let sessions = client
+        .sessions()
+        .list(
+            sessions::filter::Or::default(),
+            Default::default(),
+            false,
+            0,
+            10,
+        )
+        .await
+        .unwrap();
+    assert_eq!(sessions.total, 1);
+}
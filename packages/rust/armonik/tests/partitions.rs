@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use armonik::{
     partitions,
+    reexports::tokio_stream::StreamExt,
     server::{PartitionsServiceExt, RequestContext},
 };
 
@@ -72,6 +73,51 @@ async fn list() {
     assert_eq!(response.partitions[0].partition_id, "rpc-list-output");
 }
 
+#[tokio::test]
+async fn list_stream() {
+    let client =
+        armonik::Client::with_channel(Service::default().partitions_server()).into_partitions();
+
+    let partitions = client
+        .list_stream(
+            armonik::partitions::filter::Or::default(),
+            armonik::partitions::Sort::default(),
+            1,
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap();
+
+    assert_eq!(partitions.len(), 1337);
+    assert!(partitions
+        .iter()
+        .all(|partition| partition.partition_id == "rpc-list-output"));
+}
+
+#[tokio::test]
+async fn list_stream_failure() {
+    let client = armonik::Client::with_channel(
+        Service {
+            failure: Some(tonic::Status::unavailable("rpc-list-failure")),
+            ..Default::default()
+        }
+        .partitions_server(),
+    )
+    .into_partitions();
+
+    let partitions = client
+        .list_stream(
+            armonik::partitions::filter::Or::default(),
+            armonik::partitions::Sort::default(),
+            12,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(partitions.len(), 1);
+    assert!(partitions[0].is_err());
+}
+
 #[tokio::test]
 async fn get() {
     let mut client =
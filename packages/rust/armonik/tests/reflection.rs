@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use armonik::{
+    server::{ArmoniKServer, RequestContext, ServerConfig},
+    versions,
+};
+use tonic_reflection::pb::v1::{
+    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse, ServerReflectionRequest,
+};
+
+#[derive(Debug, Clone, Default)]
+struct Service;
+
+impl armonik::server::VersionsService for Service {
+    async fn list(
+        self: Arc<Self>,
+        _request: versions::list::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<versions::list::Response, tonic::Status> {
+        Ok(Default::default())
+    }
+}
+
+/// Serve the versions service, with reflection if `reflection`, and connect to it.
+async fn connect(reflection: bool) -> ServerReflectionClient<armonik::client::Channel> {
+    let mut builder = ArmoniKServer::builder().versions(Service);
+    if reflection {
+        builder = builder.reflection();
+    }
+    let server = builder
+        .bind(&ServerConfig::new("127.0.0.1:0".parse().unwrap()))
+        .await
+        .unwrap();
+    let endpoint = format!("http://{}", server.local_addr());
+    tokio::spawn(server.serve());
+
+    let mut args = armonik::client::ClientConfigArgs::default();
+    args.endpoint = endpoint;
+    let config = armonik::ClientConfig::from_config_args(args).unwrap();
+    ServerReflectionClient::new(armonik::transport::connect(config).await.unwrap())
+}
+
+/// Send `request` to the reflection service, and get its response.
+async fn ask(
+    client: &mut ServerReflectionClient<armonik::client::Channel>,
+    request: MessageRequest,
+) -> Result<MessageResponse, tonic::Status> {
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(request),
+    };
+    let mut responses = client
+        .server_reflection_info(futures::stream::iter([request]))
+        .await?
+        .into_inner();
+    Ok(responses
+        .message()
+        .await?
+        .unwrap()
+        .message_response
+        .unwrap())
+}
+
+#[tokio::test]
+async fn only_the_services_served_are_listed() {
+    let mut client = connect(true).await;
+
+    let MessageResponse::ListServicesResponse(response) =
+        ask(&mut client, MessageRequest::ListServices(String::new()))
+            .await
+            .unwrap()
+    else {
+        panic!("not a list of services");
+    };
+
+    let mut services = response
+        .service
+        .into_iter()
+        .map(|service| service.name)
+        .collect::<Vec<_>>();
+    services.sort();
+    assert_eq!(
+        services,
+        [
+            "armonik.api.grpc.v1.versions.Versions",
+            "grpc.health.v1.Health",
+            "grpc.reflection.v1.ServerReflection",
+            "grpc.reflection.v1alpha.ServerReflection",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn services_are_described() {
+    let mut client = connect(true).await;
+
+    let MessageResponse::FileDescriptorResponse(response) = ask(
+        &mut client,
+        MessageRequest::FileContainingSymbol(String::from("armonik.api.grpc.v1.versions.Versions")),
+    )
+    .await
+    .unwrap() else {
+        panic!("not a file descriptor");
+    };
+
+    let files = response
+        .file_descriptor_proto
+        .iter()
+        .map(|file| <prost_types::FileDescriptorProto as prost::Message>::decode(file.as_slice()))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let service = files
+        .iter()
+        .flat_map(|file| &file.service)
+        .find(|service| service.name() == "Versions")
+        .unwrap();
+    assert_eq!(service.method[0].name(), "ListVersions");
+}
+
+#[tokio::test]
+async fn reflection_is_opt_in() {
+    let mut client = connect(false).await;
+
+    let err = ask(&mut client, MessageRequest::ListServices(String::new()))
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.code(), tonic::Code::Unimplemented);
+}
@@ -0,0 +1,343 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use armonik::{
+    auth,
+    client::{CallOptions, ClientConfig, ClientConfigArgs},
+    reexports::http::HeaderMap,
+    server::{
+        ArmoniKServer, Authenticator, PeerCertificate, RequestContext, ServerConfig,
+        ServerConfigArgs,
+    },
+    versions,
+};
+use common::{ca, dir, issue, leaf, write};
+use tonic::metadata::MetadataMap;
+
+mod common;
+
+/// What the service saw of a call.
+#[derive(Debug, Clone)]
+struct Seen {
+    peer_addr: Option<std::net::SocketAddr>,
+    timeout: Option<Duration>,
+    has_deadline: bool,
+    certificate: Option<PeerCertificate>,
+    user: Option<auth::User>,
+    cancelled: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Service {
+    /// Record what the context of each call tells
+    Record(Arc<Mutex<Option<Seen>>>),
+    /// Answer with the name of the user, if granted the permission
+    Require(&'static str),
+    /// Never answer, and send once the caller gave up
+    Hang(Arc<Mutex<Option<tokio::sync::oneshot::Sender<()>>>>),
+}
+
+impl armonik::server::VersionsService for Service {
+    async fn list(
+        self: Arc<Self>,
+        _request: versions::list::Request,
+        context: RequestContext,
+    ) -> std::result::Result<versions::list::Response, tonic::Status> {
+        match self.as_ref() {
+            Service::Record(seen) => {
+                *seen.lock().unwrap() = Some(Seen {
+                    peer_addr: context.peer_addr(),
+                    timeout: context.timeout(),
+                    has_deadline: context.deadline().is_some(),
+                    certificate: context.client_certificate(),
+                    user: context.user().cloned(),
+                    cancelled: context.is_cancelled(),
+                });
+                Ok(Default::default())
+            }
+            Service::Require(permission) => {
+                let user = context.require_permission(permission)?;
+                Ok(versions::list::Response {
+                    core: user.username.clone(),
+                    ..Default::default()
+                })
+            }
+            Service::Hang(cancelled) => {
+                let sender = cancelled.lock().unwrap().take().unwrap();
+                let cancellation = context.cancelled();
+                tokio::spawn(async move {
+                    cancellation.await;
+                    sender.send(()).unwrap();
+                });
+                std::future::pending().await
+            }
+        }
+    }
+}
+
+/// Users known by the `x-user` header they send: `alice` may list versions, `bob` may not, and
+/// `anonymous` is let through as nobody. Anyone else is refused.
+struct Header;
+
+impl Authenticator for Header {
+    async fn authenticate(
+        &self,
+        context: &RequestContext,
+    ) -> Result<Option<auth::User>, tonic::Status> {
+        let name = context
+            .headers()
+            .get("x-user")
+            .and_then(|name| name.to_str().ok());
+        let permissions = match name {
+            Some("alice") => vec![String::from("Versions:ListVersions")],
+            Some("bob") => vec![String::from("Sessions:CreateSession")],
+            Some("anonymous") => return Ok(None),
+            _ => return Err(tonic::Status::unauthenticated("unknown user")),
+        };
+        Ok(Some(auth::User {
+            username: name.unwrap().to_owned(),
+            roles: Vec::new(),
+            permissions,
+        }))
+    }
+}
+
+/// Users known by the common name of their certificate.
+struct Certificate;
+
+impl Authenticator for Certificate {
+    async fn authenticate(
+        &self,
+        context: &RequestContext,
+    ) -> Result<Option<auth::User>, tonic::Status> {
+        Ok(context
+            .client_certificate()
+            .and_then(|certificate| certificate.common_name)
+            .map(|username| auth::User {
+                username,
+                roles: vec![String::from("user")],
+                permissions: vec![String::from("Versions:ListVersions")],
+            }))
+    }
+}
+
+/// Serve `service` with `config`, authenticated by `authenticator` if any, and get its address.
+async fn serve(
+    service: Service,
+    config: ServerConfig,
+    authenticator: Option<impl Authenticator>,
+) -> std::net::SocketAddr {
+    let mut builder = ArmoniKServer::builder().versions(service);
+    if let Some(authenticator) = authenticator {
+        builder = builder.authenticator(authenticator);
+    }
+    let server = builder.bind(&config).await.unwrap();
+    let address = server.local_addr();
+    tokio::spawn(server.serve());
+    address
+}
+
+fn plain_text() -> ServerConfig {
+    ServerConfig::new("127.0.0.1:0".parse().unwrap())
+}
+
+async fn client(endpoint: String, set: impl FnOnce(&mut ClientConfigArgs)) -> armonik::Client {
+    let mut args = ClientConfigArgs::default();
+    args.endpoint = endpoint;
+    set(&mut args);
+    armonik::Client::with_config(ClientConfig::from_config_args(args).unwrap())
+        .await
+        .unwrap()
+}
+
+/// List the versions as `user`, as far as the `x-user` header tells.
+async fn list_as(
+    client: &mut armonik::Client,
+    user: Option<&str>,
+) -> Result<String, armonik::client::RequestError> {
+    let mut metadata = MetadataMap::new();
+    if let Some(user) = user {
+        metadata.insert("x-user", user.parse().unwrap());
+    }
+    let response = client
+        .versions()
+        .with_options(CallOptions::new().with_metadata(metadata))
+        .list()
+        .await?;
+    Ok(response.core)
+}
+
+#[tokio::test]
+async fn peer_address_and_deadline() {
+    let seen = Arc::new(Mutex::new(None));
+    let address = serve(Service::Record(seen.clone()), plain_text(), None::<Header>).await;
+    let mut client = client(format!("http://{address}"), |_| ()).await;
+
+    client
+        .versions()
+        .with_options(CallOptions::new().with_deadline(Duration::from_secs(30)))
+        .list()
+        .await
+        .unwrap();
+
+    let seen = seen.lock().unwrap().take().unwrap();
+    let peer_addr = seen.peer_addr.unwrap();
+    assert!(peer_addr.ip().is_loopback(), "{peer_addr}");
+    assert_ne!(peer_addr.port(), address.port());
+    let timeout = seen.timeout.unwrap();
+    assert!(
+        timeout > Duration::from_secs(25) && timeout <= Duration::from_secs(30),
+        "{timeout:?}"
+    );
+    assert!(seen.has_deadline);
+    assert!(seen.certificate.is_none());
+    assert!(seen.user.is_none());
+    assert!(!seen.cancelled);
+}
+
+#[test]
+fn grpc_timeout_is_read_in_its_units() {
+    for (value, timeout) in [
+        ("2H", Some(Duration::from_secs(7200))),
+        ("3M", Some(Duration::from_secs(180))),
+        ("10S", Some(Duration::from_secs(10))),
+        ("250m", Some(Duration::from_millis(250))),
+        ("7u", Some(Duration::from_micros(7))),
+        ("99999999n", Some(Duration::from_nanos(99999999))),
+        ("100", None),
+        ("m", None),
+        ("123456789m", None),
+        ("+5S", None),
+        ("5s", None),
+    ] {
+        let mut headers = HeaderMap::new();
+        headers.insert("grpc-timeout", value.parse().unwrap());
+
+        let context = RequestContext::new(headers, Default::default());
+
+        assert_eq!(context.timeout(), timeout, "{value}");
+        assert_eq!(context.deadline().is_some(), timeout.is_some(), "{value}");
+    }
+}
+
+#[tokio::test]
+async fn permissions_are_required_of_the_authenticated_user() {
+    let address = serve(
+        Service::Require("Versions:ListVersions"),
+        plain_text(),
+        Some(Header),
+    )
+    .await;
+    let mut client = client(format!("http://{address}"), |_| ()).await;
+
+    assert_eq!(list_as(&mut client, Some("alice")).await.unwrap(), "alice");
+
+    let denied = list_as(&mut client, Some("bob")).await.unwrap_err();
+    assert!(denied.is_permission_denied(), "{denied:?}");
+
+    let anonymous = list_as(&mut client, Some("anonymous")).await.unwrap_err();
+    assert_eq!(anonymous.code(), Some(tonic::Code::Unauthenticated));
+
+    let unknown = list_as(&mut client, Some("mallory")).await.unwrap_err();
+    assert_eq!(unknown.code(), Some(tonic::Code::Unauthenticated));
+    assert_eq!(unknown.status().unwrap().message(), "unknown user");
+}
+
+#[tokio::test]
+async fn health_checks_are_not_authenticated() {
+    let address = serve(
+        Service::Require("Versions:ListVersions"),
+        plain_text(),
+        Some(Header),
+    )
+    .await;
+    let config = {
+        let mut args = ClientConfigArgs::default();
+        args.endpoint = format!("http://{address}");
+        ClientConfig::from_config_args(args).unwrap()
+    };
+    let mut health = tonic_health::pb::health_client::HealthClient::new(
+        armonik::transport::connect(config).await.unwrap(),
+    );
+
+    health
+        .check(tonic_health::pb::HealthCheckRequest {
+            service: String::from("armonik.api.grpc.v1.versions.Versions"),
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn the_handler_is_told_when_the_caller_gives_up() {
+    let (sender, cancelled) = tokio::sync::oneshot::channel();
+    let address = serve(
+        Service::Hang(Arc::new(Mutex::new(Some(sender)))),
+        plain_text(),
+        None::<Header>,
+    )
+    .await;
+    let mut client = client(format!("http://{address}"), |_| ()).await;
+
+    let token = tokio_util::sync::CancellationToken::new();
+    tokio::spawn({
+        let token = token.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            token.cancel();
+        }
+    });
+    let err = client
+        .versions()
+        .with_options(CallOptions::new().with_cancellation(token))
+        .list()
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(tonic::Code::Cancelled));
+
+    tokio::time::timeout(Duration::from_secs(5), cancelled)
+        .await
+        .expect("the handler was not told")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn the_client_certificate_authenticates_the_user() {
+    let dir = dir("request-context-mtls");
+    let ca = ca("ca");
+    let ca_cert = write(&dir, "ca.pem", ca.pem());
+    let (server_cert, server_key) = leaf(&dir, "server", &ca);
+    let (client_cert, client_key) = issue("alice", &["alice.armonik.local"], &ca);
+
+    let mut args = ServerConfigArgs::default();
+    args.address = String::from("127.0.0.1:0");
+    args.cert_pem = server_cert;
+    args.key_pem = server_key;
+    args.client_ca_cert = ca_cert.clone();
+    let config = ServerConfig::from_config_args(args).unwrap();
+    let seen = Arc::new(Mutex::new(None));
+    let address = serve(Service::Record(seen.clone()), config, Some(Certificate)).await;
+
+    let mut client = client(format!("https://localhost:{}", address.port()), |args| {
+        args.ca_cert = ca_cert;
+        args.cert_pem = write(&dir, "client.pem", client_cert.pem());
+        args.key_pem = write(&dir, "client.key", client_key.serialize_pem());
+    })
+    .await;
+    client.versions().list().await.unwrap();
+
+    let seen = seen.lock().unwrap().take().unwrap();
+    assert!(seen.peer_addr.unwrap().ip().is_loopback());
+    let certificate = seen.certificate.unwrap();
+    assert_eq!(certificate.common_name.as_deref(), Some("alice"));
+    assert_eq!(certificate.subject_alt_names, ["alice.armonik.local"]);
+    assert_eq!(
+        certificate.fingerprint,
+        PeerCertificate::parse(client_cert.der().clone())
+            .unwrap()
+            .fingerprint
+    );
+    assert_eq!(seen.user.unwrap().username, "alice");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,242 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use armonik::{
+    client::{ClientConfig, ClientConfigArgs, ResilientSubscription, RetryPolicy},
+    events,
+    mock::ControlPlane,
+    reexports::tokio_stream::{wrappers::TcpListenerStream, Stream, StreamExt},
+    results,
+    server::{
+        EventsService, EventsServiceExt, RequestContext, ResultsServiceExt, SessionsServiceExt,
+        TasksServiceExt,
+    },
+    Client, ResultStatus,
+};
+use futures::stream::BoxStream;
+
+/// Events of the control plane, whose first `cuts` subscriptions end after `after` events
+struct Flaky {
+    control_plane: ControlPlane,
+    cuts: AtomicUsize,
+    after: usize,
+}
+
+impl EventsService for Flaky {
+    async fn subscribe(
+        self: Arc<Self>,
+        request: events::subscribe::Request,
+        context: RequestContext,
+    ) -> Result<
+        impl Stream<Item = Result<events::subscribe::Response, tonic::Status>> + Send,
+        tonic::Status,
+    > {
+        let events = Arc::new(self.control_plane.clone())
+            .subscribe(request, context)
+            .await?;
+        let cut = self
+            .cuts
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |cuts| {
+                cuts.checked_sub(1)
+            })
+            .is_ok();
+        let events: BoxStream<'static, _> = if cut {
+            Box::pin(events.take(self.after))
+        } else {
+            Box::pin(events)
+        };
+        Ok(events)
+    }
+}
+
+/// Serve the sessions, tasks and results of `control_plane`, with `events` if any
+async fn serve(
+    control_plane: &ControlPlane,
+    events: Option<Flaky>,
+) -> (Client, tokio::task::JoinHandle<()>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let router = tonic::transport::Server::builder()
+        .add_service(control_plane.clone().sessions_server())
+        .add_service(control_plane.clone().results_server())
+        .add_service(control_plane.clone().tasks_server())
+        .add_optional_service(events.map(EventsServiceExt::events_server));
+    let served = tokio::spawn(async move {
+        router
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .unwrap()
+    });
+
+    let mut args = ClientConfigArgs::default();
+    args.endpoint = format!("http://{address}");
+    let client = Client::with_config(ClientConfig::from_config_args(args).unwrap())
+        .await
+        .unwrap();
+    (client, served)
+}
+
+fn retry_policy(max_attempts: u32) -> RetryPolicy {
+    let mut policy = RetryPolicy::default();
+    policy.max_attempts = max_attempts;
+    policy.initial_backoff = Duration::from_millis(10);
+    policy
+}
+
+async fn create(client: &mut Client, session_id: &str, names: &[&str]) -> Vec<String> {
+    client
+        .results()
+        .create_metadata(
+            session_id,
+            names
+                .iter()
+                .map(|name| results::create_metadata::RequestItem {
+                    name: name.to_string(),
+                    ..Default::default()
+                }),
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|result| result.result_id)
+        .collect()
+}
+
+async fn next(subscription: &mut ResilientSubscription) -> events::Update {
+    tokio::time::timeout(Duration::from_secs(10), subscription.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+        .update
+}
+
+#[tokio::test]
+async fn missed_updates_are_caught_up_after_subscribing_again() {
+    let control_plane = ControlPlane::new();
+    let events = Flaky {
+        control_plane: control_plane.clone(),
+        cuts: AtomicUsize::new(1),
+        after: 2,
+    };
+    let (mut client, served) = serve(&control_plane, Some(events)).await;
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+
+    let mut subscription = ResilientSubscription::with_retry_policy(
+        client.clone(),
+        events::subscribe::Request {
+            session_id: session_id.clone(),
+            ..Default::default()
+        },
+        retry_policy(5),
+    );
+    // Nothing happened yet, but the subscription is now open.
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), subscription.next())
+            .await
+            .is_err()
+    );
+
+    let ids = create(&mut client, &session_id, &["first", "second"]).await;
+    for id in &ids {
+        assert!(matches!(
+            next(&mut subscription).await,
+            events::Update::NewResult(events::NewResult { result_id, .. }) if result_id == *id
+        ));
+    }
+
+    // The first stream ended: this upload happens while nothing listens.
+    client
+        .results()
+        .upload(
+            &session_id,
+            &ids[0],
+            futures::stream::iter([b"data".to_vec()]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        next(&mut subscription).await,
+        events::Update::ResultStatusUpdate(events::ResultStatusUpdate {
+            result_id: ids[0].clone(),
+            status: ResultStatus::Completed,
+        })
+    );
+
+    let third = create(&mut client, &session_id, &["third"]).await.remove(0);
+    assert!(matches!(
+        next(&mut subscription).await,
+        events::Update::NewResult(events::NewResult { result_id, .. }) if result_id == third
+    ));
+    assert_eq!(control_plane.calls().get("Events", "GetEvents"), 2);
+    assert_eq!(control_plane.calls().get("Results", "ListResults"), 1);
+    assert_eq!(control_plane.calls().get("Tasks", "ListTasks"), 1);
+    served.abort();
+}
+
+#[tokio::test]
+async fn the_last_error_ends_the_stream_once_attempts_are_exhausted() {
+    let control_plane = ControlPlane::new();
+    let (mut client, served) = serve(&control_plane, None).await;
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+
+    let subscription = ResilientSubscription::with_retry_policy(
+        client,
+        events::subscribe::Request {
+            session_id,
+            ..Default::default()
+        },
+        retry_policy(3),
+    );
+    let items = tokio::time::timeout(Duration::from_secs(10), subscription.collect::<Vec<_>>())
+        .await
+        .unwrap();
+
+    assert_eq!(items.len(), 1);
+    assert!(items[0].is_err());
+    served.abort();
+}
+
+#[tokio::test]
+async fn streams_ending_without_events_are_attempts_too() {
+    let control_plane = ControlPlane::new();
+    let events = Flaky {
+        control_plane: control_plane.clone(),
+        cuts: AtomicUsize::new(usize::MAX),
+        after: 0,
+    };
+    let (mut client, served) = serve(&control_plane, Some(events)).await;
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+
+    let subscription = ResilientSubscription::with_retry_policy(
+        client,
+        events::subscribe::Request {
+            session_id,
+            ..Default::default()
+        },
+        retry_policy(3),
+    );
+    let started = std::time::Instant::now();
+    let items = tokio::time::timeout(Duration::from_secs(10), subscription.collect::<Vec<_>>())
+        .await
+        .unwrap();
+
+    assert!(items.is_empty());
+    assert_eq!(control_plane.calls().get("Events", "GetEvents"), 3);
+    // Backing off 10ms, then 20ms, between the three, at least halved by the jitter.
+    assert!(started.elapsed() >= Duration::from_millis(15));
+    served.abort();
+}
@@ -292,6 +292,50 @@ async fn list() {
     assert_eq!(response.results[0].name, "rpc-list-output");
 }
 
+#[tokio::test]
+async fn list_stream() {
+    let client = armonik::Client::with_channel(Service::default().results_server()).into_results();
+
+    let results = client
+        .list_stream(
+            armonik::results::filter::Or::default(),
+            armonik::results::Sort::default(),
+            1,
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 1337);
+    assert!(results
+        .iter()
+        .all(|result| result.name == "rpc-list-output"));
+}
+
+#[tokio::test]
+async fn list_stream_failure() {
+    let client = armonik::Client::with_channel(
+        Service {
+            failure: Some(tonic::Status::unavailable("rpc-list-failure")),
+            ..Default::default()
+        }
+        .results_server(),
+    )
+    .into_results();
+
+    let results = client
+        .list_stream(
+            armonik::results::filter::Or::default(),
+            armonik::results::Sort::default(),
+            12,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(results.len(), 1);
+    assert!(results[0].is_err());
+}
+
 #[tokio::test]
 async fn get() {
     let mut client =
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use armonik::{
+    reexports::tokio_stream::StreamExt,
     server::{RequestContext, SessionsServiceExt},
     sessions,
 };
@@ -205,6 +206,53 @@ async fn list() {
     assert_eq!(response.sessions[0].session_id, "rpc-list-output");
 }
 
+#[tokio::test]
+async fn list_stream() {
+    let client =
+        armonik::Client::with_channel(Service::default().sessions_server()).into_sessions();
+
+    let sessions = client
+        .list_stream(
+            armonik::sessions::filter::Or::default(),
+            armonik::sessions::Sort::default(),
+            true,
+            1,
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap();
+
+    assert_eq!(sessions.len(), 1337);
+    assert!(sessions
+        .iter()
+        .all(|session| session.session_id == "rpc-list-output"));
+}
+
+#[tokio::test]
+async fn list_stream_failure() {
+    let client = armonik::Client::with_channel(
+        Service {
+            failure: Some(tonic::Status::unavailable("rpc-list-failure")),
+            ..Default::default()
+        }
+        .sessions_server(),
+    )
+    .into_sessions();
+
+    let sessions = client
+        .list_stream(
+            armonik::sessions::filter::Or::default(),
+            armonik::sessions::Sort::default(),
+            true,
+            12,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].is_err());
+}
+
 #[tokio::test]
 async fn get() {
     let mut client =
@@ -0,0 +1,92 @@
+use armonik::{
+    client::{TaskGraph, TaskGraphError, TaskNode},
+    mock::{MockServer, DATA_CHUNK_MAX_SIZE},
+    TaskStatus,
+};
+
+fn node(payload: impl Into<Vec<u8>>, dependencies: &[&str], outputs: &[&str]) -> TaskNode {
+    let mut node = TaskNode::new(payload);
+    node.data_dependencies = dependencies.iter().map(|name| name.to_string()).collect();
+    node.expected_outputs = outputs.iter().map(|name| name.to_string()).collect();
+    node
+}
+
+#[tokio::test]
+async fn a_graph_is_submitted_with_its_data() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = server.client().await.unwrap();
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+    server.calls().reset();
+
+    let large = vec![7u8; DATA_CHUNK_MAX_SIZE as usize * 2 + 1];
+    let submitted = TaskGraph::new()
+        .add_task("second", node("second", &["y"], &["z"]))
+        .add_data("x", b"x".to_vec())
+        .add_task("first", node(large.clone(), &["x"], &["y"]))
+        .submit(&mut client, &session_id)
+        .await
+        .unwrap();
+
+    assert_eq!(submitted.tasks.len(), 2);
+    assert_eq!(submitted.results.len(), 3);
+    let state = server.control_plane();
+    let mut tasks = client.tasks();
+    let first = tasks.get(&submitted.tasks["first"]).await.unwrap();
+    assert_eq!(first.status, TaskStatus::Submitted);
+    assert_eq!(first.data_dependencies, [submitted.results["x"].as_str()]);
+    assert_eq!(first.expected_output_ids, [submitted.results["y"].as_str()]);
+    assert_eq!(state.result_data(&first.payload_id).unwrap(), large);
+    let second = tasks.get(&submitted.tasks["second"]).await.unwrap();
+    assert_eq!(second.status, TaskStatus::Pending);
+    assert_eq!(second.data_dependencies, [submitted.results["y"].as_str()]);
+    assert_eq!(state.result_data(&second.payload_id).unwrap(), b"second");
+
+    let calls = server.calls();
+    assert_eq!(calls.get("Results", "GetServiceConfiguration"), 1);
+    assert_eq!(calls.get("Results", "CreateResults"), 1);
+    assert_eq!(calls.get("Results", "CreateResultsMetaData"), 1);
+    assert_eq!(calls.get("Results", "UploadResultData"), 1);
+    assert_eq!(calls.get("Tasks", "SubmitTasks"), 1);
+}
+
+#[tokio::test]
+async fn an_invalid_graph_is_rejected_before_being_sent() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = server.client().await.unwrap();
+
+    let dangling = TaskGraph::new()
+        .add_task("task", node("task", &["missing"], &[]))
+        .submit(&mut client, "session")
+        .await;
+    assert!(matches!(
+        dangling,
+        Err(TaskGraphError::Dangling { task, name, .. }) if task == "task" && name == "missing"
+    ));
+
+    let cycle = TaskGraph::new()
+        .add_data("x", b"x".to_vec())
+        .add_task("entry", node("entry", &["x"], &["a"]))
+        .add_task("ping", node("ping", &["a", "c"], &["b"]))
+        .add_task("pong", node("pong", &["b"], &["c"]))
+        .submit(&mut client, "session")
+        .await;
+    assert!(matches!(
+        cycle,
+        Err(TaskGraphError::Cycle { tasks, .. }) if tasks == ["ping", "pong"]
+    ));
+
+    let duplicate = TaskGraph::new()
+        .add_data("x", b"x".to_vec())
+        .add_task("task", node("task", &[], &["x"]))
+        .validate();
+    assert!(matches!(
+        duplicate,
+        Err(TaskGraphError::Duplicate { name, .. }) if name == "x"
+    ));
+
+    assert_eq!(server.calls().total(), 0);
+}
@@ -0,0 +1,422 @@
+use std::sync::{Arc, Mutex};
+
+use armonik::{
+    agent,
+    server::{AgentServiceExt, RequestContext, WorkerServiceExt},
+    worker::{self, Processor, TaskHandler, TaskHandlerError, WorkerWrapper},
+    Output,
+};
+
+mod common;
+
+/// What the agent was asked, as `(communication_token, session_id, what)`.
+type Calls = Arc<Mutex<Vec<(String, String, String)>>>;
+
+/// An agent recording the calls it receives, so that a test can check the token and session it was
+/// given were the ones of the task.
+#[derive(Debug, Clone, Default)]
+struct Agent {
+    calls: Calls,
+}
+
+impl Agent {
+    fn record(&self, token: &str, session_id: &str, what: String) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((token.to_owned(), session_id.to_owned(), what));
+    }
+}
+
+impl armonik::server::AgentService for Agent {
+    async fn create_results_metadata(
+        self: Arc<Self>,
+        request: agent::create_results_metadata::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::create_results_metadata::Response, tonic::Status> {
+        self.record(
+            &request.communication_token,
+            &request.session_id,
+            String::from("create_results_metadata"),
+        );
+        Ok(agent::create_results_metadata::Response {
+            communication_token: request.communication_token,
+            results: request
+                .results
+                .into_iter()
+                .map(|item| agent::ResultMetaData {
+                    session_id: request.session_id.clone(),
+                    result_id: format!("id-{}", item.name),
+                    name: item.name,
+                    ..Default::default()
+                })
+                .collect(),
+        })
+    }
+
+    async fn create_results(
+        self: Arc<Self>,
+        request: agent::create_results::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::create_results::Response, tonic::Status> {
+        self.record(
+            &request.communication_token,
+            &request.session_id,
+            String::from("create_results"),
+        );
+        Ok(agent::create_results::Response {
+            communication_token: request.communication_token,
+            results: request
+                .results
+                .into_iter()
+                .map(|item| agent::ResultMetaData {
+                    session_id: request.session_id.clone(),
+                    result_id: format!("id-{}", item.name),
+                    name: item.name,
+                    ..Default::default()
+                })
+                .collect(),
+        })
+    }
+
+    async fn notify_result_data(
+        self: Arc<Self>,
+        request: agent::notify_result_data::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::notify_result_data::Response, tonic::Status> {
+        self.record(
+            &request.communication_token,
+            &request.session_id,
+            format!("notify_result_data {}", request.result_ids.join(",")),
+        );
+        Ok(agent::notify_result_data::Response {
+            result_ids: request.result_ids,
+        })
+    }
+
+    async fn submit_tasks(
+        self: Arc<Self>,
+        request: agent::submit_tasks::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::submit_tasks::Response, tonic::Status> {
+        self.record(
+            &request.communication_token,
+            &request.session_id,
+            format!("submit_tasks {}", request.items.len()),
+        );
+        Ok(agent::submit_tasks::Response {
+            communication_token: request.communication_token,
+            items: request
+                .items
+                .into_iter()
+                .map(|item| agent::submit_tasks::ResponseItem {
+                    task_id: String::from("subtask"),
+                    expected_output_ids: item.expected_output_keys,
+                    data_dependencies: item.data_dependencies,
+                    payload_id: item.payload_id,
+                })
+                .collect(),
+        })
+    }
+
+    async fn get_resource_data(
+        self: Arc<Self>,
+        _request: agent::get_resource_data::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::get_resource_data::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("get_resource_data"))
+    }
+
+    async fn get_common_data(
+        self: Arc<Self>,
+        _request: agent::get_common_data::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::get_common_data::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("get_common_data"))
+    }
+
+    async fn get_direct_data(
+        self: Arc<Self>,
+        _request: agent::get_direct_data::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::get_direct_data::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("get_direct_data"))
+    }
+
+    async fn create_tasks(
+        self: Arc<Self>,
+        _request: impl tonic::codegen::tokio_stream::Stream<
+                Item = Result<agent::create_tasks::Request, tonic::Status>,
+            > + Send
+            + 'static,
+        _context: RequestContext,
+    ) -> Result<agent::create_tasks::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("create_tasks"))
+    }
+}
+
+type AgentChannel = armonik::Client<armonik::api::v3::agent::agent_server::AgentServer<Agent>>;
+
+/// Concatenates its payload and its dependency into its output, and submits one subtask reading it.
+struct Concat;
+
+impl Processor<AgentChannel> for Concat {
+    async fn process(&self, task_handler: &TaskHandler<AgentChannel>) -> Output {
+        let outcome = async {
+            let mut output = task_handler.payload().await?.to_vec();
+            output.extend_from_slice(task_handler.data_dependency("dependency").await?);
+
+            let output_id = task_handler.expected_output_keys()[0].clone();
+            task_handler.send_result(&output_id, output).await?;
+
+            let payload = task_handler
+                .create_results([("subtask-payload", b"sub".to_vec())])
+                .await?;
+            let outputs = task_handler
+                .create_results_metadata(["subtask-output"])
+                .await?;
+            task_handler
+                .submit_tasks(
+                    None,
+                    [agent::submit_tasks::RequestItem {
+                        payload_id: payload[0].result_id.clone(),
+                        expected_output_keys: vec![outputs[0].result_id.clone()],
+                        data_dependencies: vec![output_id],
+                        task_options: None,
+                    }],
+                )
+                .await?;
+            Ok::<_, TaskHandlerError>(())
+        };
+
+        match outcome.await {
+            Ok(()) => Output::Ok,
+            Err(error) => Output::Error {
+                details: error.to_string(),
+            },
+        }
+    }
+}
+
+/// A data folder of its own per test, holding `files`.
+fn data_folder(name: &str, files: &[(&str, &[u8])]) -> std::path::PathBuf {
+    let folder = std::env::temp_dir().join(format!(
+        "armonik-task-handler-{name}-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    for (file, content) in files {
+        std::fs::write(folder.join(file), content).unwrap();
+    }
+    folder
+}
+
+fn request(folder: &std::path::Path) -> worker::process::Request {
+    worker::process::Request {
+        communication_token: String::from("token"),
+        session_id: String::from("session"),
+        task_id: String::from("task"),
+        expected_output_keys: vec![String::from("output")],
+        payload_id: String::from("payload"),
+        data_dependencies: vec![String::from("dependency")],
+        data_folder: folder.to_string_lossy().into_owned(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn process_reads_inputs_writes_outputs_and_calls_back_with_the_task_token() {
+    let folder = data_folder("process", &[("payload", b"abc"), ("dependency", b"def")]);
+    let agent = Agent::default();
+    let calls = agent.calls.clone();
+
+    let wrapper = WorkerWrapper::new(
+        Concat,
+        armonik::Client::with_channel(agent.agent_server()).into_agent(),
+    );
+    let mut client = armonik::Client::with_channel(wrapper.worker_server()).into_worker();
+
+    let output = client.process(request(&folder)).await.unwrap();
+    assert_eq!(output, Output::Ok);
+
+    assert_eq!(std::fs::read(folder.join("output")).unwrap(), b"abcdef");
+
+    let calls = calls.lock().unwrap().clone();
+    let whats = calls
+        .iter()
+        .map(|(_, _, what)| what.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        whats,
+        [
+            "notify_result_data output",
+            "create_results",
+            "create_results_metadata",
+            "submit_tasks 1",
+        ]
+    );
+    // Never written by the processor: the handler passes them on its own.
+    for (token, session_id, what) in &calls {
+        assert_eq!(token, "token", "{what}");
+        assert_eq!(session_id, "session", "{what}");
+    }
+
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[tokio::test]
+async fn a_missing_input_fails_the_task_rather_than_the_rpc() {
+    // No dependency file: the task has to end with an error output the agent can record, not a
+    // gRPC error that reads as a crashed worker.
+    let folder = data_folder("missing", &[("payload", b"abc")]);
+
+    let wrapper = WorkerWrapper::new(
+        Concat,
+        armonik::Client::with_channel(Agent::default().agent_server()).into_agent(),
+    );
+    let mut client = armonik::Client::with_channel(wrapper.worker_server()).into_worker();
+
+    match client.process(request(&folder)).await.unwrap() {
+        Output::Ok => panic!("the dependency is missing"),
+        Output::Error { details } => assert!(details.contains("dependency"), "{details}"),
+    }
+
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[tokio::test]
+async fn only_declared_dependencies_can_be_read() {
+    let folder = data_folder("undeclared", &[("other", b"xyz")]);
+    let agent = armonik::Client::with_channel(Agent::default().agent_server()).into_agent();
+
+    let task_handler = TaskHandler::new(agent, request(&folder));
+
+    let error = task_handler
+        .data_dependency("other")
+        .await
+        .expect_err("`other` is not a dependency of the task");
+    assert!(
+        matches!(error, TaskHandlerError::UnknownDependency { .. }),
+        "{error:?}"
+    );
+    assert_eq!(
+        task_handler.data_dependency_ids().collect::<Vec<_>>(),
+        ["dependency"]
+    );
+
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[tokio::test]
+async fn health_check_defaults_to_serving() {
+    let wrapper = WorkerWrapper::new(
+        Concat,
+        armonik::Client::with_channel(Agent::default().agent_server()).into_agent(),
+    );
+    let mut client = armonik::Client::with_channel(wrapper.worker_server()).into_worker();
+
+    assert_eq!(
+        client.health_check().await.unwrap(),
+        worker::health_check::Response::Serving
+    );
+}
+
+// Served over Unix domain sockets, as in a compute pod.
+
+/// Sends its payload back as its output.
+struct Echo;
+
+impl Processor for Echo {
+    async fn process(&self, task_handler: &TaskHandler) -> Output {
+        let outcome = async {
+            let payload = task_handler.payload().await?.to_vec();
+            let output_id = task_handler.expected_output_keys()[0].clone();
+            task_handler.send_result(output_id, payload).await
+        };
+
+        match outcome.await {
+            Ok(()) => Output::Ok,
+            Err(error) => Output::Error {
+                details: error.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn serve_binds_the_worker_socket_and_calls_the_agent_socket() {
+    let folder = data_folder("serve", &[("payload", b"echo")]);
+    let worker_socket = folder.join("worker.sock");
+    let agent_socket = folder.join("agent.sock");
+
+    // The agent, on its own socket.
+    let agent = Agent::default();
+    let calls = agent.calls.clone();
+    let agent_listener = tokio::net::UnixListener::bind(&agent_socket).unwrap();
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(agent.agent_server())
+            .serve_with_incoming(
+                armonik::reexports::tokio_stream::wrappers::UnixListenerStream::new(agent_listener),
+            ),
+    );
+
+    // A socket left over by a previous run, which has to be replaced rather than fail the bind.
+    std::fs::write(&worker_socket, b"").unwrap();
+
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let worker = tokio::spawn(worker::serve(
+        Echo,
+        worker::ComputePlane::new(
+            worker::GrpcChannel {
+                address: worker_socket.to_string_lossy().into_owned(),
+                socket_type: worker::SocketType::UnixDomainSocket,
+            },
+            worker::GrpcChannel {
+                address: agent_socket.to_string_lossy().into_owned(),
+                socket_type: worker::SocketType::UnixDomainSocket,
+            },
+        ),
+        async move {
+            let _ = stopped.await;
+        },
+    ));
+
+    // The agent side: dial the worker socket once it is there.
+    let mut args = armonik::client::ClientConfigArgs::default();
+    args.endpoint = format!("unix:{}", worker_socket.display());
+    let config = armonik::client::ClientConfig::from_config_args(args).unwrap();
+    let client = loop {
+        match armonik::Client::with_config(config.clone()).await {
+            Ok(client) => break client,
+            Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+        }
+    };
+    let mut client = client.into_worker();
+
+    assert_eq!(client.process(request(&folder)).await.unwrap(), Output::Ok);
+    assert_eq!(std::fs::read(folder.join("output")).unwrap(), b"echo");
+    assert_eq!(
+        calls.lock().unwrap()[0],
+        (
+            String::from("token"),
+            String::from("session"),
+            String::from("notify_result_data output")
+        )
+    );
+
+    drop(client);
+    stop.send(()).unwrap();
+    worker
+        .await
+        .unwrap()
+        .expect("the worker should stop cleanly");
+    assert!(
+        !worker_socket.exists(),
+        "the socket should be removed on shutdown"
+    );
+
+    std::fs::remove_dir_all(folder).unwrap();
+}
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use armonik::{
+    reexports::tokio_stream::StreamExt,
     server::{RequestContext, TasksServiceExt},
     tasks,
 };
@@ -11,6 +12,7 @@ mod common;
 struct Service {
     failure: Option<tonic::Status>,
     wait: Option<tokio::time::Duration>,
+    headers: Arc<std::sync::Mutex<Option<tonic::codegen::http::HeaderMap>>>,
 }
 
 impl armonik::server::TasksService for Service {
@@ -55,8 +57,9 @@ impl armonik::server::TasksService for Service {
     async fn get(
         self: Arc<Self>,
         request: tasks::get::Request,
-        _context: RequestContext,
+        context: RequestContext,
     ) -> std::result::Result<tasks::get::Response, tonic::Status> {
+        *self.headers.lock().unwrap() = Some(context.headers().clone());
         common::unary_rpc_impl(self.wait, self.failure.clone(), || {
             Ok(tasks::get::Response {
                 task: tasks::Raw {
@@ -187,6 +190,96 @@ async fn list_detailed() {
     assert_eq!(response.tasks[0].task_id, "rpc-list-detailed-output");
 }
 
+#[tokio::test]
+async fn list_stream() {
+    let client = armonik::Client::with_channel(Service::default().tasks_server()).into_tasks();
+
+    let tasks = client
+        .list_stream(
+            armonik::tasks::filter::Or::default(),
+            armonik::tasks::Sort::default(),
+            false,
+            1,
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap();
+
+    assert_eq!(tasks.len(), 1337);
+    assert!(tasks.iter().all(|task| task.task_id == "rpc-list-output"));
+}
+
+#[tokio::test]
+async fn list_stream_failure() {
+    let client = armonik::Client::with_channel(
+        Service {
+            failure: Some(tonic::Status::unavailable("rpc-list-failure")),
+            ..Default::default()
+        }
+        .tasks_server(),
+    )
+    .into_tasks();
+
+    let tasks = client
+        .list_stream(
+            armonik::tasks::filter::Or::default(),
+            armonik::tasks::Sort::default(),
+            false,
+            12,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(tasks.len(), 1);
+    assert!(tasks[0].is_err());
+}
+
+#[tokio::test]
+async fn list_detailed_stream() {
+    let client = armonik::Client::with_channel(Service::default().tasks_server()).into_tasks();
+
+    let tasks = client
+        .list_detailed_stream(
+            armonik::tasks::filter::Or::default(),
+            armonik::tasks::Sort::default(),
+            false,
+            1,
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .unwrap();
+
+    assert_eq!(tasks.len(), 1338);
+    assert!(tasks
+        .iter()
+        .all(|task| task.task_id == "rpc-list-detailed-output"));
+}
+
+#[tokio::test]
+async fn list_detailed_stream_failure() {
+    let client = armonik::Client::with_channel(
+        Service {
+            failure: Some(tonic::Status::unavailable("rpc-list-detailed-failure")),
+            ..Default::default()
+        }
+        .tasks_server(),
+    )
+    .into_tasks();
+
+    let tasks = client
+        .list_detailed_stream(
+            armonik::tasks::filter::Or::default(),
+            armonik::tasks::Sort::default(),
+            false,
+            12,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(tasks.len(), 1);
+    assert!(tasks[0].is_err());
+}
+
 #[tokio::test]
 async fn get() {
     let mut client = armonik::Client::with_channel(Service::default().tasks_server()).into_tasks();
@@ -253,3 +346,78 @@ async fn submit() {
     assert_eq!(response[0].payload_id, "rpc-submit-input");
     assert_eq!(response[0].task_id, "rpc-submit-output");
 }
+
+fn code(err: armonik::client::RequestError) -> tonic::Code {
+    match err {
+        armonik::client::RequestError::Grpc { source, .. } => source.code(),
+        err => panic!("expected a gRPC error, but got {err:?}"),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn get_past_deadline() {
+    let mut client = armonik::Client::with_channel(
+        Service {
+            wait: Some(tokio::time::Duration::from_secs(10)),
+            ..Default::default()
+        }
+        .tasks_server(),
+    )
+    .into_tasks()
+    .with_options(
+        armonik::client::CallOptions::new().with_deadline(tokio::time::Duration::from_secs(2)),
+    );
+
+    let err = client.get("rpc-get-input").await.unwrap_err();
+
+    assert_eq!(code(err), tonic::Code::DeadlineExceeded);
+}
+
+#[tokio::test]
+async fn get_with_metadata() {
+    let service = Service::default();
+    let headers = service.headers.clone();
+    let mut client = armonik::Client::with_channel(service.tasks_server()).into_tasks();
+
+    let mut options =
+        armonik::client::CallOptions::new().with_deadline(tokio::time::Duration::from_secs(2));
+    options
+        .metadata_mut()
+        .insert("x-request-id", "rpc-get-metadata".parse().unwrap());
+    let response = client
+        .call_with(
+            tasks::get::Request {
+                task_id: String::from("rpc-get-input"),
+            },
+            options,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.task.task_id, "rpc-get-input");
+    let headers = headers.lock().unwrap().take().unwrap();
+    assert_eq!(headers["x-request-id"], "rpc-get-metadata");
+    assert_eq!(headers["grpc-timeout"], "2000000u");
+}
+
+#[tokio::test(start_paused = true)]
+async fn get_cancelled() {
+    let token = tokio_util::sync::CancellationToken::new();
+    let mut client = armonik::Client::with_channel(
+        Service {
+            wait: Some(tokio::time::Duration::from_secs(10)),
+            ..Default::default()
+        }
+        .tasks_server(),
+    )
+    .into_tasks()
+    .with_options(armonik::client::CallOptions::new().with_cancellation(token.clone()));
+
+    let cancel = async {
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        token.cancel();
+    };
+    let (response, ()) = tokio::join!(client.get("rpc-get-input"), cancel);
+
+    assert_eq!(code(response.unwrap_err()), tonic::Code::Cancelled);
+}
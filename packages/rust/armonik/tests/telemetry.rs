@@ -0,0 +1,339 @@
+use std::sync::{Arc, Mutex};
+
+use armonik::{
+    agent,
+    client::CallOptions,
+    server::{AgentServiceExt, RequestContext, VersionsServiceExt, WorkerServiceExt},
+    versions,
+    worker::{self, Processor, TaskHandler, WorkerWrapper},
+    Output,
+};
+use opentelemetry::trace::{SpanContext, TraceContextExt, TracerProvider};
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    trace::{SpanData, SpanProcessor},
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Bridge spans to OpenTelemetry and send them as W3C trace context, for the current thread.
+fn traced() -> tracing::subscriber::DefaultGuard {
+    traced_into(Ended::default())
+}
+
+/// Like [`traced`], recording the spans that ended into `ended`.
+fn traced_into(ended: Ended) -> tracing::subscriber::DefaultGuard {
+    opentelemetry::global::set_text_map_propagator(
+        opentelemetry_sdk::propagation::TraceContextPropagator::new(),
+    );
+    let tracer = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_span_processor(ended)
+        .build()
+        .tracer("armonik");
+    tracing::subscriber::set_default(
+        tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer)),
+    )
+}
+
+fn span_context(span: &tracing::Span) -> SpanContext {
+    span.context().span().span_context().clone()
+}
+
+/// The spans that ended, as exported.
+#[derive(Debug, Clone, Default)]
+struct Ended(Arc<Mutex<Vec<SpanData>>>);
+
+impl Ended {
+    fn named(&self, name: &str) -> SpanData {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|span| span.name == name)
+            .unwrap_or_else(|| panic!("no span `{name}` ended"))
+            .clone()
+    }
+}
+
+impl SpanProcessor for Ended {
+    fn on_start(&self, _span: &mut opentelemetry_sdk::trace::Span, _cx: &opentelemetry::Context) {}
+
+    fn on_end(&self, span: SpanData) {
+        self.0.lock().unwrap().push(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: std::time::Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+/// What the server saw of the calls it served: the trace context sent, and the one it served them in.
+#[derive(Debug, Clone, Default)]
+struct Seen {
+    sent: Arc<Mutex<Vec<SpanContext>>>,
+    served: Arc<Mutex<Vec<SpanContext>>>,
+}
+
+impl Seen {
+    fn record(&self, context: &RequestContext) {
+        self.sent
+            .lock()
+            .unwrap()
+            .push(context.trace_context().span().span_context().clone());
+        self.served
+            .lock()
+            .unwrap()
+            .push(span_context(&tracing::Span::current()));
+    }
+}
+
+impl armonik::server::VersionsService for Seen {
+    async fn list(
+        self: Arc<Self>,
+        _request: versions::list::Request,
+        context: RequestContext,
+    ) -> std::result::Result<versions::list::Response, tonic::Status> {
+        self.record(&context);
+        Ok(versions::list::Response {
+            core: String::from("rpc-list-output"),
+            ..Default::default()
+        })
+    }
+}
+
+#[tokio::test]
+async fn calls_carry_the_current_span() {
+    let ended = Ended::default();
+    let _guard = traced_into(ended.clone());
+    let seen = Seen::default();
+    let mut client = armonik::Client::with_channel(seen.clone().versions_server());
+
+    let span = tracing::info_span!("submitter");
+    let response = tracing_futures::Instrument::instrument(client.versions().list(), span.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.core, "rpc-list-output");
+
+    let sent = seen.sent.lock().unwrap()[0].clone();
+    assert!(sent.is_remote());
+    assert_eq!(sent.trace_id(), span_context(&span).trace_id());
+    // The server hangs off the span of the call, itself under the span of the caller.
+    let call = ended.named("Versions::list");
+    assert_eq!(sent.span_id(), call.span_context.span_id());
+    assert_eq!(call.parent_span_id, span_context(&span).span_id());
+    let served = seen.served.lock().unwrap()[0].clone();
+    assert_eq!(served.trace_id(), span_context(&span).trace_id());
+}
+
+/// Call `ListVersions` with `metadata` as is, without the trace context a client of this crate sends.
+async fn list_raw(seen: &Seen, metadata: tonic::metadata::MetadataMap) {
+    let mut client = armonik::api::v3::versions::versions_client::VersionsClient::new(
+        seen.clone().versions_server(),
+    );
+    let mut request = tonic::Request::new(armonik::api::v3::versions::ListVersionsRequest {});
+    *request.metadata_mut() = metadata;
+    client.list_versions(request).await.unwrap();
+}
+
+#[tokio::test]
+async fn server_span_is_parented_on_the_trace_context_sent() {
+    let _guard = traced();
+    let seen = Seen::default();
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert(
+        "traceparent",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+            .parse()
+            .unwrap(),
+    );
+
+    list_raw(&seen, metadata).await;
+
+    let served = seen.served.lock().unwrap()[0].clone();
+    assert_eq!(
+        served.trace_id().to_string(),
+        "4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+    assert_ne!(served.span_id().to_string(), "00f067aa0ba902b7");
+}
+
+#[tokio::test]
+async fn untraced_calls_leave_the_server_span_alone() {
+    let _guard = traced();
+    let seen = Seen::default();
+
+    list_raw(&seen, Default::default()).await;
+
+    assert!(!seen.sent.lock().unwrap()[0].is_valid());
+    assert!(seen.served.lock().unwrap()[0].is_valid());
+}
+
+#[tokio::test]
+async fn calls_outside_of_any_span_send_the_span_of_the_call() {
+    let ended = Ended::default();
+    let _guard = traced_into(ended.clone());
+    let seen = Seen::default();
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert(
+        "traceparent",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+            .parse()
+            .unwrap(),
+    );
+    let mut client = armonik::Client::with_channel(seen.clone().versions_server())
+        .into_versions()
+        .with_options(CallOptions::new().with_metadata(metadata));
+
+    client.list().await.unwrap();
+
+    // The span of the call starts a trace of its own, in place of the trace context of the options.
+    let call = ended.named("Versions::list");
+    let sent = seen.sent.lock().unwrap()[0].clone();
+    assert_eq!(sent.span_id(), call.span_context.span_id());
+    assert_eq!(sent.trace_id(), call.span_context.trace_id());
+    assert_ne!(
+        sent.trace_id().to_string(),
+        "4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+}
+
+impl armonik::server::AgentService for Seen {
+    async fn create_results_metadata(
+        self: Arc<Self>,
+        request: agent::create_results_metadata::Request,
+        context: RequestContext,
+    ) -> std::result::Result<agent::create_results_metadata::Response, tonic::Status> {
+        self.record(&context);
+        Ok(agent::create_results_metadata::Response {
+            communication_token: request.communication_token,
+            results: request
+                .results
+                .into_iter()
+                .map(|item| agent::ResultMetaData {
+                    session_id: request.session_id.clone(),
+                    result_id: format!("id-{}", item.name),
+                    name: item.name,
+                    ..Default::default()
+                })
+                .collect(),
+        })
+    }
+
+    async fn create_results(
+        self: Arc<Self>,
+        _request: agent::create_results::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::create_results::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("create_results"))
+    }
+
+    async fn notify_result_data(
+        self: Arc<Self>,
+        _request: agent::notify_result_data::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::notify_result_data::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("notify_result_data"))
+    }
+
+    async fn submit_tasks(
+        self: Arc<Self>,
+        _request: agent::submit_tasks::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::submit_tasks::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("submit_tasks"))
+    }
+
+    async fn get_resource_data(
+        self: Arc<Self>,
+        _request: agent::get_resource_data::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::get_resource_data::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("get_resource_data"))
+    }
+
+    async fn get_common_data(
+        self: Arc<Self>,
+        _request: agent::get_common_data::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::get_common_data::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("get_common_data"))
+    }
+
+    async fn get_direct_data(
+        self: Arc<Self>,
+        _request: agent::get_direct_data::Request,
+        _context: RequestContext,
+    ) -> std::result::Result<agent::get_direct_data::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("get_direct_data"))
+    }
+
+    async fn create_tasks(
+        self: Arc<Self>,
+        _request: impl tonic::codegen::tokio_stream::Stream<
+                Item = Result<agent::create_tasks::Request, tonic::Status>,
+            > + Send
+            + 'static,
+        _context: RequestContext,
+    ) -> Result<agent::create_tasks::Response, tonic::Status> {
+        Err(tonic::Status::unimplemented("create_tasks"))
+    }
+}
+
+type AgentChannel = armonik::Client<armonik::api::v3::agent::agent_server::AgentServer<Seen>>;
+
+/// Creates one result, recording the span it processed the task in.
+#[derive(Clone, Default)]
+struct Create {
+    processed: Arc<Mutex<Option<SpanContext>>>,
+}
+
+impl Processor<AgentChannel> for Create {
+    async fn process(&self, task_handler: &TaskHandler<AgentChannel>) -> Output {
+        *self.processed.lock().unwrap() = Some(span_context(&tracing::Span::current()));
+        match task_handler.create_results_metadata(["output"]).await {
+            Ok(_) => Output::Ok,
+            Err(error) => Output::Error {
+                details: error.to_string(),
+            },
+        }
+    }
+}
+
+#[tokio::test]
+async fn agent_calls_of_a_worker_are_part_of_the_process_span() {
+    let ended = Ended::default();
+    let _guard = traced_into(ended.clone());
+    let agent = Seen::default();
+    let processor = Create::default();
+    let wrapper = WorkerWrapper::new(
+        processor.clone(),
+        armonik::Client::with_channel(agent.clone().agent_server()).into_agent(),
+    );
+    let mut client = armonik::Client::with_channel(wrapper.worker_server()).into_worker();
+
+    let span = tracing::info_span!("polling-agent");
+    let output = tracing_futures::Instrument::instrument(
+        client.process(worker::process::Request {
+            communication_token: String::from("token"),
+            session_id: String::from("session"),
+            task_id: String::from("task"),
+            ..Default::default()
+        }),
+        span.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(output, Output::Ok);
+
+    let processed = processor.processed.lock().unwrap().clone().unwrap();
+    let sent = agent.sent.lock().unwrap()[0].clone();
+    assert_eq!(processed.trace_id(), span_context(&span).trace_id());
+    assert_eq!(sent.trace_id(), processed.trace_id());
+    let call = ended.named("Agent::create_results_metadata");
+    assert_eq!(sent.span_id(), call.span_context.span_id());
+    assert_eq!(call.parent_span_id, processed.span_id());
+}
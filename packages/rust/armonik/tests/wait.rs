@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use armonik::{
+    client::{ClientConfig, ClientConfigArgs},
+    events,
+    mock::{ControlPlane, MockServer},
+    reexports::tokio_stream::{wrappers::TcpListenerStream, Stream, StreamExt},
+    results,
+    server::{
+        EventsService, EventsServiceExt, RequestContext, ResultsServiceExt, SessionsServiceExt,
+    },
+    tasks, Client, ResultStatus,
+};
+use futures::stream::BoxStream;
+
+/// Events of the control plane, whose first subscription ends at once
+struct Dropped {
+    control_plane: ControlPlane,
+    dropped: AtomicBool,
+}
+
+impl EventsService for Dropped {
+    async fn subscribe(
+        self: Arc<Self>,
+        request: events::subscribe::Request,
+        context: RequestContext,
+    ) -> Result<
+        impl Stream<Item = Result<events::subscribe::Response, tonic::Status>> + Send,
+        tonic::Status,
+    > {
+        let events = Arc::new(self.control_plane.clone())
+            .subscribe(request, context)
+            .await?;
+        let events: BoxStream<'static, _> = if self.dropped.swap(true, Ordering::SeqCst) {
+            Box::pin(events)
+        } else {
+            Box::pin(events.take(0))
+        };
+        Ok(events)
+    }
+}
+
+/// Create results with no data, by name
+async fn create(client: &mut Client, session_id: &str, names: &[&str]) -> Vec<String> {
+    client
+        .results()
+        .create_metadata(
+            session_id,
+            names
+                .iter()
+                .map(|name| results::create_metadata::RequestItem {
+                    name: name.to_string(),
+                    ..Default::default()
+                }),
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|result| result.result_id)
+        .collect()
+}
+
+async fn upload(client: &mut Client, session_id: &str, result_id: &str) {
+    client
+        .results()
+        .upload(
+            session_id,
+            result_id,
+            futures::stream::iter([b"data".to_vec()]),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn results_are_yielded_as_they_complete() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = server.client().await.unwrap();
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+    let ids = create(
+        &mut client,
+        &session_id,
+        &["early", "late", "aborted", "payload"],
+    )
+    .await;
+    let [early, late, aborted, payload] = <[String; 4]>::try_from(ids).unwrap();
+    upload(&mut client, &session_id, &early).await;
+    upload(&mut client, &session_id, &payload).await;
+    let task_id = client
+        .tasks()
+        .submit(
+            &session_id,
+            None,
+            [tasks::submit::RequestItem {
+                payload_id: payload,
+                expected_output_keys: vec![aborted.clone()],
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap()
+        .remove(0)
+        .task_id;
+
+    let mut waited = Box::pin(client.wait_for_results(&session_id, [&early, &late, &aborted]));
+
+    // Completed before waiting: found by listing the results once subscribed.
+    let first = waited.next().await.unwrap().unwrap();
+    assert_eq!(first, (early.clone(), ResultStatus::Completed));
+
+    upload(&mut client, &session_id, &late).await;
+    server.control_plane().fail_task(&task_id, "boom").unwrap();
+
+    let rest = tokio::time::timeout(Duration::from_secs(10), waited.collect::<Vec<_>>())
+        .await
+        .unwrap()
+        .into_iter()
+        .collect::<Result<BTreeMap<_, _>, _>>()
+        .unwrap();
+    assert_eq!(
+        rest,
+        BTreeMap::from([
+            (late, ResultStatus::Completed),
+            (aborted, ResultStatus::Aborted),
+        ])
+    );
+    assert_eq!(server.calls().get("Events", "GetEvents"), 1);
+    assert_eq!(server.calls().get("Results", "ListResults"), 1);
+}
+
+#[tokio::test]
+async fn results_are_polled_without_events() {
+    // Only sessions and results are served: subscribing to events fails.
+    let control_plane = ControlPlane::new();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let router = tonic::transport::Server::builder()
+        .add_service(control_plane.clone().sessions_server())
+        .add_service(control_plane.clone().results_server());
+    let served = tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+
+    let mut args = ClientConfigArgs::default();
+    args.endpoint = format!("http://{address}");
+    let mut client = Client::with_config(ClientConfig::from_config_args(args).unwrap())
+        .await
+        .unwrap();
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+    let result_id = create(&mut client, &session_id, &["result"])
+        .await
+        .remove(0);
+
+    let waited = client.wait_for_results(&session_id, [&result_id]);
+    let uploaded = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        upload(&mut client.clone(), &session_id, &result_id).await;
+    };
+    let (waited, ()) = tokio::time::timeout(
+        Duration::from_secs(10),
+        futures::future::join(waited.collect::<Vec<_>>(), uploaded),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(waited.len(), 1);
+    assert_eq!(
+        waited[0].as_ref().unwrap(),
+        &(result_id, ResultStatus::Completed)
+    );
+    assert!(control_plane.calls().get("Results", "ListResults") >= 2);
+    served.abort();
+}
+
+#[tokio::test]
+async fn many_results_are_listed_a_page_of_ids_at_a_time() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = server.client().await.unwrap();
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+    let names = (0..150).map(|i| format!("result-{i}")).collect::<Vec<_>>();
+    let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+    let ids = create(&mut client, &session_id, &names).await;
+    for id in &ids {
+        upload(&mut client, &session_id, id).await;
+    }
+
+    let waited = tokio::time::timeout(
+        Duration::from_secs(10),
+        client
+            .clone()
+            .into_results()
+            .wait(client.clone().into_events(), &session_id, &ids)
+            .collect::<Vec<_>>(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(waited.len(), ids.len());
+    assert!(waited
+        .iter()
+        .all(|waited| waited.as_ref().unwrap().1 == ResultStatus::Completed));
+    assert_eq!(server.calls().get("Results", "ListResults"), 2);
+}
+
+#[tokio::test]
+async fn an_events_stream_that_ends_is_subscribed_to_again() {
+    let control_plane = ControlPlane::new();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let events = Dropped {
+        control_plane: control_plane.clone(),
+        dropped: AtomicBool::new(false),
+    };
+    let router = tonic::transport::Server::builder()
+        .add_service(control_plane.clone().sessions_server())
+        .add_service(control_plane.clone().results_server())
+        .add_service(events.events_server());
+    let served = tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+
+    let mut args = ClientConfigArgs::default();
+    args.endpoint = format!("http://{address}");
+    let mut client = Client::with_config(ClientConfig::from_config_args(args).unwrap())
+        .await
+        .unwrap();
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+    let [first, second] =
+        <[String; 2]>::try_from(create(&mut client, &session_id, &["first", "second"]).await)
+            .unwrap();
+
+    let mut waited = Box::pin(client.wait_for_results(&session_id, [&first, &second]));
+    upload(&mut client, &session_id, &first).await;
+    let item = tokio::time::timeout(Duration::from_secs(10), waited.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(item, (first, ResultStatus::Completed));
+
+    // Uploaded once subscribed again, a second after the first stream ended: followed through the
+    // events, with no listing but the one along with the second subscription.
+    let uploaded = async {
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        upload(&mut client.clone(), &session_id, &second).await;
+    };
+    let (item, ()) = tokio::time::timeout(
+        Duration::from_secs(10),
+        futures::future::join(waited.next(), uploaded),
+    )
+    .await
+    .unwrap();
+    assert_eq!(item.unwrap().unwrap(), (second, ResultStatus::Completed));
+    assert_eq!(control_plane.calls().get("Events", "GetEvents"), 2);
+    assert_eq!(control_plane.calls().get("Results", "ListResults"), 2);
+    served.abort();
+}
+
+#[tokio::test]
+async fn results_not_of_the_session_are_not_found() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = server.client().await.unwrap();
+    let session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+    let other_session_id = client
+        .sessions()
+        .create(["default"], Default::default())
+        .await
+        .unwrap();
+    let result_id = create(&mut client, &session_id, &["result"])
+        .await
+        .remove(0);
+    let other_id = create(&mut client, &other_session_id, &["other"])
+        .await
+        .remove(0);
+    upload(&mut client, &session_id, &result_id).await;
+
+    let waited = tokio::time::timeout(
+        Duration::from_secs(10),
+        client
+            .wait_for_results(&session_id, [&result_id, &other_id, "missing"])
+            .collect::<Vec<_>>(),
+    )
+    .await
+    .expect("ids that are not of the session should not be waited for");
+
+    let not_found = waited
+        .iter()
+        .filter_map(|waited| waited.as_ref().err())
+        .inspect(|error| assert!(error.is_not_found(), "{error:?}"))
+        .map(|error| error.status().unwrap().message().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(not_found.len(), 2, "{not_found:?}");
+    assert!(not_found.iter().any(|message| message.contains("missing")));
+    assert!(not_found.iter().any(|message| message.contains(&other_id)));
+    let found = waited
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+    assert_eq!(found, vec![(result_id, ResultStatus::Completed)]);
+}